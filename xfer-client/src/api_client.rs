@@ -1,111 +1,857 @@
+use crate::rate_limit::{RateLimiter, Throttled};
 use anyhow::{Context, Result, bail};
-use reqwest::{blocking::Response, header};
-use serde::Deserialize;
-use std::time::Duration;
+use bytes::Bytes;
+use indicatif::ProgressBar;
+use reqwest::{
+    Method, StatusCode,
+    blocking::{Body, ClientBuilder, RequestBuilder, Response},
+    header,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{Cursor, Read},
+    ops::Range,
+    sync::Arc,
+    thread,
+    time::Duration,
+};
 use url::Url;
+use xfer_crypto::FORMAT_VERSION;
+
+/// Version of the server HTTP API this client speaks.
+const API_VERSION: u32 = 1;
+/// Header the server uses to send the API version that handled a request.
+const API_VERSION_HEADER: &str = "X-Xfer-Api-Version";
+
+/// Header the server uses to send the time a transfer expires at, in RFC 3339 format.
+pub const EXPIRES_AT_HEADER: &str = "X-Xfer-ExpiresAt";
+
+/// Header the server uses to send how many more times a transfer with a download limit can be downloaded.
+pub const DOWNLOADS_REMAINING_HEADER: &str = "X-Xfer-Downloads-Remaining";
+
+/// Header the server uses to send the token that resumes a counted download of a transfer with a
+/// download limit once all of its downloads have been taken, and clients use to send it back.
+pub const DOWNLOAD_TOKEN_HEADER: &str = "X-Xfer-Download-Token";
+
+/// Header used to request how long a new transfer is kept for, in seconds.
+const EXPIRE_AFTER_HEADER: &str = "X-Xfer-Expire-After";
+
+/// Highest proof-of-work difficulty the client will attempt, matching the most the server can require.
+/// Each bit doubles the work, so anything above this would keep the client busy practically forever.
+const MAX_PROOF_OF_WORK_DIFFICULTY: u8 = 32;
+
+/// How many times a request that failed with a temporary error is retried by default.
+pub const DEFAULT_RETRIES: u32 = 4;
+/// Delay before the first retry of a request, doubled after each attempt.
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+/// Longest delay between retries of a request.
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+/// Longest `Retry-After` the client waits for by itself. Longer waits are left to the caller.
+const RETRY_AFTER_MAX: Duration = Duration::from_secs(60);
+/// Response statuses that indicate a temporary problem worth retrying the request for.
+const RETRY_STATUSES: [StatusCode; 5] = [
+    StatusCode::TOO_MANY_REQUESTS,
+    StatusCode::INTERNAL_SERVER_ERROR,
+    StatusCode::BAD_GATEWAY,
+    StatusCode::SERVICE_UNAVAILABLE,
+    StatusCode::GATEWAY_TIMEOUT,
+];
+
+/// Error returned when the server is temporarily unable to accept a request and says when to try again.
+#[derive(Debug)]
+pub struct RetryLaterError {
+    /// How long the server asked the client to wait before retrying.
+    pub retry_after: Duration,
+    message: String,
+}
+
+impl std::fmt::Display for RetryLaterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for RetryLaterError {}
+
+/// Error returned when the server responds to a request with an unsuccessful status.
+#[derive(Debug)]
+pub struct StatusError {
+    pub status: StatusCode,
+    message: String,
+}
+
+impl std::fmt::Display for StatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for StatusError {}
 
 #[derive(Deserialize)]
 pub struct ServerConfigurationResponse {
     pub transfer: TransferConfiguration,
+    #[serde(default)]
+    pub web: WebConfiguration,
+    #[serde(default)]
+    pub relay: RelayConfiguration,
+    #[serde(default)]
+    pub motd: Option<String>,
 }
 
 #[derive(Deserialize)]
 pub struct TransferConfiguration {
-    pub expire_after_ms: u128,
     pub max_size_bytes: u64,
+    /// The shortest lifetime an upload may request. Not sent by older servers.
+    #[serde(default)]
+    pub min_expire_after_ms: Option<u64>,
+    /// The longest lifetime an upload may request. Not sent by older servers.
+    #[serde(default)]
+    pub max_expire_after_ms: Option<u64>,
+    #[serde(default = "default_uploads_enabled")]
+    pub uploads_enabled: bool,
+    #[serde(default)]
+    pub upload_token_required: bool,
+    #[serde(default)]
+    pub claim_webhooks_enabled: bool,
+    #[serde(default)]
+    pub notification_emails_enabled: bool,
+    /// Whether reserved transfers can be uploaded in chunks. Not sent by older servers.
+    #[serde(default)]
+    pub chunked_uploads_enabled: bool,
+    /// Whether an encrypted manifest of a transfer's contents can be stored. Not sent by older servers.
+    #[serde(default)]
+    pub manifests_enabled: bool,
+    /// Whether uploaders can limit how many times a transfer can be downloaded. Not sent by older servers.
+    #[serde(default)]
+    pub download_limits_enabled: bool,
+    /// Challenge that must be solved before uploading, when the server requires proof of work.
+    #[serde(default)]
+    pub proof_of_work: Option<ProofOfWorkChallenge>,
+}
+
+#[derive(Deserialize)]
+pub struct ProofOfWorkChallenge {
+    challenge: String,
+    difficulty: u8,
+}
+
+impl ProofOfWorkChallenge {
+    /// Find a nonce for which the BLAKE3 hash of the challenge and nonce has at least `difficulty`
+    /// leading zero bits, returning the solution in the form the server expects.
+    ///
+    /// Fails without doing any work if the difficulty is above what a server can legitimately require.
+    pub fn solve(&self) -> Result<String> {
+        if self.difficulty > MAX_PROOF_OF_WORK_DIFFICULTY {
+            bail!(
+                "server requested a proof-of-work difficulty of {} bits, which is above the maximum of {MAX_PROOF_OF_WORK_DIFFICULTY}",
+                self.difficulty
+            );
+        }
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(self.challenge.as_bytes());
+        let nonce = (0u64..)
+            .find(|nonce| {
+                let hash = hasher.clone().update(&nonce.to_le_bytes()).finalize();
+                let mut zero_bits = 0;
+                for byte in hash.as_bytes() {
+                    zero_bits += byte.leading_zeros();
+                    if *byte != 0 {
+                        break;
+                    }
+                }
+                zero_bits >= u32::from(self.difficulty)
+            })
+            .expect("a proof of work solution should exist");
+        Ok(format!("{}:{nonce}", self.challenge))
+    }
+}
+
+fn default_uploads_enabled() -> bool {
+    true
+}
+
+#[derive(Deserialize, Default)]
+pub struct WebConfiguration {
+    pub download_page_enabled: bool,
+}
+
+#[derive(Deserialize, Default)]
+pub struct RelayConfiguration {
+    pub enabled: bool,
+    /// The maximum size of a relayed transfer, if limited.
+    pub max_size_bytes: Option<u64>,
+}
+
+/// Body the server sends alongside unsuccessful responses.
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: String,
 }
 
 #[derive(Deserialize)]
 pub struct CreateTransferResponse {
     pub id: String,
+    /// The time the transfer expires at, in RFC 3339 format.
+    ///
+    /// Not sent by older servers.
+    #[serde(default)]
+    pub expires_at: Option<String>,
+    /// Secret that grants access to the transfer's download activity.
+    ///
+    /// Not sent by older servers.
+    #[serde(default)]
+    pub activity_token: Option<String>,
+    /// Secret that allows the transfer to be deleted before it expires.
+    ///
+    /// Not sent by older servers.
+    #[serde(default)]
+    pub deletion_token: Option<String>,
+}
+
+/// Options the server applies to a new transfer once it has been uploaded.
+pub struct TransferOptions<'a> {
+    /// Webhook to notify when the transfer is first downloaded.
+    pub claim_webhook: Option<&'a Url>,
+    /// Address to email about the transfer.
+    pub notify_email: Option<&'a str>,
+    /// How many times the transfer can be downloaded before the server removes it.
+    pub max_downloads: Option<u64>,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct ReserveTransferResponse {
+    pub id: String,
+    /// Secret to send when uploading the reserved transfer's data.
+    upload_token: String,
+    /// The time the reservation lapses if the upload isn't completed, in RFC 3339 format.
+    pub expires_at: String,
+}
+
+#[derive(Deserialize)]
+pub struct TransferChunkResponse {
+    /// The time the reservation now lapses if the upload isn't completed, in RFC 3339 format.
+    pub expires_at: String,
+}
+
+#[derive(Deserialize)]
+pub struct OpenRelayResponse {
+    pub id: String,
+    /// Secret to send when uploading the relayed data.
+    upload_token: String,
+    /// The time the relay lapses if the recipient hasn't connected, in RFC 3339 format.
+    pub expires_at: String,
 }
 
-pub struct XferApiClient<'a> {
-    base_url: &'a Url,
+pub struct XferApiClient {
+    base_url: Url,
     inner_client: reqwest::blocking::Client,
+    upload_token: Option<String>,
+    retries: u32,
+    request_timeout: Option<Duration>,
+    idle_timeout: Option<Duration>,
+    rate_limit: Option<Arc<RateLimiter>>,
 }
 
-impl<'a> XferApiClient<'a> {
-    pub fn new(base_url: &'a Url) -> Self {
-        Self {
+impl XferApiClient {
+    /// Create a client for the server at `base_url`, making requests with an HTTP client built from `http_client`.
+    pub fn new(base_url: &Url, http_client: ClientBuilder) -> Result<Self> {
+        // Relative URLs are joined onto the last path segment, so ensure servers
+        // hosted under a subpath (e.g. `https://example.com/xfer`) keep it.
+        let mut base_url = base_url.clone();
+        if !base_url.path().ends_with('/') {
+            base_url.set_path(&format!("{}/", base_url.path()));
+        }
+        Ok(Self {
             base_url,
-            inner_client: reqwest::blocking::Client::builder()
+            // Transfers can take as long as they need, so only requests without transfer data are
+            // given an overall timeout, with `limit`.
+            inner_client: http_client
+                .timeout(None)
                 .user_agent(concat!(
                     env!("CARGO_PKG_NAME"),
                     "/",
                     env!("CARGO_PKG_VERSION")
                 ))
                 .build()
-                .expect("api inner client should build"),
+                .context("failed to create HTTP client")?,
+            upload_token: None,
+            retries: DEFAULT_RETRIES,
+            request_timeout: None,
+            idle_timeout: None,
+            rate_limit: None,
+        })
+    }
+
+    /// Limit how long requests that don't send or receive transfer data can take.
+    ///
+    /// Requests that do have no overall limit, as they can take as long as the transfer needs.
+    pub fn with_request_timeout(mut self, request_timeout: Option<Duration>) -> Self {
+        self.request_timeout = request_timeout;
+        self
+    }
+
+    /// Apply the request timeout to a request that doesn't send or receive transfer data.
+    fn limit(&self, req: RequestBuilder) -> RequestBuilder {
+        match self.request_timeout {
+            Some(timeout) => req.timeout(timeout),
+            None => req,
+        }
+    }
+
+    /// Fail downloads of transfer data that go this long without receiving any.
+    pub fn with_idle_timeout(mut self, idle_timeout: Option<Duration>) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Apply the idle timeout to a request that receives transfer data.
+    ///
+    /// A blocking request's timeout also limits each read of its response body rather than the
+    /// whole of it, so the download only fails once it stops making progress.
+    fn watch(&self, req: RequestBuilder) -> RequestBuilder {
+        match self.idle_timeout {
+            Some(timeout) => req.timeout(timeout),
+            None => req,
+        }
+    }
+
+    /// Limit how many bytes of transfer data are sent and received per second, across all requests.
+    pub fn with_rate_limit(mut self, bytes_per_second: Option<u64>) -> Self {
+        self.rate_limit = bytes_per_second.map(|rate| Arc::new(RateLimiter::new(rate)));
+        self
+    }
+
+    /// Read transfer data no faster than the rate limit, if one has been set.
+    pub fn throttle<R: Read>(&self, reader: R) -> Throttled<R> {
+        Throttled::new(reader, self.rate_limit.clone())
+    }
+
+    /// Create a request body for transfer data that is sent no faster than the rate limit and
+    /// advances a progress bar as it is sent.
+    fn transfer_body(&self, body: Bytes, progress: Option<&ProgressBar>) -> Body {
+        let len = body.len() as u64;
+        let body = self.throttle(Cursor::new(body));
+        match progress {
+            Some(progress) => Body::sized(progress.wrap_read(body), len),
+            None => Body::sized(body, len),
         }
     }
 
+    /// Set how many times a request that failed with a temporary error is retried.
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// How many times a request that failed with a temporary error is retried.
+    pub fn retries(&self) -> u32 {
+        self.retries
+    }
+
+    /// Send a request, retrying it after a connection error or a temporary server error as described
+    /// in [`Self::send_with`].
+    ///
+    /// Requests with a streamed body can't be sent again, so are only sent once.
+    fn send(&self, req: RequestBuilder) -> Result<Response> {
+        match req.try_clone() {
+            Some(_) => self.send_with(|| req.try_clone().expect("request should be cloneable")),
+            None => req.send().map_err(explain_send_error),
+        }
+    }
+
+    /// Send the request built by `build`, building and sending it again after a connection error or a
+    /// temporary server error.
+    ///
+    /// Only `GET`, `HEAD` and `PUT` requests can safely be sent more than once. Other requests, such as
+    /// those creating transfers or spending a proof-of-work solution, may have been acted on even when
+    /// no response arrived, so are only sent again when connecting to the server failed.
+    ///
+    /// Retries wait for the server's `Retry-After` when it gives a short enough one, or a jittered
+    /// exponential backoff otherwise.
+    fn send_with(&self, mut build: impl FnMut() -> RequestBuilder) -> Result<Response> {
+        let mut attempt = 1;
+        loop {
+            let (client, req) = build().build_split();
+            let req = req.map_err(explain_send_error)?;
+            let repeatable = matches!(*req.method(), Method::GET | Method::HEAD | Method::PUT);
+            let result = client.execute(req);
+            let delay = match &result {
+                Ok(res) if repeatable && RETRY_STATUSES.contains(&res.status()) => retry_after(res),
+                Err(err)
+                    if (err.is_connect() || (repeatable && is_transient(err)))
+                        && !is_certificate_error(err) =>
+                {
+                    None
+                }
+                _ => return result.map_err(explain_send_error),
+            };
+            if attempt > self.retries || delay.is_some_and(|delay| delay > RETRY_AFTER_MAX) {
+                return result.map_err(explain_send_error);
+            }
+            thread::sleep(delay.unwrap_or_else(|| retry_delay(attempt)));
+            attempt += 1;
+        }
+    }
+
+    /// Authenticate uploads (and server configuration requests) with the given upload token.
+    pub fn with_upload_token(mut self, upload_token: Option<String>) -> Self {
+        self.upload_token = upload_token;
+        self
+    }
+
+    /// Attach the upload token to a request, if one has been set.
+    fn authenticate(&self, req: RequestBuilder) -> RequestBuilder {
+        match &self.upload_token {
+            Some(token) => req.bearer_auth(token),
+            None => req,
+        }
+    }
+
+    /// The server URL that API requests are made relative to.
+    pub fn base_url(&self) -> &Url {
+        &self.base_url
+    }
+
+    /// Build the URL uploaders can view a transfer's download activity at.
+    pub fn transfer_activity_url(&self, id: &str) -> Result<Url> {
+        self.api_url(&format!("transfer/{id}/activity"))
+    }
+
+    /// Build the URL of an endpoint in the versioned API.
+    fn api_url(&self, path: &str) -> Result<Url> {
+        Ok(self.base_url.join(&format!("v{API_VERSION}/{path}"))?)
+    }
+
     pub fn get_server_config(&self) -> Result<ServerConfigurationResponse> {
         let res = self
-            .inner_client
-            .get(self.base_url.join("configuration")?)
-            .send()
+            .send(
+                self.limit(
+                    self.authenticate(self.inner_client.get(self.api_url("configuration")?)),
+                ),
+            )
             .context("server configuration request failed before response")?;
 
-        if !res.status().is_success() {
-            bail!(
-                "server returned status code {} from get server configuration request. {}",
-                res.status(),
-                res.text().unwrap_or_default(),
-            );
+        ensure_api_supported(&res)?;
+
+        Ok(ensure_success(res, "get server configuration")?
+            .json::<ServerConfigurationResponse>()?)
+    }
+
+    /// Upload a transfer, including the solution to the server's proof-of-work challenge if it requires one
+    /// and how long to keep it for instead of the server's default.
+    ///
+    /// The progress bar is advanced as the data is sent.
+    pub fn create_transfer(
+        &self,
+        body: Bytes,
+        progress: &ProgressBar,
+        proof_of_work: Option<String>,
+        expire_after: Option<Duration>,
+        options: &TransferOptions,
+    ) -> Result<CreateTransferResponse> {
+        let checksum = blake3::hash(&body);
+        let mut req = transfer_headers(
+            self.authenticate(self.inner_client.post(self.api_url("transfer")?)),
+            checksum,
+            options,
+        );
+        if let Some(proof_of_work) = proof_of_work {
+            req = req.header("X-Xfer-Proof-Of-Work", proof_of_work);
+        }
+        if let Some(expire_after) = expire_after {
+            req = req.header(EXPIRE_AFTER_HEADER, expire_after.as_secs());
         }
-        Ok(res.json::<ServerConfigurationResponse>()?)
+        let req = req.header(header::CONTENT_TYPE, "application/octet-stream");
+        // The body is streamed to track its progress, so is recreated for each attempt.
+        let res = self
+            .send_with(|| {
+                progress.reset();
+                req.try_clone()
+                    .expect("request without a body should be cloneable")
+                    .body(self.transfer_body(body.clone(), Some(progress)))
+            })
+            .context("create transfer request failed before response")?;
+        Ok(ensure_not_busy(res, "create transfer")?.json::<CreateTransferResponse>()?)
     }
 
-    pub fn create_transfer(&self, body: Vec<u8>) -> Result<CreateTransferResponse> {
+    /// Reserve a transfer of `size` bytes whose data is uploaded in chunks afterwards, optionally kept
+    /// for a different time than the server's default.
+    pub fn reserve_transfer(
+        &self,
+        size: u64,
+        proof_of_work: Option<String>,
+        expire_after: Option<Duration>,
+    ) -> Result<ReserveTransferResponse> {
+        let mut req = self
+            .authenticate(self.inner_client.post(self.api_url("transfer/reserve")?))
+            .json(&serde_json::json!({ "size": size }));
+        if let Some(proof_of_work) = proof_of_work {
+            req = req.header("X-Xfer-Proof-Of-Work", proof_of_work);
+        }
+        if let Some(expire_after) = expire_after {
+            req = req.header(EXPIRE_AFTER_HEADER, expire_after.as_secs());
+        }
         let res = self
+            .send(self.limit(req))
+            .context("reserve transfer request failed before response")?;
+        ensure_api_supported(&res)?;
+        Ok(ensure_success(res, "reserve transfer")?.json::<ReserveTransferResponse>()?)
+    }
+
+    /// Upload the chunk of a reserved transfer's data that starts `offset` bytes into it.
+    pub fn upload_transfer_chunk(
+        &self,
+        reservation: &ReserveTransferResponse,
+        offset: u64,
+        body: Bytes,
+    ) -> Result<TransferChunkResponse> {
+        let req = self
             .inner_client
-            .post(self.base_url.join("transfer")?)
+            .put(self.api_url(&format!("transfer/{}/chunks/{offset}", reservation.id))?)
             .header(header::CONTENT_TYPE, "application/octet-stream")
-            .body(body)
-            .timeout(Duration::from_secs(48 * 60 * 60)) // 48 hours.
-            .send()
-            .context("create transfer request failed before response")?;
-        if !res.status().is_success() {
-            bail!(
-                "server returned status code {} from create transfer request. {}",
-                res.status(),
-                res.text().unwrap_or_default(),
-            );
+            .header("X-Xfer-Upload-Token", &reservation.upload_token);
+        // The body is streamed to limit its rate, so is recreated for each attempt.
+        let res = self
+            .send_with(|| {
+                req.try_clone()
+                    .expect("request without a body should be cloneable")
+                    .body(self.transfer_body(body.clone(), None))
+            })
+            .context("upload chunk request failed before response")?;
+        Ok(ensure_not_busy(res, "upload chunk")?.json::<TransferChunkResponse>()?)
+    }
+
+    /// Finish a transfer whose data was uploaded in chunks, with the checksum of all of its data.
+    pub fn complete_chunked_transfer(
+        &self,
+        reservation: &ReserveTransferResponse,
+        checksum: blake3::Hash,
+        options: &TransferOptions,
+    ) -> Result<CreateTransferResponse> {
+        let req = transfer_headers(
+            self.inner_client
+                .post(self.api_url(&format!("transfer/{}/complete", reservation.id))?),
+            checksum,
+            options,
+        )
+        .header("X-Xfer-Upload-Token", &reservation.upload_token);
+        let res = self
+            .send(self.limit(req))
+            .context("complete transfer request failed before response")?;
+        Ok(ensure_not_busy(res, "complete transfer")?.json::<CreateTransferResponse>()?)
+    }
+
+    /// Open a relay to send a transfer of `size` bytes directly to the recipient without it being stored on the server.
+    pub fn open_relay(
+        &self,
+        size: u64,
+        proof_of_work: Option<String>,
+    ) -> Result<OpenRelayResponse> {
+        let mut req = self
+            .authenticate(self.inner_client.post(self.api_url("relay")?))
+            .json(&serde_json::json!({ "size": size }));
+        if let Some(proof_of_work) = proof_of_work {
+            req = req.header("X-Xfer-Proof-Of-Work", proof_of_work);
         }
-        Ok(res.json::<CreateTransferResponse>()?)
+        let res = self
+            .send(self.limit(req))
+            .context("open relay request failed before response")?;
+        ensure_api_supported(&res)?;
+        Ok(ensure_success(res, "open relay")?.json::<OpenRelayResponse>()?)
     }
 
-    pub fn download_transfer(&self, id: &str) -> Result<Response> {
+    /// Send the data for a relay, returning once the recipient has received all of it.
+    ///
+    /// The progress bar is advanced as the data is sent.
+    pub fn upload_relay(
+        &self,
+        relay: &OpenRelayResponse,
+        body: Bytes,
+        progress: &ProgressBar,
+    ) -> Result<()> {
         let res = self
             .inner_client
-            .get(self.base_url.join(&format!("transfer/{id}"))?)
-            .timeout(Duration::from_secs(48 * 60 * 60)) // 48 hours.
+            .put(self.api_url(&format!("relay/{}", relay.id))?)
+            .header(header::CONTENT_TYPE, "application/octet-stream")
+            .header("X-Xfer-Upload-Token", &relay.upload_token)
+            .body(self.transfer_body(body, Some(progress)))
             .send()
+            .context("relay upload request failed before response")?;
+        ensure_success(res, "relay upload")?;
+        Ok(())
+    }
+
+    /// Download a transfer, optionally resuming from a byte offset.
+    ///
+    /// When `resume_from` is given the remainder of the transfer after the offset is requested, provided
+    /// the transfer still has the given ETag. Servers that can't resume respond with the whole transfer,
+    /// which callers can tell apart by the `206 Partial Content` status of a resumed download.
+    ///
+    /// Resuming a download of a transfer with a download limit needs the `download_token` sent
+    /// with the download being resumed once all of its downloads have been taken.
+    pub fn download_transfer(
+        &self,
+        id: &str,
+        resume_from: Option<(u64, &str)>,
+        download_token: Option<&str>,
+    ) -> Result<Response> {
+        let mut req = self
+            .inner_client
+            .get(self.api_url(&format!("transfer/{id}"))?);
+        if let Some((offset, etag)) = resume_from {
+            req = req
+                .header(header::RANGE, format!("bytes={offset}-"))
+                .header(header::IF_RANGE, etag);
+        }
+        if let Some(download_token) = download_token {
+            req = req.header(DOWNLOAD_TOKEN_HEADER, download_token);
+        }
+        let res = self
+            .send(self.watch(req))
             .context("download transfer request failed before response")?;
-        if !res.status().is_success() {
+        ensure_api_supported(&res)?;
+        ensure_success(res, "download transfer")
+    }
+
+    /// Download the part of a transfer in `range`, provided the transfer still has the given ETag.
+    ///
+    /// The request is only sent once, so callers can decide whether to retry it or leave the range
+    /// to another connection when the server is busy.
+    pub fn download_transfer_range(
+        &self,
+        id: &str,
+        range: Range<u64>,
+        etag: &str,
+        download_token: Option<&str>,
+    ) -> Result<Response> {
+        let mut req = self
+            .inner_client
+            .get(self.api_url(&format!("transfer/{id}"))?)
+            .header(
+                header::RANGE,
+                format!("bytes={}-{}", range.start, range.end - 1),
+            )
+            .header(header::IF_RANGE, etag);
+        if let Some(download_token) = download_token {
+            req = req.header(DOWNLOAD_TOKEN_HEADER, download_token);
+        }
+        let res = self
+            .watch(req)
+            .send()
+            .map_err(explain_send_error)
+            .context("download transfer range request failed before response")?;
+        ensure_api_supported(&res)?;
+        let res = ensure_success(res, "download transfer range")?;
+        if res.status() != StatusCode::PARTIAL_CONTENT {
             bail!(
-                "server returned status code {} from download transfer request. {}",
-                res.status(),
-                res.text().unwrap_or_default(),
+                "server sent the whole transfer instead of the requested range - it may have changed since the download started"
             );
         }
         Ok(res)
     }
 
+    /// Delete a transfer before it expires using the deletion token issued when it was uploaded.
+    pub fn delete_transfer(&self, id: &str, deletion_token: &str) -> Result<()> {
+        let res = self
+            .send(
+                self.limit(
+                    self.inner_client
+                        .delete(self.api_url(&format!("transfer/{id}"))?)
+                        .header("X-Xfer-Deletion-Token", deletion_token),
+                ),
+            )
+            .context("delete transfer request failed before response")?;
+        ensure_api_supported(&res)?;
+        ensure_success(res, "delete transfer")?;
+        Ok(())
+    }
+
+    /// Store the encrypted manifest of a transfer's contents using the deletion token issued when it was uploaded.
+    pub fn upload_manifest(&self, id: &str, deletion_token: &str, manifest: Vec<u8>) -> Result<()> {
+        let res = self
+            .send(
+                self.limit(
+                    self.inner_client
+                        .put(self.api_url(&format!("transfer/{id}/manifest"))?)
+                        .header("X-Xfer-Deletion-Token", deletion_token)
+                        .header(header::CONTENT_TYPE, "application/octet-stream")
+                        .body(manifest),
+                ),
+            )
+            .context("upload manifest request failed before response")?;
+        ensure_api_supported(&res)?;
+        ensure_success(res, "upload manifest")?;
+        Ok(())
+    }
+
+    /// Fetch the encrypted manifest of a transfer's contents, or `None` if its uploader didn't store one.
+    ///
+    /// Older servers respond with not found when there's no manifest, the same way they do for
+    /// transfers that don't exist, so check the transfer exists first.
+    pub fn transfer_manifest(&self, id: &str) -> Result<Option<Vec<u8>>> {
+        let res = self
+            .send(
+                self.limit(
+                    self.inner_client
+                        .get(self.api_url(&format!("transfer/{id}/manifest"))?),
+                ),
+            )
+            .context("transfer manifest request failed before response")?;
+        ensure_api_supported(&res)?;
+        if matches!(res.status(), StatusCode::NO_CONTENT | StatusCode::NOT_FOUND) {
+            return Ok(None);
+        }
+        Ok(Some(
+            ensure_success(res, "transfer manifest")?.bytes()?.to_vec(),
+        ))
+    }
+
     pub fn transfer_metadata(&self, id: &str) -> Result<Response> {
         let res = self
-            .inner_client
-            .head(self.base_url.join(&format!("transfer/{id}"))?)
-            .send()
+            .send(
+                self.limit(
+                    self.inner_client
+                        .head(self.api_url(&format!("transfer/{id}"))?),
+                ),
+            )
             .context("transfer metadata request failed before response")?;
-        if !res.status().is_success() {
-            bail!(
-                "server returned status code {} from transfer metadata request. {}",
+        ensure_api_supported(&res)?;
+        ensure_success(res, "transfer metadata")
+    }
+}
+
+/// Get how long the server asked the client to wait before retrying a request, in seconds.
+fn retry_after(res: &Response) -> Option<Duration> {
+    res.headers()
+        .get(header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok())
+        .map(Duration::from_secs)
+}
+
+/// Whether a response status indicates a temporary problem worth retrying the request for.
+pub fn is_retry_status(status: StatusCode) -> bool {
+    RETRY_STATUSES.contains(&status)
+}
+
+/// Whether a request failed because of a connection problem that may not happen again.
+fn is_transient(err: &reqwest::Error) -> bool {
+    err.is_connect() || err.is_timeout() || err.is_request() || err.is_body()
+}
+
+/// Whether a request failed because the server's TLS certificate couldn't be verified.
+fn is_certificate_error(err: &reqwest::Error) -> bool {
+    let mut source = std::error::Error::source(err);
+    while let Some(err) = source {
+        // TLS errors are passed up through the connection wrapped in `io::Error`s, which don't
+        // report the error they wrap as their source.
+        let mut inner = Some(err);
+        while let Some(err) = inner {
+            if let Some(rustls::Error::InvalidCertificate(_)) = err.downcast_ref::<rustls::Error>()
+            {
+                return true;
+            }
+            inner = err
+                .downcast_ref::<std::io::Error>()
+                .and_then(|err| err.get_ref())
+                .map(|err| err as &(dyn std::error::Error + 'static));
+        }
+        source = err.source();
+    }
+    false
+}
+
+/// Add a hint on how to fix failing to send a request where there is one.
+fn explain_send_error(err: reqwest::Error) -> anyhow::Error {
+    match is_certificate_error(&err) {
+        true => anyhow::Error::new(err).context(
+            "the server's TLS certificate could not be verified - if it is signed by a private certificate authority, pass its certificate with --ca-cert",
+        ),
+        false => err.into(),
+    }
+}
+
+/// How long to wait before retrying after the given attempt, doubling each time up to a limit.
+///
+/// The delay is randomised between half and all of it, so clients that failed together don't
+/// all retry at the same moment.
+pub fn retry_delay(attempt: u32) -> Duration {
+    let delay = RETRY_BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+        .min(RETRY_MAX_DELAY);
+    delay.mul_f64(0.5 + fastrand::f64() / 2.0)
+}
+
+/// Add the headers describing a new transfer's data and options to a request.
+fn transfer_headers(
+    req: RequestBuilder,
+    checksum: blake3::Hash,
+    options: &TransferOptions,
+) -> RequestBuilder {
+    let mut req = req
+        .header("X-Xfer-Checksum", format!("blake3={}", checksum.to_hex()))
+        .header("X-Xfer-Encryption-Version", FORMAT_VERSION.to_string());
+    if let Some(claim_webhook) = options.claim_webhook {
+        req = req.header("X-Xfer-Claim-Webhook", claim_webhook.as_str());
+    }
+    if let Some(notify_email) = options.notify_email {
+        req = req.header("X-Xfer-Notify-Email", notify_email);
+    }
+    if let Some(max_downloads) = options.max_downloads {
+        req = req.header("X-Xfer-Max-Downloads", max_downloads);
+    }
+    req
+}
+
+/// Fail unless a request succeeded, with a [`RetryLaterError`] when the server is too busy and says when to try again.
+fn ensure_not_busy(res: Response, request: &str) -> Result<Response> {
+    if res.status() == StatusCode::SERVICE_UNAVAILABLE
+        && let Some(retry_after) = retry_after(&res)
+    {
+        return Err(RetryLaterError {
+            retry_after,
+            message: error_message(res),
+        }
+        .into());
+    }
+    ensure_success(res, request)
+}
+
+/// Fail with a [`StatusError`] unless a request succeeded.
+fn ensure_success(res: Response, request: &str) -> Result<Response> {
+    if !res.status().is_success() {
+        return Err(StatusError {
+            status: res.status(),
+            message: format!(
+                "server returned status code {} from {request} request. {}",
                 res.status(),
-                res.text().unwrap_or_default(),
-            );
+                error_message(res),
+            ),
         }
-        Ok(res)
+        .into());
     }
+    Ok(res)
+}
+
+/// Fail with a clear error when a request was not found because the server doesn't speak this client's API version.
+fn ensure_api_supported(res: &Response) -> Result<()> {
+    let server_version = res
+        .headers()
+        .get(API_VERSION_HEADER)
+        .and_then(|v| v.to_str().ok());
+    if res.status() == StatusCode::NOT_FOUND
+        && server_version != Some(API_VERSION.to_string().as_str())
+    {
+        bail!(
+            "server does not support version {API_VERSION} of the xfer API, it may be too old or too new for this client - try updating the client or ask the server operator to update the server"
+        );
+    }
+    Ok(())
+}
+
+/// Extract the error message from an unsuccessful response, falling back to the raw body for older servers.
+fn error_message(res: Response) -> String {
+    let body = res.text().unwrap_or_default();
+    serde_json::from_str::<ErrorResponse>(&body).map_or(body, |err| err.error)
 }
@@ -0,0 +1,538 @@
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::{self, Cursor, Read, Write},
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
+use zip::{ZipArchive, result::ZipResult};
+
+/// Magic bytes at the start of gzip compressed data.
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+/// Magic bytes at the start of a zstd frame.
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+/// Magic bytes at the start of a zip archive, or of an empty one.
+const ZIP_MAGIC: &[&[u8]] = &[b"PK\x03\x04", b"PK\x05\x06"];
+
+/// Kind of archive transfers are packed into.
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ArchiveFormat {
+    /// A tar archive, compressed as chosen with `--compression`.
+    Tar,
+    /// A zip archive with each file compressed with deflate, which most platforms can open natively.
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// Detect the format of an archive from its header.
+    ///
+    /// Tar archives have no header of their own, so anything that isn't a zip archive is read as tar.
+    pub fn detect(archive: &[u8]) -> Self {
+        match ZIP_MAGIC.iter().any(|magic| archive.starts_with(magic)) {
+            true => Self::Zip,
+            false => Self::Tar,
+        }
+    }
+}
+
+/// Compression applied to transfer archives.
+///
+/// Each format starts with its own magic bytes, so the compression of a downloaded archive is
+/// detected from its header rather than recorded separately.
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Compression {
+    /// Widely supported, but slow on large transfers.
+    Gzip,
+    /// Much faster than gzip at a similar or better ratio.
+    Zstd,
+}
+
+impl Compression {
+    /// Compression levels the format supports.
+    pub fn levels(self) -> RangeInclusive<i32> {
+        match self {
+            Self::Gzip => 0..=9,
+            Self::Zstd => 1..=22,
+        }
+    }
+
+    /// Level used when none is chosen.
+    pub fn default_level(self) -> i32 {
+        match self {
+            Self::Gzip => 6,
+            Self::Zstd => 3,
+        }
+    }
+
+    /// Detect the compression of an archive from its header, or `None` if it isn't compressed.
+    pub fn detect(archive: &[u8]) -> Option<Self> {
+        if archive.starts_with(GZIP_MAGIC) {
+            Some(Self::Gzip)
+        } else if archive.starts_with(ZSTD_MAGIC) {
+            Some(Self::Zstd)
+        } else {
+            None
+        }
+    }
+
+    /// Ensure the format supports a compression level.
+    pub fn check_level(self, level: i32) -> Result<()> {
+        if !self.levels().contains(&level) {
+            bail!(
+                "compression level must be between {} and {} for this format (was {level})",
+                self.levels().start(),
+                self.levels().end()
+            );
+        }
+        Ok(())
+    }
+
+    /// Compress everything written to the encoder into `writer` at `level`, or the default level.
+    pub fn encoder<W: Write>(self, writer: W, level: Option<i32>) -> Result<Encoder<W>> {
+        let level = level.unwrap_or(self.default_level());
+        self.check_level(level)?;
+        Ok(match self {
+            Self::Gzip => Encoder::Gzip(flate2::write::GzEncoder::new(
+                writer,
+                flate2::Compression::new(level as u32),
+            )),
+            Self::Zstd => Encoder::Zstd(zstd::Encoder::new(writer, level)?),
+        })
+    }
+
+    /// Decompress the data read from `reader`.
+    pub fn decoder<'a>(self, reader: impl Read + 'a) -> Result<Box<dyn Read + 'a>> {
+        Ok(match self {
+            Self::Gzip => Box::new(flate2::read::GzDecoder::new(reader)),
+            Self::Zstd => Box::new(zstd::Decoder::new(reader)?),
+        })
+    }
+}
+
+/// Writer that compresses transfer archives, or passes them through when they aren't compressed.
+pub enum Encoder<W: Write> {
+    Plain(W),
+    Gzip(flate2::write::GzEncoder<W>),
+    Zstd(zstd::Encoder<'static, W>),
+}
+
+impl<W: Write> Encoder<W> {
+    /// Finish compressing and return the writer the compressed data was written to.
+    pub fn finish(self) -> io::Result<W> {
+        match self {
+            Self::Plain(writer) => Ok(writer),
+            Self::Gzip(encoder) => encoder.finish(),
+            Self::Zstd(encoder) => encoder.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Plain(writer) => writer.write(buf),
+            Self::Gzip(encoder) => encoder.write(buf),
+            Self::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Plain(writer) => writer.flush(),
+            Self::Gzip(encoder) => encoder.flush(),
+            Self::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Write a tar archive into `writer`, compressing it if a compression is given.
+pub fn tar_writer<W: Write>(
+    compression: Option<Compression>,
+    writer: W,
+    level: Option<i32>,
+) -> Result<Encoder<W>> {
+    match compression {
+        Some(compression) => compression.encoder(writer, level),
+        None => Ok(Encoder::Plain(writer)),
+    }
+}
+
+/// Read the tar archive in decrypted transfer data, decompressing it with the compression
+/// [detected](Compression::detect) from its header.
+///
+/// Archives without a known compression header are read as plain tar, as sent by older clients
+/// and uploads made with `--no-compress`.
+pub fn tar_reader<'a>(
+    compression: Option<Compression>,
+    reader: impl Read + 'a,
+) -> Result<Box<dyn Read + 'a>> {
+    match compression {
+        Some(compression) => compression.decoder(reader),
+        None => Ok(Box::new(reader)),
+    }
+}
+
+/// Paths of the files and links in an archive, relative to the directory it would be unpacked into.
+///
+/// Directories are left out, as unpacking into one that already exists doesn't replace anything.
+pub fn archive_files(archive: &[u8]) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    match ArchiveFormat::detect(archive) {
+        ArchiveFormat::Tar => {
+            let mut archive = tar::Archive::new(tar_reader(Compression::detect(archive), archive)?);
+            for entry in archive.entries()? {
+                let entry = entry?;
+                if !entry.header().entry_type().is_dir() {
+                    files.push(entry.path()?.into_owned());
+                }
+            }
+        }
+        ArchiveFormat::Zip => {
+            let mut archive = ZipArchive::new(Cursor::new(archive))?;
+            for index in 0..archive.len() {
+                let file = archive.by_index_raw(index)?;
+                if !file.is_dir()
+                    && let Some(path) = file.enclosed_name()
+                {
+                    files.push(path);
+                }
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Listing of what a transfer archive holds, uploaded encrypted alongside it so recipients can see
+/// what's inside before downloading it.
+#[derive(Serialize, Deserialize)]
+pub struct Manifest {
+    /// Hex-encoded BLAKE3 hash of the whole archive before it was encrypted, so downloads can check
+    /// they unpack exactly what was uploaded.
+    ///
+    /// Not available for manifests uploaded by older clients.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archive_blake3: Option<String>,
+    pub entries: Vec<ManifestEntry>,
+}
+
+/// A file, directory or link in a [`Manifest`].
+#[derive(Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Path relative to the directory the archive would be unpacked into.
+    pub path: String,
+    pub kind: ManifestEntryKind,
+    /// Size of the file's contents in bytes, or 0 for anything else.
+    pub size: u64,
+    /// Hex-encoded BLAKE3 hash of the file's contents, for regular files.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blake3: Option<String>,
+    /// Path a symbolic or hard link points to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ManifestEntryKind {
+    File,
+    Directory,
+    Symlink,
+    Hardlink,
+}
+
+impl Manifest {
+    /// List the contents of an archive by reading it back, hashing it and each file as it goes.
+    pub fn from_archive(archive: &[u8]) -> Result<Self> {
+        let mut entries = Vec::new();
+        match ArchiveFormat::detect(archive) {
+            ArchiveFormat::Tar => {
+                let mut archive =
+                    tar::Archive::new(tar_reader(Compression::detect(archive), archive)?);
+                for entry in archive.entries()? {
+                    let mut entry = entry?;
+                    let path = entry.path()?.to_string_lossy().into_owned();
+                    let target = entry
+                        .link_name()?
+                        .map(|target| target.to_string_lossy().into_owned());
+                    let entry_type = entry.header().entry_type();
+                    entries.push(if entry_type.is_dir() {
+                        ManifestEntry::new(path, ManifestEntryKind::Directory)
+                    } else if entry_type.is_symlink() {
+                        ManifestEntry::new(path, ManifestEntryKind::Symlink).with_target(target)
+                    } else if entry_type.is_hard_link() {
+                        ManifestEntry::new(path, ManifestEntryKind::Hardlink).with_target(target)
+                    } else if entry_type.is_file() {
+                        ManifestEntry::file(path, &mut entry)?
+                    } else {
+                        // Other kinds of entries aren't unpacked, so aren't listed either.
+                        continue;
+                    });
+                }
+            }
+            ArchiveFormat::Zip => {
+                let mut archive = ZipArchive::new(Cursor::new(archive))?;
+                for index in 0..archive.len() {
+                    let mut file = archive.by_index(index)?;
+                    let Some(path) = file.enclosed_name() else {
+                        continue;
+                    };
+                    let path = path.to_string_lossy().into_owned();
+                    entries.push(if file.is_dir() {
+                        ManifestEntry::new(path, ManifestEntryKind::Directory)
+                    } else if file.is_symlink() {
+                        let mut target = String::new();
+                        file.read_to_string(&mut target)?;
+                        ManifestEntry::new(path, ManifestEntryKind::Symlink)
+                            .with_target(Some(target))
+                    } else {
+                        ManifestEntry::file(path, &mut file)?
+                    });
+                }
+            }
+        }
+        Ok(Self {
+            archive_blake3: Some(blake3::hash(archive).to_hex().to_string()),
+            entries,
+        })
+    }
+
+    /// Serialize the manifest into the compressed form it's uploaded in.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        Ok(zstd::encode_all(
+            serde_json::to_vec(self)?.as_slice(),
+            Compression::Zstd.default_level(),
+        )?)
+    }
+
+    /// Read a manifest from the compressed form it's uploaded in.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Ok(serde_json::from_slice(&zstd::decode_all(bytes)?)?)
+    }
+}
+
+impl ManifestEntry {
+    fn new(path: String, kind: ManifestEntryKind) -> Self {
+        Self {
+            // Tar archives end directory paths with a slash, zip archives may not.
+            path: path.trim_end_matches('/').to_string(),
+            kind,
+            size: 0,
+            blake3: None,
+            target: None,
+        }
+    }
+
+    /// List a regular file, reading its contents to hash them.
+    fn file(path: String, contents: &mut impl Read) -> io::Result<Self> {
+        let mut hasher = blake3::Hasher::new();
+        let size = io::copy(contents, &mut hasher)?;
+        Ok(Self {
+            size,
+            blake3: Some(hasher.finalize().to_hex().to_string()),
+            ..Self::new(path, ManifestEntryKind::File)
+        })
+    }
+
+    fn with_target(self, target: Option<String>) -> Self {
+        Self { target, ..self }
+    }
+}
+
+/// What to restore from transfer archives when unpacking them.
+#[derive(Clone, Copy)]
+pub struct UnpackOptions {
+    /// Restore the file modes stored in the archive instead of giving files standard permissions.
+    ///
+    /// Set-user-ID, set-group-ID and sticky bits are never restored, and neither is ownership.
+    pub permissions: bool,
+    /// Restore the extended attributes stored in tar archives.
+    pub xattrs: bool,
+}
+
+/// Unpack a tar archive into `directory`.
+///
+/// Directories are created last, like [`tar::Archive::unpack`] does, so their permissions can't
+/// stop their contents from being unpacked.
+pub fn unpack_tar<R: Read>(
+    mut archive: tar::Archive<R>,
+    directory: &Path,
+    options: UnpackOptions,
+) -> io::Result<()> {
+    archive.set_unpack_xattrs(options.xattrs);
+    let mut directories = Vec::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        match entry.header().entry_type().is_dir() {
+            true => directories.push(entry),
+            false => unpack_tar_entry(&mut entry, directory, options)?,
+        }
+    }
+    directories.sort_by(|a, b| b.path_bytes().cmp(&a.path_bytes()));
+    for mut entry in directories {
+        unpack_tar_entry(&mut entry, directory, options)?;
+    }
+    Ok(())
+}
+
+fn unpack_tar_entry<R: Read>(
+    entry: &mut tar::Entry<R>,
+    directory: &Path,
+    options: UnpackOptions,
+) -> io::Result<()> {
+    // Entries that would be unpacked outside of the directory are skipped.
+    if entry.unpack_in(directory)? && !options.permissions {
+        reset_permissions(&directory.join(entry.path()?))?;
+    }
+    Ok(())
+}
+
+/// Unpack a zip archive into `directory`.
+pub fn unpack_zip(archive: Vec<u8>, directory: &Path, options: UnpackOptions) -> ZipResult<()> {
+    let mut archive = ZipArchive::new(Cursor::new(archive))?;
+    archive.extract(directory)?;
+    if !options.permissions {
+        for index in 0..archive.len() {
+            if let Some(path) = archive.by_index(index)?.enclosed_name() {
+                reset_permissions(&directory.join(path))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Standard permissions for a file or directory, only keeping whether its owner can execute it.
+#[cfg(unix)]
+pub fn standard_mode(metadata: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    match metadata.is_dir() || metadata.permissions().mode() & 0o100 != 0 {
+        true => 0o755,
+        false => 0o644,
+    }
+}
+
+/// Give an unpacked file or directory standard permissions instead of those stored in the archive.
+fn reset_permissions(path: &Path) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(path)?;
+    if metadata.is_symlink() {
+        return Ok(());
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(standard_mode(&metadata)))
+    }
+    // Other platforms can only mark files read-only, so make sure they're writable.
+    #[cfg(not(unix))]
+    {
+        let mut permissions = metadata.permissions();
+        permissions.set_readonly(false);
+        fs::set_permissions(path, permissions)
+    }
+}
+
+/// PAX records holding the extended attributes of a file, in the form [`tar::Archive`] restores them from.
+///
+/// Attributes with names that aren't valid UTF-8 can't be stored in PAX records, so are left out.
+#[cfg(unix)]
+pub fn xattr_records(path: &Path) -> io::Result<Vec<(String, Vec<u8>)>> {
+    let mut records = Vec::new();
+    for name in xattr::list(path)? {
+        if let Some(key) = name.to_str()
+            && let Some(value) = xattr::get(path, &name)?
+        {
+            records.push((format!("SCHILY.xattr.{key}"), value));
+        }
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(entry_type: tar::EntryType, size: u64) -> tar::Header {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(entry_type);
+        header.set_size(size);
+        header.set_mode(0o644);
+        header
+    }
+
+    fn entry<'a>(manifest: &'a Manifest, path: &str) -> &'a ManifestEntry {
+        manifest
+            .entries
+            .iter()
+            .find(|entry| entry.path == path)
+            .unwrap_or_else(|| panic!("{path} isn't listed"))
+    }
+
+    #[test]
+    fn manifest_round_trip() {
+        let mut builder =
+            tar::Builder::new(tar_writer(Some(Compression::Zstd), Vec::new(), None).unwrap());
+        builder
+            .append_data(
+                &mut header(tar::EntryType::Directory, 0),
+                "dir/",
+                io::empty(),
+            )
+            .unwrap();
+        builder
+            .append_data(
+                &mut header(tar::EntryType::Regular, 5),
+                "dir/file",
+                &b"hello"[..],
+            )
+            .unwrap();
+        builder
+            .append_link(&mut header(tar::EntryType::Symlink, 0), "link", "dir/file")
+            .unwrap();
+        let archive = builder.into_inner().unwrap().finish().unwrap();
+
+        let manifest = Manifest::from_bytes(
+            &Manifest::from_archive(&archive)
+                .unwrap()
+                .to_bytes()
+                .unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            manifest.archive_blake3,
+            Some(blake3::hash(&archive).to_hex().to_string())
+        );
+        assert_eq!(manifest.entries.len(), 3);
+        assert!(entry(&manifest, "dir").kind == ManifestEntryKind::Directory);
+        let file = entry(&manifest, "dir/file");
+        assert!(file.kind == ManifestEntryKind::File);
+        assert_eq!(file.size, 5);
+        assert_eq!(
+            file.blake3,
+            Some(blake3::hash(b"hello").to_hex().to_string())
+        );
+        let link = entry(&manifest, "link");
+        assert!(link.kind == ManifestEntryKind::Symlink);
+        assert_eq!(link.target.as_deref(), Some("dir/file"));
+    }
+
+    #[test]
+    fn manifest_lists_zip_archives() {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default();
+        writer.add_directory("dir/", options).unwrap();
+        writer.start_file("dir/file", options).unwrap();
+        writer.write_all(b"hello").unwrap();
+        let archive = writer.finish().unwrap().into_inner();
+
+        let manifest = Manifest::from_archive(&archive).unwrap();
+        assert_eq!(manifest.entries.len(), 2);
+        assert!(entry(&manifest, "dir").kind == ManifestEntryKind::Directory);
+        let file = entry(&manifest, "dir/file");
+        assert_eq!(file.size, 5);
+        assert_eq!(
+            file.blake3,
+            Some(blake3::hash(b"hello").to_hex().to_string())
+        );
+    }
+}
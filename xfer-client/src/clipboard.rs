@@ -0,0 +1,112 @@
+#[cfg(all(
+    unix,
+    not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+))]
+pub use server::{copy, serve_if_requested};
+
+/// Place text on the clipboard.
+#[cfg(not(all(
+    unix,
+    not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+)))]
+pub fn copy(text: &str) -> anyhow::Result<()> {
+    Ok(arboard::Clipboard::new()?.set_text(text)?)
+}
+
+/// Serve the clipboard if this process was started to do so, returning whether it was.
+///
+/// Clipboards on this platform outlive the process that set them, so that is never needed.
+#[cfg(not(all(
+    unix,
+    not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+)))]
+pub fn serve_if_requested() -> bool {
+    false
+}
+
+/// X11 and Wayland clipboards are emptied when the process that set them exits, so copied text is
+/// handed to a detached copy of the client that keeps serving it until something else is copied.
+#[cfg(all(
+    unix,
+    not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+))]
+mod server {
+    use anyhow::{Context, Result, bail};
+    use arboard::{Clipboard, SetExtLinux};
+    use std::{
+        env,
+        io::{self, BufRead, BufReader, Write},
+        os::unix::process::CommandExt,
+        process::{Command, Stdio},
+    };
+
+    /// Environment variable that makes the client serve the clipboard instead of running a command.
+    const SERVE_CLIPBOARD_ENV: &str = "XFER_CLIENT_SERVE_CLIPBOARD";
+
+    /// Reply a clipboard server sends once it holds the clipboard.
+    const SERVING_REPLY: &str = "ok";
+
+    /// Place text on the clipboard, returning once a clipboard server has taken it.
+    pub fn copy(text: &str) -> Result<()> {
+        let mut server =
+            Command::new(env::current_exe().context("failed to find the xfer executable")?)
+                .env(SERVE_CLIPBOARD_ENV, "1")
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                // Keep it out of the terminal's process group so interrupting xfer doesn't empty the clipboard.
+                .process_group(0)
+                .spawn()
+                .context("failed to start clipboard server")?;
+        server
+            .stdin
+            .take()
+            .expect("clipboard server stdin should be piped")
+            .write_all(text.as_bytes())
+            .context("failed to send text to clipboard server")?;
+        let mut reply = String::new();
+        BufReader::new(
+            server
+                .stdout
+                .take()
+                .expect("clipboard server stdout should be piped"),
+        )
+        .read_line(&mut reply)
+        .context("failed to read reply from clipboard server")?;
+        if reply.trim_end() == SERVING_REPLY {
+            return Ok(());
+        }
+        // The server exits when it can't take the clipboard.
+        let _ = server.wait();
+        match reply.trim_end() {
+            "" => bail!("clipboard server exited unexpectedly"),
+            err => bail!("{err}"),
+        }
+    }
+
+    /// Serve the clipboard if this process was started by [`copy`] to do so, returning whether it was.
+    ///
+    /// The text read from stdin is served until something else is copied, and whether the clipboard
+    /// could be taken is reported on stdout.
+    pub fn serve_if_requested() -> bool {
+        if env::var_os(SERVE_CLIPBOARD_ENV).is_none() {
+            return false;
+        }
+        let text = match io::read_to_string(io::stdin()) {
+            Ok(text) => text,
+            Err(err) => {
+                println!("failed to read text to copy: {err}");
+                return true;
+            }
+        };
+        match Clipboard::new() {
+            Ok(mut clipboard) => {
+                println!("{SERVING_REPLY}");
+                // Only returns once something else has been copied.
+                let _ = clipboard.set().wait().text(text);
+            }
+            Err(err) => println!("{err}"),
+        }
+        true
+    }
+}
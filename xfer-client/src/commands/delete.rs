@@ -0,0 +1,69 @@
+use super::NetworkOptions;
+use crate::{DEFAULT_SERVER_URL, ExecutableCommand, failure::Failure};
+use anyhow::Context;
+use clap::{Parser, ValueHint};
+use inquire::Confirm;
+use url::Url;
+
+/// Delete a transfer from a relay server before it expires.
+#[derive(Parser)]
+pub struct DeleteCommand {
+    /// Identifier of the transfer to delete.
+    ///
+    /// A full transfer key can also be given, in which case only the part before the slash is used.
+    #[clap(value_hint = ValueHint::Other)]
+    transfer_id: String,
+
+    /// Deletion token shown when the transfer was uploaded.
+    #[clap(
+        value_hint = ValueHint::Other,
+        env = "XFER_CLIENT_DELETION_TOKEN",
+        hide_env_values = true
+    )]
+    deletion_token: String,
+
+    /// Skip all confirmation dialogues.
+    #[clap(short = 'y', env = "XFER_CLIENT_NOCONFIRM", long = "yes")]
+    no_confirm: bool,
+
+    /// URL (including scheme) of the server the transfer was uploaded to.
+    #[clap(
+        short = 's',
+        env = "XFER_CLIENT_RELAY_SERVER",
+        long = "server",
+        default_value = DEFAULT_SERVER_URL,
+        value_hint = ValueHint::Url
+    )]
+    server: Url,
+
+    #[clap(flatten)]
+    network: NetworkOptions,
+}
+
+impl ExecutableCommand for DeleteCommand {
+    fn run(self) -> anyhow::Result<()> {
+        let transfer_id = self
+            .transfer_id
+            .split_once('/')
+            .map_or(self.transfer_id.as_str(), |(id, _)| id);
+
+        // Ensure the user wants to continue.
+        if !self.no_confirm
+            && !Confirm::new(&format!(
+                "Are you sure you want to delete transfer '{transfer_id}'? It can't be downloaded afterwards."
+            ))
+            .with_default(false)
+            .prompt()?
+        {
+            return Err(Failure::aborted().into());
+        }
+
+        self.network
+            .api_client(&self.server)?
+            .delete_transfer(transfer_id, &self.deletion_token)
+            .context("failed to delete transfer - it may have already expired, or the deletion token may be incorrect")?;
+        println!("Successfully deleted transfer '{transfer_id}'");
+
+        Ok(())
+    }
+}
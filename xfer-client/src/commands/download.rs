@@ -1,14 +1,39 @@
+use super::{
+    NetworkOptions, OutputFormat, decryption_key, format_expiry, print_json, rfc3339_expiry,
+    show_bytes, show_spinner, transfer_metadata,
+};
 use crate::{
-    DEFAULT_SERVER_URL, ExecutableCommand, PROGRESS_BAR_TICKRATE, api_client::XferApiClient,
-    cryptography::Cryptography,
+    DEFAULT_SERVER_URL, ExecutableCommand, PROGRESS_BAR_TICKRATE,
+    api_client::{DOWNLOAD_TOKEN_HEADER, StatusError, XferApiClient, is_retry_status, retry_delay},
+    archive::{
+        ArchiveFormat, Compression, Manifest, UnpackOptions, archive_files, tar_reader, unpack_tar,
+        unpack_zip,
+    },
+    failure::{Failure, FailureKind},
 };
 use anyhow::{Context, bail};
 use clap::{Parser, ValueHint};
 use indicatif::{DecimalBytes, ProgressBar};
-use inquire::Confirm;
-use std::{fs, io::Cursor, path::PathBuf};
+use inquire::{Confirm, Select};
+use reqwest::{StatusCode, blocking::Response};
+use serde::Serialize;
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Cursor},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    thread,
+};
 use tar::Archive;
 use url::Url;
+use xfer_crypto::Cryptography;
+
+/// Size of the segments a transfer is split into to download it over several connections.
+const DOWNLOAD_SEGMENT_SIZE: u64 = 8 * 1024 * 1024;
 
 /// Download and decrypt a transfer from a relay server.
 #[derive(Parser)]
@@ -20,17 +45,49 @@ pub struct DownloadCommand {
     ///  - The first part is the key required to fetch the transfer.
     ///
     ///  - The second part is the key requried to decrypt the transfer.
+    ///
+    /// Transfers protected with a passphrase only have the first part, and the passphrase is asked for instead.
     #[clap(value_hint = ValueHint::Other)]
     transfer_key: String,
 
+    /// Passphrase the transfer was protected with, for transfer keys without a decryption key.
+    ///
+    /// The passphrase is asked for when this isn't given.
+    #[clap(
+        long = "passphrase",
+        env = "XFER_CLIENT_PASSPHRASE",
+        hide_env_values = true,
+        value_name = "PASSPHRASE"
+    )]
+    passphrase: Option<String>,
+
+    /// Age identity file to decrypt the transfer with, for transfers encrypted to an age recipient.
+    #[clap(
+        long = "identity",
+        short = 'i',
+        env = "XFER_CLIENT_IDENTITY",
+        value_hint = ValueHint::FilePath
+    )]
+    identity: Option<PathBuf>,
+
     /// Skip all confirmation dialogues.
     #[clap(short = 'y', env = "XFER_CLIENT_NOCONFIRM", long = "yes")]
     no_confirm: bool,
 
+    /// Don't prompt, show progress or print anything unless the download fails.
+    #[clap(
+        short = 'q',
+        long = "quiet",
+        env = "XFER_CLIENT_QUIET",
+        conflicts_with = "output_format"
+    )]
+    quiet: bool,
+
     /// Directory of where the transfer should be written after download.
     ///
     /// File transfers will be placed in this directory.
     /// Directory transfer will have their folder placed in this directory.
+    /// The directory is created if it doesn't exist.
     #[clap(short = 'o', env = "XFER_CLIENT_DOWNLOAD_DIRECTORY", long = "output", value_hint = ValueHint::DirPath)]
     directory: PathBuf,
 
@@ -43,78 +100,589 @@ pub struct DownloadCommand {
         value_hint = ValueHint::Url
     )]
     server: Url,
+
+    /// Replace files that already exist in the output directory without asking.
+    #[clap(short = 'f', long = "force", env = "XFER_CLIENT_FORCE")]
+    force: bool,
+
+    /// Unpack the transfer into a new subdirectory of the output directory, named after the transfer.
+    #[clap(
+        long = "subdirectory",
+        env = "XFER_CLIENT_SUBDIRECTORY",
+        conflicts_with = "force"
+    )]
+    subdirectory: bool,
+
+    /// Format to print the results in.
+    #[clap(
+        long = "output-format",
+        env = "XFER_CLIENT_OUTPUT_FORMAT",
+        value_enum,
+        default_value_t = OutputFormat::Text
+    )]
+    output_format: OutputFormat,
+
+    /// Give unpacked files standard permissions instead of restoring the ones stored in the transfer.
+    ///
+    /// Set-user-ID, set-group-ID and sticky bits and file ownership are never restored.
+    #[cfg(unix)]
+    #[clap(long = "no-permissions", env = "XFER_CLIENT_NO_PERMISSIONS")]
+    no_permissions: bool,
+
+    /// Restore the extended attributes stored in the transfer.
+    #[cfg(unix)]
+    #[clap(long = "xattrs", env = "XFER_CLIENT_XATTRS")]
+    xattrs: bool,
+
+    /// How many connections to download the transfer over at the same time, when the server can send parts of it.
+    ///
+    /// More connections can make downloads much faster over long distances, while 1 downloads the
+    /// transfer in a single request.
+    #[clap(
+        long = "connections",
+        env = "XFER_CLIENT_CONNECTIONS",
+        default_value_t = 4,
+        value_parser = clap::value_parser!(u8).range(1..=32)
+    )]
+    connections: u8,
+
+    #[clap(flatten)]
+    network: NetworkOptions,
+}
+
+/// Machine-readable results of the download command.
+#[derive(Serialize)]
+struct DownloadOutput<'a> {
+    id: &'a str,
+    path: &'a Path,
+    size_bytes: u64,
+    expires_at: Option<String>,
+}
+
+impl DownloadCommand {
+    /// Decide where to unpack an archive, making sure it won't replace existing files in the resolved
+    /// output `directory` unless the user agrees to it.
+    ///
+    /// The user can choose to unpack into a new subdirectory instead, and the download fails if
+    /// they can't be asked.
+    fn unpack_directory(
+        &self,
+        prog_bar: &ProgressBar,
+        directory: PathBuf,
+        archive: &[u8],
+        transfer_id: &str,
+    ) -> anyhow::Result<PathBuf> {
+        if self.force {
+            return Ok(directory);
+        }
+        show_spinner(prog_bar, "Checking for existing files");
+        let existing: Vec<PathBuf> = archive_files(archive)
+            .context("failed to read decrypted transfer archive contents - archive file may be malformed")?
+            .into_iter()
+            .filter(|path| directory.join(path).symlink_metadata().is_ok())
+            .collect();
+        let Some(first) = existing.first() else {
+            return Ok(directory);
+        };
+        let (existing_display, them) = match existing.len() {
+            1 => (format!("'{}' already exists", first.display()), "it"),
+            count => (
+                format!(
+                    "{count} files in this transfer already exist, including '{}',",
+                    first.display()
+                ),
+                "them",
+            ),
+        };
+        if self.no_confirm || self.quiet {
+            bail!(
+                "{existing_display} in the output directory - pass --force to replace {them} or --subdirectory to unpack the transfer into a new directory"
+            );
+        }
+
+        let subdirectory = unique_subdirectory(&directory, transfer_id);
+        let choice = prog_bar.suspend(|| {
+            Select::new(
+                &format!(
+                    "{existing_display} in '{}'. What would you like to do?",
+                    directory.display()
+                ),
+                vec![
+                    String::from("Replace the existing files"),
+                    format!("Unpack into '{}' instead", subdirectory.display()),
+                    String::from("Cancel the download"),
+                ],
+            )
+            .raw_prompt()
+        })?;
+        match choice.index {
+            0 => Ok(directory),
+            1 => Ok(subdirectory),
+            _ => Err(Failure::aborted().into()),
+        }
+    }
 }
 
 impl ExecutableCommand for DownloadCommand {
     fn run(self) -> anyhow::Result<()> {
         // Validate output directory.
-        if !self.directory.exists() {
-            bail!("the specified output directory does not exist");
-        }
         if self.directory.is_file() {
             bail!("output directory must be a directory and not a file");
         }
 
         // Split the key into the appropriate parts
-        let (transfer_id, decryption_key) = self
-            .transfer_key
-            .split_once("/")
-            .context("invalid transfer key - please ensure you have entered it correctly")?;
+        let (transfer_id, key) = match self.transfer_key.split_once("/") {
+            Some((transfer_id, key)) => (transfer_id, Some(key)),
+            None => (self.transfer_key.as_str(), None),
+        };
 
         // Obtain the transfer size from the server before downloading.
-        // The server must send the `Content-Length` header on HEAD request
-        // to display the transfer size pre-download.
-        let api_client = XferApiClient::new(&self.server);
-        let transfer_size = {
-            let res = api_client.transfer_metadata(transfer_id)
-                    .context(
-                    "failed to get transfer - transfer may have expired, transfer key may be incorrect, or server may have returned an error",
-                )?;
-            let content_length = res
-                .headers()
-                .get("Content-Length")
-                .map(|f| f.to_str().unwrap())
-                .unwrap_or("0")
-                .parse::<u64>()?;
-            DecimalBytes(content_length)
-        };
+        let api_client = self.network.api_client(&self.server)?;
+        let metadata = transfer_metadata(&api_client, transfer_id)?;
+        let transfer_size = DecimalBytes(metadata.size);
+        let etag = metadata.etag;
+        if let Some(expires_at) = metadata.expires_at
+            && self.output_format == OutputFormat::Text
+            && !self.quiet
+        {
+            println!("This transfer will expire {}", format_expiry(expires_at)?);
+        }
+        if metadata.downloads_remaining == Some(1) && !self.quiet {
+            eprintln!(
+                "Warning: this is the final download the uploader allowed, so the transfer will be removed and can't be downloaded again"
+            );
+        }
+        let decryption_key = decryption_key(
+            key,
+            self.passphrase.as_deref(),
+            self.identity.as_deref(),
+            !self.quiet,
+        )?;
 
         // Ensure the user wants to continue.
         if !self.no_confirm
+            && !self.quiet
             && !Confirm::new(&format!(
                 "Are you sure you want to download this transfer ({transfer_size})?",
             ))
             .with_default(false)
             .prompt()?
         {
-            return Ok(());
+            return Err(Failure::aborted().into());
         }
 
-        let prog_bar =
-            ProgressBar::new_spinner().with_message("Downloading encrypted transfer archive");
+        let prog_bar = match self.quiet {
+            true => ProgressBar::hidden(),
+            false => ProgressBar::new_spinner(),
+        };
         prog_bar.enable_steady_tick(PROGRESS_BAR_TICKRATE);
 
-        // Download & decrypt the archive and unpack it on disk.
-        let mut decrypted_archive = {
-            let res = api_client.download_transfer(transfer_id)?.bytes()?;
-            prog_bar.set_message("Decrypting transfer archive");
-            let archive = Cryptography::decrypt(&res, decryption_key).context(
-                "failed to decrypt transfer archive - ensure you entered the transfer key correctly",
-            )?;
-            Archive::new(Cursor::new(archive))
+        // Download the archive into a partial file next to the output, continuing where a previous
+        // attempt left off so an interrupted download doesn't have to start from scratch.
+        fs::create_dir_all(&self.directory).context("failed to create output directory")?;
+        // Resolving a path needs all of it to exist, so is only done once the directory has been created.
+        let output_directory = self
+            .directory
+            .canonicalize()
+            .context("failed to resolve output directory")?;
+        let partial_path = self.directory.join(format!(".{transfer_id}.xfer-download"));
+        // Downloads over several connections need the ETag to be sure every segment comes from the
+        // same transfer data, and a download already part way through over one connection is
+        // resumed as it was started.
+        match etag.as_deref() {
+            Some(etag)
+                if self.connections > 1
+                    && metadata.accepts_ranges
+                    && transfer_size.0 > DOWNLOAD_SEGMENT_SIZE
+                    && !partial_path.exists() =>
+            {
+                download_archive_parallel(
+                    &api_client,
+                    transfer_id,
+                    transfer_size.0,
+                    etag,
+                    &partial_path,
+                    usize::from(self.connections),
+                    &prog_bar,
+                )?
+            }
+            _ => download_archive(
+                &api_client,
+                transfer_id,
+                transfer_size.0,
+                etag.as_deref(),
+                &partial_path,
+                &prog_bar,
+            )?,
+        }
+
+        // Verify, decrypt & unpack the archive on disk.
+        show_spinner(&prog_bar, "Verifying transfer archive");
+        let archive =
+            fs::read(&partial_path).context("failed to read downloaded transfer archive")?;
+        if let Some(expected) = etag.as_deref().and_then(etag_checksum)
+            && blake3::hash(&archive) != expected
+        {
+            fs::remove_file(&partial_path)?;
+            bail!(
+                "downloaded transfer archive did not match the checksum sent by the server - the partial download was discarded, please try again"
+            );
+        }
+        // Decryption happens in a single pass over the whole archive, so its progress can't be shown.
+        show_spinner(&prog_bar, "Decrypting transfer archive");
+        let archive = Cryptography::decrypt(&archive, &decryption_key).context(Failure::new(
+            FailureKind::Decryption,
+            "failed to decrypt transfer archive - ensure you entered the transfer key or passphrase correctly",
+        ))?;
+        show_spinner(&prog_bar, "Verifying decrypted transfer archive");
+        match archive_checksum(&api_client, transfer_id, &decryption_key)? {
+            Some(expected) if blake3::hash(&archive) != expected => {
+                fs::remove_file(&partial_path)?;
+                return Err(Failure::new(
+                    FailureKind::Integrity,
+                    "decrypted transfer archive did not match the checksum recorded by its uploader - it was not unpacked",
+                )
+                .into());
+            }
+            Some(_) => {}
+            None if !self.quiet => prog_bar.suspend(|| {
+                eprintln!(
+                    "Warning: the uploader didn't record a checksum of this transfer's contents, so only decryption could verify it"
+                )
+            }),
+            None => {}
+        }
+        #[cfg(unix)]
+        let unpack_options = UnpackOptions {
+            permissions: !self.no_permissions,
+            xattrs: self.xattrs,
+        };
+        // Other platforms can only mark files read-only, so permissions are never restored there.
+        #[cfg(not(unix))]
+        let unpack_options = UnpackOptions {
+            permissions: false,
+            xattrs: false,
         };
-        prog_bar.set_message("Unpacking transfer archive");
-        fs::create_dir_all(&self.directory)?;
-        decrypted_archive
-            .unpack(self.directory.canonicalize()?)
-            .context("failed to unpack decrypted transfer archive contents - archive file may be malformed")?;
+        let directory = match self.subdirectory {
+            true => unique_subdirectory(&output_directory, transfer_id),
+            false => self.unpack_directory(&prog_bar, output_directory, &archive, transfer_id)?,
+        };
+        fs::create_dir_all(&directory)?;
+        match ArchiveFormat::detect(&archive) {
+            ArchiveFormat::Tar => {
+                show_bytes(
+                    &prog_bar,
+                    "Unpacking transfer archive",
+                    archive.len() as u64,
+                );
+                let compression = Compression::detect(&archive);
+                let archive = Archive::new(tar_reader(
+                    compression,
+                    prog_bar.wrap_read(Cursor::new(archive)),
+                )?);
+                unpack_tar(archive, &directory, unpack_options)
+            }
+            // Zip archives are read out of order, so their progress can't be shown.
+            ArchiveFormat::Zip => {
+                show_spinner(&prog_bar, "Unpacking transfer archive");
+                unpack_zip(archive, &directory, unpack_options).map_err(io::Error::other)
+            }
+        }
+        .context(
+            "failed to unpack decrypted transfer archive contents - archive file may be malformed",
+        )?;
+        fs::remove_file(&partial_path)?;
+        if let Err(err) = fs::remove_file(download_token_path(&partial_path))
+            && err.kind() != io::ErrorKind::NotFound
+        {
+            return Err(err).context("failed to remove download token");
+        }
         prog_bar.finish_and_clear();
 
-        println!(
-            "Successfully downloaded transfer to '{}'",
-            self.directory.canonicalize()?.display()
+        match self.output_format {
+            OutputFormat::Json => print_json(&DownloadOutput {
+                id: transfer_id,
+                path: &directory,
+                size_bytes: transfer_size.0,
+                expires_at: rfc3339_expiry(metadata.expires_at),
+            })?,
+            OutputFormat::Text if self.quiet => {}
+            OutputFormat::Text => println!(
+                "Successfully downloaded transfer to '{}'",
+                directory.display()
+            ),
+        }
+
+        Ok(())
+    }
+}
+
+/// Download a transfer archive to `path`, resuming from the data already in the file if possible.
+///
+/// A partial file is kept when the download is interrupted so running the command again can resume it.
+/// Interruptions are resumed automatically as many times as the client retries requests, as long as
+/// the server sent an ETag to resume with.
+fn download_archive(
+    api_client: &XferApiClient,
+    transfer_id: &str,
+    size: u64,
+    etag: Option<&str>,
+    path: &Path,
+    prog_bar: &ProgressBar,
+) -> anyhow::Result<()> {
+    let token_path = download_token_path(path);
+    let mut attempt = 1;
+    loop {
+        let existing = fs::metadata(path).map_or(0, |metadata| metadata.len());
+        if existing == size && etag.is_some() {
+            return Ok(());
+        }
+
+        // Resuming relies on the ETag to be sure the partial file belongs to the same transfer data.
+        let resume_from = etag.filter(|_| existing > 0 && existing < size);
+        let mut res = api_client.download_transfer(
+            transfer_id,
+            resume_from.map(|etag| (existing, etag)),
+            fs::read_to_string(&token_path).ok().as_deref(),
+        )?;
+        save_download_token(&res, &token_path)?;
+        let mut file = if res.status() == StatusCode::PARTIAL_CONTENT {
+            show_bytes(
+                prog_bar,
+                format!(
+                    "Resuming download of encrypted transfer archive from {}",
+                    DecimalBytes(existing)
+                ),
+                size,
+            );
+            prog_bar.set_position(existing);
+            OpenOptions::new().append(true).open(path)?
+        } else {
+            show_bytes(prog_bar, "Downloading encrypted transfer archive", size);
+            File::create(path)?
+        };
+        let result = io::copy(
+            &mut api_client.throttle(&mut res),
+            &mut prog_bar.wrap_write(&mut file),
         );
+        file.sync_all()?;
+        match result {
+            Ok(_) => return Ok(()),
+            Err(_) if etag.is_some() && attempt <= api_client.retries() => {
+                thread::sleep(retry_delay(attempt));
+                attempt += 1;
+            }
+            Err(err) => {
+                return Err(err).context(
+                    "transfer download was interrupted - run the same command again to resume it",
+                );
+            }
+        }
+    }
+}
+
+/// Get the BLAKE3 checksum of a transfer's archive before encryption from its manifest.
+///
+/// Transfers without a manifest, or whose manifest predates archive checksums, can't be verified
+/// beyond what decryption already guarantees.
+fn archive_checksum(
+    api_client: &XferApiClient,
+    transfer_id: &str,
+    decryption_key: &str,
+) -> anyhow::Result<Option<blake3::Hash>> {
+    let Some(manifest) = api_client.transfer_manifest(transfer_id)? else {
+        return Ok(None);
+    };
+    let manifest = Cryptography::decrypt(&manifest, decryption_key).context(Failure::new(
+        FailureKind::Integrity,
+        "failed to decrypt transfer manifest to verify the transfer archive with",
+    ))?;
+    let manifest =
+        Manifest::from_bytes(&manifest).context("failed to read decrypted transfer manifest")?;
+    manifest
+        .archive_blake3
+        .map(|checksum| {
+            blake3::Hash::from_hex(checksum)
+                .context("transfer manifest contains an invalid archive checksum")
+        })
+        .transpose()
+}
+
+/// Download a transfer archive to `path` over several connections at once, then join the segments
+/// each one downloaded into their own partial file.
+///
+/// Segments downloaded by an interrupted attempt are kept so running the command again resumes
+/// them. A connection the server turns away because too many downloads of the transfer are in
+/// progress leaves its segments to the others.
+///
+/// Only the first segment is counted as a download, and once a transfer with a download limit
+/// has none left the others can only be downloaded with the token sent with it, so the first
+/// segment is downloaded before the rest.
+fn download_archive_parallel(
+    api_client: &XferApiClient,
+    transfer_id: &str,
+    size: u64,
+    etag: &str,
+    path: &Path,
+    connections: usize,
+    prog_bar: &ProgressBar,
+) -> anyhow::Result<()> {
+    let segments: Vec<Range<u64>> = (0..size)
+        .step_by(DOWNLOAD_SEGMENT_SIZE as usize)
+        .map(|start| start..(start + DOWNLOAD_SEGMENT_SIZE).min(size))
+        .collect();
+    let segment_path =
+        |index: usize| path.with_file_name(format!(".{transfer_id}.xfer-download.{index}"));
+    let connections = connections.min(segments.len());
+    show_bytes(
+        prog_bar,
+        format!("Downloading encrypted transfer archive over {connections} connections"),
+        size,
+    );
+    prog_bar.set_position(
+        segments
+            .iter()
+            .enumerate()
+            .map(|(index, segment)| {
+                fs::metadata(segment_path(index))
+                    .map_or(0, |metadata| metadata.len())
+                    .min(segment.end - segment.start)
+            })
+            .sum(),
+    );
 
+    let token_path = download_token_path(path);
+    let queue = Mutex::new(Vec::<usize>::new());
+    let active = AtomicUsize::new(connections);
+    let failed = AtomicBool::new(false);
+    let download_segments = || -> anyhow::Result<()> {
+        let mut attempt = 1;
+        while !failed.load(Ordering::Relaxed) {
+            let Some(index) = queue.lock().unwrap().pop() else {
+                return Ok(());
+            };
+            let Err(err) = download_segment(
+                api_client,
+                transfer_id,
+                segments[index].clone(),
+                etag,
+                &segment_path(index),
+                &token_path,
+                prog_bar,
+            ) else {
+                attempt = 1;
+                continue;
+            };
+            queue.lock().unwrap().push(index);
+            let status = err.downcast_ref::<StatusError>().map(|err| err.status);
+            if status == Some(StatusCode::TOO_MANY_REQUESTS)
+                && active
+                    .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |active| {
+                        (active > 1).then(|| active - 1)
+                    })
+                    .is_ok()
+            {
+                return Ok(());
+            }
+            if status.is_some_and(|status| !is_retry_status(status))
+                || attempt > api_client.retries()
+            {
+                failed.store(true, Ordering::Relaxed);
+                return Err(err);
+            }
+            thread::sleep(retry_delay(attempt));
+            attempt += 1;
+        }
         Ok(())
+    };
+    // The rest of the segments are taken from the end of the queue, so the transfer is downloaded
+    // roughly in order.
+    let rounds = [vec![0], (1..segments.len()).rev().collect()];
+    for (round, connections) in rounds.into_iter().zip([1, connections]) {
+        *queue.lock().unwrap() = round;
+        active.store(connections, Ordering::Relaxed);
+        thread::scope(|scope| -> anyhow::Result<()> {
+            let workers: Vec<_> = (0..connections)
+                .map(|_| scope.spawn(download_segments))
+                .collect();
+            for worker in workers {
+                worker
+                    .join()
+                    .expect("segment download thread should not panic")?;
+            }
+            Ok(())
+        })
+        .context("transfer download was interrupted - run the same command again to resume it")?;
+    }
+
+    let mut file = File::create(path)?;
+    for index in 0..segments.len() {
+        io::copy(&mut File::open(segment_path(index))?, &mut file)?;
     }
+    file.sync_all()?;
+    for index in 0..segments.len() {
+        fs::remove_file(segment_path(index))?;
+    }
+    Ok(())
+}
+
+/// Download the part of a transfer archive in `range` to `path`, continuing from the data already in the file.
+fn download_segment(
+    api_client: &XferApiClient,
+    transfer_id: &str,
+    range: Range<u64>,
+    etag: &str,
+    path: &Path,
+    token_path: &Path,
+    prog_bar: &ProgressBar,
+) -> anyhow::Result<()> {
+    let existing = fs::metadata(path).map_or(0, |metadata| metadata.len());
+    if existing >= range.end - range.start {
+        return Ok(());
+    }
+    let mut res = api_client.download_transfer_range(
+        transfer_id,
+        range.start + existing..range.end,
+        etag,
+        fs::read_to_string(token_path).ok().as_deref(),
+    )?;
+    save_download_token(&res, token_path)?;
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    let result = io::copy(
+        &mut api_client.throttle(&mut res),
+        &mut prog_bar.wrap_write(&mut file),
+    );
+    file.sync_all()?;
+    result?;
+    Ok(())
+}
+
+/// Path the download token of a transfer is kept at while it's downloaded to `partial_path`, so
+/// running the command again can resume it.
+fn download_token_path(partial_path: &Path) -> PathBuf {
+    partial_path.with_extension("xfer-download-token")
+}
+
+/// Keep the download token sent with a counted download, if the server sent one.
+fn save_download_token(res: &Response, token_path: &Path) -> anyhow::Result<()> {
+    if let Some(token) = res.headers().get(DOWNLOAD_TOKEN_HEADER) {
+        fs::write(token_path, token.as_bytes()).context("failed to save download token")?;
+    }
+    Ok(())
+}
+
+/// Find a path for a subdirectory of `directory` named after a transfer that doesn't exist yet.
+fn unique_subdirectory(directory: &Path, transfer_id: &str) -> PathBuf {
+    let mut path = directory.join(transfer_id);
+    let mut suffix = 1;
+    while path.symlink_metadata().is_ok() {
+        suffix += 1;
+        path = directory.join(format!("{transfer_id}-{suffix}"));
+    }
+    path
+}
+
+/// Get the BLAKE3 checksum of a transfer from its ETag, if it is one.
+fn etag_checksum(etag: &str) -> Option<blake3::Hash> {
+    blake3::Hash::from_hex(etag.trim_start_matches("W/").trim_matches('"')).ok()
 }
@@ -0,0 +1,85 @@
+use super::{
+    NetworkOptions, OutputFormat, format_expiry, print_json, rfc3339_expiry, transfer_metadata,
+};
+use crate::{DEFAULT_SERVER_URL, ExecutableCommand};
+use clap::{Parser, ValueHint};
+use indicatif::DecimalBytes;
+use serde::Serialize;
+use url::Url;
+
+/// Show the size and expiry of a transfer without downloading it.
+#[derive(Parser)]
+pub struct InfoCommand {
+    /// Identifier of the transfer to show.
+    ///
+    /// A full transfer key can also be given, in which case only the part before the slash is used.
+    #[clap(value_hint = ValueHint::Other)]
+    transfer_id: String,
+
+    /// URL (including scheme) of the server the transfer was uploaded to.
+    #[clap(
+        short = 's',
+        env = "XFER_CLIENT_RELAY_SERVER",
+        long = "server",
+        default_value = DEFAULT_SERVER_URL,
+        value_hint = ValueHint::Url
+    )]
+    server: Url,
+
+    /// Format to print the results in.
+    #[clap(
+        long = "output-format",
+        env = "XFER_CLIENT_OUTPUT_FORMAT",
+        value_enum,
+        default_value_t = OutputFormat::Text
+    )]
+    output_format: OutputFormat,
+
+    #[clap(flatten)]
+    network: NetworkOptions,
+}
+
+/// Machine-readable results of the info command.
+#[derive(Serialize)]
+struct InfoOutput<'a> {
+    id: &'a str,
+    size_bytes: u64,
+    expires_at: Option<String>,
+    /// How many more times the transfer can be downloaded, when the uploader limited its downloads.
+    downloads_remaining: Option<u64>,
+}
+
+impl ExecutableCommand for InfoCommand {
+    fn run(self) -> anyhow::Result<()> {
+        let transfer_id = self
+            .transfer_id
+            .split_once('/')
+            .map_or(self.transfer_id.as_str(), |(id, _)| id);
+        let metadata = transfer_metadata(&self.network.api_client(&self.server)?, transfer_id)?;
+
+        match self.output_format {
+            OutputFormat::Json => print_json(&InfoOutput {
+                id: transfer_id,
+                size_bytes: metadata.size,
+                expires_at: rfc3339_expiry(metadata.expires_at),
+                downloads_remaining: metadata.downloads_remaining,
+            })?,
+            OutputFormat::Text => {
+                println!(
+                    "Transfer '{transfer_id}' is {} and will expire {}",
+                    DecimalBytes(metadata.size),
+                    match metadata.expires_at {
+                        Some(expires_at) => format_expiry(expires_at)?,
+                        None =>
+                            String::from("at an unknown time (server did not provide expiry data)"),
+                    }
+                );
+                if let Some(downloads_remaining) = metadata.downloads_remaining {
+                    println!("It can be downloaded {downloads_remaining} more times");
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
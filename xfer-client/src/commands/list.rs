@@ -0,0 +1,150 @@
+use super::{NetworkOptions, OutputFormat, decryption_key, print_json, transfer_metadata};
+use crate::{
+    DEFAULT_SERVER_URL, ExecutableCommand,
+    archive::{Manifest, ManifestEntry, ManifestEntryKind},
+    failure::{Failure, FailureKind},
+};
+use anyhow::{Context, bail};
+use clap::{Parser, ValueHint};
+use indicatif::DecimalBytes;
+use serde::Serialize;
+use std::path::PathBuf;
+use url::Url;
+use xfer_crypto::Cryptography;
+
+/// List the files in a transfer without downloading it.
+///
+/// Only the encrypted manifest the uploader stored alongside the transfer is downloaded and
+/// decrypted, so this works for transfers of any size.
+#[derive(Parser)]
+pub struct ListCommand {
+    /// Key of the transfer to list the contents of.
+    #[clap(value_hint = ValueHint::Other)]
+    transfer_key: String,
+
+    /// Passphrase the transfer was protected with, for transfer keys without a decryption key.
+    ///
+    /// The passphrase is asked for when this isn't given.
+    #[clap(
+        long = "passphrase",
+        env = "XFER_CLIENT_PASSPHRASE",
+        hide_env_values = true,
+        value_name = "PASSPHRASE"
+    )]
+    passphrase: Option<String>,
+
+    /// Age identity file to decrypt the transfer with, for transfers encrypted to an age recipient.
+    #[clap(
+        long = "identity",
+        short = 'i',
+        env = "XFER_CLIENT_IDENTITY",
+        value_hint = ValueHint::FilePath
+    )]
+    identity: Option<PathBuf>,
+
+    /// URL (including scheme) of the server the transfer was uploaded to.
+    #[clap(
+        short = 's',
+        env = "XFER_CLIENT_RELAY_SERVER",
+        long = "server",
+        default_value = DEFAULT_SERVER_URL,
+        value_hint = ValueHint::Url
+    )]
+    server: Url,
+
+    /// Show the BLAKE3 hash of each file.
+    #[clap(long = "hashes")]
+    hashes: bool,
+
+    /// Format to print the results in.
+    #[clap(
+        long = "output-format",
+        env = "XFER_CLIENT_OUTPUT_FORMAT",
+        value_enum,
+        default_value_t = OutputFormat::Text
+    )]
+    output_format: OutputFormat,
+
+    #[clap(flatten)]
+    network: NetworkOptions,
+}
+
+/// Machine-readable results of the list command.
+#[derive(Serialize)]
+struct ListOutput<'a> {
+    id: &'a str,
+    entries: &'a [ManifestEntry],
+}
+
+impl ExecutableCommand for ListCommand {
+    fn run(self) -> anyhow::Result<()> {
+        let (transfer_id, key) = match self.transfer_key.split_once("/") {
+            Some((transfer_id, key)) => (transfer_id, Some(key)),
+            None => (self.transfer_key.as_str(), None),
+        };
+
+        // Older servers respond the same way for a missing manifest as a missing transfer, so make
+        // sure the transfer exists first to tell the two apart.
+        let api_client = self.network.api_client(&self.server)?;
+        transfer_metadata(&api_client, transfer_id)?;
+        let Some(manifest) = api_client.transfer_manifest(transfer_id)? else {
+            bail!(
+                "transfer has no manifest - it was uploaded by an older client or to a server that doesn't store manifests, download it to see its contents"
+            );
+        };
+        let decryption_key = decryption_key(
+            key,
+            self.passphrase.as_deref(),
+            self.identity.as_deref(),
+            true,
+        )?;
+        let manifest = Cryptography::decrypt(&manifest, &decryption_key).context(Failure::new(
+            FailureKind::Decryption,
+            "failed to decrypt transfer manifest - ensure you entered the transfer key or passphrase correctly",
+        ))?;
+        let manifest = Manifest::from_bytes(&manifest)
+            .context("failed to read decrypted transfer manifest")?;
+
+        match self.output_format {
+            OutputFormat::Json => print_json(&ListOutput {
+                id: transfer_id,
+                entries: &manifest.entries,
+            })?,
+            OutputFormat::Text => {
+                let files = manifest
+                    .entries
+                    .iter()
+                    .filter(|entry| entry.kind != ManifestEntryKind::Directory);
+                println!(
+                    "Transfer '{transfer_id}' contains {} files totalling {}\n",
+                    files.clone().count(),
+                    DecimalBytes(files.map(|entry| entry.size).sum())
+                );
+                for entry in &manifest.entries {
+                    let size = match entry.kind {
+                        ManifestEntryKind::File => DecimalBytes(entry.size).to_string(),
+                        _ => String::new(),
+                    };
+                    let mut line = format!("{size:>10}  {}", entry.path);
+                    match entry.kind {
+                        ManifestEntryKind::Directory => line.push('/'),
+                        ManifestEntryKind::Symlink | ManifestEntryKind::Hardlink => {
+                            if let Some(target) = &entry.target {
+                                line.push_str(&format!(" -> {target}"));
+                            }
+                        }
+                        ManifestEntryKind::File => {}
+                    }
+                    if self.hashes
+                        && let Some(hash) = &entry.blake3
+                    {
+                        line.push_str(&format!("  (blake3: {hash})"));
+                    }
+                    println!("{line}");
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,7 +1,434 @@
 mod completion;
+mod delete;
 mod download;
+mod info;
+mod list;
+mod secret;
 mod upload;
+mod watch;
 
 pub use completion::GenCompletionsCommand;
+pub use delete::DeleteCommand;
 pub use download::DownloadCommand;
+pub use info::InfoCommand;
+pub use list::ListCommand;
+pub use secret::SecretCommand;
 pub use upload::UploadCommand;
+pub use watch::WatchCommand;
+
+use crate::{
+    api_client::{DEFAULT_RETRIES, DOWNLOADS_REMAINING_HEADER, EXPIRES_AT_HEADER, XferApiClient},
+    failure::{Failure, FailureKind},
+};
+use anyhow::{Context, Result, bail};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use clap::ValueHint;
+use indicatif::{ProgressBar, ProgressStyle};
+use inquire::{Password, PasswordDisplayMode};
+use serde::Serialize;
+use std::{
+    borrow::Cow,
+    fs,
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use time::{UtcDateTime, UtcOffset, format_description, format_description::well_known::Rfc3339};
+use url::Url;
+use xfer_crypto::age;
+
+/// Fewest characters a passphrase chosen for a transfer can have.
+const MIN_PASSPHRASE_LENGTH: usize = 8;
+
+/// Prefix of decryption keys that have been encrypted to age recipients with `upload --recipient`.
+const RECIPIENT_KEY_PREFIX: &str = "age:";
+
+/// Options for how the client talks to the server, shared by every command that makes requests.
+#[derive(clap::Args, Clone)]
+struct NetworkOptions {
+    /// How many times to retry a request that failed because of a connection problem or a temporary server error.
+    ///
+    /// Downloads that are interrupted part way through are also resumed this many times.
+    #[clap(long = "retries", env = "XFER_CLIENT_RETRIES", default_value_t = DEFAULT_RETRIES)]
+    retries: u32,
+
+    /// Seconds to wait for a connection to the server to be established.
+    #[clap(
+        long = "connect-timeout",
+        env = "XFER_CLIENT_CONNECT_TIMEOUT",
+        value_name = "SECONDS",
+        default_value_t = 30,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    connect_timeout: u64,
+
+    /// Seconds to wait for requests that don't send or receive transfer data to finish, or 0 to wait indefinitely.
+    #[clap(
+        long = "request-timeout",
+        env = "XFER_CLIENT_REQUEST_TIMEOUT",
+        value_name = "SECONDS",
+        default_value_t = 60
+    )]
+    request_timeout: u64,
+
+    /// Seconds a connection can stop responding for before it is dropped, or 0 to never drop it.
+    ///
+    /// Transfers have no overall time limit, so this is how stalled uploads and downloads are detected.
+    /// Downloads fail when no data arrives for this long. Connections are also checked with TCP
+    /// keepalives, and on Linux sent data must be acknowledged in time, which is how stalled uploads are found.
+    #[clap(
+        long = "idle-timeout",
+        env = "XFER_CLIENT_IDLE_TIMEOUT",
+        value_name = "SECONDS",
+        default_value_t = 60
+    )]
+    idle_timeout: u64,
+
+    /// URL of a proxy to send all requests through, such as `http://proxy.example.com:8080` or `socks5h://127.0.0.1:9050`.
+    ///
+    /// Credentials can be included in the URL. When this isn't set, the proxies in the `HTTP_PROXY`,
+    /// `HTTPS_PROXY` and `ALL_PROXY` environment variables are used, except for hosts in `NO_PROXY`.
+    /// Use `socks5h://` rather than `socks5://` to resolve the server's hostname through the proxy, as Tor requires.
+    #[clap(long = "proxy", env = "XFER_CLIENT_PROXY", value_hint = ValueHint::Url)]
+    proxy: Option<Url>,
+
+    /// PEM file of certificate authorities to trust as well as the built-in ones, for servers with certificates from a private CA.
+    #[clap(long = "ca-cert", env = "XFER_CLIENT_CA_CERT", value_hint = ValueHint::FilePath)]
+    ca_cert: Option<PathBuf>,
+
+    /// Don't verify the server's TLS certificate.
+    ///
+    /// Anyone between you and the server can then read and change requests. Transfers stay end-to-end
+    /// encrypted, but upload and deletion tokens do not, so only use this while setting up a server.
+    #[clap(
+        long = "insecure",
+        env = "XFER_CLIENT_INSECURE",
+        conflicts_with = "ca_cert"
+    )]
+    insecure: bool,
+
+    /// PEM file of the certificate chain to identify this client with, for servers behind a proxy that requires mutual TLS.
+    ///
+    /// The file can also hold the certificate's private key, otherwise pass it with `--client-key`.
+    #[clap(long = "client-cert", env = "XFER_CLIENT_CLIENT_CERT", value_hint = ValueHint::FilePath)]
+    client_cert: Option<PathBuf>,
+
+    /// PEM file of the private key for the certificate given with `--client-cert`.
+    #[clap(
+        long = "client-key",
+        env = "XFER_CLIENT_CLIENT_KEY",
+        value_hint = ValueHint::FilePath,
+        requires = "client_cert"
+    )]
+    client_key: Option<PathBuf>,
+
+    /// Most transfer data to send or receive per second, such as `500K` or `2M`, so a large transfer doesn't use the whole connection.
+    ///
+    /// Suffixes of K, M and G count in units of 1024 bytes. The limit applies to all of a command's
+    /// connections together, including parallel chunk uploads.
+    #[clap(
+        long = "limit-rate",
+        env = "XFER_CLIENT_LIMIT_RATE",
+        value_name = "BYTES",
+        value_parser = parse_rate
+    )]
+    limit_rate: Option<u64>,
+}
+
+impl NetworkOptions {
+    /// Create a client for the API of the server at `server` with these options.
+    fn api_client(&self, server: &Url) -> Result<XferApiClient> {
+        // Keepalive probes start halfway through the idle timeout, so a connection is dropped
+        // once all of them have gone unanswered by the end of it.
+        let idle_timeout = Some(Duration::from_secs(self.idle_timeout)).filter(|t| !t.is_zero());
+        let mut http_client = reqwest::blocking::Client::builder()
+            .connect_timeout(Duration::from_secs(self.connect_timeout))
+            .tcp_keepalive(idle_timeout.map(|timeout| (timeout / 2).max(Duration::from_secs(1))))
+            .tcp_keepalive_interval(
+                idle_timeout.map(|timeout| (timeout / 6).max(Duration::from_secs(1))),
+            )
+            .tcp_keepalive_retries(idle_timeout.map(|_| 3));
+        #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+        {
+            http_client = http_client.tcp_user_timeout(idle_timeout);
+        }
+        if let Some(path) = &self.ca_cert {
+            let pem = fs::read(path).with_context(|| {
+                format!("failed to read CA certificate file at '{}'", path.display())
+            })?;
+            let certificates = reqwest::Certificate::from_pem_bundle(&pem).with_context(|| {
+                format!(
+                    "failed to parse CA certificate file at '{}'",
+                    path.display()
+                )
+            })?;
+            if certificates.is_empty() {
+                bail!(
+                    "CA certificate file at '{}' does not contain any PEM certificates",
+                    path.display()
+                );
+            }
+            for certificate in certificates {
+                http_client = http_client.add_root_certificate(certificate);
+            }
+        }
+        if let Some(cert_path) = &self.client_cert {
+            let mut pem = fs::read(cert_path).with_context(|| {
+                format!(
+                    "failed to read client certificate file at '{}'",
+                    cert_path.display()
+                )
+            })?;
+            if let Some(key_path) = &self.client_key {
+                pem.push(b'\n');
+                pem.extend(fs::read(key_path).with_context(|| {
+                    format!("failed to read client key file at '{}'", key_path.display())
+                })?);
+            }
+            http_client = http_client.identity(reqwest::Identity::from_pem(&pem).context(
+                "failed to parse client certificate and key - they must be PEM encoded, with the key in PKCS#8, PKCS#1 or SEC1 format",
+            )?);
+        }
+        if self.insecure {
+            eprintln!(
+                "Warning: the server's TLS certificate will not be verified, so requests to it can be read and changed by others"
+            );
+            http_client = http_client.danger_accept_invalid_certs(true);
+        }
+        if let Some(proxy) = &self.proxy {
+            http_client = http_client
+                .proxy(reqwest::Proxy::all(proxy.as_str()).context("invalid proxy URL")?);
+        }
+        Ok(XferApiClient::new(server, http_client)?
+            .with_retries(self.retries)
+            .with_rate_limit(self.limit_rate)
+            .with_request_timeout(
+                Some(Duration::from_secs(self.request_timeout)).filter(|t| !t.is_zero()),
+            )
+            .with_idle_timeout(idle_timeout))
+    }
+}
+
+/// Format the results of a command are printed in.
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum OutputFormat {
+    /// Human-readable prose.
+    Text,
+    /// A single JSON object on standard output, for use in scripts.
+    Json,
+}
+
+/// Print the results of a command as a single line of JSON on standard output.
+fn print_json(output: &impl Serialize) -> Result<()> {
+    println!("{}", serde_json::to_string(output)?);
+    Ok(())
+}
+
+/// Details the server sends about a transfer without it being downloaded.
+struct TransferMetadata {
+    /// Size of the encrypted transfer archive in bytes.
+    size: u64,
+    expires_at: Option<UtcDateTime>,
+    etag: Option<String>,
+    /// Whether the server can send parts of the transfer on their own.
+    accepts_ranges: bool,
+    /// How many more times the transfer can be downloaded, when the uploader limited its downloads.
+    downloads_remaining: Option<u64>,
+}
+
+/// Fetch the details of a transfer from the server, after checking its identifier is well-formed.
+fn transfer_metadata(api_client: &XferApiClient, transfer_id: &str) -> Result<TransferMetadata> {
+    if !xfer_crypto::is_valid_transfer_id(transfer_id) {
+        bail!("invalid transfer key - please ensure you have entered it correctly");
+    }
+    // The server must send the `Content-Length` header on HEAD request
+    // to display the transfer size pre-download.
+    let res = api_client.transfer_metadata(transfer_id).context(
+        "failed to get transfer - transfer may have expired, transfer key may be incorrect, or server may have returned an error",
+    )?;
+    let size = res
+        .headers()
+        .get("Content-Length")
+        .map(|f| f.to_str().unwrap())
+        .unwrap_or("0")
+        .parse::<u64>()?;
+    let expires_at = res
+        .headers()
+        .get(EXPIRES_AT_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| UtcDateTime::parse(v, &Rfc3339).ok());
+    let etag = res
+        .headers()
+        .get("ETag")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let accepts_ranges = res
+        .headers()
+        .get("Accept-Ranges")
+        .is_some_and(|v| v.as_bytes() == b"bytes");
+    let downloads_remaining = res
+        .headers()
+        .get(DOWNLOADS_REMAINING_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    Ok(TransferMetadata {
+        size,
+        expires_at,
+        etag,
+        accepts_ranges,
+        downloads_remaining,
+    })
+}
+
+/// Use the passphrase given with `--passphrase`, or ask for it when the option was passed without one.
+///
+/// Passphrases being chosen for a new transfer are entered twice and must be long enough, while
+/// ones for existing transfers are taken as they are.
+fn resolve_passphrase(given: &str, can_prompt: bool, new: bool) -> Result<String> {
+    let passphrase = match given.is_empty() {
+        false => given.to_string(),
+        true if can_prompt && io::stdin().is_terminal() => {
+            let prompt = Password::new("Transfer passphrase:")
+                .with_display_mode(PasswordDisplayMode::Masked);
+            match new {
+                true => prompt
+                    .with_custom_confirmation_message("Confirm transfer passphrase:")
+                    .prompt()?,
+                false => prompt.without_confirmation().prompt()?,
+            }
+        }
+        true => bail!(
+            "a passphrase is required, pass it with --passphrase=<PASSPHRASE> or the XFER_CLIENT_PASSPHRASE environment variable"
+        ),
+    };
+    if new && passphrase.chars().count() < MIN_PASSPHRASE_LENGTH {
+        bail!("passphrase must be at least {MIN_PASSPHRASE_LENGTH} characters long");
+    }
+    Ok(passphrase)
+}
+
+/// Parse a rate limit given on the command line as a number of bytes, with an optional K, M or G suffix.
+fn parse_rate(value: &str) -> Result<u64, String> {
+    let (number, multiplier) = match value.char_indices().last() {
+        Some((index, 'k' | 'K')) => (&value[..index], 1 << 10),
+        Some((index, 'm' | 'M')) => (&value[..index], 1 << 20),
+        Some((index, 'g' | 'G')) => (&value[..index], 1 << 30),
+        _ => (value, 1),
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(multiplier))
+        .filter(|rate| *rate > 0)
+        .ok_or_else(|| {
+            String::from("expected a positive number of bytes, optionally followed by K, M or G")
+        })
+}
+
+/// Parse how long to keep a transfer for given on the command line as a number of seconds, or of
+/// minutes, hours or days with an m, h or d suffix.
+fn parse_expire_after(value: &str) -> Result<Duration, String> {
+    let (number, multiplier) = match value.char_indices().last() {
+        Some((index, 's' | 'S')) => (&value[..index], 1),
+        Some((index, 'm' | 'M')) => (&value[..index], 60),
+        Some((index, 'h' | 'H')) => (&value[..index], 60 * 60),
+        Some((index, 'd' | 'D')) => (&value[..index], 24 * 60 * 60),
+        _ => (value, 1),
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(multiplier))
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs)
+        .ok_or_else(|| {
+            String::from("expected a positive number of seconds, optionally followed by m, h or d")
+        })
+}
+
+/// Parse an age recipient given on the command line.
+fn parse_recipient(value: &str) -> Result<age::Recipient, String> {
+    value.parse().map_err(|err| format!("{err:#}"))
+}
+
+/// Encrypt a transfer's decryption key to age recipients, so it can be shared in the transfer key
+/// without anyone but them being able to decrypt the transfer.
+fn encrypt_key_to_recipients(
+    decryption_key: &str,
+    recipients: &[age::Recipient],
+) -> Result<String> {
+    Ok(format!(
+        "{RECIPIENT_KEY_PREFIX}{}",
+        URL_SAFE_NO_PAD.encode(age::encrypt(decryption_key.as_bytes(), recipients)?)
+    ))
+}
+
+/// Work out the key to decrypt a transfer with from the part of its transfer key after the slash.
+///
+/// Keys encrypted to age recipients are decrypted with the identity file, and the passphrase of
+/// transfers protected with one is used when there is no key.
+fn decryption_key(
+    key: Option<&str>,
+    passphrase: Option<&str>,
+    identity: Option<&Path>,
+    can_prompt: bool,
+) -> Result<String> {
+    let Some(key) = key else {
+        return resolve_passphrase(passphrase.unwrap_or_default(), can_prompt, false);
+    };
+    let Some(encrypted) = key.strip_prefix(RECIPIENT_KEY_PREFIX) else {
+        return Ok(key.to_string());
+    };
+    let Some(identity) = identity else {
+        bail!(
+            "this transfer was encrypted to an age recipient, pass the identity file to decrypt it with using --identity"
+        );
+    };
+    let identities =
+        age::parse_identities(&fs::read_to_string(identity).with_context(|| {
+            format!("failed to read identity file at '{}'", identity.display())
+        })?)
+        .with_context(|| format!("failed to parse identity file at '{}'", identity.display()))?;
+    let encrypted = URL_SAFE_NO_PAD
+        .decode(encrypted)
+        .context("invalid transfer key - please ensure you have entered it correctly")?;
+    String::from_utf8(age::decrypt(&encrypted, &identities).context(Failure::new(
+        FailureKind::Decryption,
+        "failed to decrypt transfer key - ensure it was encrypted to one of the identities in the identity file",
+    ))?)
+    .context("decrypted transfer key is not valid UTF-8")
+}
+
+/// Format a transfer expiry time in RFC 3339 format for machine-readable output.
+fn rfc3339_expiry(expires_at: Option<UtcDateTime>) -> Option<String> {
+    expires_at.and_then(|expires_at| expires_at.format(&Rfc3339).ok())
+}
+
+/// Format a transfer expiry time for display in the user's local timezone.
+fn format_expiry(expires_at: UtcDateTime) -> Result<String> {
+    Ok(expires_at
+        .to_offset(UtcOffset::current_local_offset().unwrap_or(UtcOffset::UTC))
+        .format(&format_description::parse_borrowed::<2>(
+            "on [day]-[month]-[year] at [hour]:[minute]:[second] (UTC[offset_hour sign:mandatory]:[offset_minute])",
+        )?)?)
+}
+
+/// Switch a progress bar to a spinner for steps whose progress can't be measured.
+fn show_spinner(prog_bar: &ProgressBar, message: impl Into<Cow<'static, str>>) {
+    prog_bar.set_style(ProgressStyle::default_spinner());
+    prog_bar.set_message(message);
+}
+
+/// Switch a progress bar to tracking a step that processes `len` bytes, showing the processed
+/// and total bytes, the rate and the estimated time remaining.
+fn show_bytes(prog_bar: &ProgressBar, message: impl Into<Cow<'static, str>>, len: u64) {
+    prog_bar.set_style(
+        ProgressStyle::with_template(
+            "{spinner} {msg}\n{wide_bar} {decimal_bytes}/{decimal_total_bytes} ({decimal_bytes_per_sec}, {eta} remaining)",
+        )
+        .expect("progress bar template should be valid"),
+    );
+    prog_bar.set_length(len);
+    prog_bar.reset();
+    prog_bar.set_message(message);
+}
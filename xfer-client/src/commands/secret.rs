@@ -0,0 +1,75 @@
+use crate::{ExecutableCommand, failure::Failure, secrets};
+use anyhow::{Result, bail};
+use clap::{Parser, ValueHint};
+use inquire::{Confirm, Password};
+use std::io::{self, IsTerminal, Read};
+
+/// Manage secrets such as upload tokens in the system keyring.
+///
+/// Stored secrets can be referenced from profiles in the config file with `{ keyring = "<name>" }`
+/// instead of writing them out in plain text.
+#[derive(Parser)]
+pub struct SecretCommand {
+    #[clap(subcommand)]
+    action: SecretAction,
+}
+
+#[derive(Parser)]
+enum SecretAction {
+    /// Store a secret, replacing any secret already stored under the same name.
+    ///
+    /// The secret is prompted for, or read from standard input when it isn't a terminal, so it
+    /// never appears in shell history.
+    Set {
+        /// Name to store the secret under.
+        #[clap(value_hint = ValueHint::Other)]
+        name: String,
+    },
+    /// Remove a stored secret.
+    Remove {
+        /// Name the secret is stored under.
+        #[clap(value_hint = ValueHint::Other)]
+        name: String,
+
+        /// Skip all confirmation dialogues.
+        #[clap(short = 'y', env = "XFER_CLIENT_NOCONFIRM", long = "yes")]
+        no_confirm: bool,
+    },
+}
+
+impl ExecutableCommand for SecretCommand {
+    fn run(self) -> Result<()> {
+        match self.action {
+            SecretAction::Set { name } => {
+                let secret = if io::stdin().is_terminal() {
+                    Password::new(&format!("Secret to store as '{name}':"))
+                        .without_confirmation()
+                        .prompt()?
+                } else {
+                    let mut secret = String::new();
+                    io::stdin().read_to_string(&mut secret)?;
+                    secret.trim_end_matches(['\r', '\n']).to_string()
+                };
+                if secret.is_empty() {
+                    bail!("secret must not be empty");
+                }
+                secrets::set_secret(&name, &secret)?;
+                println!("Stored secret '{name}' in the system keyring");
+            }
+            SecretAction::Remove { name, no_confirm } => {
+                if !no_confirm
+                    && !Confirm::new(&format!(
+                        "Are you sure you want to remove secret '{name}' from the system keyring?"
+                    ))
+                    .with_default(false)
+                    .prompt()?
+                {
+                    return Err(Failure::aborted().into());
+                }
+                secrets::remove_secret(&name)?;
+                println!("Removed secret '{name}' from the system keyring");
+            }
+        }
+        Ok(())
+    }
+}
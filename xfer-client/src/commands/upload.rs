@@ -1,35 +1,194 @@
+use super::{
+    NetworkOptions, OutputFormat, RECIPIENT_KEY_PREFIX, encrypt_key_to_recipients, format_expiry,
+    parse_expire_after, parse_recipient, print_json, resolve_passphrase, show_bytes, show_spinner,
+};
+#[cfg(unix)]
+use crate::archive::{standard_mode, xattr_records};
 use crate::{
-    DEFAULT_SERVER_URL, ExecutableCommand, PROGRESS_BAR_TICKRATE, api_client::XferApiClient,
-    cryptography::Cryptography,
+    DEFAULT_SERVER_URL, ExecutableCommand, PROGRESS_BAR_TICKRATE,
+    api_client::{
+        CreateTransferResponse, RetryLaterError, ServerConfigurationResponse, TransferOptions,
+        XferApiClient,
+    },
+    archive::{ArchiveFormat, Compression, Manifest, tar_writer},
+    failure::{Failure, FailureKind},
+    upload_state::{UploadState, UploadStateFiles},
 };
 use anyhow::{Context, Result, bail};
+use bytes::Bytes;
 use clap::{Parser, ValueHint};
-use flate2::{Compression, bufread::GzEncoder};
-use indicatif::{DecimalBytes, ProgressBar};
+use ignore::{WalkBuilder, overrides::OverrideBuilder};
+use indicatif::{DecimalBytes, HumanDuration, ProgressBar, ProgressStyle};
 use inquire::Confirm;
+use qrcode::{QrCode, render::unicode::Dense1x2};
+use serde::Serialize;
+#[cfg(unix)]
+use std::collections::{HashMap, HashSet};
 use std::{
-    env, fs,
-    io::Cursor,
-    ops::Add,
-    path::PathBuf,
-    time::{Duration, SystemTime, UNIX_EPOCH},
+    collections::BTreeSet,
+    env,
+    fs::{self, File},
+    io::{self, Cursor},
+    path::{Path, PathBuf},
+    slice,
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    thread,
+    time::Duration,
 };
-use time::{UtcDateTime, UtcOffset, format_description};
+use time::{OffsetDateTime, UtcDateTime, format_description::well_known::Rfc3339};
 use url::Url;
+use xfer_crypto::{Cryptography, age::Recipient};
+use zip::{CompressionMethod, ZipWriter, write::SimpleFileOptions};
+
+/// A file or directory to include in a transfer archive.
+struct UploadPath {
+    canonical: PathBuf,
+    /// Name of the file or directory at the top level of the archive.
+    name: String,
+}
+
+/// A file, directory or symbolic link added to a transfer archive.
+struct ArchiveEntry {
+    source: PathBuf,
+    /// Path of the entry inside the archive.
+    name: PathBuf,
+    /// Metadata of the entry itself, rather than the file it points to when it is a symbolic link kept as one.
+    metadata: fs::Metadata,
+}
+
+impl ArchiveEntry {
+    /// Number of bytes of file contents the entry adds to the archive.
+    fn size(&self) -> u64 {
+        match self.metadata.is_file() {
+            true => self.metadata.len(),
+            false => 0,
+        }
+    }
+}
+
+/// Format a list of paths or names for display, quoting each of them.
+fn quoted_list<T: std::fmt::Display>(items: impl IntoIterator<Item = T>) -> String {
+    items
+        .into_iter()
+        .map(|item| format!("'{item}'"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Render text as a QR code made of Unicode half blocks, so it can be scanned from the terminal.
+///
+/// Colours are inverted as most terminals draw light text on a dark background.
+fn qr_code(text: &str) -> Result<String> {
+    Ok(QrCode::new(text)
+        .context("failed to encode QR code")?
+        .render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .build())
+}
+
+/// What `--copy` places on the clipboard.
+#[cfg(feature = "clipboard")]
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum CopyTarget {
+    /// The command the recipient should run.
+    Command,
+    /// Only the transfer key.
+    Key,
+}
+
+/// Machine-readable results of the upload command.
+#[derive(Serialize)]
+struct UploadOutput<'a> {
+    id: &'a str,
+    /// The full transfer key the recipient downloads with.
+    key: String,
+    paths: Vec<&'a Path>,
+    /// Size of the encrypted transfer archive in bytes.
+    size_bytes: u64,
+    /// Whether the transfer is relayed straight to the recipient instead of stored on the server.
+    relay: bool,
+    expires_at: Option<&'a str>,
+    download_command: String,
+    download_url: Option<String>,
+    deletion_token: Option<&'a str>,
+    activity_token: Option<&'a str>,
+}
+
+/// Machine-readable results of uploading paths with `--separate`.
+#[derive(Serialize)]
+struct SeparateUploadOutput<'a> {
+    transfers: Vec<UploadOutput<'a>>,
+}
+
+/// A transfer that has been uploaded, with what's needed to share it.
+struct CreatedTransfer {
+    response: CreateTransferResponse,
+    /// The full transfer key the recipient downloads with.
+    key: String,
+    /// Size of the encrypted transfer archive in bytes.
+    size_bytes: u64,
+    download_url: Option<String>,
+    /// URL the transfer's download activity can be seen at with its activity token.
+    activity_url: Url,
+    passphrase_protected: bool,
+}
+
+/// Resolve each path and the name it will have in the archive, refusing names that would collide.
+fn upload_paths(paths: &[PathBuf]) -> Result<Vec<UploadPath>> {
+    let mut upload_paths: Vec<UploadPath> = Vec::with_capacity(paths.len());
+    for path in paths {
+        let canonical = match fs::canonicalize(path) {
+            Ok(path) => path,
+            Err(err) => bail!(
+                "failed while trying to read file or directory at '{}': {err}",
+                path.display()
+            ),
+        };
+        let name = canonical
+            .file_name()
+            .context("failed to read file or directory name")?
+            .to_str()
+            .context("failed to parse file or directory name as str")?
+            .to_string();
+        if let Some(existing) = upload_paths.iter().find(|path| path.name == name) {
+            bail!(
+                "'{}' and '{}' would both be named '{name}' in the transfer, rename one of them or transfer them separately",
+                existing.canonical.display(),
+                canonical.display()
+            );
+        }
+        upload_paths.push(UploadPath { canonical, name });
+    }
+    Ok(upload_paths)
+}
 
 /// Encrypt and create a transfer on a relay server.
-#[derive(Parser)]
+#[derive(Parser, Clone)]
 pub struct UploadCommand {
-    /// File or directory to transfer.
+    /// Files or directories to transfer.
     ///
     /// When a directory is specified, all subdirectories will also be included.
-    #[clap(value_hint = ValueHint::AnyPath)]
-    path: PathBuf,
+    /// Several paths can be sent as one transfer, as long as they don't share a name.
+    #[clap(required = true, value_hint = ValueHint::AnyPath)]
+    paths: Vec<PathBuf>,
 
     /// Skip all confirmation dialogues.
     #[clap(short = 'y', env = "XFER_CLIENT_NOCONFIRM", long = "yes")]
     no_confirm: bool,
 
+    /// Don't prompt or show progress, and only print the transfer key once the transfer is created.
+    #[clap(
+        short = 'q',
+        long = "quiet",
+        env = "XFER_CLIENT_QUIET",
+        conflicts_with = "output_format"
+    )]
+    quiet: bool,
+
     /// URL (including scheme) of the server create the transfer on.
     #[clap(
         short = 's',
@@ -39,126 +198,1338 @@ pub struct UploadCommand {
         value_hint = ValueHint::Url,
     )]
     server: Url,
+
+    /// Upload token to authenticate with, for servers that require one or apply per-token limits.
+    #[clap(
+        long = "upload-token",
+        env = "XFER_CLIENT_UPLOAD_TOKEN",
+        hide_env_values = true
+    )]
+    upload_token: Option<String>,
+
+    /// Send the transfer straight to the recipient through the server instead of storing it there.
+    ///
+    /// Relayed transfers can be larger than the server's maximum transfer size, but the recipient
+    /// must start downloading within a few minutes and this command waits until they have finished.
+    #[clap(long = "relay", env = "XFER_CLIENT_RELAY")]
+    relay: bool,
+
+    /// How long the server should keep the transfer for instead of its default, such as `90s`, `15m`,
+    /// `12h` or `7d`.
+    ///
+    /// Must be within the shortest and longest lifetimes the server allows.
+    #[clap(
+        long = "expire",
+        env = "XFER_CLIENT_EXPIRE",
+        value_name = "DURATION",
+        value_parser = parse_expire_after,
+        conflicts_with_all = ["relay", "resume"]
+    )]
+    expire_after: Option<Duration>,
+
+    /// How many times the transfer can be downloaded before the server removes it, such as 1 to have
+    /// it removed once the recipient has it.
+    ///
+    /// Only supported by servers that allow uploaders to limit downloads.
+    #[clap(
+        long = "max-downloads",
+        env = "XFER_CLIENT_MAX_DOWNLOADS",
+        value_name = "COUNT",
+        value_parser = clap::value_parser!(u64).range(1..),
+        conflicts_with = "relay"
+    )]
+    max_downloads: Option<u64>,
+
+    /// URL the server should send a `POST` request to when the transfer is first downloaded.
+    ///
+    /// Only supported by servers that allow uploaders to register claim webhooks.
+    #[clap(
+        long = "claim-webhook",
+        env = "XFER_CLIENT_CLAIM_WEBHOOK",
+        value_hint = ValueHint::Url,
+        conflicts_with = "relay"
+    )]
+    claim_webhook: Option<Url>,
+
+    /// Address the server should email when the transfer is uploaded, first downloaded or about to expire
+    /// without being downloaded.
+    ///
+    /// Only supported by servers that allow uploaders to register notification emails.
+    #[clap(
+        long = "notify-email",
+        env = "XFER_CLIENT_NOTIFY_EMAIL",
+        value_hint = ValueHint::EmailAddress,
+        conflicts_with = "relay"
+    )]
+    notify_email: Option<String>,
+
+    /// Size of each chunk in MiB when uploading to servers that accept transfers in chunks.
+    ///
+    /// Transfers no larger than a single chunk are uploaded in one request.
+    #[clap(
+        long = "chunk-size",
+        env = "XFER_CLIENT_CHUNK_SIZE",
+        default_value_t = 8,
+        value_parser = clap::value_parser!(u16).range(1..=1024)
+    )]
+    chunk_size_mib: u16,
+
+    /// How many chunks, or transfers with `--separate`, to upload at the same time.
+    ///
+    /// Uploading several chunks at once can greatly improve throughput on high-latency links.
+    #[clap(
+        long = "parallel-uploads",
+        env = "XFER_CLIENT_PARALLEL_UPLOADS",
+        default_value_t = 4,
+        value_parser = clap::value_parser!(u8).range(1..=32)
+    )]
+    parallel_uploads: u8,
+
+    /// Glob of files or directories to leave out when transferring directories, matched like a `.gitignore` entry.
+    ///
+    /// Can be given multiple times. Exclusions take precedence over `--include`.
+    #[clap(long = "exclude", value_name = "GLOB")]
+    exclude: Vec<String>,
+
+    /// Glob of files to include when transferring directories, leaving out all other files.
+    ///
+    /// Can be given multiple times. Globs are matched like a `.gitignore` entry.
+    #[clap(long = "include", value_name = "GLOB")]
+    include: Vec<String>,
+
+    /// Leave out files ignored by `.gitignore` files when transferring directories.
+    ///
+    /// Git's global excludes file and `.git/info/exclude` are also honoured.
+    #[clap(long = "gitignore", env = "XFER_CLIENT_GITIGNORE")]
+    gitignore: bool,
+
+    /// Continue an interrupted upload of the same path to the same server instead of starting a new one.
+    ///
+    /// Only uploads sent in chunks can be resumed, and only until the server's reservation for them lapses.
+    /// Their progress and encrypted archive are kept in the local state directory until they finish.
+    #[clap(long = "resume", conflicts_with = "relay")]
+    resume: bool,
+
+    /// Encrypt the transfer with a passphrase of your choosing instead of a generated key, so it can be read out to the recipient.
+    ///
+    /// The passphrase is asked for when this is passed without one. The recipient is only given the
+    /// transfer's identifier and is asked for the passphrase when downloading it.
+    #[clap(
+        long = "passphrase",
+        env = "XFER_CLIENT_PASSPHRASE",
+        hide_env_values = true,
+        value_name = "PASSPHRASE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = ""
+    )]
+    passphrase: Option<String>,
+
+    /// Encrypt the transfer's key to an age public key (age1...), so only the holder of the matching identity can decrypt it.
+    ///
+    /// Can be given multiple times to let any of several recipients decrypt the transfer. Recipients
+    /// download it with `--identity` pointing at their identity file, and the transfer key can be
+    /// shared openly as it is useless without one.
+    #[clap(
+        long = "recipient",
+        short = 'r',
+        value_name = "RECIPIENT",
+        value_parser = parse_recipient,
+        conflicts_with = "passphrase"
+    )]
+    recipients: Vec<Recipient>,
+
+    /// Upload each path as its own transfer instead of all of them together, then print the key of each.
+    ///
+    /// Transfers are uploaded as many at a time as `--parallel-uploads`. Every option applies to each
+    /// transfer, and a passphrase given with `--passphrase` protects all of them.
+    #[clap(long = "separate", conflicts_with_all = ["relay", "resume", "qr"])]
+    separate: bool,
+
+    /// Also show the command the recipient should run as a QR code, to scan onto a phone or another machine.
+    #[clap(long = "qr", env = "XFER_CLIENT_QR")]
+    qr: bool,
+
+    /// Copy the command the recipient should run, or only the transfer key with `--copy=key`, to the clipboard.
+    #[cfg(feature = "clipboard")]
+    #[clap(
+        long = "copy",
+        env = "XFER_CLIENT_COPY",
+        value_enum,
+        value_name = "WHAT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "command"
+    )]
+    copy: Option<CopyTarget>,
+
+    /// Format to print the results in.
+    ///
+    /// With `json`, the transfer is printed as a single JSON object, as soon as the recipient can
+    /// download it when relaying.
+    #[clap(
+        long = "output-format",
+        env = "XFER_CLIENT_OUTPUT_FORMAT",
+        value_enum,
+        default_value_t = OutputFormat::Text
+    )]
+    output_format: OutputFormat,
+
+    /// Kind of archive to pack the transfer into.
+    ///
+    /// Zip archives can be opened natively on most platforms, including when the transfer is saved
+    /// from the browser download page. The recipient's client unpacks either kind.
+    #[clap(
+        long = "format",
+        env = "XFER_CLIENT_ARCHIVE_FORMAT",
+        value_enum,
+        default_value_t = ArchiveFormat::Tar
+    )]
+    format: ArchiveFormat,
+
+    /// Format to compress tar archives with.
+    ///
+    /// The recipient's client detects the format automatically. Zip archives are always compressed with deflate.
+    #[clap(
+        long = "compression",
+        env = "XFER_CLIENT_COMPRESSION",
+        value_enum,
+        default_value_t = Compression::Gzip
+    )]
+    compression: Compression,
+
+    /// Level to compress the transfer archive at, from 0 to 9 for gzip and zip (6 by default) or 1 to 22 for zstd (3 by default).
+    ///
+    /// Higher levels make smaller transfers, but take longer to create.
+    #[clap(
+        long = "level",
+        env = "XFER_CLIENT_COMPRESSION_LEVEL",
+        allow_negative_numbers = true
+    )]
+    level: Option<i32>,
+
+    /// Keep symbolic links inside directories as links instead of sending the files they point to.
+    ///
+    /// Links are sent as they are, so ones pointing outside of the transfer may not resolve for the recipient.
+    #[clap(long = "symlinks", env = "XFER_CLIENT_SYMLINKS")]
+    symlinks: bool,
+
+    /// Send files that are hard linked more than once in the transfer a single time, keeping them as
+    /// hard links to each other.
+    ///
+    /// Not supported for zip archives.
+    #[cfg(unix)]
+    #[clap(long = "hardlinks", env = "XFER_CLIENT_HARDLINKS")]
+    hardlinks: bool,
+
+    /// Include the extended attributes of files, so the recipient can restore them with `download --xattrs`.
+    ///
+    /// Not supported for zip archives.
+    #[cfg(unix)]
+    #[clap(long = "xattrs", env = "XFER_CLIENT_XATTRS")]
+    xattrs: bool,
+
+    /// Send files with standard permissions instead of their own, only keeping whether they are executable.
+    #[cfg(unix)]
+    #[clap(long = "no-permissions", env = "XFER_CLIENT_NO_PERMISSIONS")]
+    no_permissions: bool,
+
+    /// Send the transfer archive without compressing it.
+    ///
+    /// Saves time when transferring data that is already compressed, such as videos, images or other archives.
+    #[clap(
+        long = "no-compress",
+        env = "XFER_CLIENT_NO_COMPRESS",
+        conflicts_with_all = ["compression", "level"]
+    )]
+    no_compress: bool,
+
+    #[clap(flatten)]
+    network: NetworkOptions,
 }
 
-impl ExecutableCommand for UploadCommand {
-    fn run(self) -> Result<()> {
-        let path_canonical = match fs::canonicalize(&self.path) {
-            Ok(path) => path,
-            Err(err) => bail!(
-                "failed while trying to read file or directory at '{}': {err}",
-                self.path.display()
+impl UploadCommand {
+    /// Build a command that runs this client against the same server with the given arguments.
+    fn client_command(&self, args: &str) -> Result<String> {
+        Ok(format!(
+            "{} {args}{}",
+            env::current_exe()?.file_name().map_or_else(
+                || env!("CARGO_PKG_NAME"),
+                |s| s.to_str().expect("current exe name should be valid UTF-8"),
             ),
-        };
-        let path_name = path_canonical
-            .file_name()
-            .context("failed to read file or directory name")?
-            .to_str()
-            .context("failed to parse file or directory name as str")?;
+            match self.server.as_str() == DEFAULT_SERVER_URL {
+                true => String::new(),
+                false => format!(" -s {}", self.server),
+            },
+        ))
+    }
 
-        // Ask the user if they'd like to upload the content.
-        if !self.no_confirm
-            && !Confirm::new(&format!(
-                "Are you sure you want to upload '{}'? ",
-                path_canonical.display()
-            ))
-            .with_default(false)
-            .prompt()?
-        {
-            return Ok(());
+    /// Build the command the recipient should run to download a transfer.
+    fn download_command(&self, transfer_key: &str) -> Result<String> {
+        Ok(format!(
+            "{} -o <PATH>",
+            self.client_command(&format!("download {transfer_key}"))?
+        ))
+    }
+
+    /// Build the instructions shown for downloading a transfer: the command the recipient should
+    /// run, followed by it as a QR code if requested.
+    fn download_instructions(&self, transfer_key: &str) -> Result<String> {
+        let command = self.download_command(transfer_key)?;
+        match self.qr {
+            true => Ok(format!("{command}\n\n{}", qr_code(&command)?)),
+            false => Ok(command),
         }
+    }
 
-        let prog_bar = ProgressBar::new_spinner();
-        prog_bar.enable_steady_tick(PROGRESS_BAR_TICKRATE);
+    /// Copy the command the recipient should run, or only the transfer key, to the clipboard if requested.
+    ///
+    /// The transfer already exists at this point, so failing to copy only prints a warning.
+    #[cfg(feature = "clipboard")]
+    fn copy_download_command(&self, transfer_key: &str) -> Result<()> {
+        let text = match self.copy {
+            None => return Ok(()),
+            Some(CopyTarget::Command) => self.download_command(transfer_key)?,
+            Some(CopyTarget::Key) => transfer_key.to_string(),
+        };
+        match crate::clipboard::copy(&text) {
+            Ok(()) if self.quiet || self.output_format == OutputFormat::Json => {}
+            Ok(()) => println!("\nCopied to the clipboard"),
+            Err(err) => eprintln!("\nWarning: failed to copy to the clipboard: {err}"),
+        }
+        Ok(())
+    }
 
+    /// Create the transfer archive for the paths and encrypt it with the chosen passphrase or a
+    /// generated one, returning it with its encrypted manifest, when the server can store one, and
+    /// its decryption key, encrypted to the recipients if any were given.
+    fn create_archive(
+        &self,
+        prog_bar: &ProgressBar,
+        paths: &[UploadPath],
+        server_config: &ServerConfigurationResponse,
+        passphrase: Option<String>,
+    ) -> Result<(Vec<u8>, Option<Vec<u8>>, String)> {
         // Compress into an archive.
         let mut archive_data = {
-            prog_bar.set_message(format!(
-                "Creating transfer archive for '{}'",
-                path_canonical.display()
-            ));
-            let mut archive =
-                tar::Builder::new(GzEncoder::new(Cursor::new(vec![]), Compression::default()));
-            if self.path.is_file() {
+            let mut entries = Vec::new();
+            for path in paths {
+                entries.extend(self.archive_entries(path)?);
+            }
+            show_bytes(
+                prog_bar,
+                format!(
+                    "Creating transfer archive for {}",
+                    quoted_list(paths.iter().map(|path| path.canonical.display()))
+                ),
+                entries.iter().map(ArchiveEntry::size).sum(),
+            );
+            match self.format {
+                ArchiveFormat::Tar => self.tar_archive(prog_bar, entries)?,
+                ArchiveFormat::Zip => self.zip_archive(prog_bar, entries)?,
+            }
+        };
+
+        // Encrypt and validate the archive size with the server.
+        show_spinner(prog_bar, "Validating transfer archive");
+        let max_size = match self.relay {
+            true => server_config.relay.max_size_bytes,
+            false => Some(server_config.transfer.max_size_bytes),
+        };
+        if let Some(max_size) = max_size
+            && archive_data.len() as u64 > max_size
+        {
+            return Err(Failure::new(
+                FailureKind::SizeLimit,
+                format!(
+                    "Transfer archive is larger than the server's maximum size of {} (was {})",
+                    DecimalBytes(max_size),
+                    DecimalBytes(archive_data.len() as u64)
+                ),
+            )
+            .into());
+        }
+        // Relays are downloaded as soon as they're sent, so there's nothing to list beforehand.
+        let mut manifest = match !self.relay && server_config.transfer.manifests_enabled {
+            true => {
+                show_spinner(prog_bar, "Listing transfer archive contents");
+                Some(Manifest::from_archive(&archive_data)?.to_bytes()?)
+            }
+            false => None,
+        };
+        // Encryption happens in a single pass over the whole archive, so its progress can't be shown.
+        show_spinner(prog_bar, "Encrypting transfer archive");
+        let mut arrays = vec![&mut archive_data];
+        arrays.extend(&mut manifest);
+        let decryption_key = match passphrase {
+            Some(passphrase) => {
+                Cryptography::encrypt_all_in_place_with_passphrase(&mut arrays, &passphrase)?;
+                passphrase
+            }
+            None => Cryptography::encrypt_all_in_place(&mut arrays)?,
+        };
+        let decryption_key = match self.recipients.is_empty() {
+            true => decryption_key,
+            false => encrypt_key_to_recipients(&decryption_key, &self.recipients)
+                .context("failed to encrypt decryption key to recipients")?,
+        };
+        if let Some(max_size) = max_size
+            && archive_data.len() as u64 > max_size
+        {
+            return Err(Failure::new(
+                FailureKind::SizeLimit,
+                format!(
+                    "Encrypted transfer archive is larger than the server's maximum size of {} (was {})",
+                    DecimalBytes(max_size),
+                    DecimalBytes(archive_data.len() as u64)
+                ),
+            )
+            .into());
+        }
+
+        Ok((archive_data, manifest, decryption_key))
+    }
+
+    /// Pack entries into a tar archive, compressing it unless asked not to.
+    fn tar_archive(&self, prog_bar: &ProgressBar, entries: Vec<ArchiveEntry>) -> Result<Vec<u8>> {
+        let compression = (!self.no_compress).then_some(self.compression);
+        let mut encoder = tar_writer(compression, Vec::new(), self.level)?;
+        let mut archive = tar::Builder::new(prog_bar.wrap_write(&mut encoder));
+        // Name each hard linked file was first added under, by device and inode.
+        #[cfg(unix)]
+        let mut hardlinks = HashMap::new();
+        for entry in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_metadata(&entry.metadata);
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::MetadataExt;
+                if self.no_permissions {
+                    header.set_mode(standard_mode(&entry.metadata));
+                }
+                if self.xattrs {
+                    let records = xattr_records(&entry.source)
+                        .context("failed to read extended attributes")?;
+                    if !records.is_empty() {
+                        archive.append_pax_extensions(
+                            records
+                                .iter()
+                                .map(|(key, value)| (key.as_str(), value.as_slice())),
+                        )?;
+                    }
+                }
+                if self.hardlinks && entry.metadata.is_file() && entry.metadata.nlink() > 1 {
+                    let key = (entry.metadata.dev(), entry.metadata.ino());
+                    if let Some(first) = hardlinks.get(&key) {
+                        header.set_entry_type(tar::EntryType::Link);
+                        header.set_size(0);
+                        archive
+                            .append_link(&mut header, &entry.name, first)
+                            .context("failed to append hard link to transfer archive")?;
+                        continue;
+                    }
+                    hardlinks.insert(key, entry.name.clone());
+                }
+            }
+            let file_type = entry.metadata.file_type();
+            if file_type.is_dir() {
+                archive
+                    .append_data(&mut header, &entry.name, io::empty())
+                    .context("failed to append directory to transfer archive")?;
+            } else if file_type.is_symlink() {
                 archive
-                    .append_path_with_name(&path_canonical, path_name)
+                    .append_link(&mut header, &entry.name, fs::read_link(&entry.source)?)
+                    .context("failed to append symbolic link to transfer archive")?;
+            } else {
+                archive
+                    .append_data(&mut header, &entry.name, File::open(&entry.source)?)
                     .context("failed to append file to transfer archive")?;
-            } else if self.path.is_dir() {
+            }
+        }
+        archive
+            .into_inner()
+            .context("failed to creatr transfer archive")?;
+        encoder
+            .finish()
+            .context("failed to compress transfer archive")
+    }
+
+    /// Pack entries into a zip archive, compressing each file with deflate unless asked not to.
+    ///
+    /// Modification times, symbolic links and, on Unix, permissions are kept like they are in tar archives.
+    fn zip_archive(&self, prog_bar: &ProgressBar, entries: Vec<ArchiveEntry>) -> Result<Vec<u8>> {
+        let mut archive = ZipWriter::new(Cursor::new(Vec::new()));
+        for entry in entries {
+            let mut options = SimpleFileOptions::default()
+                .compression_method(match self.no_compress {
+                    true => CompressionMethod::Stored,
+                    false => CompressionMethod::Deflated,
+                })
+                .compression_level(self.level.map(i64::from))
+                .large_file(entry.size() >= u64::from(u32::MAX));
+            // Zip can't store times before 1980, so those files are left without one.
+            if let Some(modified) = entry
+                .metadata
+                .modified()
+                .ok()
+                .and_then(|modified| OffsetDateTime::from(modified).try_into().ok())
+            {
+                options = options.last_modified_time(modified);
+            }
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                options = options.unix_permissions(match self.no_permissions {
+                    true => standard_mode(&entry.metadata),
+                    false => entry.metadata.permissions().mode(),
+                });
+            }
+            // Zip entry names always separate their components with forward slashes.
+            let name = entry
+                .name
+                .iter()
+                .map(|component| component.to_str())
+                .collect::<Option<Vec<_>>>()
+                .context("failed to parse file or directory name as str")?
+                .join("/");
+            let file_type = entry.metadata.file_type();
+            if file_type.is_dir() {
                 archive
-                    .append_dir_all(path_name, &path_canonical)
-                    .context("failed to append directory recursively to transfer archive")?;
+                    .add_directory(name, options)
+                    .context("failed to append directory to transfer archive")?;
+            } else if file_type.is_symlink() {
+                let target = fs::read_link(&entry.source)?;
+                archive
+                    .add_symlink(
+                        name,
+                        target
+                            .to_str()
+                            .context("failed to parse symbolic link target as str")?,
+                        options,
+                    )
+                    .context("failed to append symbolic link to transfer archive")?;
             } else {
-                bail!("could not determine if {path_canonical:?} is a file or directory");
+                archive
+                    .start_file(name, options)
+                    .context("failed to append file to transfer archive")?;
+                io::copy(
+                    &mut File::open(&entry.source)?,
+                    &mut prog_bar.wrap_write(&mut archive),
+                )
+                .context("failed to append file to transfer archive")?;
+            }
+        }
+        Ok(archive
+            .finish()
+            .context("failed to create transfer archive")?
+            .into_inner())
+    }
+
+    /// List what to add to the transfer archive for a path, applying the include, exclude and gitignore
+    /// rules to the contents of directories.
+    fn archive_entries(&self, path: &UploadPath) -> Result<Vec<ArchiveEntry>> {
+        if path.canonical.is_file() {
+            return Ok(vec![ArchiveEntry {
+                source: path.canonical.clone(),
+                name: PathBuf::from(&path.name),
+                metadata: fs::metadata(&path.canonical)?,
+            }]);
+        }
+        if !path.canonical.is_dir() {
+            bail!(
+                "could not determine if {:?} is a file or directory",
+                path.canonical
+            );
+        }
+
+        // Exclusions are added last so they win over inclusions that match the same path.
+        let mut overrides = OverrideBuilder::new(&path.canonical);
+        for glob in &self.include {
+            overrides
+                .add(glob)
+                .with_context(|| format!("invalid include glob '{glob}'"))?;
+        }
+        for glob in &self.exclude {
+            overrides
+                .add(&format!("!{glob}"))
+                .with_context(|| format!("invalid exclude glob '{glob}'"))?;
+        }
+        let mut walker = WalkBuilder::new(&path.canonical);
+        walker
+            .standard_filters(false)
+            .follow_links(!self.symlinks)
+            .overrides(overrides.build()?)
+            .sort_by_file_name(|a, b| a.cmp(b));
+        if self.gitignore {
+            walker
+                .git_ignore(true)
+                .git_global(true)
+                .git_exclude(true)
+                .parents(true)
+                .require_git(false);
+        }
+
+        let mut entries = Vec::new();
+        for entry in walker.build() {
+            let entry = entry.context("failed to read directory contents")?;
+            let relative = entry
+                .path()
+                .strip_prefix(&path.canonical)
+                .expect("walked path should be inside the directory being walked");
+            entries.push(ArchiveEntry {
+                source: entry.path().to_path_buf(),
+                name: Path::new(&path.name).join(relative),
+                metadata: entry.metadata()?,
+            });
+        }
+        // Every directory is walked to look for included files, so leave out the ones that don't hold any.
+        if !self.include.is_empty() {
+            let populated: HashSet<PathBuf> = entries
+                .iter()
+                .filter(|entry| !entry.metadata.is_dir())
+                .flat_map(|entry| entry.name.ancestors().skip(1).map(Path::to_path_buf))
+                .collect();
+            entries.retain(|entry| {
+                !entry.metadata.is_dir()
+                    || entry.name == Path::new(&path.name)
+                    || populated.contains(&entry.name)
+            });
+        }
+        Ok(entries)
+    }
+
+    /// Upload the chunks of a reserved transfer that the server hasn't accepted yet from several threads at once.
+    ///
+    /// Progress is saved after every chunk so the upload can be resumed if it is interrupted.
+    fn upload_in_chunks(
+        &self,
+        api_client: &XferApiClient,
+        prog_bar: &ProgressBar,
+        data: &Bytes,
+        state: UploadState,
+        state_files: &UploadStateFiles,
+    ) -> Result<CreateTransferResponse> {
+        let chunk_size = state.chunk_size;
+        let chunks: Vec<(u64, Bytes)> = (0..data.len())
+            .step_by(chunk_size)
+            .map(|offset| {
+                let end = data.len().min(offset + chunk_size);
+                (offset as u64, data.slice(offset..end))
+            })
+            .filter(|(offset, _)| !state.uploaded_offsets.contains(offset))
+            .collect();
+        let next_chunk = AtomicUsize::new(0);
+        show_bytes(
+            prog_bar,
+            "Uploading encrypted transfer archive to server",
+            data.len() as u64,
+        );
+        prog_bar.set_position(
+            data.len() as u64
+                - chunks
+                    .iter()
+                    .map(|(_, chunk)| chunk.len() as u64)
+                    .sum::<u64>(),
+        );
+        let failed = AtomicBool::new(false);
+        let reservation = state.reservation.clone();
+        let state = Mutex::new(state);
+        let upload_chunks = || -> Result<()> {
+            while !failed.load(Ordering::Relaxed) {
+                let Some((offset, chunk)) = chunks.get(next_chunk.fetch_add(1, Ordering::Relaxed))
+                else {
+                    return Ok(());
+                };
+                let result = api_client
+                    .upload_transfer_chunk(&reservation, *offset, chunk.clone())
+                    .context(format!(
+                        "failed to upload chunk at offset {offset} to server"
+                    ))
+                    .and_then(|response| {
+                        let mut state = state.lock().unwrap();
+                        state.reservation.expires_at = response.expires_at;
+                        state.uploaded_offsets.insert(*offset);
+                        state_files.save(&state)
+                    });
+                if let Err(err) = result {
+                    failed.store(true, Ordering::Relaxed);
+                    return Err(err);
+                }
+                prog_bar.inc(chunk.len() as u64);
             }
-            archive
-                .into_inner()
-                .context("failed to creatr transfer archive")?
-                .into_inner()
-                .into_inner()
+            Ok(())
         };
+        thread::scope(|scope| -> Result<()> {
+            let workers: Vec<_> = (0..usize::from(self.parallel_uploads).min(chunks.len()))
+                .map(|_| scope.spawn(upload_chunks))
+                .collect();
+            for worker in workers {
+                worker
+                    .join()
+                    .expect("chunk upload thread should not panic")?;
+            }
+            Ok(())
+        })
+        .context(
+            "upload was interrupted - run the same command again with --resume to continue it",
+        )?;
+        let state = state.into_inner().unwrap();
 
-        // Encrypt and validate the archive size with the server.
-        prog_bar.set_message("Validating transfer archive");
-        let api_client = XferApiClient::new(&self.server);
+        show_spinner(prog_bar, "Finishing upload");
+        let checksum = blake3::hash(data);
+        loop {
+            match api_client.complete_chunked_transfer(
+                &state.reservation,
+                checksum,
+                &self.transfer_options(),
+            ) {
+                Ok(response) => {
+                    state_files.remove();
+                    return Ok(response);
+                }
+                Err(err) => match err.downcast_ref::<RetryLaterError>() {
+                    Some(retry) => thread::sleep(retry.retry_after),
+                    None => return Err(err.context("failed to finish chunked upload")),
+                },
+            }
+        }
+    }
+}
+
+impl UploadCommand {
+    /// Directories given to `watch` in place of the paths to upload.
+    pub(super) fn watch_directories(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// Prepare the options given to `watch` to upload each new file without asking anything, so
+    /// uploads can happen unattended.
+    pub(super) fn into_unattended(self) -> Result<Self> {
+        if self.relay {
+            bail!("relayed transfers wait for the recipient to connect, watch without --relay");
+        }
+        if self.resume {
+            bail!("interrupted uploads can't be resumed while watching, watch without --resume");
+        }
+        let passphrase = match &self.passphrase {
+            Some(given) => Some(resolve_passphrase(given, !self.quiet, true)?),
+            None => None,
+        };
+        Ok(Self {
+            no_confirm: true,
+            separate: false,
+            passphrase,
+            ..self
+        })
+    }
+
+    /// Upload a file as a new transfer with the same options.
+    pub(super) fn upload_file(&self, path: PathBuf) -> Result<()> {
+        Self {
+            paths: vec![path],
+            ..self.clone()
+        }
+        .run()
+    }
+
+    /// Upload the paths as a transfer, returning it once created, or `None` for relays, which print
+    /// their own results as they need to be shared before the upload can finish.
+    fn upload(&self, paths: &[UploadPath]) -> Result<Option<CreatedTransfer>> {
+        let paths_display = quoted_list(paths.iter().map(|path| path.canonical.display()));
+        let names_display = quoted_list(paths.iter().map(|path| &path.name));
+
+        let api_client = self
+            .network
+            .api_client(&self.server)?
+            .with_upload_token(self.upload_token.clone());
         let server_config = api_client
             .get_server_config()
             .context("failed to obtain server config, are you using the right server?")?;
-        let bytes_human = DecimalBytes(server_config.transfer.max_size_bytes);
-        if archive_data.len() as u64 > server_config.transfer.max_size_bytes {
-            bail!(
-                "Transfer archive is larger than the server's maximum size of {} (was {})",
-                bytes_human,
-                DecimalBytes(archive_data.len() as u64)
-            )
+        if let Some(motd) = &server_config.motd {
+            // Strip control characters so the server can't inject terminal escape sequences.
+            let motd: String = motd
+                .chars()
+                .filter(|c| *c == '\n' || !c.is_control())
+                .collect();
+            match self.output_format {
+                _ if self.quiet => {}
+                OutputFormat::Json => eprintln!("Message from {}:\n{motd}\n", self.server),
+                OutputFormat::Text => println!("Message from {}:\n{motd}\n", self.server),
+            }
         }
-        prog_bar.set_message("Encrypting transfer archive");
-        let decryption_key = Cryptography::encrypt_in_place(&mut archive_data)?;
-        if archive_data.len() as u64 > server_config.transfer.max_size_bytes {
-            bail!(
-                "Encrypted transfer archive is larger than the server's maximum size of {} (was {})",
-                bytes_human,
-                DecimalBytes(archive_data.len() as u64)
-            )
+        if !server_config.transfer.uploads_enabled {
+            bail!("server is in maintenance mode and not accepting new transfers, try again later");
+        }
+        if server_config.transfer.upload_token_required && self.upload_token.is_none() {
+            bail!("server requires an upload token, pass one with --upload-token");
+        }
+        if self.relay && !server_config.relay.enabled {
+            bail!("server does not support relayed transfers, upload without --relay");
+        }
+        if self.claim_webhook.is_some() && !server_config.transfer.claim_webhooks_enabled {
+            bail!("server does not allow claim webhooks, upload without --claim-webhook");
+        }
+        if self.notify_email.is_some() && !server_config.transfer.notification_emails_enabled {
+            bail!("server does not allow notification emails, upload without --notify-email");
+        }
+        if self.max_downloads.is_some() && !server_config.transfer.download_limits_enabled {
+            bail!("server does not support limiting downloads, upload without --max-downloads");
+        }
+        if let Some(expire_after) = self.expire_after {
+            let (Some(min), Some(max)) = (
+                server_config.transfer.min_expire_after_ms,
+                server_config.transfer.max_expire_after_ms,
+            ) else {
+                bail!(
+                    "server does not support choosing when transfers expire, upload without --expire"
+                );
+            };
+            let (min, max) = (Duration::from_millis(min), Duration::from_millis(max));
+            if expire_after < min || expire_after > max {
+                bail!(
+                    "server only keeps transfers for between {} and {}, choose an --expire within that range",
+                    HumanDuration(min),
+                    HumanDuration(max)
+                );
+            }
+        }
+
+        // Find the interrupted upload to continue when resuming.
+        let state_files = UploadStateFiles::new(
+            &self.server,
+            paths.iter().map(|path| path.canonical.as_path()),
+        )?;
+        let resumed = match self.resume {
+            true => {
+                if !server_config.transfer.chunked_uploads_enabled {
+                    bail!("server does not support resuming uploads");
+                }
+                let state = state_files.load()?.with_context(|| {
+                    format!("no interrupted upload of {paths_display} to this server was found")
+                })?;
+                if UtcDateTime::parse(&state.reservation.expires_at, &Rfc3339)
+                    .is_ok_and(|expires_at| expires_at <= UtcDateTime::now())
+                {
+                    state_files.remove();
+                    bail!(
+                        "the interrupted upload has lapsed on the server, upload it again without --resume"
+                    );
+                }
+                Some(state)
+            }
+            false => None,
+        };
+
+        // Ask the user if they'd like to upload the content.
+        if !self.no_confirm
+            && !self.quiet
+            && !Confirm::new(&format!(
+                "Are you sure you want to {} {paths_display}? ",
+                match resumed {
+                    Some(_) => "resume uploading",
+                    None => "upload",
+                },
+            ))
+            .with_default(false)
+            .prompt()?
+        {
+            return Err(Failure::aborted().into());
+        }
+
+        // Resumed uploads were already encrypted, so don't need the passphrase again.
+        let passphrase = match (&self.passphrase, &resumed) {
+            (Some(given), None) => Some(resolve_passphrase(given, !self.quiet, true)?),
+            _ => None,
+        };
+
+        let prog_bar = match self.quiet {
+            true => ProgressBar::hidden(),
+            false => ProgressBar::new_spinner(),
+        };
+        prog_bar.enable_steady_tick(PROGRESS_BAR_TICKRATE);
+
+        let (archive_data, manifest, decryption_key) = match &resumed {
+            // Reuse the archive that was encrypted for the interrupted upload.
+            Some(state) => {
+                prog_bar.set_message("Reading encrypted transfer archive");
+                let archive_data = state_files.read_archive()?;
+                if blake3::hash(&archive_data).to_hex().as_str() != state.checksum {
+                    state_files.remove();
+                    bail!(
+                        "encrypted archive of the interrupted upload was modified, upload it again without --resume"
+                    );
+                }
+                let decryption_key = match (&state.decryption_key, state.passphrase_protected) {
+                    (Some(decryption_key), _) => decryption_key.clone(),
+                    // Only the identifier is shared for transfers protected with a passphrase, so it isn't needed.
+                    (None, true) => String::new(),
+                    (None, false) => {
+                        state_files.remove();
+                        bail!(
+                            "the interrupted upload's decryption key was not saved, upload it again without --resume"
+                        );
+                    }
+                };
+                (archive_data, state_files.read_manifest()?, decryption_key)
+            }
+            None => self.create_archive(&prog_bar, paths, &server_config, passphrase)?,
+        };
+        // Shared rather than copied by every request that sends the archive, including retries.
+        let archive_data = Bytes::from(archive_data);
+        // Transfers protected with a passphrase are shared by their identifier alone, as the
+        // recipient is told the passphrase separately.
+        let passphrase_protected = match &resumed {
+            Some(state) => state.passphrase_protected,
+            None => self.passphrase.is_some(),
+        };
+        let transfer_key = |id: &str| match passphrase_protected {
+            true => id.to_string(),
+            false => format!("{id}/{decryption_key}"),
+        };
+        // Solve a fresh proof-of-work challenge as the one fetched earlier may have expired while archiving.
+        let solve_proof_of_work = || -> Result<Option<String>> {
+            Ok(match server_config.transfer.proof_of_work {
+                Some(_) => {
+                    prog_bar.set_message("Solving server proof-of-work challenge");
+                    api_client
+                        .get_server_config()
+                        .context("failed to obtain proof-of-work challenge from server")?
+                        .transfer
+                        .proof_of_work
+                        .map(|challenge| challenge.solve())
+                        .transpose()?
+                }
+                None => None,
+            })
+        };
+        // Resumed uploads already hold a reservation, so don't need to prove any work again.
+        let mut proof_of_work = match resumed {
+            Some(_) => None,
+            None => solve_proof_of_work()?,
+        };
+
+        // Relay the archive straight to the recipient once they connect.
+        if self.relay {
+            let relay = api_client
+                .open_relay(archive_data.len() as u64, proof_of_work)
+                .context("failed to open relay on server")?;
+            let relay_key = transfer_key(&relay.id);
+            prog_bar.suspend(|| -> Result<()> {
+                if self.quiet {
+                    #[cfg(feature = "clipboard")]
+                    self.copy_download_command(&relay_key)?;
+                    println!("{relay_key}");
+                    return Ok(());
+                }
+                if self.output_format == OutputFormat::Json {
+                    #[cfg(feature = "clipboard")]
+                    self.copy_download_command(&relay_key)?;
+                    return print_json(&UploadOutput {
+                        id: &relay.id,
+                        key: relay_key.clone(),
+                        paths: paths.iter().map(|path| path.canonical.as_path()).collect(),
+                        size_bytes: archive_data.len() as u64,
+                        relay: true,
+                        expires_at: Some(&relay.expires_at),
+                        download_command: self.download_command(&relay_key)?,
+                        download_url: None,
+                        deletion_token: None,
+                        activity_token: None,
+                    });
+                }
+                println!(
+                    "\nOpened relay for {}\nThe recipient should run:\n\n{}\n\nThis relay will lapse {} if the recipient hasn't started downloading",
+                    names_display,
+                    self.download_instructions(&relay_key)?,
+                    UtcDateTime::parse(&relay.expires_at, &Rfc3339)
+                        .ok()
+                        .and_then(|expires_at| format_expiry(expires_at).ok())
+                        .unwrap_or(String::from("in a few minutes")),
+                );
+                #[cfg(feature = "clipboard")]
+                self.copy_download_command(&relay_key)?;
+                println!();
+                Ok(())
+            })?;
+            show_bytes(
+                &prog_bar,
+                "Waiting for the recipient to download the transfer",
+                archive_data.len() as u64,
+            );
+            api_client
+                .upload_relay(&relay, archive_data, &prog_bar)
+                .context("failed to relay encrypted transfer archive to the recipient")?;
+            prog_bar.finish_and_clear();
+            if self.output_format == OutputFormat::Text && !self.quiet {
+                println!("The recipient has received the transfer");
+            }
+            return Ok(None);
         }
 
-        // Upload the archive.
-        prog_bar.set_message(format!(
-            "Uploading encrypted transfer archive to server ({})",
-            DecimalBytes(archive_data.len() as u64)
-        ));
-        let transfer_response = api_client
-            .create_transfer(archive_data)
-            .context("failed to upload encrypted transfer archive to server")?;
+        // Upload the archive, in parallel chunks when the server supports it and it's large enough to benefit.
+        let chunked = server_config.transfer.chunked_uploads_enabled
+            && archive_data.len() > usize::from(self.chunk_size_mib) * 1024 * 1024;
+        let transfer_response = if let Some(state) = resumed {
+            self.upload_in_chunks(&api_client, &prog_bar, &archive_data, state, &state_files)?
+        } else if chunked {
+            let reservation = api_client
+                .reserve_transfer(archive_data.len() as u64, proof_of_work, self.expire_after)
+                .context("failed to reserve transfer on server")?;
+            let state = UploadState {
+                reservation,
+                decryption_key: (!passphrase_protected).then(|| decryption_key.clone()),
+                passphrase_protected,
+                checksum: blake3::hash(&archive_data).to_hex().to_string(),
+                chunk_size: usize::from(self.chunk_size_mib) * 1024 * 1024,
+                uploaded_offsets: BTreeSet::new(),
+            };
+            state_files.save_archive(&archive_data)?;
+            if let Some(manifest) = &manifest {
+                state_files.save_manifest(manifest)?;
+            }
+            state_files.save(&state)?;
+            self.upload_in_chunks(&api_client, &prog_bar, &archive_data, state, &state_files)?
+        } else {
+            loop {
+                show_bytes(
+                    &prog_bar,
+                    "Uploading encrypted transfer archive to server",
+                    archive_data.len() as u64,
+                );
+                let err = match api_client.create_transfer(
+                    archive_data.clone(),
+                    &prog_bar,
+                    proof_of_work,
+                    self.expire_after,
+                    &self.transfer_options(),
+                ) {
+                    Ok(response) => break response,
+                    Err(err) => err,
+                };
+                let Some(retry) = err.downcast_ref::<RetryLaterError>() else {
+                    return Err(
+                        err.context("failed to upload encrypted transfer archive to server")
+                    );
+                };
+                let retry_after = retry.retry_after;
+                // Ask the user if they'd like to wait for the server to become available again.
+                let wait = prog_bar.suspend(|| -> Result<bool> {
+                    if self.quiet {
+                        return Ok(true);
+                    }
+                    println!("The server could not accept the transfer right now: {retry}");
+                    Ok(self.no_confirm
+                        || Confirm::new(&format!(
+                            "The server asked to try again in {}. Wait and retry? ",
+                            HumanDuration(retry_after)
+                        ))
+                        .with_default(true)
+                        .prompt()?)
+                })?;
+                if !wait {
+                    return Err(
+                        err.context("failed to upload encrypted transfer archive to server")
+                    );
+                }
+                show_spinner(
+                    &prog_bar,
+                    format!(
+                        "Waiting {} for the server to become available",
+                        HumanDuration(retry_after)
+                    ),
+                );
+                thread::sleep(retry_after);
+                proof_of_work = solve_proof_of_work()?;
+            }
+        };
+        // The transfer is usable without its manifest, so failing to store it only loses `xfer list`.
+        if let (Some(manifest), Some(deletion_token)) =
+            (manifest, &transfer_response.deletion_token)
+        {
+            show_spinner(&prog_bar, "Uploading encrypted manifest to server");
+            if let Err(err) =
+                api_client.upload_manifest(&transfer_response.id, deletion_token, manifest)
+                && !self.quiet
+            {
+                prog_bar.suspend(|| {
+                    eprintln!("Warning: failed to store the transfer's manifest, so its contents can't be listed before downloading: {err:#}")
+                });
+            }
+        }
         prog_bar.finish_and_clear();
 
+        let key = transfer_key(&transfer_response.id);
+        // The download page asks for the passphrase of transfers protected with one, but can't
+        // decrypt keys encrypted to age recipients as it has no way to read their identity.
+        let download_url = match server_config.web.download_page_enabled
+            && !decryption_key.starts_with(RECIPIENT_KEY_PREFIX)
+        {
+            true => {
+                let mut url = api_client
+                    .base_url()
+                    .join(&format!("t/{}", transfer_response.id))?;
+                if !passphrase_protected {
+                    url.set_fragment(Some(&decryption_key));
+                }
+                Some(url.to_string())
+            }
+            false => None,
+        };
+        Ok(Some(CreatedTransfer {
+            activity_url: api_client.transfer_activity_url(&transfer_response.id)?,
+            response: transfer_response,
+            key,
+            size_bytes: archive_data.len() as u64,
+            download_url,
+            passphrase_protected,
+        }))
+    }
+
+    /// Options for the server to apply to the transfer once it has been uploaded.
+    fn transfer_options(&self) -> TransferOptions<'_> {
+        TransferOptions {
+            claim_webhook: self.claim_webhook.as_ref(),
+            notify_email: self.notify_email.as_deref(),
+            max_downloads: self.max_downloads,
+        }
+    }
+
+    /// Describe a created transfer for machine-readable output.
+    fn upload_output<'a>(
+        &self,
+        paths: &'a [UploadPath],
+        transfer: &'a CreatedTransfer,
+    ) -> Result<UploadOutput<'a>> {
+        Ok(UploadOutput {
+            id: &transfer.response.id,
+            key: transfer.key.clone(),
+            paths: paths.iter().map(|path| path.canonical.as_path()).collect(),
+            size_bytes: transfer.size_bytes,
+            relay: false,
+            expires_at: transfer.response.expires_at.as_deref(),
+            download_command: self.download_command(&transfer.key)?,
+            download_url: transfer.download_url.clone(),
+            deletion_token: transfer.response.deletion_token.as_deref(),
+            activity_token: transfer.response.activity_token.as_deref(),
+        })
+    }
+
+    /// Print how to download and manage a created transfer.
+    fn print_transfer(&self, paths: &[UploadPath], transfer: &CreatedTransfer) -> Result<()> {
+        let key = &transfer.key;
+        let transfer_response = &transfer.response;
+        if self.quiet {
+            #[cfg(feature = "clipboard")]
+            self.copy_download_command(key)?;
+            println!("{key}");
+            return Ok(());
+        }
+        if self.output_format == OutputFormat::Json {
+            #[cfg(feature = "clipboard")]
+            self.copy_download_command(key)?;
+            return print_json(&self.upload_output(paths, transfer)?);
+        }
+
         println!(
-            "\nCreated transfer for '{}'\nThe recipient should run:\n\n{} download {}{} -o <PATH>\n\nThis transfer will expire {}",
-            path_name,
-            env::current_exe()?.file_name().map_or_else(
-                || env!("CARGO_PKG_NAME"),
-                |s| s.to_str().expect("current exe name should be valid UTF-8"),
-            ),
-            format_args!("{}/{}", transfer_response.id, decryption_key),
-            match self.server.as_str() == DEFAULT_SERVER_URL {
-                true => String::new(),
-                false => format!(" -s {}", self.server),
-            },
-            UtcDateTime::from_unix_timestamp(
-                SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .context("clock moved backwards")?
-                    .add(Duration::from_millis(
-                        server_config.transfer.expire_after_ms as u64,
-                    ))
-                    .as_secs() as i64
-            )
-            .context("expiry timestamp was out of range")?
-            .to_offset(UtcOffset::current_local_offset().unwrap_or(UtcOffset::UTC))
-            .format(&format_description::parse_borrowed::<2>(
-                "on [day]-[month]-[year] at [hour]:[minute]:[second] (UTC[offset_hour sign:mandatory]:[offset_minute])",
-            )?).unwrap_or(String::from("at an unknown time (server did not provide expiry data)")),
+            "\nCreated transfer for {}\nThe recipient should run:\n\n{}\n\nThis transfer will expire {}",
+            quoted_list(paths.iter().map(|path| &path.name)),
+            self.download_instructions(key)?,
+            transfer_response
+                .expires_at
+                .as_deref()
+                .and_then(|v| UtcDateTime::parse(v, &Rfc3339).ok())
+                .and_then(|expires_at| format_expiry(expires_at).ok())
+                .unwrap_or(String::from(
+                    "at an unknown time (server did not provide expiry data)"
+                )),
         );
+        match self.max_downloads {
+            Some(1) => println!("It will be removed once it has been downloaded"),
+            Some(max_downloads) => {
+                println!("It will be removed once it has been downloaded {max_downloads} times")
+            }
+            None => {}
+        }
+        if transfer.passphrase_protected {
+            println!(
+                "\nThey will be asked for the passphrase you chose, so tell it to them separately"
+            );
+        }
+        #[cfg(feature = "clipboard")]
+        self.copy_download_command(key)?;
+        if let Some(download_url) = &transfer.download_url {
+            println!(
+                "\nRecipients without xfer installed can download it in their browser at:\n\n{download_url}"
+            );
+        }
+        if let Some(deletion_token) = &transfer_response.deletion_token {
+            println!(
+                "\nTo delete the transfer before it expires, run:\n\n{}",
+                self.client_command(&format!("delete {} {deletion_token}", transfer_response.id))?
+            );
+        }
+        if let Some(activity_token) = &transfer_response.activity_token {
+            println!(
+                "\nTo see when the transfer has been downloaded, run:\n\ncurl -H 'X-Xfer-Activity-Token: {}' {}",
+                activity_token, transfer.activity_url
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Upload each path as its own transfer, several at a time, then print all of their keys.
+    fn upload_separately(&self) -> Result<()> {
+        let paths = self
+            .paths
+            .iter()
+            .map(|path| upload_paths(slice::from_ref(path)))
+            .collect::<Result<Vec<_>>>()?;
+        if !self.no_confirm
+            && !self.quiet
+            && !Confirm::new(&format!(
+                "Are you sure you want to upload {} as {} separate transfers? ",
+                quoted_list(paths.iter().flatten().map(|path| path.canonical.display())),
+                paths.len()
+            ))
+            .with_default(false)
+            .prompt()?
+        {
+            return Err(Failure::aborted().into());
+        }
+
+        // Transfers are uploaded without prompting or showing their own progress, so the passphrase
+        // is asked for once for all of them.
+        let upload = Self {
+            no_confirm: true,
+            quiet: true,
+            passphrase: match &self.passphrase {
+                Some(given) => Some(resolve_passphrase(given, !self.quiet, true)?),
+                None => None,
+            },
+            ..self.clone()
+        };
+        let prog_bar = match self.quiet {
+            true => ProgressBar::hidden(),
+            false => ProgressBar::new(paths.len() as u64).with_style(
+                ProgressStyle::with_template("{spinner} {msg}\n{wide_bar} {pos}/{len} transfers")
+                    .expect("progress bar template should be valid"),
+            ),
+        };
+        prog_bar.set_message(format!("Uploading {} separate transfers", paths.len()));
+        prog_bar.enable_steady_tick(PROGRESS_BAR_TICKRATE);
+        let next_path = AtomicUsize::new(0);
+        let upload_paths = || {
+            let mut results = Vec::new();
+            while let Some(index) =
+                Some(next_path.fetch_add(1, Ordering::Relaxed)).filter(|index| *index < paths.len())
+            {
+                let result = upload.upload(&paths[index]).map(|transfer| {
+                    transfer.expect("relayed transfers should not be uploaded separately")
+                });
+                results.push((index, result));
+                prog_bar.inc(1);
+            }
+            results
+        };
+        let mut results: Vec<_> = thread::scope(|scope| {
+            let workers: Vec<_> = (0..usize::from(self.parallel_uploads).min(paths.len()))
+                .map(|_| scope.spawn(upload_paths))
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| {
+                    worker
+                        .join()
+                        .expect("transfer upload thread should not panic")
+                })
+                .collect()
+        });
+        results.sort_by_key(|(index, _)| *index);
+        prog_bar.finish_and_clear();
 
+        // Print every transfer that was created before failing, so none of them are lost.
+        let mut created = Vec::new();
+        for (index, result) in results {
+            match result {
+                Ok(transfer) => created.push((&paths[index], transfer)),
+                Err(err) => eprintln!(
+                    "Error: failed to upload '{}': {err:?}",
+                    self.paths[index].display()
+                ),
+            }
+        }
+        match self.output_format {
+            _ if self.quiet => {
+                for (_, transfer) in &created {
+                    println!("{}", transfer.key);
+                }
+            }
+            OutputFormat::Json => print_json(&SeparateUploadOutput {
+                transfers: created
+                    .iter()
+                    .map(|(paths, transfer)| self.upload_output(paths, transfer))
+                    .collect::<Result<_>>()?,
+            })?,
+            OutputFormat::Text if !created.is_empty() => {
+                let name_width = created
+                    .iter()
+                    .map(|(paths, _)| paths[0].name.len())
+                    .max()
+                    .unwrap_or_default();
+                println!("\nCreated {} separate transfers:\n", created.len());
+                for (paths, transfer) in &created {
+                    println!("{:name_width$}  {}", paths[0].name, transfer.key);
+                }
+                println!(
+                    "\nEach recipient should run the following with the key of their transfer:\n\n{}",
+                    self.download_command("<KEY>")?
+                );
+                if created
+                    .iter()
+                    .any(|(_, transfer)| transfer.passphrase_protected)
+                {
+                    println!(
+                        "\nThey will be asked for the passphrase you chose, so tell it to them separately"
+                    );
+                }
+                println!(
+                    "\nUse --output-format json to also get the tokens to delete each transfer or see its downloads"
+                );
+            }
+            OutputFormat::Text => {}
+        }
+        let failed = paths.len() - created.len();
+        if failed > 0 {
+            bail!("failed to upload {failed} of {} transfers", paths.len());
+        }
         Ok(())
     }
 }
+
+impl ExecutableCommand for UploadCommand {
+    fn run(self) -> Result<()> {
+        #[cfg(unix)]
+        if self.format == ArchiveFormat::Zip && (self.hardlinks || self.xattrs) {
+            bail!(
+                "zip archives can't store hard links or extended attributes, upload without --format zip"
+            );
+        }
+        if let Some(level) = self.level {
+            // Zip archives are compressed with deflate, which has the same levels as gzip.
+            match self.format {
+                ArchiveFormat::Tar => self.compression,
+                ArchiveFormat::Zip => Compression::Gzip,
+            }
+            .check_level(level)?;
+        }
+        if self.separate {
+            return self.upload_separately();
+        }
+        let paths = upload_paths(&self.paths)?;
+        let Some(transfer) = self.upload(&paths)? else {
+            return Ok(());
+        };
+        self.print_transfer(&paths, &transfer)
+    }
+}
@@ -0,0 +1,124 @@
+use super::UploadCommand;
+use crate::ExecutableCommand;
+use anyhow::{Context, bail};
+use clap::{Parser, ValueHint};
+use notify::{
+    EventKind, RecursiveMode, Watcher,
+    event::{ModifyKind, RenameMode},
+};
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::mpsc::{self, RecvTimeoutError},
+    time::{Duration, Instant},
+};
+
+/// Watch directories and upload each file added to them as its own transfer.
+///
+/// Files are only uploaded once they have stopped changing, so ones still being written or copied
+/// aren't sent half finished. Files that were already there when watching started, hidden files and
+/// anything inside subdirectories are left alone. All of the options for uploading apply to every
+/// upload, which print their results as they finish.
+#[derive(Parser)]
+#[command(mut_arg("paths", |arg| arg
+    .help("Directories to watch for new files")
+    .long_help(None)
+    .value_name("DIRECTORY")
+    .value_hint(ValueHint::DirPath)
+))]
+pub struct WatchCommand {
+    /// Seconds a new file must go without changing before it is uploaded.
+    #[clap(
+        long = "settle",
+        env = "XFER_CLIENT_WATCH_SETTLE",
+        value_name = "SECONDS",
+        default_value_t = 2
+    )]
+    settle: u64,
+
+    #[clap(flatten)]
+    upload: UploadCommand,
+}
+
+impl ExecutableCommand for WatchCommand {
+    fn run(self) -> anyhow::Result<()> {
+        let settle = Duration::from_secs(self.settle);
+        let directories = self.upload.watch_directories().to_vec();
+        let upload = self.upload.into_unattended()?;
+
+        let (events_tx, events) = mpsc::channel();
+        let mut watcher =
+            notify::recommended_watcher(events_tx).context("failed to start watching for files")?;
+        for directory in &directories {
+            if !directory.is_dir() {
+                bail!("'{}' is not a directory", directory.display());
+            }
+            watcher
+                .watch(directory, RecursiveMode::NonRecursive)
+                .with_context(|| format!("failed to watch '{}'", directory.display()))?;
+        }
+        eprintln!(
+            "Watching {} for new files, press Ctrl+C to stop",
+            directories
+                .iter()
+                .map(|directory| format!("'{}'", directory.display()))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+
+        // When each added file last changed, so it can be uploaded once it has settled. Changes to
+        // files that weren't added while watching are ignored.
+        let mut added: HashMap<PathBuf, Instant> = HashMap::new();
+        loop {
+            match events.recv_timeout(settle.max(Duration::from_millis(200))) {
+                Ok(Ok(event)) => match event.kind {
+                    EventKind::Create(_)
+                    | EventKind::Modify(ModifyKind::Name(RenameMode::To | RenameMode::Any)) => {
+                        for path in event.paths {
+                            added.insert(path, Instant::now());
+                        }
+                    }
+                    // Renames within a directory carry the old and new path, in that order.
+                    EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+                        if let Some(path) = event.paths.into_iter().nth(1) {
+                            added.insert(path, Instant::now());
+                        }
+                    }
+                    EventKind::Modify(_) => {
+                        for path in event.paths {
+                            if let Some(changed_at) = added.get_mut(&path) {
+                                *changed_at = Instant::now();
+                            }
+                        }
+                    }
+                    _ => {}
+                },
+                Err(RecvTimeoutError::Timeout) => {}
+                Ok(Err(err)) => eprintln!("Warning: failed to watch for files: {err}"),
+                Err(RecvTimeoutError::Disconnected) => bail!("stopped watching for files"),
+            }
+
+            let settled: Vec<PathBuf> = added
+                .iter()
+                .filter(|(_, changed_at)| changed_at.elapsed() >= settle)
+                .map(|(path, _)| path.clone())
+                .collect();
+            for path in settled {
+                added.remove(&path);
+                // Files that were removed or renamed away since they changed are skipped here.
+                let is_file = path
+                    .symlink_metadata()
+                    .is_ok_and(|metadata| metadata.is_file());
+                let hidden = path
+                    .file_name()
+                    .is_none_or(|name| name.to_string_lossy().starts_with('.'));
+                if !is_file || hidden {
+                    continue;
+                }
+                if let Err(err) = upload.upload_file(path.clone()) {
+                    eprintln!("Error: failed to upload '{}': {err:?}", path.display());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use crate::secrets;
+use anyhow::{Context, Result, bail};
+use clap::{ArgMatches, Command, parser::ValueSource};
+use serde::Deserialize;
+use std::{collections::HashMap, env, fs, io::ErrorKind, path::PathBuf};
+use toml::{Table, Value};
+
+/// Client configuration file, holding named profiles of defaults for command-line options.
+///
+/// ```toml
+/// default_profile = "work"
+///
+/// [profiles.work]
+/// server = "https://xfer.example.com/"
+/// upload-token = { keyring = "work-upload-token" }
+/// output = "~/Downloads"
+///
+/// [profiles.work.upload]
+/// parallel-uploads = 8
+/// ```
+///
+/// Each key of a profile is the long name of an option, and applies to every command that has
+/// that option. Options for a single command can be set in a table named after the command.
+/// A value of `{ keyring = "<name>" }` is read from the secret stored under that name in the
+/// system keyring.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct ClientConfig {
+    /// Profile used when none is selected with `--profile`.
+    default_profile: Option<String>,
+    #[serde(default)]
+    profiles: HashMap<String, Table>,
+}
+
+/// Get the path of the client configuration file.
+pub fn config_path() -> Result<PathBuf> {
+    if let Some(dir) = env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        return Ok(PathBuf::from(dir).join("xfer/config.toml"));
+    }
+    if cfg!(windows)
+        && let Some(dir) = env::var_os("APPDATA")
+    {
+        return Ok(PathBuf::from(dir).join("xfer/config.toml"));
+    }
+    let home = env::var_os("HOME").context("could not determine home directory")?;
+    Ok(PathBuf::from(home).join(".config/xfer/config.toml"))
+}
+
+/// Use the options of a profile from the configuration file as the defaults of a command's arguments.
+///
+/// The profile is the one named by `profile`, or the configured default profile otherwise.
+/// Options given on the command line or through environment variables still take precedence.
+/// Every option of the profile is checked, but only those of the invoked `subcommand` that weren't
+/// already given in its `matches` are applied, so secrets are only read from the keyring when needed.
+pub fn apply_profile(
+    mut command: Command,
+    profile: Option<&str>,
+    subcommand: Option<(&str, &ArgMatches)>,
+) -> Result<Command> {
+    let path = config_path()?;
+    let config: ClientConfig = match fs::read_to_string(&path) {
+        Ok(contents) => toml::from_str(&contents)
+            .with_context(|| format!("failed to parse config file at '{}'", path.display()))?,
+        Err(err) if err.kind() == ErrorKind::NotFound => ClientConfig::default(),
+        Err(err) => {
+            return Err(err)
+                .with_context(|| format!("failed to read config file at '{}'", path.display()));
+        }
+    };
+    let Some(name) = profile.or(config.default_profile.as_deref()) else {
+        return Ok(command);
+    };
+    let Some(options) = config.profiles.get(name) else {
+        bail!(
+            "profile '{name}' does not exist in config file at '{}'",
+            path.display()
+        );
+    };
+
+    // Options shared by every command are applied first so command-specific tables override them.
+    for (key, value) in options.iter().filter(|(_, value)| !is_command_table(value)) {
+        check_keyring_reference(name, key, value)?;
+        if !command
+            .get_subcommands()
+            .any(|subcommand| has_option(subcommand, key))
+        {
+            bail!("profile '{name}' sets unknown option '{key}'");
+        }
+        if let Some((subcommand, matches)) = subcommand
+            && command
+                .find_subcommand(subcommand)
+                .is_some_and(|subcommand| has_option(subcommand, key))
+        {
+            command = apply_option(command, subcommand, matches, key, value)?;
+        }
+    }
+    for (table_command, value) in options.iter().filter(|(_, value)| is_command_table(value)) {
+        let Some(table) = value.as_table() else {
+            continue;
+        };
+        let Some(found) = command.find_subcommand(table_command) else {
+            bail!("profile '{name}' has options for unknown command '{table_command}'");
+        };
+        if let Some(key) = table.keys().find(|key| !has_option(found, key)) {
+            bail!("profile '{name}' sets unknown option '{key}' for the {table_command} command");
+        }
+        for (key, value) in table {
+            check_keyring_reference(name, key, value)?;
+        }
+        if let Some((subcommand, matches)) = subcommand
+            && subcommand == table_command
+        {
+            for (key, value) in table {
+                command = apply_option(command, table_command, matches, key, value)?;
+            }
+        }
+    }
+    Ok(command)
+}
+
+/// Whether a profile value is a table of options for a single command, rather than a keyring reference.
+fn is_command_table(value: &Value) -> bool {
+    value
+        .as_table()
+        .is_some_and(|table| !table.contains_key("keyring"))
+}
+
+/// Ensure an option that is set to a table is a well-formed keyring reference.
+fn check_keyring_reference(profile: &str, key: &str, value: &Value) -> Result<()> {
+    if value.is_table() && keyring_reference(value).is_none() {
+        bail!(
+            "profile '{profile}' sets option '{key}' to an invalid keyring reference - it must be of the form {{ keyring = \"<name>\" }}"
+        );
+    }
+    Ok(())
+}
+
+/// Get the name of the keyring secret a value of the form `{ keyring = "<name>" }` refers to.
+fn keyring_reference(value: &Value) -> Option<&str> {
+    let table = value.as_table()?;
+    if table.len() != 1 {
+        return None;
+    }
+    table.get("keyring")?.as_str()
+}
+
+/// Whether a command has an argument with the given long name.
+fn has_option(command: &Command, long: &str) -> bool {
+    command
+        .get_arguments()
+        .any(|arg| arg.get_long() == Some(long))
+}
+
+/// Set the default value of the argument with the given long name on a subcommand, unless it was
+/// already given on the command line or through an environment variable.
+fn apply_option(
+    command: Command,
+    subcommand: &str,
+    matches: &ArgMatches,
+    long: &str,
+    value: &Value,
+) -> Result<Command> {
+    let Some(id) = command
+        .find_subcommand(subcommand)
+        .and_then(|subcommand| {
+            subcommand
+                .get_arguments()
+                .find(|arg| arg.get_long() == Some(long))
+        })
+        .map(|arg| arg.get_id().clone())
+    else {
+        return Ok(command);
+    };
+    if matches!(
+        matches.value_source(id.as_str()),
+        Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+    ) {
+        return Ok(command);
+    }
+    let values = match value {
+        Value::Array(values) => values
+            .iter()
+            .map(option_value)
+            .collect::<Result<Vec<_>>>()?,
+        value => vec![option_value(value)?],
+    };
+    // Arguments with a default are never missing, so they no longer need to be required.
+    Ok(command.mut_subcommand(subcommand, |subcommand| {
+        subcommand.mut_arg(id, |arg| arg.default_values(values).required(false))
+    }))
+}
+
+/// Convert a configuration value to the form it would be given on the command line.
+fn option_value(value: &Value) -> Result<String> {
+    if let Some(name) = keyring_reference(value) {
+        return secrets::get_secret(name);
+    }
+    Ok(match value {
+        Value::String(value) => match (value.strip_prefix("~/"), env::var_os("HOME")) {
+            (Some(rest), Some(home)) => PathBuf::from(home).join(rest).display().to_string(),
+            _ => value.clone(),
+        },
+        value => value.to_string(),
+    })
+}
@@ -0,0 +1,112 @@
+use crate::api_client::StatusError;
+use reqwest::StatusCode;
+use std::{fmt, io, process::ExitCode};
+
+/// Kinds of failure the client exits with a distinct code for, so scripts can tell them apart.
+///
+/// Exit code 2 is left for invalid command-line usage, which is reported by clap.
+#[derive(Clone, Copy, Debug)]
+pub enum FailureKind {
+    /// The server couldn't be reached or the connection to it was lost.
+    Network = 3,
+    /// The transfer doesn't exist, or has expired or been deleted.
+    NotFound = 4,
+    /// The transfer couldn't be decrypted, usually because the key is wrong.
+    Decryption = 5,
+    /// The transfer is larger than the server allows.
+    SizeLimit = 6,
+    /// The user declined a confirmation or cancelled a prompt.
+    Aborted = 7,
+    /// The decrypted transfer didn't match the hash recorded by its uploader.
+    Integrity = 8,
+}
+
+impl FailureKind {
+    /// Exit code for failures that don't have a more specific kind.
+    const OTHER_EXIT_CODE: u8 = 1;
+
+    /// Describe the exit codes for the command-line help.
+    pub fn help() -> &'static str {
+        "Exit codes:\n  \
+        0  Success\n  \
+        1  Any other failure\n  \
+        2  Invalid command-line usage\n  \
+        3  Network error\n  \
+        4  Transfer not found, expired or deleted\n  \
+        5  Transfer could not be decrypted\n  \
+        6  Transfer is larger than the server allows\n  \
+        7  Cancelled by the user\n  \
+        8  Transfer contents did not match the uploader's checksum"
+    }
+}
+
+/// Error marking a failure as being of a specific kind, added as context or returned directly.
+#[derive(Debug)]
+pub struct Failure {
+    kind: FailureKind,
+    message: String,
+}
+
+impl Failure {
+    pub fn new(kind: FailureKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    /// Failure for when the user declines to continue.
+    pub fn aborted() -> Self {
+        Self::new(FailureKind::Aborted, "cancelled by user")
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for Failure {}
+
+/// Work out which exit code to finish with for an error.
+pub fn exit_code(err: &anyhow::Error) -> ExitCode {
+    ExitCode::from(failure_kind(err).map_or(FailureKind::OTHER_EXIT_CODE, |kind| kind as u8))
+}
+
+fn failure_kind(err: &anyhow::Error) -> Option<FailureKind> {
+    if let Some(failure) = err.downcast_ref::<Failure>() {
+        return Some(failure.kind);
+    }
+    err.chain().find_map(|cause| {
+        if let Some(failure) = cause.downcast_ref::<Failure>() {
+            return Some(failure.kind);
+        }
+        if let Some(status) = cause.downcast_ref::<StatusError>() {
+            return match status.status {
+                StatusCode::NOT_FOUND | StatusCode::GONE => Some(FailureKind::NotFound),
+                StatusCode::PAYLOAD_TOO_LARGE => Some(FailureKind::SizeLimit),
+                _ => None,
+            };
+        }
+        if let Some(
+            inquire::InquireError::OperationCanceled | inquire::InquireError::OperationInterrupted,
+        ) = cause.downcast_ref::<inquire::InquireError>()
+        {
+            return Some(FailureKind::Aborted);
+        }
+        // Response bodies are read through `io::Read`, which wraps connection errors in an `io::Error`.
+        let is_network = |err: &reqwest::Error| {
+            err.is_connect() || err.is_timeout() || err.is_request() || err.is_body()
+        };
+        let network_error = cause.downcast_ref::<reqwest::Error>().or_else(|| {
+            cause
+                .downcast_ref::<io::Error>()
+                .and_then(|err| err.get_ref())
+                .and_then(|err| err.downcast_ref::<reqwest::Error>())
+        });
+        network_error
+            .filter(|err| is_network(err))
+            .map(|_| FailureKind::Network)
+    })
+}
@@ -1,11 +1,21 @@
 mod api_client;
+mod archive;
+#[cfg(feature = "clipboard")]
+mod clipboard;
 mod commands;
-mod cryptography;
+mod config;
+mod failure;
+mod rate_limit;
+mod secrets;
+mod upload_state;
 
 use anyhow::Result;
-use clap::Parser;
-use commands::{DownloadCommand, GenCompletionsCommand, UploadCommand};
-use std::time::Duration;
+use clap::{CommandFactory, FromArgMatches, Parser};
+use commands::{
+    DeleteCommand, DownloadCommand, GenCompletionsCommand, InfoCommand, ListCommand, SecretCommand,
+    UploadCommand, WatchCommand,
+};
+use std::{process::ExitCode, time::Duration};
 
 // Compile-time options
 pub const DEFAULT_SERVER_URL: &str = "https://xfer.dollware.net/"; // Must end with trailing slash.
@@ -20,14 +30,25 @@ pub trait ExecutableCommand: Parser {
 enum Command {
     GenCompletions(GenCompletionsCommand),
     Upload(UploadCommand),
+    Watch(WatchCommand),
     Download(DownloadCommand),
+    Info(InfoCommand),
+    List(ListCommand),
+    Delete(DeleteCommand),
+    Secret(SecretCommand),
 }
 
 #[derive(Parser)]
-#[command(author, version, about, long_about)]
+#[command(author, version, about, long_about, after_long_help = failure::FailureKind::help())]
 struct RootCommand {
     #[clap(subcommand)]
     command: Command,
+
+    /// Name of the profile in the config file to take default options from.
+    ///
+    /// The config file is read from `~/.config/xfer/config.toml`, and its default profile is used when this isn't set.
+    #[clap(long = "profile", env = "XFER_CLIENT_PROFILE", global = true)]
+    profile: Option<String>,
 }
 
 impl ExecutableCommand for RootCommand {
@@ -35,11 +56,47 @@ impl ExecutableCommand for RootCommand {
         match self.command {
             Command::GenCompletions(cmd) => cmd.run(),
             Command::Upload(cmd) => cmd.run(),
+            Command::Watch(cmd) => cmd.run(),
             Command::Download(cmd) => cmd.run(),
+            Command::Info(cmd) => cmd.run(),
+            Command::List(cmd) => cmd.run(),
+            Command::Delete(cmd) => cmd.run(),
+            Command::Secret(cmd) => cmd.run(),
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    #[cfg(feature = "clipboard")]
+    if clipboard::serve_if_requested() {
+        return ExitCode::SUCCESS;
+    }
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {err:?}");
+            failure::exit_code(&err)
         }
     }
 }
 
-fn main() -> Result<()> {
-    RootCommand::parse().run()
+fn run() -> Result<()> {
+    // Find the selected profile and command first, as the profile's options are the defaults the
+    // arguments are parsed with.
+    let first_pass = RootCommand::command()
+        .ignore_errors(true)
+        .try_get_matches()
+        .ok();
+    let profile = first_pass
+        .as_ref()
+        .and_then(|matches| matches.get_one::<String>("profile").cloned());
+    let matches = config::apply_profile(
+        RootCommand::command(),
+        profile.as_deref(),
+        first_pass.as_ref().and_then(|matches| matches.subcommand()),
+    )?
+    .get_matches();
+    RootCommand::from_arg_matches(&matches)
+        .unwrap_or_else(|err| err.exit())
+        .run()
 }
@@ -0,0 +1,87 @@
+use std::{
+    io::{self, Read},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Token bucket limiting how many bytes per second are sent and received, shared by every
+/// connection a command makes so the limit applies to all of them together.
+#[derive(Debug)]
+pub struct RateLimiter {
+    bytes_per_second: u64,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// Bytes that can be transferred straight away, negative when readers are waiting on the bucket
+    /// to refill.
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(bytes_per_second: u64) -> Self {
+        Self {
+            bytes_per_second,
+            bucket: Mutex::new(Bucket {
+                tokens: bytes_per_second as f64,
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Take `amount` bytes from the bucket, sleeping until it has refilled enough to cover them.
+    ///
+    /// The bucket holds at most a second's worth of bytes, so transfers can't burst past the limit
+    /// for longer than that after being idle.
+    fn take(&self, amount: usize) {
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap();
+            let now = Instant::now();
+            let rate = self.bytes_per_second as f64;
+            bucket.tokens = (bucket.tokens
+                + now.duration_since(bucket.refilled_at).as_secs_f64() * rate)
+                .min(rate);
+            bucket.refilled_at = now;
+            bucket.tokens -= amount as f64;
+            Duration::from_secs_f64((-bucket.tokens).max(0.0) / rate)
+        };
+        if !wait.is_zero() {
+            thread::sleep(wait);
+        }
+    }
+
+    /// Most bytes to read at once, so data trickles through at the limit instead of arriving in
+    /// bursts followed by long pauses.
+    fn max_read(&self) -> usize {
+        usize::try_from(self.bytes_per_second / 10)
+            .unwrap_or(usize::MAX)
+            .max(1)
+    }
+}
+
+/// Reader that only yields data as fast as its rate limiter allows, or as fast as it can without one.
+pub struct Throttled<R> {
+    inner: R,
+    limiter: Option<Arc<RateLimiter>>,
+}
+
+impl<R> Throttled<R> {
+    pub fn new(inner: R, limiter: Option<Arc<RateLimiter>>) -> Self {
+        Self { inner, limiter }
+    }
+}
+
+impl<R: Read> Read for Throttled<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let Some(limiter) = &self.limiter else {
+            return self.inner.read(buf);
+        };
+        let len = buf.len().min(limiter.max_read());
+        let read = self.inner.read(&mut buf[..len])?;
+        limiter.take(read);
+        Ok(read)
+    }
+}
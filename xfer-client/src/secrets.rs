@@ -0,0 +1,31 @@
+use anyhow::{Context, Result};
+use keyring::Entry;
+
+/// Service name secrets are stored under in the system keyring.
+const KEYRING_SERVICE: &str = "xfer";
+
+fn entry(name: &str) -> Result<Entry> {
+    Entry::new(KEYRING_SERVICE, name)
+        .with_context(|| format!("failed to open secret '{name}' in the system keyring"))
+}
+
+/// Read a secret stored in the system keyring.
+pub fn get_secret(name: &str) -> Result<String> {
+    entry(name)?.get_password().with_context(|| {
+        format!("failed to read secret '{name}' from the system keyring - store it with 'xfer secret set {name}'")
+    })
+}
+
+/// Store a secret in the system keyring, replacing any secret already stored under `name`.
+pub fn set_secret(name: &str, secret: &str) -> Result<()> {
+    entry(name)?
+        .set_password(secret)
+        .with_context(|| format!("failed to store secret '{name}' in the system keyring"))
+}
+
+/// Remove a secret from the system keyring.
+pub fn remove_secret(name: &str) -> Result<()> {
+    entry(name)?
+        .delete_credential()
+        .with_context(|| format!("failed to remove secret '{name}' from the system keyring"))
+}
@@ -0,0 +1,143 @@
+use crate::api_client::ReserveTransferResponse;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    env,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+use url::Url;
+
+/// Progress of a chunked upload, saved after every chunk so an interrupted upload can be resumed later.
+#[derive(Serialize, Deserialize)]
+pub struct UploadState {
+    /// The reservation the transfer is being uploaded to.
+    pub reservation: ReserveTransferResponse,
+    /// Key the uploaded archive was encrypted with, which the transfer key is made from.
+    ///
+    /// Passphrases are never saved, as the uploader already knows them and they're left out of the transfer key.
+    #[serde(default)]
+    pub decryption_key: Option<String>,
+    /// Whether the key is a passphrase chosen by the uploader, so is left out of the transfer key.
+    #[serde(default)]
+    pub passphrase_protected: bool,
+    /// BLAKE3 checksum of the encrypted archive in hex.
+    pub checksum: String,
+    /// Size of the chunks the archive is uploaded in, in bytes.
+    pub chunk_size: usize,
+    /// Offsets of the chunks the server has already accepted.
+    pub uploaded_offsets: BTreeSet<u64>,
+}
+
+/// Where the state, encrypted archive and encrypted manifest of an upload are kept between invocations.
+///
+/// Uploads are identified by the server and the paths being uploaded, so running the same upload
+/// command again with `--resume` finds them.
+pub struct UploadStateFiles {
+    state_path: PathBuf,
+    archive_path: PathBuf,
+    manifest_path: PathBuf,
+}
+
+impl UploadStateFiles {
+    pub fn new<'a>(server: &Url, paths: impl IntoIterator<Item = &'a Path>) -> Result<Self> {
+        let directory = state_directory()?.join("uploads");
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(server.as_str().as_bytes());
+        for path in paths {
+            hasher.update(b"\n");
+            hasher.update(path.as_os_str().as_encoded_bytes());
+        }
+        let name = hasher.finalize().to_hex();
+        Ok(Self {
+            state_path: directory.join(format!("{name}.json")),
+            archive_path: directory.join(format!("{name}.archive")),
+            manifest_path: directory.join(format!("{name}.manifest")),
+        })
+    }
+
+    /// Load the state of an interrupted upload, if there is one.
+    pub fn load(&self) -> Result<Option<UploadState>> {
+        let contents = match fs::read(&self.state_path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err).context("failed to read upload state file"),
+        };
+        Ok(Some(
+            serde_json::from_slice(&contents).context("failed to parse upload state file")?,
+        ))
+    }
+
+    /// Read the encrypted archive of an interrupted upload.
+    pub fn read_archive(&self) -> Result<Vec<u8>> {
+        fs::read(&self.archive_path).context("failed to read encrypted archive of upload")
+    }
+
+    /// Save the encrypted archive of an upload so it doesn't have to be created again when resuming.
+    pub fn save_archive(&self, archive: &[u8]) -> Result<()> {
+        write_private(&self.archive_path, archive)
+            .context("failed to save encrypted archive of upload")
+    }
+
+    /// Read the encrypted manifest of an interrupted upload, if one was saved.
+    pub fn read_manifest(&self) -> Result<Option<Vec<u8>>> {
+        match fs::read(&self.manifest_path) {
+            Ok(manifest) => Ok(Some(manifest)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err).context("failed to read encrypted manifest of upload"),
+        }
+    }
+
+    /// Save the encrypted manifest of an upload so it can be stored once a resumed upload finishes.
+    pub fn save_manifest(&self, manifest: &[u8]) -> Result<()> {
+        write_private(&self.manifest_path, manifest)
+            .context("failed to save encrypted manifest of upload")
+    }
+
+    /// Save the progress of an upload.
+    pub fn save(&self, state: &UploadState) -> Result<()> {
+        write_private(&self.state_path, &serde_json::to_vec(state)?)
+            .context("failed to save upload state file")
+    }
+
+    /// Remove the state, archive and manifest of an upload once it is finished or can no longer be resumed.
+    pub fn remove(&self) {
+        let _ = fs::remove_file(&self.state_path);
+        let _ = fs::remove_file(&self.archive_path);
+        let _ = fs::remove_file(&self.manifest_path);
+    }
+}
+
+/// Get the directory xfer keeps local state in.
+fn state_directory() -> Result<PathBuf> {
+    if let Some(dir) = env::var_os("XDG_STATE_HOME").filter(|dir| !dir.is_empty()) {
+        return Ok(PathBuf::from(dir).join("xfer"));
+    }
+    if cfg!(windows)
+        && let Some(dir) = env::var_os("LOCALAPPDATA")
+    {
+        return Ok(PathBuf::from(dir).join("xfer"));
+    }
+    let home = env::var_os("HOME").context("could not determine home directory")?;
+    Ok(PathBuf::from(home).join(".local/state/xfer"))
+}
+
+/// Atomically write a file that only the current user can read, as it contains the decryption key.
+fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(&temp_path)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(&temp_path, path)?;
+    Ok(())
+}
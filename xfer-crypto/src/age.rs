@@ -0,0 +1,220 @@
+//! Encryption of short secrets to [age](https://age-encryption.org/v1) X25519 recipients.
+//!
+//! Secrets are encrypted to `age1...` public keys and decrypted with the matching
+//! `AGE-SECRET-KEY-1...` identities using the [`age`] crate, so the files produced are regular age
+//! files that any age implementation can decrypt.
+
+use age::{DecryptError, Decryptor, Encryptor, x25519};
+use anyhow::{Context, Result, anyhow, ensure};
+use std::{
+    fmt,
+    io::{Read, Write},
+    str::FromStr,
+};
+
+/// An X25519 public key that secrets can be encrypted to, in its `age1...` form.
+#[derive(Clone)]
+pub struct Recipient(x25519::Recipient);
+
+impl FromStr for Recipient {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        s.parse().map(Self).map_err(|err| {
+            anyhow!("not a valid age recipient - it should start with 'age1' ({err})")
+        })
+    }
+}
+
+impl fmt::Display for Recipient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// An X25519 secret key that can decrypt secrets encrypted to its [`Recipient`], in its
+/// `AGE-SECRET-KEY-1...` form.
+pub struct Identity(x25519::Identity);
+
+impl Identity {
+    /// The public key of this identity.
+    pub fn recipient(&self) -> Recipient {
+        Recipient(self.0.to_public())
+    }
+}
+
+impl FromStr for Identity {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        s.parse().map(Self).map_err(|err| {
+            anyhow!("not a valid age identity - it should start with 'AGE-SECRET-KEY-1' ({err})")
+        })
+    }
+}
+
+/// Read the identities in an age identity file, ignoring blank lines and `#` comments.
+pub fn parse_identities(contents: &str) -> Result<Vec<Identity>> {
+    let identities = contents
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(index, line)| {
+            line.parse()
+                .with_context(|| format!("invalid identity on line {}", index + 1))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    ensure!(
+        !identities.is_empty(),
+        "identity file contains no identities"
+    );
+    Ok(identities)
+}
+
+/// Encrypt a secret to each of the recipients, so any one of their identities can decrypt it.
+pub fn encrypt(secret: &[u8], recipients: &[Recipient]) -> Result<Vec<u8>> {
+    let encryptor =
+        Encryptor::with_recipients(recipients.iter().map(|r| &r.0 as &dyn age::Recipient))
+            .context("at least one recipient is required")?;
+    let mut encrypted = Vec::new();
+    let mut writer = encryptor
+        .wrap_output(&mut encrypted)
+        .context("failed to write age header")?;
+    writer
+        .write_all(secret)
+        .context("failed to encrypt age payload")?;
+    writer.finish().context("failed to encrypt age payload")?;
+    Ok(encrypted)
+}
+
+/// Decrypt a secret encrypted to one of the identities.
+pub fn decrypt(encrypted: &[u8], identities: &[Identity]) -> Result<Vec<u8>> {
+    let decryptor = Decryptor::new(encrypted)
+        .context("not an age encrypted file, or one from an unsupported version of age")?;
+    let mut reader = decryptor
+        .decrypt(identities.iter().map(|i| &i.0 as &dyn age::Identity))
+        .map_err(|err| match err {
+            DecryptError::NoMatchingKeys => {
+                anyhow!("none of the identities can decrypt this secret")
+            }
+            err => anyhow::Error::new(err).context("failed to decrypt age header"),
+        })?;
+    let mut secret = Vec::new();
+    reader
+        .read_to_end(&mut secret)
+        .context("failed to decrypt age payload")?;
+    Ok(secret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bech32::{ToBase32, Variant};
+
+    fn hex(s: &str) -> [u8; 32] {
+        let bytes = (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect::<Vec<_>>();
+        bytes.try_into().unwrap()
+    }
+
+    fn identity(secret: [u8; 32]) -> Identity {
+        bech32::encode("age-secret-key-", secret.to_base32(), Variant::Bech32)
+            .unwrap()
+            .to_uppercase()
+            .parse()
+            .unwrap()
+    }
+
+    fn recipient(public: [u8; 32]) -> String {
+        bech32::encode("age", public.to_base32(), Variant::Bech32).unwrap()
+    }
+
+    /// RFC 7748 section 5.2.
+    #[test]
+    fn x25519_function_vectors() {
+        assert_eq!(
+            x25519_dalek::x25519(
+                hex("a546e36bf0527c9d3b16154b82465edd62144c0ac1fc5a18506a2244ba449ac4"),
+                hex("e6db6867583030db3594c1a424b15f7c726624ec26b3353b10a903a6d0ab1c4c"),
+            ),
+            hex("c3da55379de9c6908e94ea4df28d084f32eccf03491c71f754b4075577a28552")
+        );
+        assert_eq!(
+            x25519_dalek::x25519(
+                hex("4b66e9d4d1b4673c5ad22691957d6af5c11b6421e0ea01d42ca4169e7918ba0d"),
+                hex("e5210f12786811d3f4b7959d0538ae2c31dbe7106fc03c3efc4cd549c715a493"),
+            ),
+            hex("95cbde9476e8907d7aade45cb4b873f88b595a68799fa152e6f8f7647aac7957")
+        );
+
+        let mut k = x25519_dalek::X25519_BASEPOINT_BYTES;
+        let mut u = k;
+        for iteration in 1..=1000 {
+            let result = x25519_dalek::x25519(k, u);
+            u = k;
+            k = result;
+            match iteration {
+                1 => assert_eq!(
+                    k,
+                    hex("422c8e7a6227d7bca1350b3e2bb7279f7897b87bb6854b783c60e80311ae3079")
+                ),
+                1000 => assert_eq!(
+                    k,
+                    hex("684cf59ba83309552800ef566f2f4d3c1c3887c49360e3875f2eb94d99532c51")
+                ),
+                _ => {}
+            }
+        }
+    }
+
+    /// RFC 7748 section 6.1.
+    #[test]
+    fn x25519_diffie_hellman_vectors() {
+        let alice_secret = hex("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a");
+        let alice_public = hex("8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a");
+        let bob_secret = hex("5dab087e624a8a4b79e17f8b83800ee66f3bb1292618b6fd1c2f8b27ff88e0eb");
+        let bob_public = hex("de9edb7d7b7dc1b4d35b61c2ece435373f8343c85b78674dadfc7e146f882b4f");
+        let shared = hex("4a5d9d5ba4ce2de1728e3bf480350f25e07e21c947d19e3376f09b3c1e161742");
+
+        assert_eq!(
+            identity(alice_secret).recipient().to_string(),
+            recipient(alice_public)
+        );
+        assert_eq!(
+            identity(bob_secret).recipient().to_string(),
+            recipient(bob_public)
+        );
+        assert_eq!(x25519_dalek::x25519(alice_secret, bob_public), shared);
+        assert_eq!(x25519_dalek::x25519(bob_secret, alice_public), shared);
+    }
+
+    #[test]
+    fn round_trip() {
+        let alice = identity(hex(
+            "77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a",
+        ));
+        let bob = identity(hex(
+            "5dab087e624a8a4b79e17f8b83800ee66f3bb1292618b6fd1c2f8b27ff88e0eb",
+        ));
+        let encrypted = encrypt(b"transfer key", &[alice.recipient(), bob.recipient()]).unwrap();
+        assert_eq!(decrypt(&encrypted, &[bob]).unwrap(), b"transfer key");
+        assert_eq!(decrypt(&encrypted, &[alice]).unwrap(), b"transfer key");
+    }
+
+    /// The `x25519` file from the C2SP age test vectors, which was encrypted by the reference age.
+    #[test]
+    fn decrypts_age_files() {
+        let secret = decrypt(
+            include_bytes!("../testdata/x25519.age"),
+            &parse_identities(
+                "AGE-SECRET-KEY-1XMWWC06LY3EE5RYTXM9MFLAZ2U56JJJ36S0MYPDRWSVLUL66MV4QX3S7F6",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(secret, b"age");
+    }
+}
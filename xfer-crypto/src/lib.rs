@@ -1,9 +1,15 @@
+#[cfg(feature = "age")]
+pub mod age;
+
 use anyhow::{Context, Result};
 use argon2::Argon2;
+#[cfg(feature = "encrypt")]
+use chacha20poly1305::aead::{AeadMutInPlace, OsRng, rand_core::RngCore};
 use chacha20poly1305::{
     AeadCore, KeyInit,
-    aead::{Aead, AeadMutInPlace, OsRng, generic_array::typenum::Unsigned, rand_core::RngCore},
+    aead::{Aead, generic_array::typenum::Unsigned},
 };
+#[cfg(feature = "encrypt")]
 use rand::seq::IndexedRandom;
 
 // Argon2id settings.
@@ -13,8 +19,25 @@ const ARGON2ID_M_COST: u32 = 512 * 1024;
 const ARGON2ID_T_COST: u32 = 6;
 const ARGON2ID_P_COST: u32 = 2;
 // Passphrase generation.
+#[cfg(feature = "encrypt")]
 const PASSPHRASE_WORDS: usize = 5;
+#[cfg(feature = "encrypt")]
 const PASSPHRASE_SEPARATOR: &str = "-";
+// Encrypted blob format.
+/// Version of the encrypted blob format produced by this crate, declared to servers on upload.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Whether a transfer identifier only uses the characters servers issue identifiers with.
+///
+/// Identifiers appear unescaped in URL paths and storage file names, so they are limited to ASCII
+/// letters, digits, `-` and `_`, which need no escaping in either.
+pub fn is_valid_transfer_id(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
 // Cryptography implementation.
 type CryptoImpl = chacha20poly1305::XChaCha20Poly1305;
 type CryptoNonce = chacha20poly1305::XNonce;
@@ -34,6 +57,7 @@ impl Cryptography {
     }
 
     /// Generate a passphrase from [`eff_wordlist::large::LIST`].
+    #[cfg(feature = "encrypt")]
     fn generate_passphrase(len: usize, separator: &str) -> String {
         eff_wordlist::large::LIST
             .choose_multiple(&mut rand::rng(), len)
@@ -52,17 +76,42 @@ impl Cryptography {
     /// later.
     ///
     /// To decrypt, use [`Cryptography::decrypt`].
+    #[cfg(feature = "encrypt")]
     pub fn encrypt_in_place(bytes: &mut Vec<u8>) -> Result<String> {
-        // Create passphrase & derive a key.
+        Self::encrypt_all_in_place(&mut [bytes])
+    }
+
+    /// Encrypt several byte arrays in-place with the same passphrase.
+    ///
+    /// This works like [`Cryptography::encrypt_in_place`], but the key is only derived once
+    /// and each byte array is encrypted with its own nonce, so each one can be decrypted on
+    /// its own with [`Cryptography::decrypt`] and the returned passphrase.
+    #[cfg(feature = "encrypt")]
+    pub fn encrypt_all_in_place(arrays: &mut [&mut Vec<u8>]) -> Result<String> {
         let passphrase = Self::generate_passphrase(PASSPHRASE_WORDS, PASSPHRASE_SEPARATOR);
+        Self::encrypt_all_in_place_with_passphrase(arrays, &passphrase)?;
+        Ok(passphrase)
+    }
+
+    /// Encrypt several byte arrays in-place with a passphrase chosen by the user.
+    ///
+    /// The passphrase is ran through a KDF alongside a randomized salt like a generated one,
+    /// but callers are responsible for making sure it is strong enough.
+    #[cfg(feature = "encrypt")]
+    pub fn encrypt_all_in_place_with_passphrase(
+        arrays: &mut [&mut Vec<u8>],
+        passphrase: &str,
+    ) -> Result<()> {
         let mut salt = [0u8; ARGON2ID_SALT_LEN];
         let mut derived_key = [0u8; ARGON2ID_KEY_LEN];
         OsRng.fill_bytes(&mut salt);
         Self::argon2()
             .hash_password_into(passphrase.as_bytes(), &salt, &mut derived_key)
             .unwrap();
-        EncryptedBlob::encrypt_in_place(&derived_key, salt, bytes)?;
-        Ok(passphrase)
+        for bytes in arrays {
+            EncryptedBlob::encrypt_in_place(&derived_key, salt, bytes)?;
+        }
+        Ok(())
     }
 
     /// Decrypt an encrypted byte array.
@@ -100,6 +149,7 @@ impl<'a> EncryptedBlob<'a> {
         })
     }
 
+    #[cfg(feature = "encrypt")]
     pub fn encrypt_in_place<I: IntoIterator<Item = u8>>(
         key: &[u8; ARGON2ID_KEY_LEN],
         salt: I,
@@ -122,3 +172,18 @@ impl<'a> EncryptedBlob<'a> {
             .context("failed to decrypt bytes")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_transfer_ids() {
+        assert!(is_valid_transfer_id("aZ09-_"));
+        assert!(!is_valid_transfer_id(""));
+        assert!(!is_valid_transfer_id("../transfer"));
+        assert!(!is_valid_transfer_id("transfer.meta"));
+        assert!(!is_valid_transfer_id("trans fer"));
+        assert!(!is_valid_transfer_id("transfér"));
+    }
+}
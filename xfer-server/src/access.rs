@@ -0,0 +1,152 @@
+use crate::{
+    AppState,
+    client_ip::{self, ClientIp},
+    error::ApiError,
+};
+use anyhow::{Context, Result, bail};
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use ipnet::IpNet;
+use std::{fs, net::IpAddr, path::Path};
+use tracing::{debug, info};
+
+/// Networks that are allowed or denied access to an operation.
+///
+/// Denied networks always take precedence. When no networks are allowed every
+/// address that isn't denied is permitted.
+#[derive(Debug, Default)]
+pub struct NetworkPolicy {
+    allow: Vec<IpNet>,
+    deny: Vec<IpNet>,
+}
+
+impl NetworkPolicy {
+    /// Create a new [`NetworkPolicy`] from lists of allowed and denied networks.
+    pub fn new(allow: Vec<IpNet>, deny: Vec<IpNet>) -> Self {
+        Self { allow, deny }
+    }
+
+    /// Whether the given address is permitted by this policy.
+    pub fn is_allowed(&self, ip: IpAddr) -> bool {
+        !self.deny.iter().any(|net| net.contains(&ip))
+            && (self.allow.is_empty() || self.allow.iter().any(|net| net.contains(&ip)))
+    }
+}
+
+/// Separate network policies for uploading and downloading transfers.
+#[derive(Debug, Default)]
+pub struct AccessPolicy {
+    pub upload: NetworkPolicy,
+    pub download: NetworkPolicy,
+}
+
+impl AccessPolicy {
+    /// Extend the policy with the rules in the given file.
+    ///
+    /// Each non-empty line that doesn't start with `#` is a rule in the form
+    /// `<upload|download> <allow|deny> <address or CIDR network>`.
+    pub fn load_file(&mut self, path: &Path) -> Result<()> {
+        for (number, line) in fs::read_to_string(path)
+            .context("Failed to read access policy file")?
+            .lines()
+            .enumerate()
+            .map(|(number, line)| (number + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        {
+            let [operation, rule, network] = line.split_whitespace().collect::<Vec<_>>()[..] else {
+                bail!("Invalid access policy rule on line {number}: '{line}'");
+            };
+            let network = client_ip::parse_ip_net(network).map_err(|err| {
+                anyhow::anyhow!("Invalid access policy rule on line {number}: {err}")
+            })?;
+            let policy = match operation {
+                "upload" => &mut self.upload,
+                "download" => &mut self.download,
+                _ => {
+                    bail!("Unknown operation '{operation}' on line {number} of access policy file")
+                }
+            };
+            match rule {
+                "allow" => policy.allow.push(network),
+                "deny" => policy.deny.push(network),
+                _ => bail!("Unknown rule '{rule}' on line {number} of access policy file"),
+            }
+        }
+        info!(
+            "Loaded access policy (upload: {} allowed, {} denied; download: {} allowed, {} denied)",
+            self.upload.allow.len(),
+            self.upload.deny.len(),
+            self.download.allow.len(),
+            self.download.deny.len()
+        );
+        Ok(())
+    }
+}
+
+/// Middleware that rejects uploads from clients not permitted by the upload network policy.
+pub async fn upload_access_middleware(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    req: Request,
+    next: Next,
+) -> Response {
+    if !state.access_policy.upload.is_allowed(client_ip) {
+        debug!(
+            "Rejecting upload from {} as it is not permitted by the access policy",
+            state.ip_privacy.redact(client_ip)
+        );
+        return ApiError::new(
+            StatusCode::FORBIDDEN,
+            "uploads are not permitted from your network",
+        )
+        .into_response();
+    }
+    if !state.geoip_policy.is_upload_allowed(client_ip) {
+        debug!(
+            "Rejecting upload from {} as it is not permitted by the country policy",
+            state.ip_privacy.redact(client_ip)
+        );
+        return ApiError::new(
+            StatusCode::FORBIDDEN,
+            "uploads are not permitted from your country",
+        )
+        .into_response();
+    }
+    next.run(req).await
+}
+
+/// Middleware that rejects downloads from clients not permitted by the download network policy.
+pub async fn download_access_middleware(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    req: Request,
+    next: Next,
+) -> Response {
+    if !state.access_policy.download.is_allowed(client_ip) {
+        debug!(
+            "Rejecting download from {} as it is not permitted by the access policy",
+            state.ip_privacy.redact(client_ip)
+        );
+        return ApiError::new(
+            StatusCode::FORBIDDEN,
+            "downloads are not permitted from your network",
+        )
+        .into_response();
+    }
+    if !state.geoip_policy.is_download_allowed(client_ip) {
+        debug!(
+            "Rejecting download from {} as it is not permitted by the country policy",
+            state.ip_privacy.redact(client_ip)
+        );
+        return ApiError::new(
+            StatusCode::FORBIDDEN,
+            "downloads are not permitted from your country",
+        )
+        .into_response();
+    }
+    next.run(req).await
+}
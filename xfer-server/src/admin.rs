@@ -0,0 +1,69 @@
+use crate::{AppState, error::ApiError};
+use axum::{
+    extract::{Request, State},
+    http::{StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::{sync::atomic::Ordering, time::Duration};
+use tracing::debug;
+
+/// How long clients are asked to wait before retrying an upload while in maintenance mode.
+const MAINTENANCE_RETRY_AFTER: Duration = Duration::from_secs(5 * 60);
+
+/// Middleware that only allows requests bearing the configured admin token through.
+pub async fn admin_auth_middleware(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(admin_token) = &state.admin_token else {
+        return ApiError::not_found("not found").into_response();
+    };
+    let provided = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    // Compare hashes so the comparison runs in constant time.
+    match provided {
+        Some(provided)
+            if blake3::hash(provided.as_bytes()) == blake3::hash(admin_token.as_bytes()) =>
+        {
+            next.run(req).await
+        }
+        _ => {
+            debug!("Rejecting admin request with missing or invalid token");
+            (
+                [(header::WWW_AUTHENTICATE, "Bearer")],
+                ApiError::new(StatusCode::UNAUTHORIZED, "a valid admin token is required"),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Middleware that rejects uploads while the server is in maintenance mode.
+///
+/// Downloads are unaffected so recipients can still collect existing transfers while the server drains.
+pub async fn upload_maintenance_middleware(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if !state.maintenance_mode.load(Ordering::Relaxed) {
+        return next.run(req).await;
+    }
+    debug!("Rejecting upload as the server is in maintenance mode");
+    (
+        [(
+            header::RETRY_AFTER,
+            MAINTENANCE_RETRY_AFTER.as_secs().to_string(),
+        )],
+        ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "server is in maintenance mode and not accepting new transfers, try again later",
+        ),
+    )
+        .into_response()
+}
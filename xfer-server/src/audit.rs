@@ -0,0 +1,175 @@
+use crate::ip_privacy::IpPrivacy;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, ErrorKind, Write},
+    net::IpAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tracing::warn;
+
+/// Number of bytes of the keyed IP hash kept in audit events.
+const CLIENT_IP_HASH_LENGTH: usize = 16;
+
+/// Kind of action recorded in the audit log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Upload,
+    Download,
+    Delete,
+    Expire,
+    Block,
+    Report,
+    AdminQuery,
+    Maintenance,
+    Pin,
+    Unpin,
+    CreateUploadToken,
+    RevokeUploadToken,
+    Restore,
+    Purge,
+}
+
+/// A single entry in the audit log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEvent {
+    pub timestamp: SystemTime,
+    pub action: AuditAction,
+    pub transfer_id: Option<String>,
+    /// Keyed hash of the client's IP address, stable for the lifetime of the audit key.
+    ///
+    /// When client IPs are truncated or hashed in logs, the hash is of that redacted form instead.
+    pub client_ip_hash: Option<String>,
+}
+
+/// Filter applied when reading events back out of the audit log.
+#[derive(Debug, Default, Deserialize)]
+pub struct AuditQuery {
+    pub action: Option<AuditAction>,
+    pub transfer_id: Option<String>,
+    /// Only include events at or after this unix timestamp, in seconds.
+    pub since: Option<u64>,
+}
+
+/// Append-only audit trail of transfer activity, stored as JSON lines.
+///
+/// Client IP addresses are never written directly; they are hashed with a key that is
+/// generated on first use and kept alongside the data so hashes can be correlated without
+/// the log revealing who performed an action.
+#[derive(Debug)]
+pub struct AuditLog {
+    path: Option<PathBuf>,
+    ip_hash_key: [u8; 32],
+    ip_privacy: Arc<IpPrivacy>,
+    lock: Mutex<()>,
+}
+
+impl AuditLog {
+    /// Create a new [`AuditLog`] that appends to the given file, or a disabled one when no file is given.
+    pub fn new(path: Option<PathBuf>, key_path: &Path, ip_privacy: Arc<IpPrivacy>) -> Result<Self> {
+        let ip_hash_key = match &path {
+            Some(_) => Self::load_or_create_key(key_path)?,
+            None => [0; 32],
+        };
+        Ok(Self {
+            path,
+            ip_hash_key,
+            ip_privacy,
+            lock: Mutex::default(),
+        })
+    }
+
+    fn load_or_create_key(key_path: &Path) -> Result<[u8; 32]> {
+        match fs::read(key_path) {
+            Ok(bytes) => bytes
+                .try_into()
+                .map_err(|_| anyhow::anyhow!("Audit key file is not 32 bytes long")),
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                let key: [u8; 32] = rand::random();
+                fs::write(key_path, key).context("Failed to write audit key file")?;
+                Ok(key)
+            }
+            Err(err) => Err(err).context("Failed to read audit key file"),
+        }
+    }
+
+    /// Whether audit events are being recorded.
+    pub fn is_enabled(&self) -> bool {
+        self.path.is_some()
+    }
+
+    /// Record an action, logging rather than returning any failure so callers are never interrupted.
+    pub fn record(
+        &self,
+        action: AuditAction,
+        transfer_id: Option<&str>,
+        client_ip: Option<IpAddr>,
+    ) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let event = AuditEvent {
+            timestamp: SystemTime::now(),
+            action,
+            transfer_id: transfer_id.map(str::to_string),
+            client_ip_hash: client_ip.map(|ip| self.hash_ip(ip)),
+        };
+        let result = serde_json::to_vec(&event)
+            .context("Failed to serialize audit event")
+            .and_then(|mut line| {
+                line.push(b'\n');
+                let _guard = self.lock.lock().unwrap();
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .and_then(|mut file| file.write_all(&line))
+                    .context("Failed to write audit event")
+            });
+        if let Err(err) = result {
+            warn!("Failed to record audit event: {err:?}");
+        }
+    }
+
+    /// Read back the most recent `limit` events matching the query, oldest first.
+    pub fn query(&self, query: &AuditQuery, limit: usize) -> Result<Vec<AuditEvent>> {
+        let Some(path) = &self.path else {
+            return Ok(Vec::new());
+        };
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err).context("Failed to open audit log"),
+        };
+        let mut events = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line.context("Failed to read audit log")?;
+            let Ok(event) = serde_json::from_str::<AuditEvent>(&line) else {
+                continue;
+            };
+            if query.action.is_some_and(|action| action != event.action)
+                || query
+                    .transfer_id
+                    .as_ref()
+                    .is_some_and(|id| event.transfer_id.as_ref() != Some(id))
+                || query
+                    .since
+                    .is_some_and(|since| event.timestamp < UNIX_EPOCH + Duration::from_secs(since))
+            {
+                continue;
+            }
+            events.push(event);
+        }
+        let skip = events.len().saturating_sub(limit);
+        Ok(events.split_off(skip))
+    }
+
+    fn hash_ip(&self, ip: IpAddr) -> String {
+        let hash = blake3::keyed_hash(&self.ip_hash_key, self.ip_privacy.redact(ip).as_bytes());
+        hash.to_hex()[..CLIENT_IP_HASH_LENGTH * 2].to_string()
+    }
+}
@@ -0,0 +1,112 @@
+use crate::AppState;
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::{Extensions, HeaderMap, request::Parts},
+};
+use ipnet::IpNet;
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+};
+
+/// The resolved IP address of the client that made a request.
+///
+/// Forwarding headers are only honoured when the connecting peer is a trusted proxy.
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub IpAddr);
+
+impl FromRequestParts<AppState> for ClientIp {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        Ok(Self(resolve_request_client_ip(
+            parts,
+            &state.trusted_proxies,
+        )))
+    }
+}
+
+/// Parse an IP network in CIDR notation, or a single IP address as a network containing only itself.
+pub fn parse_ip_net(value: &str) -> Result<IpNet, String> {
+    value
+        .parse::<IpNet>()
+        .or_else(|_| value.parse::<IpAddr>().map(IpNet::from))
+        .map_err(|_| format!("'{value}' is not a valid IP address or CIDR network"))
+}
+
+/// Get the IP address of the directly connected peer from the request extensions.
+pub fn peer_ip(extensions: &Extensions) -> IpAddr {
+    extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|info| info.0.ip())
+        .unwrap_or(IpAddr::from([0, 0, 0, 0]))
+}
+
+/// Resolve the client IP for the given request parts using its connection info and headers.
+pub fn resolve_request_client_ip(parts: &Parts, trusted_proxies: &[IpNet]) -> IpAddr {
+    resolve_client_ip(peer_ip(&parts.extensions), &parts.headers, trusted_proxies)
+}
+
+/// Resolve the real client IP from the connecting peer's address and the request headers.
+///
+/// When the peer is a trusted proxy the forwarding chain (`X-Forwarded-For`, or `Forwarded` if absent)
+/// is walked from the nearest hop outwards and the first untrusted address is returned.
+pub fn resolve_client_ip(peer: IpAddr, headers: &HeaderMap, trusted_proxies: &[IpNet]) -> IpAddr {
+    let is_trusted = |ip: &IpAddr| trusted_proxies.iter().any(|net| net.contains(ip));
+    if !is_trusted(&peer) {
+        return peer;
+    }
+
+    let mut chain = forwarded_for_chain(headers);
+    if chain.is_empty() {
+        return peer;
+    }
+    let outermost = chain[0];
+    chain
+        .drain(..)
+        .rev()
+        .find(|ip| !is_trusted(ip))
+        .unwrap_or(outermost)
+}
+
+/// Collect the list of forwarded-for addresses from the request headers, ordered from client to nearest proxy.
+fn forwarded_for_chain(headers: &HeaderMap) -> Vec<IpAddr> {
+    let x_forwarded_for = headers
+        .get_all("X-Forwarded-For")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(parse_forwarded_node)
+        .collect::<Vec<_>>();
+    if !x_forwarded_for.is_empty() {
+        return x_forwarded_for;
+    }
+
+    headers
+        .get_all("Forwarded")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .flat_map(|element| element.split(';'))
+        .filter_map(|pair| {
+            let (key, value) = pair.trim().split_once('=')?;
+            key.eq_ignore_ascii_case("for").then_some(value)
+        })
+        .filter_map(parse_forwarded_node)
+        .collect()
+}
+
+/// Parse a single forwarded node, which may be quoted, bracketed or include a port.
+fn parse_forwarded_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+    if let Some(rest) = node.strip_prefix('[') {
+        return rest.split_once(']')?.0.parse().ok();
+    }
+    node.parse().ok().or_else(|| {
+        node.rsplit_once(':')
+            .and_then(|(host, _)| host.parse::<IpAddr>().ok())
+    })
+}
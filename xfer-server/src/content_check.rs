@@ -0,0 +1,100 @@
+use crate::error::ApiError;
+use axum::http::HeaderMap;
+use clap::ValueEnum;
+
+/// Header clients use to declare the version of the encryption format an upload uses.
+pub const ENCRYPTION_VERSION_HEADER: &str = "X-Xfer-Encryption-Version";
+
+/// Encryption format versions that uploads may declare.
+const SUPPORTED_ENCRYPTION_VERSIONS: [&str; 1] = ["1"];
+
+/// Number of bytes from the start of an upload that are checked.
+pub const CONTENT_SAMPLE_SIZE: usize = 64 * 1024;
+
+/// Samples smaller than this are too short for their entropy to be meaningful and aren't checked.
+const MIN_CONTENT_SAMPLE_SIZE: usize = 1024;
+
+/// Error returned by [`TransferStorage::create_transfer`](crate::storage::TransferStorage::create_transfer)
+/// when the received data doesn't look like ciphertext.
+#[derive(Debug)]
+pub struct TransferNotEncryptedError;
+
+impl std::fmt::Display for TransferNotEncryptedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "transfer data does not appear to be encrypted")
+    }
+}
+
+impl std::error::Error for TransferNotEncryptedError {}
+
+/// How strictly uploads are checked for being encrypted before they are accepted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ContentCheckMode {
+    /// Accept uploads without checking them.
+    Off,
+    /// Reject uploads that are clearly not encrypted, such as plain text or uncompressed media.
+    Lenient,
+    /// Require uploads to declare a supported encryption version and be indistinguishable from random data.
+    Strict,
+}
+
+impl ContentCheckMode {
+    /// How far below the entropy expected of random data a sample may fall, in bits per byte.
+    fn entropy_margin(self) -> f64 {
+        match self {
+            Self::Off => f64::INFINITY,
+            Self::Lenient => 1.0,
+            Self::Strict => 0.25,
+        }
+    }
+
+    /// Check the encryption version declared in an upload's headers, if any.
+    pub fn check_declared_version(self, headers: &HeaderMap) -> Result<(), ApiError> {
+        if self == Self::Off {
+            return Ok(());
+        }
+        match headers.get(ENCRYPTION_VERSION_HEADER) {
+            Some(value)
+                if value
+                    .to_str()
+                    .is_ok_and(|v| SUPPORTED_ENCRYPTION_VERSIONS.contains(&v.trim())) =>
+            {
+                Ok(())
+            }
+            Some(_) => Err(ApiError::bad_request(format!(
+                "unsupported encryption version, supported versions are: {}",
+                SUPPORTED_ENCRYPTION_VERSIONS.join(", ")
+            ))),
+            None if self == Self::Strict => Err(ApiError::bad_request(
+                "uploads must declare their encryption version",
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Whether the given sample from the start of an upload looks like ciphertext.
+    ///
+    /// The Shannon entropy of the sample is compared against the entropy expected of
+    /// uniformly random data of the same length, which falls short of 8 bits per byte
+    /// for short samples.
+    pub fn looks_encrypted(self, sample: &[u8]) -> bool {
+        if self == Self::Off || sample.len() < MIN_CONTENT_SAMPLE_SIZE {
+            return true;
+        }
+        let mut counts = [0u64; 256];
+        for &byte in sample {
+            counts[byte as usize] += 1;
+        }
+        let len = sample.len() as f64;
+        let entropy: f64 = counts
+            .iter()
+            .filter(|&&count| count > 0)
+            .map(|&count| {
+                let p = count as f64 / len;
+                -p * p.log2()
+            })
+            .sum();
+        let expected = 8.0 - 255.0 / (2.0 * len * std::f64::consts::LN_2);
+        entropy >= expected - self.entropy_margin()
+    }
+}
@@ -0,0 +1,69 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// How long clients are told to wait before retrying a download rejected by [`DownloadSlots`].
+pub const CONCURRENT_DOWNLOADS_RETRY_AFTER: Duration = Duration::from_secs(30);
+
+/// Limits how many downloads of a single transfer can be in progress at once,
+/// so a leaked identifier can't be used to mass-download a transfer.
+///
+/// Downloads are tracked in memory, so the limit applies to each instance separately.
+#[derive(Debug)]
+pub struct DownloadSlots {
+    limit: usize,
+    active: Arc<Mutex<HashMap<String, usize>>>,
+}
+
+/// A download in progress, which frees its slot when dropped.
+#[derive(Debug)]
+pub struct DownloadSlot {
+    id: String,
+    active: Arc<Mutex<HashMap<String, usize>>>,
+}
+
+impl DownloadSlots {
+    /// Create a new [`DownloadSlots`] allowing `limit` concurrent downloads of each transfer, or any number when 0.
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            active: Arc::default(),
+        }
+    }
+
+    /// Take a download slot for the given transfer, returning `None` if all of its slots are in use.
+    ///
+    /// Always succeeds when downloads aren't limited.
+    pub fn try_acquire(&self, id: &str) -> Option<DownloadSlot> {
+        if self.limit == 0 {
+            return Some(DownloadSlot {
+                id: String::new(),
+                active: Arc::default(),
+            });
+        }
+        let mut active = self.active.lock().unwrap();
+        let count = active.entry(id.to_string()).or_default();
+        if *count >= self.limit {
+            return None;
+        }
+        *count += 1;
+        Some(DownloadSlot {
+            id: id.to_string(),
+            active: Arc::clone(&self.active),
+        })
+    }
+}
+
+impl Drop for DownloadSlot {
+    fn drop(&mut self) {
+        let mut active = self.active.lock().unwrap();
+        if let Some(count) = active.get_mut(&self.id) {
+            *count -= 1;
+            if *count == 0 {
+                active.remove(&self.id);
+            }
+        }
+    }
+}
@@ -0,0 +1,276 @@
+use crate::{error::ApiError, storage::TransferStorage, store::TransferPin};
+use anyhow::{Context, Result};
+use axum::http::HeaderMap;
+use bytesize::ByteSize;
+use lettre::{
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+    message::{Mailbox, header::ContentType},
+};
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+use tracing::{debug, warn};
+
+/// Header uploaders can use to give an address that is emailed about their transfer.
+pub const NOTIFY_EMAIL_HEADER: &str = "X-Xfer-Notify-Email";
+
+/// How long to wait for the SMTP server to respond.
+const SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How many times sending an email is attempted before giving up.
+const EMAIL_ATTEMPTS: u32 = 3;
+
+/// How long to wait before retrying a failed email, doubled after each attempt.
+const EMAIL_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Something that happened to a transfer that is worth emailing about.
+#[derive(Debug, Clone, Copy)]
+enum EmailEvent {
+    Created,
+    Downloaded,
+    ExpiringUnclaimed,
+}
+
+impl EmailEvent {
+    fn subject(self, id: &str) -> String {
+        match self {
+            Self::Created => format!("Transfer {id} was uploaded"),
+            Self::Downloaded => format!("Transfer {id} was downloaded"),
+            Self::ExpiringUnclaimed => {
+                format!("Transfer {id} expires soon without being downloaded")
+            }
+        }
+    }
+
+    fn summary(self) -> &'static str {
+        match self {
+            Self::Created => "A transfer was uploaded.",
+            Self::Downloaded => "A transfer was downloaded for the first time.",
+            Self::ExpiringUnclaimed => {
+                "A transfer will expire soon and has not been downloaded yet. Once it expires it can no longer be downloaded."
+            }
+        }
+    }
+}
+
+/// Emails an operator-configured address, and optionally an address the uploader gave,
+/// when transfers are created, first downloaded or about to expire without being downloaded.
+#[derive(Debug)]
+pub struct EmailNotifications {
+    transport: Option<AsyncSmtpTransport<Tokio1Executor>>,
+    from: Option<Mailbox>,
+    operator_address: Option<Mailbox>,
+    uploader_addresses_allowed: bool,
+    expiry_warning: Duration,
+}
+
+impl EmailNotifications {
+    /// Create a new [`EmailNotifications`] that sends through the SMTP server at `smtp_url`.
+    ///
+    /// No emails are sent when `smtp_url` is `None`.
+    pub fn new(
+        smtp_url: Option<&str>,
+        from: Option<Mailbox>,
+        operator_address: Option<Mailbox>,
+        uploader_addresses_allowed: bool,
+        expiry_warning: Duration,
+    ) -> Result<Self> {
+        let transport = smtp_url
+            .map(|url| {
+                AsyncSmtpTransport::<Tokio1Executor>::from_url(url)
+                    .context("Failed to parse SMTP URL")
+                    .map(|builder| builder.timeout(Some(SMTP_TIMEOUT)).build())
+            })
+            .transpose()?;
+        Ok(Self {
+            transport,
+            from,
+            operator_address,
+            uploader_addresses_allowed,
+            expiry_warning,
+        })
+    }
+
+    /// Whether uploaders may give an address to be emailed about their transfer.
+    pub fn uploader_addresses_allowed(&self) -> bool {
+        self.transport.is_some() && self.uploader_addresses_allowed
+    }
+
+    /// Get the address an uploader gave in the headers of an upload, if any.
+    pub fn uploader_address(&self, headers: &HeaderMap) -> Result<Option<Mailbox>, ApiError> {
+        let Some(value) = headers.get(NOTIFY_EMAIL_HEADER) else {
+            return Ok(None);
+        };
+        if !self.uploader_addresses_allowed() {
+            return Err(ApiError::bad_request(
+                "this server does not allow uploaders to register notification emails",
+            ));
+        }
+        let address = value
+            .to_str()
+            .ok()
+            .and_then(|v| v.trim().parse::<Mailbox>().ok())
+            .ok_or_else(|| ApiError::bad_request("notification email must be a valid address"))?;
+        Ok(Some(address))
+    }
+
+    /// Email that a transfer was created.
+    pub fn notify_created(
+        self: &Arc<Self>,
+        id: &str,
+        size: u64,
+        expires_at: SystemTime,
+        uploader_address: Option<&str>,
+    ) {
+        let details = format!(
+            "Size: {}\nExpires at: {}",
+            ByteSize::b(size),
+            format_timestamp(expires_at)
+        );
+        self.notify(EmailEvent::Created, id, &details, uploader_address);
+    }
+
+    /// Email that a transfer was downloaded for the first time.
+    pub fn notify_downloaded(
+        self: &Arc<Self>,
+        id: &str,
+        downloaded_at: SystemTime,
+        uploader_address: Option<&str>,
+    ) {
+        let details = format!("Downloaded at: {}", format_timestamp(downloaded_at));
+        self.notify(EmailEvent::Downloaded, id, &details, uploader_address);
+    }
+
+    /// Warn about transfers that expire within the expiry warning period and haven't been downloaded.
+    ///
+    /// Each transfer is only warned about once, and transfers kept for less than the warning period
+    /// aren't warned about at all.
+    pub fn warn_expiring_transfers(self: &Arc<Self>, storage: &TransferStorage) {
+        if self.transport.is_none() {
+            return;
+        }
+        let state_store = storage.state_store();
+        for id in storage.transfers_expiring_before(SystemTime::now() + self.expiry_warning) {
+            let Ok(Some(mut metadata)) = state_store.get_metadata(&id) else {
+                continue;
+            };
+            if metadata.expiry_warning_sent
+                || matches!(metadata.pin, Some(TransferPin { expires_at: None }))
+            {
+                continue;
+            }
+            let expires_at = match storage.get_transfer_expiry(&id) {
+                Ok(expires_at) => expires_at,
+                Err(err) => {
+                    warn!("Failed to get expiry of transfer (id: '{id}'): {err:?}");
+                    continue;
+                }
+            };
+            if expires_at
+                .duration_since(metadata.created_at)
+                .is_ok_and(|lifetime| lifetime <= self.expiry_warning)
+            {
+                continue;
+            }
+            match state_store.get_download_count(&id) {
+                Ok(0) => {}
+                Ok(_) => continue,
+                Err(err) => {
+                    warn!("Failed to get download count of transfer (id: '{id}'): {err:?}");
+                    continue;
+                }
+            }
+            metadata.expiry_warning_sent = true;
+            if let Err(err) = state_store.set_metadata(&id, &metadata) {
+                warn!("Failed to record expiry warning for transfer (id: '{id}'): {err:?}");
+                continue;
+            }
+            let details = format!("Expires at: {}", format_timestamp(expires_at));
+            self.notify(
+                EmailEvent::ExpiringUnclaimed,
+                &id,
+                &details,
+                metadata.notify_email.as_deref(),
+            );
+        }
+    }
+
+    /// Email the operator's address, and the uploader's address if one was given, about a transfer.
+    ///
+    /// Emails are sent in the background and retried a few times on failure.
+    fn notify(
+        self: &Arc<Self>,
+        event: EmailEvent,
+        id: &str,
+        details: &str,
+        uploader_address: Option<&str>,
+    ) {
+        let (Some(_), Some(from)) = (&self.transport, &self.from) else {
+            return;
+        };
+        let uploader_address = uploader_address
+            .filter(|_| self.uploader_addresses_allowed)
+            .and_then(|address| address.parse::<Mailbox>().ok());
+        let recipients: Vec<Mailbox> = self
+            .operator_address
+            .iter()
+            .cloned()
+            .chain(uploader_address)
+            .collect();
+        let body = format!(
+            "{}\n\nTransfer: {id}\n{details}\n\n-- \nSent by xfer-server. Transfers are end-to-end encrypted, so the server can't tell what they contain.\n",
+            event.summary()
+        );
+        for to in recipients {
+            let message = match Message::builder()
+                .from(from.clone())
+                .to(to)
+                .subject(event.subject(id))
+                .header(ContentType::TEXT_PLAIN)
+                .body(body.clone())
+            {
+                Ok(message) => message,
+                Err(err) => {
+                    warn!("Failed to build notification email for transfer (id: '{id}'): {err:?}");
+                    continue;
+                }
+            };
+            let notifications = Arc::clone(self);
+            let id = id.to_string();
+            tokio::spawn(async move { notifications.send(message, &id).await });
+        }
+    }
+
+    /// Send an email, retrying with a growing delay until the SMTP server accepts it.
+    ///
+    /// Recipients aren't logged, as they may belong to uploaders.
+    async fn send(&self, message: Message, id: &str) {
+        let Some(transport) = &self.transport else {
+            return;
+        };
+        let mut delay = EMAIL_RETRY_DELAY;
+        for attempt in 1..=EMAIL_ATTEMPTS {
+            match transport.send(message.clone()).await {
+                Ok(_) => {
+                    debug!("Sent notification email for transfer (id: '{id}')");
+                    return;
+                }
+                Err(err) => warn!(
+                    "Failed to send notification email for transfer (id: '{id}') (attempt {attempt}/{EMAIL_ATTEMPTS}): {err}"
+                ),
+            }
+            if attempt < EMAIL_ATTEMPTS {
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+        }
+    }
+}
+
+fn format_timestamp(time: SystemTime) -> String {
+    OffsetDateTime::from(time)
+        .format(&Rfc3339)
+        .unwrap_or_else(|_| String::from("unknown"))
+}
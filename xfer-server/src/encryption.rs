@@ -0,0 +1,208 @@
+use anyhow::{Context, Result, bail};
+use axum::body::Bytes;
+use chacha20poly1305::{
+    KeyInit, XChaCha20Poly1305, XNonce,
+    aead::{Aead, Payload},
+};
+use futures_util::{StreamExt, stream::BoxStream};
+use std::{
+    fmt::Debug,
+    io::{self, SeekFrom},
+    ops::RangeInclusive,
+};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+/// Magic bytes identifying the at-rest encryption format.
+const MAGIC: &[u8; 8] = b"XFERENC1";
+const NONCE_PREFIX_LENGTH: usize = 16;
+/// Length of the header written before the first chunk: the magic bytes and nonce prefix.
+const HEADER_LENGTH: u64 = (MAGIC.len() + NONCE_PREFIX_LENGTH) as u64;
+/// Amount of plaintext sealed into each chunk.
+const CHUNK_SIZE: u64 = 64 * 1024;
+const TAG_LENGTH: u64 = 16;
+const ENCRYPTED_CHUNK_SIZE: u64 = CHUNK_SIZE + TAG_LENGTH;
+
+/// Parse a 32-byte key, such as the at-rest encryption key, from 64 hexadecimal characters.
+pub fn parse_encryption_key(value: &str) -> Result<[u8; 32], String> {
+    let value = value.trim();
+    if value.len() != 64 || !value.is_ascii() {
+        return Err(String::from(
+            "key must be 64 hexadecimal characters (32 bytes)",
+        ));
+    }
+    let mut key = [0; 32];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&value[i * 2..i * 2 + 2], 16)
+            .map_err(|_| String::from("key must only contain hexadecimal characters"))?;
+    }
+    Ok(key)
+}
+
+/// Server-side cipher used to encrypt transfer files at rest.
+///
+/// Files are split into fixed-size chunks that are each sealed with XChaCha20-Poly1305 so that
+/// byte ranges can be served without decrypting the whole file. Every file has its own random
+/// nonce prefix, and the final chunk is authenticated as such so truncation is detected.
+#[derive(Clone)]
+pub struct AtRestCipher {
+    cipher: XChaCha20Poly1305,
+}
+
+impl Debug for AtRestCipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AtRestCipher").finish_non_exhaustive()
+    }
+}
+
+impl AtRestCipher {
+    /// Create a new [`AtRestCipher`] from the given key.
+    pub fn new(key: &[u8; 32]) -> Self {
+        Self {
+            cipher: XChaCha20Poly1305::new(key.into()),
+        }
+    }
+
+    /// Start encrypting a new file.
+    pub fn encryptor(&self) -> ChunkEncryptor {
+        ChunkEncryptor {
+            cipher: self.cipher.clone(),
+            nonce_prefix: rand::random(),
+            index: 0,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Get the size of the plaintext stored in an encrypted file of the given size.
+    pub fn plaintext_size(encrypted_size: u64) -> u64 {
+        let body = encrypted_size.saturating_sub(HEADER_LENGTH);
+        body.saturating_sub(body.div_ceil(ENCRYPTED_CHUNK_SIZE) * TAG_LENGTH)
+    }
+
+    /// Stream the decrypted plaintext within `range` (inclusive) from an encrypted file.
+    pub async fn decrypt_stream(
+        &self,
+        mut file: tokio::fs::File,
+        range: Option<RangeInclusive<u64>>,
+    ) -> Result<BoxStream<'static, io::Result<Bytes>>> {
+        let encrypted_size = file
+            .metadata()
+            .await
+            .context("Failed to read encrypted file metadata")?
+            .len();
+        let plaintext_size = Self::plaintext_size(encrypted_size);
+        let range = range.unwrap_or(0..=plaintext_size.saturating_sub(1));
+        if plaintext_size == 0 {
+            return Ok(futures_util::stream::empty().boxed());
+        }
+
+        let mut header = [0; HEADER_LENGTH as usize];
+        file.read_exact(&mut header)
+            .await
+            .context("Failed to read encrypted file header")?;
+        if &header[..MAGIC.len()] != MAGIC {
+            bail!("File is not in a supported at-rest encryption format");
+        }
+        let nonce_prefix: [u8; NONCE_PREFIX_LENGTH] = header[MAGIC.len()..].try_into()?;
+
+        let body_size = encrypted_size - HEADER_LENGTH;
+        let last_index = body_size.div_ceil(ENCRYPTED_CHUNK_SIZE) - 1;
+        let (start, end) = (*range.start(), *range.end());
+        let first_chunk = start / CHUNK_SIZE;
+        let last_chunk = end / CHUNK_SIZE;
+        file.seek(SeekFrom::Start(
+            HEADER_LENGTH + first_chunk * ENCRYPTED_CHUNK_SIZE,
+        ))
+        .await
+        .context("Failed to seek encrypted file")?;
+
+        let cipher = self.cipher.clone();
+        Ok(
+            futures_util::stream::try_unfold((file, first_chunk), move |(mut file, index)| {
+                let cipher = cipher.clone();
+                async move {
+                    if index > last_chunk {
+                        return Ok(None);
+                    }
+                    let length = ENCRYPTED_CHUNK_SIZE.min(body_size - index * ENCRYPTED_CHUNK_SIZE);
+                    let mut sealed = vec![0; length as usize];
+                    file.read_exact(&mut sealed).await?;
+                    let plaintext = cipher
+                        .decrypt(
+                            &chunk_nonce(&nonce_prefix, index),
+                            Payload {
+                                msg: &sealed,
+                                aad: &[u8::from(index == last_index)],
+                            },
+                        )
+                        .map_err(|_| {
+                            io::Error::new(io::ErrorKind::InvalidData, "failed to decrypt chunk")
+                        })?;
+                    let chunk_start = index * CHUNK_SIZE;
+                    let from = start.saturating_sub(chunk_start) as usize;
+                    let to = (end + 1 - chunk_start).min(plaintext.len() as u64) as usize;
+                    Ok(Some((
+                        Bytes::from(plaintext).slice(from..to),
+                        (file, index + 1),
+                    )))
+                }
+            })
+            .boxed(),
+        )
+    }
+}
+
+/// Incrementally encrypts a file written through [`AtRestCipher`].
+pub struct ChunkEncryptor {
+    cipher: XChaCha20Poly1305,
+    nonce_prefix: [u8; NONCE_PREFIX_LENGTH],
+    index: u64,
+    buffer: Vec<u8>,
+}
+
+impl ChunkEncryptor {
+    /// The header that must be written before any encrypted chunks.
+    pub fn header(&self) -> Vec<u8> {
+        [MAGIC.as_slice(), &self.nonce_prefix].concat()
+    }
+
+    /// Add plaintext, returning any ciphertext that is ready to be written.
+    ///
+    /// A full chunk is held back until more data arrives so that the final chunk can be marked as such.
+    pub fn update(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+        self.buffer.extend_from_slice(data);
+        let mut out = Vec::new();
+        while self.buffer.len() as u64 > CHUNK_SIZE {
+            let chunk: Vec<u8> = self.buffer.drain(..CHUNK_SIZE as usize).collect();
+            out.extend(self.seal(&chunk, false)?);
+        }
+        Ok(out)
+    }
+
+    /// Encrypt any remaining plaintext as the final chunk.
+    pub fn finish(mut self) -> Result<Vec<u8>> {
+        let chunk = std::mem::take(&mut self.buffer);
+        self.seal(&chunk, true)
+    }
+
+    fn seal(&mut self, chunk: &[u8], last: bool) -> Result<Vec<u8>> {
+        let sealed = self
+            .cipher
+            .encrypt(
+                &chunk_nonce(&self.nonce_prefix, self.index),
+                Payload {
+                    msg: chunk,
+                    aad: &[u8::from(last)],
+                },
+            )
+            .map_err(|_| anyhow::anyhow!("Failed to encrypt chunk"))?;
+        self.index += 1;
+        Ok(sealed)
+    }
+}
+
+fn chunk_nonce(prefix: &[u8; NONCE_PREFIX_LENGTH], index: u64) -> XNonce {
+    let mut nonce = XNonce::default();
+    nonce[..NONCE_PREFIX_LENGTH].copy_from_slice(prefix);
+    nonce[NONCE_PREFIX_LENGTH..].copy_from_slice(&index.to_le_bytes());
+    nonce
+}
@@ -0,0 +1,127 @@
+use crate::{
+    content_check::TransferNotEncryptedError,
+    storage::{TransferChecksumMismatchError, TransferStalledError, TransferTooLargeError},
+};
+use axum::{
+    Json,
+    http::{HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use std::{borrow::Cow, io, time::Duration};
+use tracing::error;
+
+/// Error returned from route handlers and middleware, rendered as a JSON body with a matching status code.
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    message: Cow<'static, str>,
+    retry_after: Option<Duration>,
+}
+
+#[derive(Serialize)]
+struct ApiErrorBody<'a> {
+    error: &'a str,
+}
+
+impl ApiError {
+    /// Create a new [`ApiError`] with the given status code and message.
+    pub fn new(status: StatusCode, message: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+            retry_after: None,
+        }
+    }
+
+    /// Tell the client how long to wait before retrying with a `Retry-After` header.
+    pub fn with_retry_after(mut self, retry_after: Duration) -> Self {
+        self.retry_after = Some(retry_after);
+        self
+    }
+
+    /// Create a `400 Bad Request` error.
+    pub fn bad_request(message: impl Into<Cow<'static, str>>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, message)
+    }
+
+    /// Create a `404 Not Found` error.
+    pub fn not_found(message: impl Into<Cow<'static, str>>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, message)
+    }
+
+    /// Create the `404 Not Found` error for a transfer that doesn't exist.
+    ///
+    /// Also used for malformed identifiers so clients can't tell them apart from
+    /// well-formed identifiers that aren't in use.
+    pub fn transfer_not_found() -> Self {
+        Self::not_found("transfer does not exist")
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    /// Map an internal error onto the most appropriate response, logging anything unexpected.
+    fn from(err: anyhow::Error) -> Self {
+        if let Some(err) = err.downcast_ref::<TransferTooLargeError>() {
+            return Self::new(StatusCode::PAYLOAD_TOO_LARGE, err.to_string());
+        }
+        if err.is::<TransferStalledError>() {
+            return Self::new(
+                StatusCode::REQUEST_TIMEOUT,
+                "transfer upload stalled or was too slow",
+            );
+        }
+        if err.is::<TransferChecksumMismatchError>() {
+            return Self::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "transfer data did not match the supplied checksum, it may have been corrupted in transit",
+            );
+        }
+        if err.is::<TransferNotEncryptedError>() {
+            return Self::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "transfer does not appear to be encrypted, only encrypted transfers are accepted",
+            );
+        }
+        match err
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<io::Error>())
+            .map(io::Error::kind)
+        {
+            Some(io::ErrorKind::NotFound) => Self::transfer_not_found(),
+            Some(io::ErrorKind::AlreadyExists) => {
+                Self::new(StatusCode::CONFLICT, "transfer already exists")
+            }
+            Some(io::ErrorKind::StorageFull) => Self::new(
+                StatusCode::INSUFFICIENT_STORAGE,
+                "server does not currently have enough storage space, try again later",
+            ),
+            _ => {
+                error!("Request failed due to an internal error: {err:?}");
+                Self::new(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "an internal server error occurred",
+                )
+            }
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let mut response = (
+            self.status,
+            Json(ApiErrorBody {
+                error: &self.message,
+            }),
+        )
+            .into_response();
+        if let Some(retry_after) = self.retry_after {
+            response.headers_mut().insert(
+                header::RETRY_AFTER,
+                HeaderValue::from(retry_after.as_secs().max(1)),
+            );
+        }
+        response
+    }
+}
@@ -0,0 +1,105 @@
+use crate::error::ApiError;
+use anyhow::{Result, bail};
+use axum::http::HeaderMap;
+use std::time::Duration;
+
+/// Header clients may use to request how long a transfer is kept for, in seconds.
+pub const EXPIRE_AFTER_HEADER: &str = "X-Xfer-Expire-After";
+
+/// The range of lifetimes uploads may request, and the lifetime used when they don't request one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExpiryBounds {
+    pub min: Duration,
+    pub default: Duration,
+    pub max: Duration,
+}
+
+impl ExpiryBounds {
+    /// Create a new [`ExpiryBounds`], failing unless `min <= default <= max`.
+    pub fn new(min: Duration, default: Duration, max: Duration) -> Result<Self> {
+        if min > default || default > max {
+            bail!(
+                "Transfer expiry bounds must satisfy minimum <= default <= maximum (got {}s, {}s and {}s)",
+                min.as_secs(),
+                default.as_secs(),
+                max.as_secs()
+            );
+        }
+        Ok(Self { min, default, max })
+    }
+
+    /// Get the lifetime requested in the headers of an upload, if any, rejecting it when it is out of bounds.
+    pub fn requested(&self, headers: &HeaderMap) -> Result<Option<Duration>, ApiError> {
+        let Some(value) = headers.get(EXPIRE_AFTER_HEADER) else {
+            return Ok(None);
+        };
+        let expire_after = value
+            .to_str()
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .map(Duration::from_secs)
+            .ok_or_else(|| {
+                ApiError::bad_request("expire after header must be a whole number of seconds")
+            })?;
+        if expire_after < self.min || expire_after > self.max {
+            return Err(ApiError::bad_request(format!(
+                "requested expiry must be between {} and {} seconds",
+                self.min.as_secs(),
+                self.max.as_secs()
+            )));
+        }
+        Ok(Some(expire_after))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn bounds() -> ExpiryBounds {
+        ExpiryBounds::new(
+            Duration::from_secs(60),
+            Duration::from_secs(3600),
+            Duration::from_secs(86400),
+        )
+        .unwrap()
+    }
+
+    fn requested(value: &str) -> Result<Option<Duration>, ApiError> {
+        let mut headers = HeaderMap::new();
+        headers.insert(EXPIRE_AFTER_HEADER, HeaderValue::from_str(value).unwrap());
+        bounds().requested(&headers)
+    }
+
+    #[test]
+    fn rejects_out_of_order_bounds() {
+        let minute = Duration::from_secs(60);
+        let hour = Duration::from_secs(3600);
+        assert!(ExpiryBounds::new(hour, minute, hour).is_err());
+        assert!(ExpiryBounds::new(minute, hour, minute).is_err());
+        assert!(ExpiryBounds::new(minute, minute, minute).is_ok());
+    }
+
+    #[test]
+    fn accepts_lifetimes_within_bounds() {
+        assert_eq!(bounds().requested(&HeaderMap::new()).unwrap(), None);
+        assert_eq!(requested("60").unwrap(), Some(Duration::from_secs(60)));
+        assert_eq!(
+            requested(" 7200 ").unwrap(),
+            Some(Duration::from_secs(7200))
+        );
+        assert_eq!(
+            requested("86400").unwrap(),
+            Some(Duration::from_secs(86400))
+        );
+    }
+
+    #[test]
+    fn rejects_lifetimes_outside_bounds() {
+        assert!(requested("59").is_err());
+        assert!(requested("86401").is_err());
+        assert!(requested("-1").is_err());
+        assert!(requested("1h").is_err());
+    }
+}
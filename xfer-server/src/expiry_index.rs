@@ -0,0 +1,70 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+/// In-memory index of when transfers expire, so sweeps only need to check the transfers that are due
+/// rather than every stored transfer.
+///
+/// Entries are hints: the sweeper re-checks a transfer's expiry before removing it, and re-indexes
+/// transfers whose expiry has moved since they were indexed.
+#[derive(Debug, Default)]
+pub struct ExpiryIndex {
+    inner: Mutex<ExpiryIndexInner>,
+}
+
+#[derive(Debug, Default)]
+struct ExpiryIndexInner {
+    by_id: HashMap<String, SystemTime>,
+    by_expiry: BTreeSet<(SystemTime, String)>,
+}
+
+impl ExpiryIndex {
+    /// Record when a transfer expires, replacing any earlier entry for it.
+    pub fn insert(&self, id: &str, expires_at: SystemTime) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(previous) = inner.by_id.insert(id.to_string(), expires_at) {
+            inner.by_expiry.remove(&(previous, id.to_string()));
+        }
+        inner.by_expiry.insert((expires_at, id.to_string()));
+    }
+
+    /// Forget a transfer, such as once it has been deleted.
+    pub fn remove(&self, id: &str) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(expires_at) = inner.by_id.remove(id) {
+            inner.by_expiry.remove(&(expires_at, id.to_string()));
+        }
+    }
+
+    /// Remove and return the transfers that expire at or before `now`, soonest first.
+    pub fn take_due(&self, now: SystemTime) -> Vec<String> {
+        let mut inner = self.inner.lock().unwrap();
+        let mut due = Vec::new();
+        while let Some((expires_at, _)) = inner.by_expiry.first()
+            && *expires_at <= now
+        {
+            let (_, id) = inner.by_expiry.pop_first().unwrap();
+            inner.by_id.remove(&id);
+            due.push(id);
+        }
+        due
+    }
+
+    /// Return the transfers that expire at or before `time`, soonest first, without removing them.
+    pub fn due_before(&self, time: SystemTime) -> Vec<String> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .by_expiry
+            .iter()
+            .take_while(|(expires_at, _)| *expires_at <= time)
+            .map(|(_, id)| id.clone())
+            .collect()
+    }
+
+    /// The number of transfers in the index.
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().by_id.len()
+    }
+}
@@ -0,0 +1,171 @@
+use crate::{API_VERSION, AppState, error::ApiError};
+use anyhow::{Context, Result};
+use axum::{
+    body::Body,
+    extract::{Path, Request, State},
+    http::{HeaderMap, HeaderName, Method, Response, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Redirect},
+};
+use clap::ValueEnum;
+use reqwest::Url;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// Header sent with requests to peers so they only look for transfers locally, preventing loops.
+pub const FEDERATED_HEADER: &str = "X-Xfer-Federated";
+
+/// How long to wait for a peer to respond before treating it as not having a transfer.
+const PEER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Request headers passed on to peers when proxying a download.
+const PROXIED_REQUEST_HEADERS: [HeaderName; 4] = [
+    header::RANGE,
+    header::IF_RANGE,
+    header::IF_NONE_MATCH,
+    header::ACCEPT_ENCODING,
+];
+
+/// How downloads of transfers stored on a peer are served.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum FederationMode {
+    /// Redirect the client to the peer with a `307 Temporary Redirect`.
+    Redirect,
+    /// Stream the transfer from the peer through this server.
+    Proxy,
+}
+
+/// A set of peer relays that are checked for transfers that aren't stored locally,
+/// so a small federation of relays can present a single download URL space.
+#[derive(Debug)]
+pub struct Federation {
+    peers: Vec<Url>,
+    mode: FederationMode,
+    client: reqwest::Client,
+}
+
+impl Federation {
+    /// Create a new [`Federation`] with the given peer base URLs.
+    ///
+    /// Federation is disabled when no peers are given.
+    pub fn new(peers: Vec<Url>, mode: FederationMode) -> Result<Self> {
+        Ok(Self {
+            peers,
+            mode,
+            client: reqwest::Client::builder()
+                .user_agent(concat!("xfer-server/", env!("CARGO_PKG_VERSION")))
+                .connect_timeout(PEER_TIMEOUT)
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .context("Failed to create federation HTTP client")?,
+        })
+    }
+
+    /// Whether any peers are configured.
+    pub fn is_enabled(&self) -> bool {
+        !self.peers.is_empty()
+    }
+
+    /// Get the URL of the transfer with the given identifier on a peer.
+    fn transfer_url(peer: &Url, id: &str) -> Result<Url> {
+        let mut url = peer.clone();
+        url.path_segments_mut()
+            .map_err(|()| anyhow::anyhow!("Peer URL cannot be a base"))?
+            .pop_if_empty()
+            .extend([&format!("v{API_VERSION}"), "transfer", id]);
+        Ok(url)
+    }
+
+    /// Find the URL of a transfer on the first peer, in configured order, that has it.
+    pub async fn locate(&self, id: &str) -> Option<Url> {
+        let lookups = self.peers.iter().map(|peer| async move {
+            let url = Self::transfer_url(peer, id).ok()?;
+            match self
+                .client
+                .head(url.clone())
+                .header(FEDERATED_HEADER, "1")
+                .timeout(PEER_TIMEOUT)
+                .send()
+                .await
+            {
+                Ok(res) if res.status().is_success() => Some(url),
+                Ok(_) => None,
+                Err(err) => {
+                    warn!("Failed to look up transfer on peer {peer}: {err}");
+                    None
+                }
+            }
+        });
+        futures_util::future::join_all(lookups)
+            .await
+            .into_iter()
+            .flatten()
+            .next()
+    }
+
+    /// Forward a download request to a peer and stream its response back.
+    async fn proxy(&self, url: Url, method: Method, headers: &HeaderMap) -> Result<Response<Body>> {
+        let mut request = self
+            .client
+            .request(method, url)
+            .header(FEDERATED_HEADER, "1");
+        for name in PROXIED_REQUEST_HEADERS {
+            if let Some(value) = headers.get(&name) {
+                request = request.header(name, value);
+            }
+        }
+        let res = request
+            .send()
+            .await
+            .context("Failed to send request to peer")?;
+        let mut response = Response::builder().status(res.status());
+        for (name, value) in res.headers() {
+            // Hop-by-hop headers only apply to the connection with the peer.
+            if name != header::CONNECTION && name != header::TRANSFER_ENCODING {
+                response = response.header(name, value);
+            }
+        }
+        response
+            .body(Body::from_stream(res.bytes_stream()))
+            .context("Failed to build proxied response")
+    }
+}
+
+/// Middleware that serves downloads of transfers that aren't stored locally from a peer that has them.
+pub async fn federation_middleware(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    req: Request,
+    next: Next,
+) -> Response<Body> {
+    if !state.federation.is_enabled()
+        || !matches!(*req.method(), Method::GET | Method::HEAD)
+        || req.headers().contains_key(FEDERATED_HEADER)
+    {
+        return next.run(req).await;
+    }
+    let method = req.method().clone();
+    let headers = req.headers().clone();
+    let response = next.run(req).await;
+    if response.status() != StatusCode::NOT_FOUND {
+        return response;
+    }
+    let Some(url) = state.federation.locate(&id).await else {
+        return response;
+    };
+    debug!("Serving transfer from peer (id: '{id}', url: '{url}')");
+    match state.federation.mode {
+        FederationMode::Redirect => Redirect::temporary(url.as_str()).into_response(),
+        FederationMode::Proxy => match state.federation.proxy(url, method, &headers).await {
+            Ok(response) => response,
+            Err(err) => {
+                warn!("Failed to proxy transfer from peer (id: '{id}'): {err:?}");
+                ApiError::new(
+                    StatusCode::BAD_GATEWAY,
+                    "failed to fetch the transfer from a peer server",
+                )
+                .into_response()
+            }
+        },
+    }
+}
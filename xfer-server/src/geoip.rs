@@ -0,0 +1,116 @@
+use anyhow::{Context, Result, bail};
+use maxminddb::{Reader, geoip2};
+use std::{net::IpAddr, path::Path};
+use tracing::info;
+
+/// Countries that are allowed or denied access to an operation, as ISO 3166-1 alpha-2 codes.
+///
+/// Denied countries always take precedence. When no countries are allowed every address
+/// that isn't in a denied country is permitted, including addresses with no known country.
+#[derive(Debug, Default)]
+pub struct CountryPolicy {
+    allow: Vec<String>,
+    deny: Vec<String>,
+}
+
+impl CountryPolicy {
+    /// Create a new [`CountryPolicy`] from lists of allowed and denied country codes.
+    pub fn new(allow: Vec<String>, deny: Vec<String>) -> Self {
+        Self { allow, deny }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+
+    /// Whether the given country is permitted by this policy.
+    fn is_allowed(&self, country: Option<&str>) -> bool {
+        match country {
+            Some(country) => {
+                !self.deny.iter().any(|c| c == country)
+                    && (self.allow.is_empty() || self.allow.iter().any(|c| c == country))
+            }
+            None => self.allow.is_empty(),
+        }
+    }
+}
+
+/// Separate country policies for uploading and downloading transfers, resolved using a MaxMind database.
+pub struct GeoIpPolicy {
+    reader: Option<Reader<Vec<u8>>>,
+    upload: CountryPolicy,
+    download: CountryPolicy,
+}
+
+impl std::fmt::Debug for GeoIpPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GeoIpPolicy")
+            .field("enabled", &self.reader.is_some())
+            .field("upload", &self.upload)
+            .field("download", &self.download)
+            .finish()
+    }
+}
+
+impl GeoIpPolicy {
+    /// Create a new [`GeoIpPolicy`] that looks up countries in the MaxMind database at the given path.
+    ///
+    /// The policy permits everything when no database is given, and fails if countries
+    /// are configured without one.
+    pub fn new(
+        database: Option<&Path>,
+        upload: CountryPolicy,
+        download: CountryPolicy,
+    ) -> Result<Self> {
+        let reader = match database {
+            Some(path) => {
+                let reader = Reader::open_readfile(path).with_context(|| {
+                    format!("Failed to open GeoIP database at {}", path.display())
+                })?;
+                info!(
+                    "Loaded GeoIP database (type: {}, built: {})",
+                    reader.metadata.database_type, reader.metadata.build_epoch
+                );
+                Some(reader)
+            }
+            None if !upload.is_empty() || !download.is_empty() => {
+                bail!("A GeoIP database is required to allow or deny countries")
+            }
+            None => None,
+        };
+        Ok(Self {
+            reader,
+            upload,
+            download,
+        })
+    }
+
+    /// Look up the ISO country code of the given address.
+    fn country(&self, ip: IpAddr) -> Option<String> {
+        let country = self.reader.as_ref()?.lookup::<geoip2::Country>(ip).ok()?;
+        country
+            .country
+            .or(country.registered_country)
+            .and_then(|country| country.iso_code)
+            .map(str::to_string)
+    }
+
+    /// Whether the given address may upload transfers.
+    pub fn is_upload_allowed(&self, ip: IpAddr) -> bool {
+        self.upload.is_empty() || self.upload.is_allowed(self.country(ip).as_deref())
+    }
+
+    /// Whether the given address may download transfers.
+    pub fn is_download_allowed(&self, ip: IpAddr) -> bool {
+        self.download.is_empty() || self.download.is_allowed(self.country(ip).as_deref())
+    }
+}
+
+/// Parse an ISO 3166-1 alpha-2 country code, normalising it to uppercase.
+pub fn parse_country_code(value: &str) -> Result<String, String> {
+    let value = value.trim();
+    if value.len() != 2 || !value.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(format!("'{value}' is not a two-letter ISO country code"));
+    }
+    Ok(value.to_ascii_uppercase())
+}
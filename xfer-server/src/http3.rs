@@ -0,0 +1,138 @@
+use crate::ip_privacy::IpPrivacy;
+use anyhow::{Context, Result};
+use axum::{
+    Router,
+    body::Body,
+    extract::ConnectInfo,
+    http::{HeaderValue, Response},
+};
+use bytes::{Buf, Bytes};
+use futures_util::StreamExt;
+use h3::server::RequestResolver;
+use quinn::{Endpoint, crypto::rustls::QuicServerConfig};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject};
+use std::{io, net::SocketAddr, path::Path, sync::Arc};
+use tower::ServiceExt;
+use tracing::{debug, info};
+
+/// Bind a QUIC endpoint for serving HTTP/3 using the given PEM-encoded certificate chain and private key.
+pub fn bind(
+    address: SocketAddr,
+    certificate_file: &Path,
+    private_key_file: &Path,
+) -> Result<Endpoint> {
+    let certificates = CertificateDer::pem_file_iter(certificate_file)
+        .context("Failed to read TLS certificate file")?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to parse TLS certificate file")?;
+    let private_key = PrivateKeyDer::from_pem_file(private_key_file)
+        .context("Failed to read TLS private key file")?;
+    let mut tls_config = rustls::ServerConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_protocol_versions(&[&rustls::version::TLS13])?
+    .with_no_client_auth()
+    .with_single_cert(certificates, private_key)
+    .context("Invalid TLS certificate or private key")?;
+    tls_config.alpn_protocols = vec![b"h3".to_vec()];
+    let server_config = quinn::ServerConfig::with_crypto(Arc::new(
+        QuicServerConfig::try_from(tls_config)
+            .context("Failed to create QUIC server configuration")?,
+    ));
+    let endpoint =
+        Endpoint::server(server_config, address).context("Failed to bind HTTP/3 endpoint")?;
+    info!(
+        "* Listening on (HTTP/3): https://{}/",
+        endpoint.local_addr()?
+    );
+    Ok(endpoint)
+}
+
+/// Accept HTTP/3 connections on the endpoint and serve their requests with the router until the endpoint is closed.
+///
+/// Client addresses are redacted with `ip_privacy` before being logged.
+pub async fn serve(endpoint: Endpoint, router: Router, ip_privacy: Arc<IpPrivacy>) {
+    while let Some(incoming) = endpoint.accept().await {
+        let router = router.clone();
+        let ip_privacy = Arc::clone(&ip_privacy);
+        tokio::spawn(async move {
+            let connection = match incoming.await {
+                Ok(connection) => connection,
+                Err(err) => {
+                    debug!("Failed to establish QUIC connection: {err}");
+                    return;
+                }
+            };
+            let remote_address = connection.remote_address();
+            let client_ip = Arc::new(ip_privacy.redact(remote_address.ip()));
+            let mut connection = match h3::server::builder()
+                .build::<_, Bytes>(h3_quinn::Connection::new(connection))
+                .await
+            {
+                Ok(connection) => connection,
+                Err(err) => {
+                    debug!("Failed to establish HTTP/3 connection with {client_ip}: {err}");
+                    return;
+                }
+            };
+            loop {
+                match connection.accept().await {
+                    Ok(Some(resolver)) => {
+                        let router = router.clone();
+                        let client_ip = Arc::clone(&client_ip);
+                        tokio::spawn(async move {
+                            if let Err(err) = handle_request(resolver, router, remote_address).await
+                            {
+                                debug!("Failed to handle HTTP/3 request from {client_ip}: {err:?}");
+                            }
+                        });
+                    }
+                    Ok(None) => break,
+                    Err(err) => {
+                        debug!("HTTP/3 connection with {client_ip} closed: {err}");
+                        break;
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// Pass a single HTTP/3 request through the router, streaming both the request and response bodies.
+async fn handle_request(
+    resolver: RequestResolver<h3_quinn::Connection, Bytes>,
+    router: Router,
+    remote_address: SocketAddr,
+) -> Result<()> {
+    let (request, stream) = resolver.resolve_request().await?;
+    let (mut send, recv) = stream.split();
+
+    let body = Body::from_stream(futures_util::stream::unfold(
+        Some(recv),
+        |recv| async move {
+            let mut recv = recv?;
+            match recv.recv_data().await {
+                Ok(Some(mut data)) => Some((Ok(data.copy_to_bytes(data.remaining())), Some(recv))),
+                Ok(None) => None,
+                Err(err) => Some((Err(io::Error::other(err)), None)),
+            }
+        },
+    ));
+    let mut request = request.map(|()| body);
+    request.extensions_mut().insert(ConnectInfo(remote_address));
+
+    let (parts, body) = router.oneshot(request).await?.into_parts();
+    send.send_response(Response::from_parts(parts, ())).await?;
+    let mut body = body.into_data_stream();
+    while let Some(chunk) = body.next().await {
+        send.send_data(chunk?).await?;
+    }
+    send.finish().await?;
+    Ok(())
+}
+
+/// Build the `Alt-Svc` header value advertising the HTTP/3 endpoint on the given port.
+pub fn alt_svc_header(port: u16) -> HeaderValue {
+    HeaderValue::try_from(format!("h3=\":{port}\"; ma=86400"))
+        .expect("alt-svc header value should be valid")
+}
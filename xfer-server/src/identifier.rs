@@ -0,0 +1,142 @@
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use rand::{Rng, seq::IndexedRandom};
+use std::{fs, path::Path};
+use uuid::Uuid;
+
+/// Alphabet used for base32 codes (RFC 4648, lowercased).
+const BASE32_ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+/// Scheme used to generate transfer identifiers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum IdentifierFormat {
+    /// Passphrases made of random words from a wordlist (e.g. `correct-horse-battery-staple`).
+    Words,
+    /// Time-ordered UUIDs (version 7).
+    Uuid,
+    /// Short random codes using the base32 alphabet.
+    Base32,
+}
+
+/// Generates and validates transfer identifiers in the configured format.
+#[derive(Debug)]
+pub struct IdentifierGenerator {
+    format: IdentifierFormat,
+    word_count: usize,
+    separator: String,
+    wordlist: Vec<String>,
+    code_length: usize,
+}
+
+impl IdentifierGenerator {
+    /// Create a new [`IdentifierGenerator`].
+    ///
+    /// When no wordlist file is given the built-in EFF large wordlist is used for word identifiers.
+    pub fn new(
+        format: IdentifierFormat,
+        word_count: usize,
+        separator: String,
+        wordlist_file: Option<&Path>,
+        code_length: usize,
+    ) -> Result<Self> {
+        if format == IdentifierFormat::Words && !Self::is_safe(&separator) {
+            bail!(
+                "Identifier word separator must not be empty and may only contain letters, digits, '-' and '_'"
+            );
+        }
+        let wordlist = match wordlist_file {
+            Some(path) => Self::load_wordlist(path, &separator)?,
+            None => eff_wordlist::large::LIST
+                .iter()
+                .map(|word| word.1.to_string())
+                .collect(),
+        };
+        if format == IdentifierFormat::Words && wordlist.len() < word_count {
+            bail!(
+                "Identifier wordlist must contain at least {word_count} words but only contains {}",
+                wordlist.len()
+            );
+        }
+        Ok(Self {
+            format,
+            word_count,
+            separator,
+            wordlist,
+            code_length,
+        })
+    }
+
+    /// Read a wordlist file with one word per line.
+    ///
+    /// Lines may be prefixed by dice numbers (as in the EFF wordlists), in which case only the last
+    /// whitespace-separated field is used. Empty lines and lines starting with `#` are ignored.
+    fn load_wordlist(path: &Path, separator: &str) -> Result<Vec<String>> {
+        let mut words = Vec::new();
+        for (number, line) in fs::read_to_string(path)
+            .context("Failed to read identifier wordlist file")?
+            .lines()
+            .enumerate()
+            .map(|(number, line)| (number + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        {
+            let word = line.split_whitespace().last().unwrap_or_default();
+            if !Self::is_safe(word) || word.contains(separator) {
+                bail!(
+                    "Invalid word on line {number} of identifier wordlist file: '{word}' (words may only contain letters, digits, '-' and '_', and must not contain the separator)"
+                );
+            }
+            words.push(word.to_string());
+        }
+        words.sort_unstable();
+        words.dedup();
+        Ok(words)
+    }
+
+    /// Whether a value is safe to use as (part of) a transfer identifier, in URLs and as a file
+    /// name in transfer storage, using the same rules clients check identifiers with.
+    ///
+    /// Unlike [`Self::validate`] this is independent of the configured format, so it also
+    /// accepts identifiers of transfers created before the format was changed.
+    pub fn is_safe(value: &str) -> bool {
+        xfer_crypto::is_valid_transfer_id(value)
+    }
+
+    /// Generate a new random identifier.
+    pub fn generate(&self) -> String {
+        match self.format {
+            IdentifierFormat::Words => self
+                .wordlist
+                .choose_multiple(&mut rand::rng(), self.word_count)
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join(&self.separator),
+            IdentifierFormat::Uuid => Uuid::now_v7().hyphenated().to_string(),
+            IdentifierFormat::Base32 => {
+                let mut rng = rand::rng();
+                (0..self.code_length)
+                    .map(|_| BASE32_ALPHABET[rng.random_range(0..BASE32_ALPHABET.len())] as char)
+                    .collect()
+            }
+        }
+    }
+
+    /// Validates that the given value is in the same format as [`Self::generate`] would generate.
+    /// Used for light validation of transfer identifiers when receiving them from clients.
+    pub fn validate(&self, id: &str) -> bool {
+        if !Self::is_safe(id) {
+            return false;
+        }
+        match self.format {
+            IdentifierFormat::Words => {
+                let parts = id.split(self.separator.as_str()).collect::<Vec<_>>();
+                parts.len() == self.word_count && parts.iter().all(|word| !word.is_empty())
+            }
+            IdentifierFormat::Uuid => Uuid::try_parse(id).is_ok_and(|uuid| {
+                uuid.get_version_num() == 7 && uuid.hyphenated().to_string() == id
+            }),
+            IdentifierFormat::Base32 => {
+                id.len() == self.code_length && id.bytes().all(|b| BASE32_ALPHABET.contains(&b))
+            }
+        }
+    }
+}
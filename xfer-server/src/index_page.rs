@@ -0,0 +1,129 @@
+use crate::expiry::ExpiryBounds;
+use anyhow::{Context, Result, bail};
+use bytesize::ByteSize;
+use std::{fs, path::Path, time::Duration};
+
+/// Values that can be substituted into an index page template.
+#[derive(Debug)]
+pub struct IndexPageVariables<'a> {
+    pub max_size: ByteSize,
+    pub expiry: ExpiryBounds,
+    pub contact: Option<&'a str>,
+    pub motd: Option<&'a str>,
+}
+
+impl IndexPageVariables<'_> {
+    /// Get the value of a template variable, or `None` if no variable has that name.
+    fn get(&self, name: &str) -> Option<String> {
+        Some(match name {
+            "max_size" => self.max_size.to_string(),
+            "max_size_bytes" => self.max_size.as_u64().to_string(),
+            "expire_after" => format_duration(self.expiry.default),
+            "min_expire_after" => format_duration(self.expiry.min),
+            "max_expire_after" => format_duration(self.expiry.max),
+            "contact" => self.contact.unwrap_or_default().to_string(),
+            "motd" => self.motd.unwrap_or_default().to_string(),
+            "version" => env!("CARGO_PKG_VERSION").to_string(),
+            "repository" => env!("CARGO_PKG_REPOSITORY").to_string(),
+            _ => return None,
+        })
+    }
+}
+
+/// Landing page served at the API root in place of the default text,
+/// rendered once at startup from an operator-provided template.
+#[derive(Debug)]
+pub struct IndexPage {
+    body: String,
+    html: bool,
+}
+
+impl IndexPage {
+    /// Load and render the template at `path`, failing on unknown or unterminated variables.
+    ///
+    /// Templates with an `.html` or `.htm` extension are served as HTML, with substituted values escaped.
+    pub fn load(path: &Path, variables: &IndexPageVariables) -> Result<Self> {
+        let template = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read index page template {path:?}"))?;
+        let html = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("html") || ext.eq_ignore_ascii_case("htm"));
+        let body = render(&template, variables, html)
+            .with_context(|| format!("Failed to render index page template {path:?}"))?;
+        Ok(Self { body, html })
+    }
+
+    /// The rendered page.
+    pub fn body(&self) -> &str {
+        &self.body
+    }
+
+    /// The content type the page should be served with.
+    pub fn content_type(&self) -> &'static str {
+        if self.html {
+            "text/html; charset=utf-8"
+        } else {
+            "text/plain; charset=utf-8"
+        }
+    }
+}
+
+/// Replace every `{{ variable }}` in a template with its value.
+fn render(template: &str, variables: &IndexPageVariables, html: bool) -> Result<String> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find("}}") else {
+            bail!("Unterminated template variable");
+        };
+        let name = rest[start + 2..start + end].trim();
+        let Some(value) = variables.get(name) else {
+            bail!("Unknown template variable '{name}'");
+        };
+        if html {
+            rendered.push_str(&escape_html(&value));
+        } else {
+            rendered.push_str(&value);
+        }
+        rest = &rest[start + end + 2..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Format a duration in its largest whole unit, such as "7 days" or "90 minutes".
+fn format_duration(duration: Duration) -> String {
+    const UNITS: [(u64, &str); 4] = [
+        (86400, "day"),
+        (3600, "hour"),
+        (60, "minute"),
+        (1, "second"),
+    ];
+    let secs = duration.as_secs();
+    let (size, unit) = UNITS
+        .into_iter()
+        .find(|(size, _)| secs >= *size && secs.is_multiple_of(*size))
+        .unwrap_or((1, "second"));
+    let count = secs / size;
+    if count == 1 {
+        format!("1 {unit}")
+    } else {
+        format!("{count} {unit}s")
+    }
+}
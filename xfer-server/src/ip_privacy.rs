@@ -0,0 +1,100 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use ipnet::{IpNet, Ipv4Net, Ipv6Net};
+use std::{
+    fs,
+    io::ErrorKind,
+    net::IpAddr,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Prefix length IPv4 addresses are truncated to.
+const TRUNCATED_IPV4_PREFIX: u8 = 24;
+
+/// Prefix length IPv6 addresses are truncated to.
+const TRUNCATED_IPV6_PREFIX: u8 = 48;
+
+/// Number of bytes of the salted IP hash that are shown.
+const HASHED_IP_LENGTH: usize = 8;
+
+/// Length of the period a hashing salt is used for before rotating, in seconds.
+const SALT_ROTATION_SECS: u64 = 24 * 60 * 60;
+
+/// How client IP addresses appear in logs and the audit trail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum IpLogMode {
+    /// Show the full address.
+    Full,
+    /// Show only the network the address belongs to (`/24` for IPv4, `/48` for IPv6).
+    Truncate,
+    /// Show a hash of the address salted with a value that rotates daily.
+    Hash,
+}
+
+/// Redacts client IP addresses before they are logged, so operators keep enough
+/// visibility to spot abuse without retaining addresses that identify individuals.
+#[derive(Debug)]
+pub struct IpPrivacy {
+    mode: IpLogMode,
+    key: [u8; 32],
+}
+
+impl IpPrivacy {
+    /// Create a new [`IpPrivacy`] using the given mode.
+    ///
+    /// In [`IpLogMode::Hash`] the key daily salts are derived from is read from `key_path`,
+    /// or generated and written there on first use, so instances sharing a data directory agree.
+    pub fn new(mode: IpLogMode, key_path: &Path) -> Result<Self> {
+        let key = match mode {
+            IpLogMode::Hash => Self::load_or_create_key(key_path)?,
+            IpLogMode::Full | IpLogMode::Truncate => [0; 32],
+        };
+        Ok(Self { mode, key })
+    }
+
+    fn load_or_create_key(key_path: &Path) -> Result<[u8; 32]> {
+        match fs::read(key_path) {
+            Ok(bytes) => bytes
+                .try_into()
+                .map_err(|_| anyhow::anyhow!("IP privacy key file is not 32 bytes long")),
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                let key: [u8; 32] = rand::random();
+                fs::write(key_path, key).context("Failed to write IP privacy key file")?;
+                Ok(key)
+            }
+            Err(err) => Err(err).context("Failed to read IP privacy key file"),
+        }
+    }
+
+    /// Get the form of an IP address that may be written to logs.
+    pub fn redact(&self, ip: IpAddr) -> String {
+        match self.mode {
+            IpLogMode::Full => ip.to_string(),
+            IpLogMode::Truncate => Self::truncate(ip).to_string(),
+            IpLogMode::Hash => {
+                let period = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs()
+                    / SALT_ROTATION_SECS;
+                let salt = blake3::keyed_hash(&self.key, &period.to_le_bytes());
+                let hash = blake3::keyed_hash(salt.as_bytes(), ip.to_string().as_bytes());
+                hash.to_hex()[..HASHED_IP_LENGTH * 2].to_string()
+            }
+        }
+    }
+
+    fn truncate(ip: IpAddr) -> IpNet {
+        match ip {
+            IpAddr::V4(ip) => Ipv4Net::new(ip, TRUNCATED_IPV4_PREFIX)
+                .expect("prefix length is valid")
+                .trunc()
+                .into(),
+            IpAddr::V6(ip) => Ipv6Net::new(ip, TRUNCATED_IPV6_PREFIX)
+                .expect("prefix length is valid")
+                .trunc()
+                .into(),
+        }
+    }
+}
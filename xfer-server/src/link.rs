@@ -0,0 +1,142 @@
+use anyhow::{Context, Result};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chacha20poly1305::{KeyInit, XChaCha20Poly1305, XNonce, aead::Aead};
+use std::{
+    fmt::Debug,
+    fs::{self, OpenOptions},
+    io::{ErrorKind, Write},
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const NONCE_LENGTH: usize = 24;
+
+/// Issues and verifies time-limited download link tokens.
+///
+/// Each token is the transfer identifier and link expiry sealed with XChaCha20-Poly1305, so links
+/// can't be forged or extended and don't reveal the identifier of the transfer they point to.
+#[derive(Clone)]
+pub struct LinkSigner {
+    cipher: XChaCha20Poly1305,
+}
+
+impl Debug for LinkSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LinkSigner").finish_non_exhaustive()
+    }
+}
+
+impl LinkSigner {
+    /// Create a new [`LinkSigner`] from the given key.
+    pub fn new(key: &[u8; 32]) -> Self {
+        Self {
+            cipher: XChaCha20Poly1305::new(key.into()),
+        }
+    }
+
+    /// Create a new [`LinkSigner`] using the key stored at the given path, generating it on first use.
+    pub fn load_or_create(key_path: &Path) -> Result<Self> {
+        let key = match fs::read(key_path) {
+            Ok(bytes) => bytes
+                .try_into()
+                .map_err(|_| anyhow::anyhow!("Link signing key file is not 32 bytes long"))?,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                let key: [u8; 32] = rand::random();
+                // Anyone who can read the key can forge links, so only the server's user may read it.
+                let mut options = OpenOptions::new();
+                options.write(true).create_new(true);
+                #[cfg(unix)]
+                std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+                options
+                    .open(key_path)
+                    .and_then(|mut file| file.write_all(&key))
+                    .context("Failed to write link signing key file")?;
+                key
+            }
+            Err(err) => return Err(err).context("Failed to read link signing key file"),
+        };
+        Ok(Self::new(&key))
+    }
+
+    /// Create a token granting access to the given transfer until `expires_at`.
+    pub fn sign(&self, id: &str, expires_at: SystemTime) -> Result<String> {
+        let expires_at = expires_at
+            .duration_since(UNIX_EPOCH)
+            .context("Link expiry is before the unix epoch")?
+            .as_secs();
+        let nonce: [u8; NONCE_LENGTH] = rand::random();
+        let sealed = self
+            .cipher
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                [expires_at.to_le_bytes().as_slice(), id.as_bytes()]
+                    .concat()
+                    .as_slice(),
+            )
+            .map_err(|_| anyhow::anyhow!("Failed to seal link token"))?;
+        Ok(URL_SAFE_NO_PAD.encode([nonce.as_slice(), &sealed].concat()))
+    }
+
+    /// Get the transfer identifier a token grants access to, or `None` if it is invalid or has expired.
+    pub fn verify(&self, token: &str) -> Option<String> {
+        let bytes = URL_SAFE_NO_PAD.decode(token).ok()?;
+        if bytes.len() < NONCE_LENGTH {
+            return None;
+        }
+        let (nonce, sealed) = bytes.split_at(NONCE_LENGTH);
+        let payload = self
+            .cipher
+            .decrypt(XNonce::from_slice(nonce), sealed)
+            .ok()?;
+        let (expires_at, id) = payload.split_first_chunk::<8>()?;
+        if UNIX_EPOCH + Duration::from_secs(u64::from_le_bytes(*expires_at)) <= SystemTime::now() {
+            return None;
+        }
+        String::from_utf8(id.to_vec()).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signer() -> LinkSigner {
+        LinkSigner::new(&[7; 32])
+    }
+
+    #[test]
+    fn verifies_signed_tokens() {
+        let token = signer()
+            .sign("some-transfer", SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+        assert_eq!(signer().verify(&token).as_deref(), Some("some-transfer"));
+    }
+
+    #[test]
+    fn rejects_expired_tokens() {
+        let token = signer()
+            .sign("some-transfer", SystemTime::now() - Duration::from_secs(1))
+            .unwrap();
+        assert_eq!(signer().verify(&token), None);
+    }
+
+    #[test]
+    fn rejects_tokens_signed_with_another_key() {
+        let token = LinkSigner::new(&[8; 32])
+            .sign("some-transfer", SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+        assert_eq!(signer().verify(&token), None);
+    }
+
+    #[test]
+    fn rejects_tampered_tokens() {
+        let token = signer()
+            .sign("some-transfer", SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+        let mut bytes = URL_SAFE_NO_PAD.decode(&token).unwrap();
+        *bytes.last_mut().unwrap() ^= 1;
+        assert_eq!(signer().verify(&URL_SAFE_NO_PAD.encode(bytes)), None);
+        assert_eq!(signer().verify(&token[..NONCE_LENGTH]), None);
+        assert_eq!(signer().verify("not a token"), None);
+    }
+}
@@ -0,0 +1,136 @@
+use crate::API_VERSION;
+use anyhow::{Context, Result};
+use axum::{
+    Router,
+    extract::Request,
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::{io, net::SocketAddr};
+use tokio::net::TcpListener;
+
+/// Number of pending connections the kernel queues for each listener.
+const LISTEN_BACKLOG: i32 = 1024;
+
+/// Top-level route segments that belong to the [`ListenerScope::Admin`] scope.
+const ADMIN_ROUTE_SEGMENTS: [&str; 2] = ["admin", "metrics"];
+
+/// Which routes a listener serves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListenerScope {
+    /// Every route.
+    All,
+    /// Every route except the admin API and metrics.
+    Public,
+    /// Only the admin API and metrics.
+    Admin,
+}
+
+/// An address the server listens on and the routes served there.
+#[derive(Debug, Clone, Copy)]
+pub struct ListenAddress {
+    pub address: SocketAddr,
+    pub scope: ListenerScope,
+}
+
+/// Parse a listen address in the form `<socket address>[=<all|public|admin>]`.
+pub fn parse_listen_address(value: &str) -> Result<ListenAddress, String> {
+    let (address, scope) = match value.rsplit_once('=') {
+        Some((address, scope)) => (address, scope),
+        None => (value, "all"),
+    };
+    let address = address
+        .trim()
+        .parse::<SocketAddr>()
+        .map_err(|_| format!("'{address}' is not a valid socket address"))?;
+    let scope = parse_listener_scope(scope)?;
+    Ok(ListenAddress { address, scope })
+}
+
+/// Parse a listener scope of `all`, `public` or `admin`.
+pub fn parse_listener_scope(value: &str) -> Result<ListenerScope, String> {
+    match value.trim() {
+        "all" => Ok(ListenerScope::All),
+        "public" => Ok(ListenerScope::Public),
+        "admin" => Ok(ListenerScope::Admin),
+        scope => Err(format!(
+            "'{scope}' is not a valid listener scope, expected one of: all, public, admin"
+        )),
+    }
+}
+
+/// Bind a listener for each of the given addresses.
+pub fn bind(addresses: &[ListenAddress]) -> Result<Vec<(TcpListener, ListenerScope)>> {
+    addresses
+        .iter()
+        .map(|listen| {
+            let listener = bind_address(listen.address, addresses)
+                .with_context(|| format!("Failed to listen on {}", listen.address))?;
+            Ok((listener, listen.scope))
+        })
+        .collect()
+}
+
+/// Bind a single listener.
+///
+/// IPv6 listeners sharing a port with an IPv4 listener only accept IPv6 connections,
+/// so both the IPv4 and IPv6 wildcard addresses can be bound together.
+fn bind_address(address: SocketAddr, addresses: &[ListenAddress]) -> io::Result<TcpListener> {
+    let socket = Socket::new(
+        Domain::for_address(address),
+        Type::STREAM,
+        Some(Protocol::TCP),
+    )?;
+    if address.is_ipv6()
+        && addresses
+            .iter()
+            .any(|other| other.address.is_ipv4() && other.address.port() == address.port())
+    {
+        socket.set_only_v6(true)?;
+    }
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+    socket.bind(&address.into())?;
+    socket.listen(LISTEN_BACKLOG)?;
+    socket.set_nonblocking(true)?;
+    TcpListener::from_std(socket.into())
+}
+
+/// Whether a request path, relative to the base path, belongs to the admin scope.
+fn is_admin_path(path: &str) -> bool {
+    let mut segments = path.trim_start_matches('/').split('/');
+    let mut first = segments.next().unwrap_or_default();
+    if first == format!("v{API_VERSION}") {
+        first = segments.next().unwrap_or_default();
+    }
+    ADMIN_ROUTE_SEGMENTS.contains(&first)
+}
+
+/// Hide the routes outside of a scope from a router.
+pub fn with_scope(router: Router, scope: ListenerScope, base_path: &str) -> Router {
+    let base_path = base_path.to_string();
+    router.layer(axum::middleware::from_fn(
+        move |req: Request, next: Next| {
+            let base_path = base_path.clone();
+            async move { scope_middleware(scope, &base_path, req, next).await }
+        },
+    ))
+}
+
+/// Middleware that hides routes outside of a listener's scope.
+async fn scope_middleware(
+    scope: ListenerScope,
+    base_path: &str,
+    req: Request,
+    next: Next,
+) -> Response {
+    let path = req.uri().path();
+    let is_admin = is_admin_path(path.strip_prefix(base_path).unwrap_or(path));
+    match scope {
+        ListenerScope::Public if is_admin => StatusCode::NOT_FOUND.into_response(),
+        ListenerScope::Admin if !is_admin => StatusCode::NOT_FOUND.into_response(),
+        _ => next.run(req).await,
+    }
+}
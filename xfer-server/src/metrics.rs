@@ -0,0 +1,252 @@
+use crate::storage::{IntegrityStatistics, SweepStatistics};
+use serde::Serialize;
+use std::{
+    collections::VecDeque,
+    fmt::Write,
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Period over which recent upload and download traffic is totalled.
+const TRAFFIC_WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+/// Width of each bucket that traffic is grouped into within the window.
+const TRAFFIC_BUCKET_WIDTH: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+struct TrafficBucket {
+    started_at: u64,
+    uploaded_bytes: u64,
+    downloaded_bytes: u64,
+}
+
+/// Totals of the bytes transferred within the traffic window.
+#[derive(Debug, Serialize)]
+pub struct TrafficSummary {
+    pub uploaded_bytes: u64,
+    pub downloaded_bytes: u64,
+}
+
+/// Summary of expiry sweeps since the server started.
+#[derive(Debug, Serialize)]
+pub struct SweepSummary {
+    pub total: u64,
+    pub failed: u64,
+    pub transfers_removed: u64,
+    pub last_duration_ms: u64,
+    pub last_run_timestamp_seconds: u64,
+}
+
+/// Result of the most recent integrity verification run.
+#[derive(Debug, Clone, Serialize)]
+pub struct IntegrityReport {
+    #[serde(flatten)]
+    pub stats: IntegrityStatistics,
+    pub duration_ms: u64,
+    pub run_timestamp_seconds: u64,
+}
+
+/// In-memory counters exposed in the Prometheus text format.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    sweeps_total: AtomicU64,
+    sweeps_failed_total: AtomicU64,
+    sweep_transfers_scanned_total: AtomicU64,
+    sweep_transfers_removed_total: AtomicU64,
+    sweep_transfers_failed_total: AtomicU64,
+    sweep_last_duration_ms: AtomicU64,
+    sweep_last_run_timestamp_seconds: AtomicU64,
+    integrity_checks_total: AtomicU64,
+    integrity_transfers_verified_total: AtomicU64,
+    integrity_transfers_corrupted_total: AtomicU64,
+    integrity_last_report: Mutex<Option<IntegrityReport>>,
+    traffic: Mutex<VecDeque<TrafficBucket>>,
+}
+
+impl Metrics {
+    /// Record the result of a sweep that ran to completion.
+    pub fn record_sweep(&self, stats: &SweepStatistics, duration: Duration) {
+        self.sweeps_total.fetch_add(1, Ordering::Relaxed);
+        self.sweep_transfers_scanned_total
+            .fetch_add(stats.scanned, Ordering::Relaxed);
+        self.sweep_transfers_removed_total
+            .fetch_add(stats.removed.len() as u64, Ordering::Relaxed);
+        self.sweep_transfers_failed_total
+            .fetch_add(stats.failed, Ordering::Relaxed);
+        self.record_sweep_run(duration);
+    }
+
+    /// Record a sweep that failed before it could check any transfers.
+    pub fn record_failed_sweep(&self, duration: Duration) {
+        self.sweeps_total.fetch_add(1, Ordering::Relaxed);
+        self.sweeps_failed_total.fetch_add(1, Ordering::Relaxed);
+        self.record_sweep_run(duration);
+    }
+
+    fn record_sweep_run(&self, duration: Duration) {
+        self.sweep_last_duration_ms
+            .store(duration.as_millis() as u64, Ordering::Relaxed);
+        self.sweep_last_run_timestamp_seconds
+            .store(unix_now(), Ordering::Relaxed);
+    }
+
+    /// Record the result of an integrity verification run.
+    pub fn record_integrity_check(&self, stats: &IntegrityStatistics, duration: Duration) {
+        self.integrity_checks_total.fetch_add(1, Ordering::Relaxed);
+        self.integrity_transfers_verified_total
+            .fetch_add(stats.verified, Ordering::Relaxed);
+        self.integrity_transfers_corrupted_total
+            .fetch_add(stats.corrupted.len() as u64, Ordering::Relaxed);
+        *self.integrity_last_report.lock().unwrap() = Some(IntegrityReport {
+            stats: stats.clone(),
+            duration_ms: duration.as_millis() as u64,
+            run_timestamp_seconds: unix_now(),
+        });
+    }
+
+    /// Get the result of the most recent integrity verification run, if one has completed.
+    pub fn integrity_report(&self) -> Option<IntegrityReport> {
+        self.integrity_last_report.lock().unwrap().clone()
+    }
+
+    /// Record bytes received for a new transfer.
+    pub fn record_upload(&self, bytes: u64) {
+        self.record_traffic(bytes, 0);
+    }
+
+    /// Record bytes sent for a transfer download.
+    pub fn record_download(&self, bytes: u64) {
+        self.record_traffic(0, bytes);
+    }
+
+    fn record_traffic(&self, uploaded_bytes: u64, downloaded_bytes: u64) {
+        let bucket_start =
+            unix_now() / TRAFFIC_BUCKET_WIDTH.as_secs() * TRAFFIC_BUCKET_WIDTH.as_secs();
+        let mut traffic = self.traffic.lock().unwrap();
+        match traffic.back_mut() {
+            Some(bucket) if bucket.started_at == bucket_start => {
+                bucket.uploaded_bytes += uploaded_bytes;
+                bucket.downloaded_bytes += downloaded_bytes;
+            }
+            _ => traffic.push_back(TrafficBucket {
+                started_at: bucket_start,
+                uploaded_bytes,
+                downloaded_bytes,
+            }),
+        }
+        let window_start = bucket_start.saturating_sub(TRAFFIC_WINDOW.as_secs());
+        while traffic
+            .front()
+            .is_some_and(|bucket| bucket.started_at < window_start)
+        {
+            traffic.pop_front();
+        }
+    }
+
+    /// Get the number of bytes uploaded and downloaded within the traffic window.
+    pub fn recent_traffic(&self) -> TrafficSummary {
+        let window_start = unix_now().saturating_sub(TRAFFIC_WINDOW.as_secs());
+        let traffic = self.traffic.lock().unwrap();
+        let recent = traffic
+            .iter()
+            .filter(|bucket| bucket.started_at >= window_start);
+        TrafficSummary {
+            uploaded_bytes: recent.clone().map(|bucket| bucket.uploaded_bytes).sum(),
+            downloaded_bytes: recent.map(|bucket| bucket.downloaded_bytes).sum(),
+        }
+    }
+
+    /// Get a summary of the expiry sweeps that have run.
+    pub fn sweep_summary(&self) -> SweepSummary {
+        SweepSummary {
+            total: self.sweeps_total.load(Ordering::Relaxed),
+            failed: self.sweeps_failed_total.load(Ordering::Relaxed),
+            transfers_removed: self.sweep_transfers_removed_total.load(Ordering::Relaxed),
+            last_duration_ms: self.sweep_last_duration_ms.load(Ordering::Relaxed),
+            last_run_timestamp_seconds: self
+                .sweep_last_run_timestamp_seconds
+                .load(Ordering::Relaxed),
+        }
+    }
+
+    /// Render all metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for (name, kind, help, value) in [
+            (
+                "xfer_sweeps_total",
+                "counter",
+                "Number of expiry sweeps that have run.",
+                &self.sweeps_total,
+            ),
+            (
+                "xfer_sweeps_failed_total",
+                "counter",
+                "Number of expiry sweeps that failed to run.",
+                &self.sweeps_failed_total,
+            ),
+            (
+                "xfer_sweep_transfers_scanned_total",
+                "counter",
+                "Number of transfers checked for expiry.",
+                &self.sweep_transfers_scanned_total,
+            ),
+            (
+                "xfer_sweep_transfers_removed_total",
+                "counter",
+                "Number of expired transfers removed.",
+                &self.sweep_transfers_removed_total,
+            ),
+            (
+                "xfer_sweep_transfers_failed_total",
+                "counter",
+                "Number of transfers that failed to be checked or removed.",
+                &self.sweep_transfers_failed_total,
+            ),
+            (
+                "xfer_sweep_last_duration_milliseconds",
+                "gauge",
+                "Duration of the most recent expiry sweep.",
+                &self.sweep_last_duration_ms,
+            ),
+            (
+                "xfer_sweep_last_run_timestamp_seconds",
+                "gauge",
+                "Unix timestamp of the most recent expiry sweep.",
+                &self.sweep_last_run_timestamp_seconds,
+            ),
+            (
+                "xfer_integrity_checks_total",
+                "counter",
+                "Number of integrity verification runs that have completed.",
+                &self.integrity_checks_total,
+            ),
+            (
+                "xfer_integrity_transfers_verified_total",
+                "counter",
+                "Number of transfers re-hashed during integrity verification.",
+                &self.integrity_transfers_verified_total,
+            ),
+            (
+                "xfer_integrity_transfers_corrupted_total",
+                "counter",
+                "Number of transfers found to be corrupted and quarantined.",
+                &self.integrity_transfers_corrupted_total,
+            ),
+        ] {
+            let _ = writeln!(out, "# HELP {name} {help}");
+            let _ = writeln!(out, "# TYPE {name} {kind}");
+            let _ = writeln!(out, "{name} {}", value.load(Ordering::Relaxed));
+        }
+        out
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,163 @@
+use crate::storage::TransferStorage;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::{
+    collections::HashSet,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    sync::{Mutex, RwLock},
+    time::SystemTime,
+};
+use tracing::{debug, info, warn};
+
+const BLOCKLIST_CHECKSUM_PREFIX: &str = "blake3:";
+
+#[derive(Debug, Default)]
+struct BlocklistEntries {
+    ids: HashSet<String>,
+    checksums: HashSet<String>,
+    modified: Option<SystemTime>,
+}
+
+/// Operator-managed list of transfer identifiers and content hashes that must not be stored or served.
+///
+/// The blocklist file contains one entry per line: either a transfer identifier or a
+/// `blake3:<hex digest>` content hash. Empty lines and lines starting with `#` are ignored.
+#[derive(Debug)]
+pub struct Blocklist {
+    path: Option<PathBuf>,
+    entries: RwLock<BlocklistEntries>,
+}
+
+impl Blocklist {
+    /// Create a new [`Blocklist`] loaded from the given file, or an empty one when no file is given.
+    pub fn new(path: Option<PathBuf>) -> Result<Self> {
+        let blocklist = Self {
+            path,
+            entries: RwLock::default(),
+        };
+        blocklist.reload()?;
+        Ok(blocklist)
+    }
+
+    /// Reload the blocklist from disk if the file has changed since it was last read.
+    pub fn reload(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let modified = fs::metadata(path)
+            .and_then(|m| m.modified())
+            .context("Failed to read blocklist file metadata")?;
+        if self.entries.read().unwrap().modified == Some(modified) {
+            return Ok(());
+        }
+
+        let mut entries = BlocklistEntries {
+            modified: Some(modified),
+            ..Default::default()
+        };
+        for line in fs::read_to_string(path)
+            .context("Failed to read blocklist file")?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        {
+            match line.strip_prefix(BLOCKLIST_CHECKSUM_PREFIX) {
+                Some(checksum) => entries.checksums.insert(checksum.to_lowercase()),
+                None => entries.ids.insert(line.to_string()),
+            };
+        }
+        info!(
+            "Loaded blocklist ({} identifiers, {} checksums)",
+            entries.ids.len(),
+            entries.checksums.len()
+        );
+        *self.entries.write().unwrap() = entries;
+        Ok(())
+    }
+
+    /// Whether a transfer is blocked by its identifier or content checksum.
+    pub fn is_blocked(&self, id: &str, checksum: Option<&str>) -> bool {
+        let entries = self.entries.read().unwrap();
+        entries.ids.contains(id) || checksum.is_some_and(|c| entries.checksums.contains(c))
+    }
+
+    /// Whether the stored transfer with the given identifier is blocked.
+    pub fn is_transfer_blocked(&self, storage: &TransferStorage, id: &str) -> bool {
+        let checksum = storage
+            .state_store()
+            .get_metadata(id)
+            .ok()
+            .flatten()
+            .and_then(|metadata| metadata.checksum);
+        self.is_blocked(id, checksum.as_deref())
+    }
+
+    /// Remove every stored transfer that is blocked, returning the identifiers of those removed.
+    pub fn remove_blocked_transfers(&self, storage: &TransferStorage) -> Result<Vec<String>> {
+        {
+            let entries = self.entries.read().unwrap();
+            if entries.ids.is_empty() && entries.checksums.is_empty() {
+                return Ok(Vec::new());
+            }
+        }
+        let mut removed = Vec::new();
+        for id in storage.list_transfers()? {
+            if !self.is_transfer_blocked(storage, &id) {
+                continue;
+            }
+            info!("Removing blocked transfer (id: '{id}')");
+            match storage.delete_transfer(&id) {
+                Ok(()) => removed.push(id),
+                Err(err) => warn!("Failed to remove blocked transfer (id: '{id}'): {err:?}"),
+            }
+        }
+        Ok(removed)
+    }
+}
+
+#[derive(Serialize)]
+struct AbuseReport<'a> {
+    id: &'a str,
+    checksum: Option<&'a str>,
+    reason: &'a str,
+    reported_at: SystemTime,
+}
+
+/// Append-only log of abuse reports submitted against transfers, stored as JSON lines.
+#[derive(Debug)]
+pub struct ReportLog {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl ReportLog {
+    /// Create a new [`ReportLog`] that appends to the given file.
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            lock: Mutex::default(),
+        }
+    }
+
+    /// Record an abuse report for a transfer.
+    pub fn record(&self, id: &str, checksum: Option<&str>, reason: &str) -> Result<()> {
+        let mut line = serde_json::to_vec(&AbuseReport {
+            id,
+            checksum,
+            reason,
+            reported_at: SystemTime::now(),
+        })?;
+        line.push(b'\n');
+        let _guard = self.lock.lock().unwrap();
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(&line))
+            .context("Failed to write abuse report")?;
+        debug!("Recorded abuse report for transfer (id: '{id}')");
+        Ok(())
+    }
+}
@@ -0,0 +1,266 @@
+use crate::{AppState, error::ApiError, upload_token::UploadScope};
+use anyhow::{Context, Result};
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::ErrorKind,
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tracing::{debug, warn};
+
+/// Header clients use to send a solved challenge, in the form `<challenge>:<nonce>`.
+pub const PROOF_OF_WORK_HEADER: &str = "X-Xfer-Proof-Of-Work";
+
+/// How long an issued challenge can be solved and redeemed for.
+const CHALLENGE_EXPIRE_AFTER: Duration = Duration::from_secs(10 * 60);
+
+/// Number of bytes of the keyed hash kept to authenticate a challenge.
+const CHALLENGE_MAC_LENGTH: usize = 16;
+
+/// A challenge that must be solved before uploading.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProofOfWorkChallenge {
+    /// Opaque challenge string issued by the server.
+    pub challenge: String,
+    /// Number of leading zero bits the BLAKE3 hash of the challenge and nonce must have.
+    pub difficulty: u8,
+}
+
+/// Issues and verifies proof-of-work challenges for anonymous uploads.
+///
+/// A challenge is solved by finding a nonce for which `blake3(challenge || nonce)`, with the nonce as
+/// a little-endian `u64`, starts with at least `difficulty` zero bits. Challenges are authenticated
+/// with a keyed hash so they don't need to be stored, and can only be redeemed once.
+#[derive(Debug)]
+pub struct ProofOfWork {
+    difficulty: u8,
+    key: [u8; 32],
+}
+
+impl ProofOfWork {
+    /// Create a new [`ProofOfWork`] requiring the given difficulty, using the key stored at the
+    /// given path (generating it on first use) to authenticate challenges.
+    ///
+    /// A difficulty of 0 disables proof of work.
+    pub fn new(difficulty: u8, key_path: &Path) -> Result<Self> {
+        if difficulty == 0 {
+            return Ok(Self {
+                difficulty,
+                key: [0; 32],
+            });
+        }
+        let key = match fs::read(key_path) {
+            Ok(bytes) => bytes
+                .try_into()
+                .map_err(|_| anyhow::anyhow!("Proof of work key file is not 32 bytes long"))?,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                let key: [u8; 32] = rand::random();
+                fs::write(key_path, key).context("Failed to write proof of work key file")?;
+                key
+            }
+            Err(err) => return Err(err).context("Failed to read proof of work key file"),
+        };
+        Ok(Self { difficulty, key })
+    }
+
+    /// Whether uploads without an upload token must include a solved challenge.
+    pub fn is_enabled(&self) -> bool {
+        self.difficulty > 0
+    }
+
+    /// Issue a new challenge, or `None` when proof of work is disabled.
+    pub fn issue(&self) -> Option<ProofOfWorkChallenge> {
+        if !self.is_enabled() {
+            return None;
+        }
+        let expires_at = (SystemTime::now() + CHALLENGE_EXPIRE_AFTER)
+            .duration_since(UNIX_EPOCH)
+            .ok()?
+            .as_secs();
+        let payload = format!(
+            "{expires_at}.{}",
+            blake3::Hash::from_bytes(rand::random()).to_hex()
+        );
+        Some(ProofOfWorkChallenge {
+            challenge: format!("{payload}.{}", self.mac(&payload)),
+            difficulty: self.difficulty,
+        })
+    }
+
+    /// Check that a solution is for an authentic, unexpired challenge and meets the difficulty,
+    /// returning the solved challenge.
+    pub fn verify<'a>(&self, solution: &'a str) -> Option<&'a str> {
+        let (challenge, nonce) = solution.rsplit_once(':')?;
+        let nonce = nonce.parse::<u64>().ok()?;
+        let (payload, mac) = challenge.rsplit_once('.')?;
+        // Compare hashes so the comparison runs in constant time.
+        if blake3::hash(mac.as_bytes()) != blake3::hash(self.mac(payload).as_bytes()) {
+            return None;
+        }
+        let (expires_at, _) = payload.split_once('.')?;
+        if UNIX_EPOCH + Duration::from_secs(expires_at.parse().ok()?) <= SystemTime::now() {
+            return None;
+        }
+        let hash = blake3::Hasher::new()
+            .update(challenge.as_bytes())
+            .update(&nonce.to_le_bytes())
+            .finalize();
+        (leading_zero_bits(hash.as_bytes()) >= u32::from(self.difficulty)).then_some(challenge)
+    }
+
+    /// Compute the hex-encoded authentication code for a challenge payload.
+    fn mac(&self, payload: &str) -> String {
+        blake3::keyed_hash(&self.key, payload.as_bytes()).to_hex()[..CHALLENGE_MAC_LENGTH * 2]
+            .to_string()
+    }
+}
+
+/// Count the number of leading zero bits in a byte string.
+fn leading_zero_bits(bytes: &[u8]) -> u32 {
+    let mut count = 0;
+    for byte in bytes {
+        count += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    count
+}
+
+/// Middleware that requires uploads made without an upload token to include a solved proof-of-work challenge.
+pub async fn upload_proof_of_work_middleware(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if !state.proof_of_work.is_enabled() || req.extensions().get::<UploadScope>().is_some() {
+        return next.run(req).await;
+    }
+    let Some(challenge) = req
+        .headers()
+        .get(PROOF_OF_WORK_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| state.proof_of_work.verify(v))
+    else {
+        debug!("Rejecting upload with a missing or invalid proof of work");
+        return ApiError::new(
+            StatusCode::FORBIDDEN,
+            "a valid proof of work is required to upload to this server, fetch a challenge from the configuration endpoint",
+        )
+        .into_response();
+    };
+    // Record the challenge as redeemed for as long as it could otherwise be replayed.
+    match state
+        .transfer_storage
+        .state_store()
+        .rate_limit_hit(&format!("pow:{challenge}"), CHALLENGE_EXPIRE_AFTER)
+    {
+        Ok((1, _)) => next.run(req).await,
+        Ok(_) => {
+            debug!("Rejecting upload with a proof of work that has already been used");
+            ApiError::new(
+                StatusCode::FORBIDDEN,
+                "this proof of work has already been used, solve a new challenge",
+            )
+            .into_response()
+        }
+        Err(err) => {
+            // Fail open so a state store outage doesn't take down uploads entirely.
+            warn!("Failed to record proof of work redemption: {err:?}");
+            next.run(req).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proof_of_work(key: u8) -> ProofOfWork {
+        ProofOfWork {
+            difficulty: 8,
+            key: [key; 32],
+        }
+    }
+
+    /// Find the first nonce for which the challenge hash does or doesn't meet the difficulty.
+    fn solve(challenge: &ProofOfWorkChallenge, meets_difficulty: bool) -> String {
+        let nonce = (0u64..)
+            .find(|nonce| {
+                let hash = blake3::Hasher::new()
+                    .update(challenge.challenge.as_bytes())
+                    .update(&nonce.to_le_bytes())
+                    .finalize();
+                (leading_zero_bits(hash.as_bytes()) >= u32::from(challenge.difficulty))
+                    == meets_difficulty
+            })
+            .unwrap();
+        format!("{}:{nonce}", challenge.challenge)
+    }
+
+    #[test]
+    fn accepts_solved_challenges() {
+        let challenge = proof_of_work(1).issue().unwrap();
+        let solution = solve(&challenge, true);
+        assert_eq!(
+            proof_of_work(1).verify(&solution),
+            Some(challenge.challenge.as_str())
+        );
+    }
+
+    #[test]
+    fn rejects_unsolved_challenges() {
+        let challenge = proof_of_work(1).issue().unwrap();
+        assert_eq!(proof_of_work(1).verify(&solve(&challenge, false)), None);
+    }
+
+    #[test]
+    fn rejects_challenges_issued_with_another_key() {
+        let challenge = proof_of_work(2).issue().unwrap();
+        assert_eq!(proof_of_work(1).verify(&solve(&challenge, true)), None);
+    }
+
+    #[test]
+    fn rejects_expired_challenges() {
+        let proof_of_work = proof_of_work(1);
+        let payload = format!("{}.{}", 0, "0".repeat(64));
+        let challenge = ProofOfWorkChallenge {
+            challenge: format!("{payload}.{}", proof_of_work.mac(&payload)),
+            difficulty: proof_of_work.difficulty,
+        };
+        assert_eq!(proof_of_work.verify(&solve(&challenge, true)), None);
+    }
+
+    #[test]
+    fn rejects_malformed_solutions() {
+        let proof_of_work = proof_of_work(1);
+        assert_eq!(proof_of_work.verify(""), None);
+        assert_eq!(proof_of_work.verify("challenge"), None);
+        assert_eq!(proof_of_work.verify("challenge:nonce"), None);
+    }
+
+    #[test]
+    fn counts_leading_zero_bits() {
+        assert_eq!(leading_zero_bits(&[0xff, 0]), 0);
+        assert_eq!(leading_zero_bits(&[0x01, 0]), 7);
+        assert_eq!(leading_zero_bits(&[0, 0x10]), 11);
+        assert_eq!(leading_zero_bits(&[0, 0]), 16);
+    }
+
+    #[test]
+    fn issues_nothing_when_disabled() {
+        let proof_of_work = ProofOfWork {
+            difficulty: 0,
+            key: [0; 32],
+        };
+        assert!(!proof_of_work.is_enabled());
+        assert!(proof_of_work.issue().is_none());
+    }
+}
@@ -0,0 +1,228 @@
+use crate::{AppState, client_ip::ClientIp, error::ApiError, upload_token::UploadScope};
+use axum::{
+    extract::{Request, State},
+    http::{StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use rand::Rng;
+use std::{net::IpAddr, time::Duration};
+use tracing::{debug, warn};
+
+/// How long clients are asked to wait before retrying when the server is handling too many uploads.
+const CONCURRENT_UPLOADS_RETRY_AFTER: Duration = Duration::from_secs(30);
+
+/// Range of the random delay, in milliseconds, added to responses for transfer lookups.
+const DOWNLOAD_MISS_DELAY_MS: std::ops::RangeInclusive<u64> = 50..=250;
+
+/// Extra delay added to responses for transfer lookups for each miss already made within the window.
+const DOWNLOAD_MISS_DELAY_STEP: Duration = Duration::from_millis(100);
+
+/// The longest a response for a transfer lookup is delayed for.
+const DOWNLOAD_MISS_MAX_DELAY: Duration = Duration::from_secs(5);
+
+/// Middleware that limits how many transfers a single client can create within the configured window.
+///
+/// Uploads made with an upload token are limited per token, using the token's own limits when it has them.
+pub async fn upload_rate_limit_middleware(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    req: Request,
+    next: Next,
+) -> Response {
+    let (key, limit, window) = match req.extensions().get::<UploadScope>() {
+        Some(UploadScope(token)) => (
+            format!("upload-token:{}", token.name),
+            token.policy.rate_limit.unwrap_or(state.upload_rate_limit),
+            token
+                .policy
+                .rate_limit_window_secs
+                .map_or(state.upload_rate_limit_window, Duration::from_secs),
+        ),
+        None => (
+            format!("upload:{}", client_ip),
+            state.upload_rate_limit,
+            state.upload_rate_limit_window,
+        ),
+    };
+    if limit == 0 {
+        return next.run(req).await;
+    }
+
+    match state
+        .transfer_storage
+        .state_store()
+        .rate_limit_hit(&key, window)
+    {
+        Ok((hits, _)) if hits <= limit => next.run(req).await,
+        Ok((_, reset_after)) => {
+            debug!(
+                "Client {} exceeded the upload rate limit",
+                state.ip_privacy.redact(client_ip)
+            );
+            (
+                [(
+                    header::RETRY_AFTER,
+                    reset_after.as_secs().max(1).to_string(),
+                )],
+                ApiError::new(
+                    StatusCode::TOO_MANY_REQUESTS,
+                    "too many transfers created recently, try again later",
+                ),
+            )
+                .into_response()
+        }
+        Err(err) => {
+            // Fail open so a state store outage doesn't take down uploads entirely.
+            warn!("Failed to check upload rate limit: {err:?}");
+            next.run(req).await
+        }
+    }
+}
+
+/// Middleware that limits how many uploads can be processed at once.
+///
+/// Uploads beyond the limit are rejected immediately rather than queued so that a
+/// burst of large uploads can't exhaust file handles or IO.
+pub async fn upload_concurrency_middleware(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(semaphore) = &state.upload_semaphore else {
+        return next.run(req).await;
+    };
+    match semaphore.clone().try_acquire_owned() {
+        Ok(_permit) => next.run(req).await,
+        Err(_) => {
+            debug!("Rejecting upload as the concurrent upload limit has been reached");
+            (
+                [(
+                    header::RETRY_AFTER,
+                    CONCURRENT_UPLOADS_RETRY_AFTER.as_secs().to_string(),
+                )],
+                ApiError::new(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "server is handling too many uploads right now, try again shortly",
+                ),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Middleware that makes guessing transfer identifiers slow and noisy.
+///
+/// Responses are delayed by a small random amount whether or not the transfer exists, so the two can't
+/// be told apart by timing, growing with each miss the client has already made within the configured
+/// window. Clients that reach the limit are banned from looking up transfers, for the length of the
+/// window at first and twice as long for each repeat offence, up to the configured maximum ban.
+pub async fn download_miss_middleware(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    req: Request,
+    next: Next,
+) -> Response {
+    let key = format!("download-miss:{client_ip}");
+    let strikes_key = format!("download-miss-strikes:{client_ip}");
+    let store = state.transfer_storage.state_store();
+    if state.download_miss_limit > 0 {
+        let ban = store
+            .rate_limit_count(&strikes_key, state.download_miss_max_ban)
+            .and_then(|(strikes, _)| match strikes {
+                0 => Ok(None),
+                strikes => store
+                    .rate_limit_count(
+                        &format!("download-miss-ban:{client_ip}:{strikes}"),
+                        download_miss_ban(&state, strikes),
+                    )
+                    .map(|(hits, reset_after)| (hits > 0).then_some(reset_after)),
+            });
+        match ban {
+            Ok(Some(reset_after)) => {
+                debug!(
+                    "Client {} is banned for exceeding the download miss limit",
+                    state.ip_privacy.redact(client_ip)
+                );
+                return (
+                    [(
+                        header::RETRY_AFTER,
+                        reset_after.as_secs().max(1).to_string(),
+                    )],
+                    ApiError::new(
+                        StatusCode::TOO_MANY_REQUESTS,
+                        "too many requests for transfers that don't exist, try again later",
+                    ),
+                )
+                    .into_response();
+            }
+            Ok(None) => {}
+            // Fail open so a state store outage doesn't take down downloads entirely.
+            Err(err) => warn!("Failed to check download miss ban: {err:?}"),
+        }
+    }
+
+    let response = next.run(req).await;
+    // Transfers that exist are delayed just like ones that don't, so the outcome of a lookup can't
+    // be told apart by timing. Only misses count towards the limit.
+    let mut previous_misses = 0;
+    if state.download_miss_limit > 0 {
+        if response.status() == StatusCode::NOT_FOUND {
+            match store.rate_limit_hit(&key, state.download_miss_window) {
+                Ok((misses, _)) => {
+                    previous_misses = misses - 1;
+                    // Bans start when the limit is reached, so a scan is only logged once per ban.
+                    if misses == state.download_miss_limit {
+                        ban_download_misses(&state, client_ip, misses);
+                    }
+                }
+                Err(err) => warn!("Failed to record download miss: {err:?}"),
+            }
+        } else {
+            match store.rate_limit_count(&key, state.download_miss_window) {
+                Ok((misses, _)) => previous_misses = misses,
+                Err(err) => warn!("Failed to check download misses: {err:?}"),
+            }
+        }
+    }
+    let delay = Duration::from_millis(rand::rng().random_range(DOWNLOAD_MISS_DELAY_MS))
+        + DOWNLOAD_MISS_DELAY_STEP
+            .saturating_mul(u32::try_from(previous_misses).unwrap_or(u32::MAX));
+    tokio::time::sleep(delay.min(DOWNLOAD_MISS_MAX_DELAY)).await;
+    response
+}
+
+/// How long a client is banned for on its given strike: the miss window, doubled for each previous strike.
+fn download_miss_ban(state: &AppState, strikes: u64) -> Duration {
+    let factor = 1u32
+        .checked_shl(u32::try_from(strikes - 1).unwrap_or(u32::MAX))
+        .unwrap_or(u32::MAX);
+    state
+        .download_miss_window
+        .saturating_mul(factor)
+        .min(state.download_miss_max_ban)
+}
+
+/// Ban a client that reached the download miss limit from looking up transfers.
+fn ban_download_misses(state: &AppState, client_ip: IpAddr, misses: u64) {
+    let store = state.transfer_storage.state_store();
+    let result = store
+        .rate_limit_hit(
+            &format!("download-miss-strikes:{client_ip}"),
+            state.download_miss_max_ban,
+        )
+        .and_then(|(strikes, _)| {
+            let ban = download_miss_ban(state, strikes);
+            store
+                .rate_limit_hit(&format!("download-miss-ban:{client_ip}:{strikes}"), ban)
+                .map(|_| (strikes, ban))
+        });
+    match result {
+        Ok((strikes, ban)) => warn!(
+            "Possible transfer enumeration attempt from {}: {misses} requests for transfers that don't exist within {:?}, banned for {ban:?} (strike {strikes})",
+            state.ip_privacy.redact(client_ip),
+            state.download_miss_window
+        ),
+        Err(err) => warn!("Failed to ban client for download misses: {err:?}"),
+    }
+}
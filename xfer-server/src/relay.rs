@@ -0,0 +1,185 @@
+use axum::body::Bytes;
+use futures_util::Stream;
+use std::{
+    collections::HashMap,
+    io,
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+use tokio::sync::mpsc;
+
+/// How long both sides of a relay have to connect, and how long either side may stall for once connected.
+pub const RELAY_WAIT_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Number of chunks buffered between the uploader and downloader of a relay.
+const RELAY_BUFFER_CHUNKS: usize = 16;
+
+/// A message passed from the uploader of a relay to its downloader.
+#[derive(Debug)]
+pub enum RelayMessage {
+    Data(Bytes),
+    /// Sent once all data has been passed on, so the downloader can tell a complete relay from a dropped one.
+    End,
+}
+
+/// A relay opened with [`Relay::open`].
+#[derive(Debug)]
+pub struct RelayReservation {
+    /// Secret that must be given to [`Relay::connect_uploader`].
+    pub upload_token: String,
+    /// The time the relay lapses if both sides haven't connected.
+    pub expires_at: SystemTime,
+}
+
+#[derive(Debug)]
+struct RelaySlot {
+    size: u64,
+    upload_token_hash: blake3::Hash,
+    expires_at: SystemTime,
+    sender: Option<mpsc::Sender<RelayMessage>>,
+    receiver: Option<mpsc::Receiver<RelayMessage>>,
+}
+
+/// Pipes transfers directly from an uploader to a downloader without persisting them,
+/// so transfers larger than the server's storage can still be sent.
+///
+/// Relays are held in memory and are only reachable through the instance they were opened on.
+#[derive(Debug)]
+pub struct Relay {
+    enabled: bool,
+    max_size: Option<u64>,
+    slots: Mutex<HashMap<String, RelaySlot>>,
+}
+
+impl Relay {
+    /// Create a new [`Relay`] accepting transfers of at most `max_size` bytes, or any size when unset.
+    pub fn new(enabled: bool, max_size: Option<u64>) -> Self {
+        Self {
+            enabled,
+            max_size,
+            slots: Mutex::default(),
+        }
+    }
+
+    /// Whether relays can be opened.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// The maximum size of a relayed transfer, in bytes, if limited.
+    pub fn max_size(&self) -> Option<u64> {
+        self.max_size
+    }
+
+    /// Open a relay for a transfer of exactly `size` bytes under the given identifier,
+    /// returning `None` if a relay with the identifier is already open.
+    pub fn open(&self, id: String, size: u64) -> Option<RelayReservation> {
+        let mut slots = self.slots.lock().unwrap();
+        Self::remove_lapsed(&mut slots);
+        if slots.contains_key(&id) {
+            return None;
+        }
+        let upload_token = blake3::Hash::from_bytes(rand::random())
+            .to_hex()
+            .to_string();
+        let expires_at = SystemTime::now() + RELAY_WAIT_TIMEOUT;
+        let (sender, receiver) = mpsc::channel(RELAY_BUFFER_CHUNKS);
+        slots.insert(
+            id,
+            RelaySlot {
+                size,
+                upload_token_hash: blake3::hash(upload_token.as_bytes()),
+                expires_at,
+                sender: Some(sender),
+                receiver: Some(receiver),
+            },
+        );
+        Some(RelayReservation {
+            upload_token,
+            expires_at,
+        })
+    }
+
+    /// Get the size of an open relay that is still waiting for its downloader.
+    pub fn pending_size(&self, id: &str) -> Option<u64> {
+        let mut slots = self.slots.lock().unwrap();
+        Self::remove_lapsed(&mut slots);
+        slots
+            .get(id)
+            .filter(|slot| slot.receiver.is_some())
+            .map(|slot| slot.size)
+    }
+
+    /// Connect the uploader of a relay, returning its size and the channel to send data down,
+    /// or `None` if no relay matches or its uploader has already connected.
+    pub fn connect_uploader(
+        &self,
+        id: &str,
+        upload_token: &str,
+    ) -> Option<(u64, mpsc::Sender<RelayMessage>)> {
+        let mut slots = self.slots.lock().unwrap();
+        Self::remove_lapsed(&mut slots);
+        let slot = slots.get_mut(id)?;
+        // Compare hashes so the comparison runs in constant time.
+        if slot.upload_token_hash != blake3::hash(upload_token.as_bytes()) {
+            return None;
+        }
+        let sender = slot.sender.take()?;
+        let size = slot.size;
+        Self::remove_if_connected(&mut slots, id);
+        Some((size, sender))
+    }
+
+    /// Connect the downloader of a relay, returning its size and a stream of its data,
+    /// or `None` if no relay matches or its downloader has already connected.
+    ///
+    /// The stream ends with an error if the uploader disconnects or stalls before sending all data.
+    pub fn connect_downloader(
+        &self,
+        id: &str,
+    ) -> Option<(u64, impl Stream<Item = io::Result<Bytes>> + use<>)> {
+        let mut slots = self.slots.lock().unwrap();
+        Self::remove_lapsed(&mut slots);
+        let slot = slots.get_mut(id)?;
+        let receiver = slot.receiver.take()?;
+        let size = slot.size;
+        Self::remove_if_connected(&mut slots, id);
+        let stream = futures_util::stream::unfold(Some(receiver), |receiver| async move {
+            let mut receiver = receiver?;
+            match tokio::time::timeout(RELAY_WAIT_TIMEOUT, receiver.recv()).await {
+                Ok(Some(RelayMessage::Data(data))) => Some((Ok(data), Some(receiver))),
+                Ok(Some(RelayMessage::End)) => None,
+                Ok(None) => Some((
+                    Err(io::Error::other(
+                        "uploader disconnected before sending all data",
+                    )),
+                    None,
+                )),
+                Err(_) => Some((
+                    Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "timed out waiting for the uploader",
+                    )),
+                    None,
+                )),
+            }
+        });
+        Some((size, stream))
+    }
+
+    /// Stop tracking a relay once both sides have connected, as they now hold the channel between them.
+    fn remove_if_connected(slots: &mut HashMap<String, RelaySlot>, id: &str) {
+        if slots
+            .get(id)
+            .is_some_and(|slot| slot.sender.is_none() && slot.receiver.is_none())
+        {
+            slots.remove(id);
+        }
+    }
+
+    /// Remove relays that lapsed before both sides connected.
+    fn remove_lapsed(slots: &mut HashMap<String, RelaySlot>) {
+        let now = SystemTime::now();
+        slots.retain(|_, slot| slot.expires_at > now);
+    }
+}
@@ -0,0 +1,400 @@
+use crate::{
+    AppState,
+    audit::{AuditAction, AuditEvent, AuditQuery},
+    client_ip::ClientIp,
+    error::ApiError,
+    metrics::{IntegrityReport, SweepSummary, TrafficSummary},
+    storage::StorageStatistics,
+    store::TransferPin,
+    upload_token::{UploadToken, UploadTokenPolicy},
+};
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::atomic::Ordering,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tracing::info;
+
+/// Number of audit events returned when no limit is given.
+const AUDIT_QUERY_DEFAULT_LIMIT: usize = 100;
+/// Maximum number of audit events that can be returned by a single query.
+const AUDIT_QUERY_MAX_LIMIT: usize = 10_000;
+/// Maximum length of an upload token name.
+const UPLOAD_TOKEN_NAME_MAX_LENGTH: usize = 64;
+
+#[derive(Deserialize)]
+pub struct AuditQueryParams {
+    action: Option<AuditAction>,
+    transfer_id: Option<String>,
+    since: Option<u64>,
+    limit: Option<usize>,
+}
+
+pub async fn admin_audit_handler(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    Query(params): Query<AuditQueryParams>,
+) -> Result<Json<Vec<AuditEvent>>, ApiError> {
+    if !state.audit_log.is_enabled() {
+        return Err(ApiError::not_found("audit logging is not enabled"));
+    }
+    state
+        .audit_log
+        .record(AuditAction::AdminQuery, None, Some(client_ip));
+    let limit = params
+        .limit
+        .unwrap_or(AUDIT_QUERY_DEFAULT_LIMIT)
+        .min(AUDIT_QUERY_MAX_LIMIT);
+    let query = AuditQuery {
+        action: params.action,
+        transfer_id: params.transfer_id,
+        since: params.since,
+    };
+    Ok(Json(state.audit_log.query(&query, limit)?))
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MaintenanceModeBody {
+    enabled: bool,
+}
+
+pub async fn admin_get_maintenance_handler(
+    State(state): State<AppState>,
+) -> Json<MaintenanceModeBody> {
+    Json(MaintenanceModeBody {
+        enabled: state.maintenance_mode.load(Ordering::Relaxed),
+    })
+}
+
+pub async fn admin_set_maintenance_handler(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    Json(body): Json<MaintenanceModeBody>,
+) -> Json<MaintenanceModeBody> {
+    state
+        .maintenance_mode
+        .store(body.enabled, Ordering::Relaxed);
+    info!(
+        "Maintenance mode {} by admin",
+        if body.enabled { "enabled" } else { "disabled" }
+    );
+    state
+        .audit_log
+        .record(AuditAction::Maintenance, None, Some(client_ip));
+    Json(body)
+}
+
+#[derive(Serialize)]
+pub struct ServerStatisticsResponse {
+    storage: StorageStatistics,
+    traffic_24h: TrafficSummary,
+    sweeps: SweepSummary,
+}
+
+pub async fn admin_stats_handler(
+    State(state): State<AppState>,
+) -> Result<Json<ServerStatisticsResponse>, ApiError> {
+    Ok(Json(ServerStatisticsResponse {
+        storage: state.transfer_storage.storage_statistics()?,
+        traffic_24h: state.metrics.recent_traffic(),
+        sweeps: state.metrics.sweep_summary(),
+    }))
+}
+
+pub async fn admin_integrity_handler(
+    State(state): State<AppState>,
+) -> Result<Json<IntegrityReport>, ApiError> {
+    state
+        .metrics
+        .integrity_report()
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found("no integrity verification has completed yet"))
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PinBody {
+    /// Unix timestamp, in seconds, the pinned transfer should expire at instead.
+    ///
+    /// When omitted the transfer is kept until it is unpinned.
+    #[serde(default)]
+    expires_at: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct PinnedTransfer {
+    id: String,
+    #[serde(flatten)]
+    pin: PinBody,
+}
+
+impl PinnedTransfer {
+    fn new(id: String, pin: TransferPin) -> Self {
+        Self {
+            id,
+            pin: PinBody {
+                expires_at: pin
+                    .expires_at
+                    .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                    .map(|duration| duration.as_secs()),
+            },
+        }
+    }
+}
+
+pub async fn admin_list_pins_handler(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<PinnedTransfer>>, ApiError> {
+    Ok(Json(
+        state
+            .transfer_storage
+            .list_pinned_transfers()?
+            .into_iter()
+            .map(|(id, pin)| PinnedTransfer::new(id, pin))
+            .collect(),
+    ))
+}
+
+pub async fn admin_pin_transfer_handler(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    Path(id): Path<String>,
+    Json(body): Json<PinBody>,
+) -> Result<Json<PinnedTransfer>, ApiError> {
+    if !state.transfer_storage.validate_identifier(&id) {
+        return Err(ApiError::bad_request(
+            "transfer identifier failed to validate server-side",
+        ));
+    }
+    if !state.transfer_storage.transfer_exists(&id)? {
+        return Err(ApiError::not_found("transfer does not exist"));
+    }
+    let expires_at = body
+        .expires_at
+        .map(|secs| UNIX_EPOCH + Duration::from_secs(secs));
+    if expires_at.is_some_and(|time| time <= SystemTime::now()) {
+        return Err(ApiError::bad_request("pin expiry must be in the future"));
+    }
+    state.transfer_storage.pin_transfer(&id, expires_at)?;
+    info!("Transfer (id: '{id}') pinned by admin");
+    state
+        .audit_log
+        .record(AuditAction::Pin, Some(&id), Some(client_ip));
+    Ok(Json(PinnedTransfer::new(id, TransferPin { expires_at })))
+}
+
+pub async fn admin_unpin_transfer_handler(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    if !state.transfer_storage.validate_identifier(&id)
+        || !state.transfer_storage.unpin_transfer(&id)?
+    {
+        return Err(ApiError::not_found("transfer is not pinned"));
+    }
+    info!("Transfer (id: '{id}') unpinned by admin");
+    state
+        .audit_log
+        .record(AuditAction::Unpin, Some(&id), Some(client_ip));
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct UploadTokenBody {
+    name: String,
+    #[serde(flatten)]
+    policy: UploadTokenPolicy,
+    /// Unix timestamp, in seconds, the token was created at.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    created_at: Option<u64>,
+}
+
+impl From<UploadToken> for UploadTokenBody {
+    fn from(token: UploadToken) -> Self {
+        Self {
+            name: token.name,
+            policy: token.policy,
+            created_at: token
+                .created_at
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|duration| duration.as_secs()),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct CreatedUploadToken {
+    #[serde(flatten)]
+    token: UploadTokenBody,
+    /// The token's secret. It is only returned once and can't be recovered later.
+    secret: String,
+}
+
+pub async fn admin_list_upload_tokens_handler(
+    State(state): State<AppState>,
+) -> Json<Vec<UploadTokenBody>> {
+    Json(
+        state
+            .upload_tokens
+            .list()
+            .into_iter()
+            .map(UploadTokenBody::from)
+            .collect(),
+    )
+}
+
+pub async fn admin_create_upload_token_handler(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    Json(body): Json<UploadTokenBody>,
+) -> Result<(StatusCode, Json<CreatedUploadToken>), ApiError> {
+    if body.name.is_empty()
+        || body.name.len() > UPLOAD_TOKEN_NAME_MAX_LENGTH
+        || !body
+            .name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(ApiError::bad_request(format!(
+            "upload token name must be 1-{UPLOAD_TOKEN_NAME_MAX_LENGTH} characters long and only contain letters, digits, '-' and '_'"
+        )));
+    }
+    if body.policy.rate_limit_window_secs == Some(0) {
+        return Err(ApiError::bad_request(
+            "upload token rate limit window must be at least 1 second",
+        ));
+    }
+    let Some(secret) = state
+        .upload_tokens
+        .create(&body.name, body.policy.clone())?
+    else {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            "an upload token with this name already exists",
+        ));
+    };
+    info!("Upload token '{}' created by admin", body.name);
+    state
+        .audit_log
+        .record(AuditAction::CreateUploadToken, None, Some(client_ip));
+    Ok((
+        StatusCode::CREATED,
+        Json(CreatedUploadToken {
+            token: UploadTokenBody {
+                created_at: Some(
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs(),
+                ),
+                ..body
+            },
+            secret,
+        }),
+    ))
+}
+
+pub async fn admin_revoke_upload_token_handler(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    Path(name): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    if !state.upload_tokens.revoke(&name)? {
+        return Err(ApiError::not_found("upload token does not exist"));
+    }
+    info!("Upload token '{name}' revoked by admin");
+    state
+        .audit_log
+        .record(AuditAction::RevokeUploadToken, None, Some(client_ip));
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Serialize)]
+pub struct DeletedTransferSummary {
+    id: String,
+    /// Unix timestamp, in seconds, the transfer was deleted at.
+    deleted_at: u64,
+    /// Unix timestamp, in seconds, the transfer will be permanently removed at.
+    purge_at: u64,
+}
+
+pub async fn admin_list_deleted_handler(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<DeletedTransferSummary>>, ApiError> {
+    let retention = state
+        .transfer_storage
+        .deleted_retention()
+        .unwrap_or_default();
+    let unix_secs = |time: SystemTime| {
+        time.duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+    };
+    Ok(Json(
+        state
+            .transfer_storage
+            .list_deleted_transfers()?
+            .into_iter()
+            .map(|deleted| DeletedTransferSummary {
+                id: deleted.id,
+                deleted_at: unix_secs(deleted.deleted_at),
+                purge_at: unix_secs(deleted.deleted_at + retention),
+            })
+            .collect(),
+    ))
+}
+
+/// Restore a deleted transfer, optionally pinning it so it isn't removed again by the next sweep if it has expired.
+pub async fn admin_restore_deleted_handler(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    Path(id): Path<String>,
+    body: Option<Json<PinBody>>,
+) -> Result<StatusCode, ApiError> {
+    if !state.transfer_storage.validate_identifier(&id) {
+        return Err(ApiError::not_found("deleted transfer does not exist"));
+    }
+    let expires_at = body.map(|Json(body)| {
+        body.expires_at
+            .map(|secs| UNIX_EPOCH + Duration::from_secs(secs))
+    });
+    if expires_at
+        .flatten()
+        .is_some_and(|time| time <= SystemTime::now())
+    {
+        return Err(ApiError::bad_request("pin expiry must be in the future"));
+    }
+    state.transfer_storage.restore_deleted_transfer(&id)?;
+    info!("Deleted transfer (id: '{id}') restored by admin");
+    state
+        .audit_log
+        .record(AuditAction::Restore, Some(&id), Some(client_ip));
+    if let Some(expires_at) = expires_at {
+        state.transfer_storage.pin_transfer(&id, expires_at)?;
+        state
+            .audit_log
+            .record(AuditAction::Pin, Some(&id), Some(client_ip));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn admin_purge_deleted_handler(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    if !state.transfer_storage.validate_identifier(&id) {
+        return Err(ApiError::not_found("deleted transfer does not exist"));
+    }
+    state.transfer_storage.purge_deleted_transfer(&id)?;
+    info!("Deleted transfer (id: '{id}') purged by admin");
+    state
+        .audit_log
+        .record(AuditAction::Purge, Some(&id), Some(client_ip));
+    Ok(StatusCode::NO_CONTENT)
+}
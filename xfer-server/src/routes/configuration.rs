@@ -1,25 +1,102 @@
-use crate::AppState;
-use axum::{Json, extract::State};
+use crate::{AppState, pow::ProofOfWorkChallenge};
+use axum::{Json, extract::State, http::HeaderMap};
 use serde::{Deserialize, Serialize};
+use std::sync::atomic::Ordering;
 
 #[derive(Serialize, Deserialize)]
 pub struct ServerConfigurationResponse {
     transfer: TransferConfiguration,
+    web: WebConfiguration,
+    relay: RelayConfiguration,
+    signaling: SignalingConfiguration,
+    motd: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct TransferConfiguration {
+    /// How long transfers are kept when the upload doesn't request a lifetime.
     expire_after_ms: u128,
+    /// The shortest lifetime an upload may request.
+    min_expire_after_ms: u128,
+    /// The longest lifetime an upload may request.
+    max_expire_after_ms: u128,
     max_size_bytes: u64,
+    uploads_enabled: bool,
+    upload_token_required: bool,
+    /// Whether uploaders may register a webhook to be notified when their transfer is first downloaded.
+    claim_webhooks_enabled: bool,
+    /// Whether uploaders may give an address to be emailed about their transfer.
+    notification_emails_enabled: bool,
+    /// Whether reserved transfers can be uploaded in chunks.
+    chunked_uploads_enabled: bool,
+    /// Whether uploaders can store an encrypted manifest of their transfer's contents.
+    manifests_enabled: bool,
+    /// Whether uploaders can limit how many times their transfer can be downloaded.
+    download_limits_enabled: bool,
+    /// Challenge that must be solved before uploading without an upload token, if proof of work is enabled.
+    proof_of_work: Option<ProofOfWorkChallenge>,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct RelayConfiguration {
+    enabled: bool,
+    /// The maximum size of a relayed transfer, if limited.
+    max_size_bytes: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SignalingConfiguration {
+    enabled: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct WebConfiguration {
+    download_page_enabled: bool,
+}
+
+/// Get the server's configuration.
+///
+/// When a valid upload token is sent the transfer limits reflect that token's policy.
 pub async fn configuration_handler(
     State(state): State<AppState>,
+    headers: HeaderMap,
 ) -> Json<ServerConfigurationResponse> {
+    let token = state
+        .upload_tokens
+        .authenticate_headers(&headers)
+        .ok()
+        .flatten();
+    let proof_of_work = match token {
+        Some(_) => None,
+        None => state.proof_of_work.issue(),
+    };
+    let policy = token.map(|token| token.policy).unwrap_or_default();
+    let expiry = policy.expiry_bounds(state.transfer_expiry);
     Json(ServerConfigurationResponse {
         transfer: TransferConfiguration {
-            expire_after_ms: state.transfer_expire_after.as_millis(),
-            max_size_bytes: state.transfer_max_size.as_u64(),
+            expire_after_ms: expiry.default.as_millis(),
+            min_expire_after_ms: expiry.min.as_millis(),
+            max_expire_after_ms: expiry.max.as_millis(),
+            max_size_bytes: policy.max_size(state.transfer_max_size.as_u64()),
+            uploads_enabled: !state.maintenance_mode.load(Ordering::Relaxed),
+            upload_token_required: state.upload_tokens.is_required(),
+            claim_webhooks_enabled: state.claim_webhooks.uploader_webhooks_allowed(),
+            notification_emails_enabled: state.email_notifications.uploader_addresses_allowed(),
+            chunked_uploads_enabled: true,
+            manifests_enabled: true,
+            download_limits_enabled: true,
+            proof_of_work,
+        },
+        web: WebConfiguration {
+            download_page_enabled: state.web_download_page_enabled,
+        },
+        relay: RelayConfiguration {
+            enabled: state.relay.is_enabled(),
+            max_size_bytes: state.relay.max_size(),
+        },
+        signaling: SignalingConfiguration {
+            enabled: state.signaling.is_enabled(),
         },
+        motd: state.motd.as_deref().map(str::to_string),
     })
 }
@@ -1,3 +1,19 @@
-pub async fn index_handler() -> &'static str {
-    concat!("xfer relay server ready.\n\n", env!("CARGO_PKG_REPOSITORY"))
+use crate::AppState;
+use axum::{
+    extract::State,
+    http::header,
+    response::{IntoResponse, Response},
+};
+
+pub async fn index_handler(State(state): State<AppState>) -> Response {
+    match &state.index_page {
+        Some(page) => (
+            [(header::CONTENT_TYPE, page.content_type())],
+            page.body().to_string(),
+        )
+            .into_response(),
+        None => {
+            concat!("xfer relay server ready.\n\n", env!("CARGO_PKG_REPOSITORY")).into_response()
+        }
+    }
 }
@@ -0,0 +1,79 @@
+use super::download_transfer_handler;
+use crate::{AppState, client_ip::ClientIp, error::ApiError};
+use anyhow::Context;
+use axum::{
+    Json,
+    body::Body,
+    extract::{Path, Query, State},
+    http::{HeaderMap, Response, StatusCode},
+};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+
+/// How long download links remain valid for when no lifetime is requested.
+const LINK_DEFAULT_LIFETIME: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Deserialize)]
+pub struct CreateLinkParams {
+    /// Number of seconds the link should remain valid for.
+    expires_in: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct CreateLinkResponse {
+    /// Path of the download link, relative to the API root.
+    pub path: String,
+    /// The time the link expires at, in RFC 3339 format.
+    pub expires_at: String,
+}
+
+/// Create a time-limited download link for a transfer that doesn't reveal its identifier.
+///
+/// Links always expire no later than the transfer itself.
+pub async fn create_link_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(params): Query<CreateLinkParams>,
+) -> Result<(StatusCode, Json<CreateLinkResponse>), ApiError> {
+    if !state.transfer_storage.validate_identifier(&id) {
+        return Err(ApiError::transfer_not_found());
+    }
+    if !state.transfer_storage.transfer_exists(&id)? {
+        return Err(ApiError::transfer_not_found());
+    }
+    let lifetime = params
+        .expires_in
+        .map_or(LINK_DEFAULT_LIFETIME, Duration::from_secs);
+    let transfer_expires_at = state.transfer_storage.get_transfer_expiry(&id)?;
+    let expires_at = SystemTime::now()
+        .checked_add(lifetime)
+        .map_or(transfer_expires_at, |expires_at| {
+            expires_at.min(transfer_expires_at)
+        });
+    let token = state.link_signer.sign(&id, expires_at)?;
+    Ok((
+        StatusCode::CREATED,
+        Json(CreateLinkResponse {
+            path: format!("link/{token}"),
+            expires_at: OffsetDateTime::from(expires_at)
+                .format(&Rfc3339)
+                .context("Failed to format link expiry")?,
+        }),
+    ))
+}
+
+/// Download the transfer a time-limited link points to.
+pub async fn link_download_handler(
+    State(state): State<AppState>,
+    client_ip: ClientIp,
+    Path(token): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response<Body>, ApiError> {
+    let Some(id) = state.link_signer.verify(&token) else {
+        return Err(ApiError::not_found(
+            "download link is invalid or has expired",
+        ));
+    };
+    download_transfer_handler(State(state), client_ip, Path(id), headers).await
+}
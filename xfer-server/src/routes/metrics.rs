@@ -0,0 +1,9 @@
+use crate::AppState;
+use axum::{extract::State, http::header, response::IntoResponse};
+
+pub async fn metrics_handler(State(state): State<AppState>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(),
+    )
+}
@@ -1,7 +1,27 @@
+mod admin;
 mod configuration;
 mod index;
+mod link;
+mod metrics;
+mod openapi;
+mod relay;
+mod report;
+mod signaling;
 mod transfer;
+mod web;
+mod webdav;
+mod well_known;
 
+pub use admin::*;
 pub use configuration::*;
 pub use index::*;
+pub use link::*;
+pub use metrics::*;
+pub use openapi::*;
+pub use relay::*;
+pub use report::*;
+pub use signaling::*;
 pub use transfer::*;
+pub use web::*;
+pub use webdav::*;
+pub use well_known::*;
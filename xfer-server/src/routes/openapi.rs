@@ -0,0 +1,1198 @@
+use crate::API_VERSION;
+use axum::{Json, response::Html};
+use serde_json::{Map, Value, json};
+use std::sync::LazyLock;
+
+/// OpenAPI document describing every route of the versioned API.
+///
+/// Routes that are disabled by default are included and marked as such in their description.
+static OPENAPI_DOCUMENT: LazyLock<Value> = LazyLock::new(openapi_document);
+
+pub async fn openapi_handler() -> Json<&'static Value> {
+    Json(&OPENAPI_DOCUMENT)
+}
+
+pub async fn swagger_ui_handler() -> Html<&'static str> {
+    Html(include_str!("swagger_ui.html"))
+}
+
+fn schema_ref(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{name}") })
+}
+
+fn json_response(description: &str, schema: Value) -> Value {
+    json!({
+        "description": description,
+        "content": { "application/json": { "schema": schema } }
+    })
+}
+
+fn error_response(description: &str) -> Value {
+    json_response(description, schema_ref("Error"))
+}
+
+fn json_body(schema: Value) -> Value {
+    json!({
+        "required": true,
+        "content": { "application/json": { "schema": schema } }
+    })
+}
+
+fn path_parameter(name: &str, description: &str) -> Value {
+    json!({
+        "name": name,
+        "in": "path",
+        "required": true,
+        "description": description,
+        "schema": { "type": "string" }
+    })
+}
+
+fn header_parameter(name: &str, description: &str, required: bool) -> Value {
+    json!({
+        "name": name,
+        "in": "header",
+        "required": required,
+        "description": description,
+        "schema": { "type": "string" }
+    })
+}
+
+fn query_parameter(name: &str, description: &str, schema: Value) -> Value {
+    json!({
+        "name": name,
+        "in": "query",
+        "required": false,
+        "description": description,
+        "schema": schema
+    })
+}
+
+fn transfer_id() -> Value {
+    path_parameter("id", "The transfer identifier.")
+}
+
+fn upload_headers() -> Vec<Value> {
+    vec![
+        header_parameter(
+            "X-Xfer-Checksum",
+            "Checksum of the transfer data in the form `<algorithm>=<hex digest>`, verified once the upload completes.",
+            false,
+        ),
+        header_parameter(
+            "X-Xfer-Encryption-Version",
+            "Version of the encryption format the transfer was encrypted with.",
+            false,
+        ),
+        header_parameter(
+            "X-Xfer-Proof-Of-Work",
+            "Solved proof-of-work challenge in the form `<challenge>:<nonce>`, when one is required.",
+            false,
+        ),
+        json!({
+            "name": "X-Xfer-Expire-After",
+            "in": "header",
+            "required": false,
+            "description": "How long the transfer is kept for, in seconds, within the advertised bounds.",
+            "schema": { "type": "integer", "format": "int64", "minimum": 0 }
+        }),
+        header_parameter(
+            "X-Xfer-Claim-Webhook",
+            "URL notified when the transfer is first downloaded, when the server allows it.",
+            false,
+        ),
+        header_parameter(
+            "X-Xfer-Notify-Email",
+            "Address emailed when the transfer is uploaded, first downloaded or about to expire without being downloaded, when the server allows it.",
+            false,
+        ),
+        json!({
+            "name": "X-Xfer-Max-Downloads",
+            "in": "header",
+            "required": false,
+            "description": "How many times the transfer can be downloaded before the server refuses further downloads and removes it.",
+            "schema": { "type": "integer", "format": "int64", "minimum": 1 }
+        }),
+    ]
+}
+
+fn transfer_download_responses() -> Value {
+    json!({
+        "200": {
+            "description": "The encrypted transfer data.",
+            "headers": {
+                "ETag": { "schema": { "type": "string" } },
+                "X-Xfer-ExpiresAt": {
+                    "description": "The time the transfer expires at.",
+                    "schema": { "type": "string", "format": "date-time" }
+                },
+                "X-Xfer-Downloads-Remaining": {
+                    "description": "How many more times the transfer can be downloaded, when its downloads are limited.",
+                    "schema": { "type": "integer", "format": "int64" }
+                },
+                "X-Xfer-Download-Token": {
+                    "description": "Token that lets this download be resumed after every download has been taken, sent with downloads from the start of transfers with limited downloads.",
+                    "schema": { "type": "string" }
+                }
+            },
+            "content": {
+                "application/octet-stream": { "schema": { "type": "string", "format": "binary" } }
+            }
+        },
+        "206": { "description": "The requested range of the transfer data." },
+        "304": { "description": "The transfer matches the `If-None-Match` header." },
+        "404": error_response("The transfer does not exist."),
+        "410": error_response("The transfer has already been downloaded as many times as allowed."),
+        "416": { "description": "The requested range is not satisfiable." },
+        "429": error_response("Too many downloads of the transfer, or too many missed lookups, are in progress.")
+    })
+}
+
+fn openapi_document() -> Value {
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "xfer",
+            "description": "API for uploading and downloading end-to-end encrypted transfers.",
+            "version": env!("CARGO_PKG_VERSION")
+        },
+        "servers": [{ "url": format!("./v{API_VERSION}") }],
+        "tags": [
+            { "name": "transfers" },
+            { "name": "relay" },
+            { "name": "signaling" },
+            { "name": "webdav" },
+            { "name": "server" },
+            { "name": "admin" }
+        ],
+        "paths": paths(),
+        "components": {
+            "securitySchemes": {
+                "adminToken": {
+                    "type": "http",
+                    "scheme": "bearer",
+                    "description": "The admin token configured on the server."
+                },
+                "uploadToken": {
+                    "type": "http",
+                    "scheme": "bearer",
+                    "description": "An upload token issued by the server operator."
+                }
+            },
+            "schemas": schemas()
+        }
+    })
+}
+
+fn paths() -> Value {
+    let mut paths = Map::new();
+    for group in [transfer_paths(), optional_paths(), admin_paths()] {
+        if let Value::Object(group) = group {
+            paths.extend(group);
+        }
+    }
+    Value::Object(paths)
+}
+
+/// Routes that are always available.
+fn transfer_paths() -> Value {
+    json!({
+        "/": {
+            "get": {
+                "tags": ["server"],
+                "summary": "Get a short description of the server.",
+                "responses": {
+                    "200": {
+                        "description": "Plain text description of the server.",
+                        "content": { "text/plain": { "schema": { "type": "string" } } }
+                    }
+                }
+            }
+        },
+        "/configuration": {
+            "get": {
+                "tags": ["server"],
+                "summary": "Get the limits and features of the server.",
+                "responses": {
+                    "200": json_response("The server configuration.", schema_ref("Configuration"))
+                }
+            }
+        },
+        "/transfer": {
+            "post": {
+                "tags": ["transfers"],
+                "summary": "Upload a transfer.",
+                "security": [{}, { "uploadToken": [] }],
+                "parameters": upload_headers(),
+                "requestBody": {
+                    "required": true,
+                    "content": {
+                        "application/octet-stream": { "schema": { "type": "string", "format": "binary" } }
+                    }
+                },
+                "responses": {
+                    "201": json_response("The transfer was created.", schema_ref("CreateTransferResponse")),
+                    "400": error_response("The upload headers are invalid or the checksum did not match."),
+                    "401": error_response("An upload token is required."),
+                    "413": error_response("The transfer exceeds the maximum size."),
+                    "429": error_response("Too many uploads have been made."),
+                    "503": error_response("Uploads are disabled or the server is in maintenance mode.")
+                }
+            }
+        },
+        "/transfer/reserve": {
+            "post": {
+                "tags": ["transfers"],
+                "summary": "Reserve an identifier for a transfer that is uploaded later.",
+                "security": [{}, { "uploadToken": [] }],
+                "parameters": upload_headers(),
+                "requestBody": json_body(schema_ref("TransferSize")),
+                "responses": {
+                    "201": json_response("The identifier was reserved.", schema_ref("ReserveTransferResponse")),
+                    "413": error_response("The transfer exceeds the maximum size."),
+                    "429": error_response("Too many uploads have been made.")
+                }
+            }
+        },
+        "/transfer/{id}": {
+            "parameters": [transfer_id()],
+            "get": {
+                "tags": ["transfers"],
+                "summary": "Download a transfer.",
+                "parameters": [
+                    header_parameter("Range", "Byte range of the transfer to download.", false),
+                    header_parameter("If-Range", "Only honour the range if the transfer still matches this ETag.", false),
+                    header_parameter("If-None-Match", "ETag of a copy of the transfer the client already has.", false),
+                    header_parameter("X-Xfer-Download-Token", "Token from a counted download of the transfer, required to resume it once no downloads are left.", false)
+                ],
+                "responses": transfer_download_responses()
+            },
+            "head": {
+                "tags": ["transfers"],
+                "summary": "Get the size and expiry of a transfer without downloading it.",
+                "responses": {
+                    "200": { "description": "The transfer exists." },
+                    "404": { "description": "The transfer does not exist." }
+                }
+            },
+            "put": {
+                "tags": ["transfers"],
+                "summary": "Upload the data of a reserved transfer.",
+                "parameters": [
+                    header_parameter("X-Xfer-Upload-Token", "The upload token returned when the transfer was reserved.", true)
+                ],
+                "requestBody": {
+                    "required": true,
+                    "content": {
+                        "application/octet-stream": { "schema": { "type": "string", "format": "binary" } }
+                    }
+                },
+                "responses": {
+                    "201": json_response("The transfer was uploaded.", schema_ref("CreateTransferResponse")),
+                    "401": error_response("The upload token is missing or invalid."),
+                    "413": error_response("The upload does not match the reserved size.")
+                }
+            },
+            "delete": {
+                "tags": ["transfers"],
+                "summary": "Delete a transfer before it expires.",
+                "parameters": [
+                    header_parameter("X-Xfer-Deletion-Token", "The deletion token returned when the transfer was uploaded.", true)
+                ],
+                "responses": {
+                    "204": { "description": "The transfer was deleted." },
+                    "401": error_response("The deletion token is missing."),
+                    "403": error_response("The deletion token is invalid."),
+                    "404": error_response("The transfer does not exist.")
+                }
+            }
+        },
+        "/transfer/{id}/chunks/{offset}": {
+            "parameters": [
+                transfer_id(),
+                path_parameter("offset", "Byte offset in the transfer that the chunk starts at.")
+            ],
+            "put": {
+                "tags": ["transfers"],
+                "summary": "Upload a chunk of a reserved transfer and extend the reservation.",
+                "parameters": [
+                    header_parameter("X-Xfer-Upload-Token", "The upload token returned when the transfer was reserved.", true)
+                ],
+                "requestBody": {
+                    "required": true,
+                    "content": {
+                        "application/octet-stream": { "schema": { "type": "string", "format": "binary" } }
+                    }
+                },
+                "responses": {
+                    "200": json_response("The chunk was stored.", schema_ref("TransferChunkResponse")),
+                    "401": error_response("The upload token is missing or invalid."),
+                    "413": error_response("The chunk extends past the reserved size.")
+                }
+            }
+        },
+        "/transfer/{id}/complete": {
+            "parameters": [transfer_id()],
+            "post": {
+                "tags": ["transfers"],
+                "summary": "Finish a chunked upload once every chunk of the reserved transfer was uploaded.",
+                "parameters": upload_headers().into_iter().chain([
+                    header_parameter("X-Xfer-Upload-Token", "The upload token returned when the transfer was reserved.", true)
+                ]).collect::<Vec<_>>(),
+                "responses": {
+                    "201": json_response("The transfer was created.", schema_ref("CreateTransferResponse")),
+                    "400": error_response("The chunks don't cover the reserved size or no checksum was given."),
+                    "401": error_response("The upload token is missing or invalid.")
+                }
+            }
+        },
+        "/transfer/{id}/activity": {
+            "get": {
+                "tags": ["transfers"],
+                "summary": "Get the download activity of a transfer.",
+                "parameters": [
+                    transfer_id(),
+                    header_parameter("X-Xfer-Activity-Token", "The activity token returned when the transfer was uploaded.", true)
+                ],
+                "responses": {
+                    "200": json_response("The transfer's download activity.", schema_ref("TransferActivity")),
+                    "401": error_response("The activity token is missing."),
+                    "403": error_response("The activity token is invalid."),
+                    "404": error_response("The transfer does not exist.")
+                }
+            }
+        },
+        "/transfer/{id}/manifest": {
+            "parameters": [transfer_id()],
+            "get": {
+                "tags": ["transfers"],
+                "summary": "Get the encrypted manifest of a transfer's contents without downloading it.",
+                "responses": {
+                    "200": {
+                        "description": "The encrypted manifest.",
+                        "content": {
+                            "application/octet-stream": { "schema": { "type": "string", "format": "binary" } }
+                        }
+                    },
+                    "204": { "description": "The transfer has no manifest." },
+                    "404": error_response("The transfer does not exist.")
+                }
+            },
+            "put": {
+                "tags": ["transfers"],
+                "summary": "Store an encrypted manifest of a transfer's contents.",
+                "parameters": [
+                    header_parameter("X-Xfer-Deletion-Token", "The deletion token returned when the transfer was uploaded.", true)
+                ],
+                "requestBody": {
+                    "required": true,
+                    "content": {
+                        "application/octet-stream": { "schema": { "type": "string", "format": "binary" } }
+                    }
+                },
+                "responses": {
+                    "204": { "description": "The manifest was stored." },
+                    "400": error_response("The manifest is empty."),
+                    "401": error_response("The deletion token is missing."),
+                    "403": error_response("The deletion token is invalid."),
+                    "404": error_response("The transfer does not exist."),
+                    "413": error_response("The manifest is too large.")
+                }
+            }
+        },
+        "/transfer/{id}/link": {
+            "post": {
+                "tags": ["transfers"],
+                "summary": "Create a time-limited download link that doesn't reveal the transfer identifier.",
+                "parameters": [
+                    transfer_id(),
+                    query_parameter(
+                        "expires_in",
+                        "Number of seconds the link should remain valid for.",
+                        json!({ "type": "integer", "format": "int64", "minimum": 1 })
+                    )
+                ],
+                "responses": {
+                    "201": json_response("The link was created.", schema_ref("CreateLinkResponse")),
+                    "404": error_response("The transfer does not exist.")
+                }
+            }
+        },
+        "/link/{token}": {
+            "get": {
+                "tags": ["transfers"],
+                "summary": "Download a transfer through a download link.",
+                "parameters": [path_parameter("token", "The download link token.")],
+                "responses": transfer_download_responses()
+            }
+        },
+        "/report/{id}": {
+            "post": {
+                "tags": ["transfers"],
+                "summary": "Report a transfer to the server operator.",
+                "parameters": [transfer_id()],
+                "requestBody": {
+                    "required": true,
+                    "content": {
+                        "text/plain": { "schema": { "type": "string", "maxLength": 2048 } }
+                    }
+                },
+                "responses": {
+                    "202": { "description": "The report was received." },
+                    "404": error_response("The transfer does not exist."),
+                    "429": error_response("Too many reports have been made.")
+                }
+            }
+        },
+        "/t/{id}": {
+            "get": {
+                "tags": ["transfers"],
+                "summary": "Browser download page for a transfer.",
+                "description": "Only available when web assets are configured.",
+                "parameters": [transfer_id()],
+                "responses": {
+                    "200": {
+                        "description": "The download page.",
+                        "content": { "text/html": { "schema": { "type": "string" } } }
+                    }
+                }
+            }
+        },
+        "/metrics": {
+            "get": {
+                "tags": ["server"],
+                "summary": "Get metrics in the Prometheus text format.",
+                "description": "Only available when metrics are enabled.",
+                "responses": {
+                    "200": {
+                        "description": "The server's metrics.",
+                        "content": { "text/plain": { "schema": { "type": "string" } } }
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Routes that are only available when their feature is enabled.
+fn optional_paths() -> Value {
+    json!({
+        "/relay": {
+            "post": {
+                "tags": ["relay"],
+                "summary": "Open a relay that pipes a transfer straight from its uploader to its downloader.",
+                "description": "Only available when relaying is enabled. The downloader fetches the transfer from `/transfer/{id}`.",
+                "security": [{}, { "uploadToken": [] }],
+                "requestBody": json_body(schema_ref("TransferSize")),
+                "responses": {
+                    "201": json_response("The relay was opened.", schema_ref("ReserveTransferResponse")),
+                    "413": error_response("The transfer exceeds the maximum relay size.")
+                }
+            }
+        },
+        "/relay/{id}": {
+            "put": {
+                "tags": ["relay"],
+                "summary": "Upload the data of a relayed transfer, waiting for the downloader to connect.",
+                "description": "Only available when relaying is enabled.",
+                "parameters": [
+                    transfer_id(),
+                    header_parameter("X-Xfer-Upload-Token", "The upload token returned when the relay was opened.", true)
+                ],
+                "requestBody": {
+                    "required": true,
+                    "content": {
+                        "application/octet-stream": { "schema": { "type": "string", "format": "binary" } }
+                    }
+                },
+                "responses": {
+                    "204": { "description": "The transfer was relayed to the downloader." },
+                    "401": error_response("The upload token is missing or invalid."),
+                    "408": error_response("No downloader connected in time."),
+                    "410": error_response("The downloader disconnected.")
+                }
+            }
+        },
+        "/dav": {
+            "get": {
+                "tags": ["webdav"],
+                "summary": "The WebDAV collection transfers are opened within.",
+                "description": "Only available when WebDAV is enabled. The collection is always empty so it can't be used to list transfers. The route also answers `HEAD`, `OPTIONS` and `PROPFIND` requests.",
+                "responses": {
+                    "200": {
+                        "description": "A note on how to open a transfer.",
+                        "content": { "text/plain": { "schema": { "type": "string" } } }
+                    }
+                }
+            }
+        },
+        "/dav/{id}": {
+            "get": {
+                "tags": ["webdav"],
+                "summary": "Download the encrypted data of a transfer over WebDAV.",
+                "description": "Only available when WebDAV is enabled. The route also answers `HEAD`, `OPTIONS` and `PROPFIND` (depth 0) requests.",
+                "parameters": [transfer_id()],
+                "responses": transfer_download_responses()
+            }
+        },
+        "/signal": {
+            "post": {
+                "tags": ["signaling"],
+                "summary": "Create a signaling session for negotiating a direct connection.",
+                "description": "Only available when signaling is enabled.",
+                "responses": {
+                    "201": json_response("The session was created.", schema_ref("SignalSession"))
+                }
+            }
+        },
+        "/signal/{id}/join": {
+            "post": {
+                "tags": ["signaling"],
+                "summary": "Join a signaling session as the second peer.",
+                "description": "Only available when signaling is enabled.",
+                "parameters": [path_parameter("id", "The signaling session identifier.")],
+                "responses": {
+                    "200": json_response(
+                        "The session was joined.",
+                        json!({
+                            "type": "object",
+                            "required": ["token"],
+                            "properties": { "token": { "type": "string" } }
+                        })
+                    ),
+                    "404": error_response("The session does not exist or was already joined.")
+                }
+            }
+        },
+        "/signal/{id}/messages": {
+            "parameters": [
+                path_parameter("id", "The signaling session identifier."),
+                header_parameter("X-Xfer-Signal-Token", "The token returned when creating or joining the session.", true)
+            ],
+            "get": {
+                "tags": ["signaling"],
+                "summary": "Receive messages sent by the other peer.",
+                "description": "Only available when signaling is enabled.",
+                "parameters": [
+                    query_parameter(
+                        "after",
+                        "Only return messages numbered after this one.",
+                        json!({ "type": "integer", "format": "int64", "minimum": 0 })
+                    ),
+                    query_parameter(
+                        "wait",
+                        "How long to wait for a message to arrive, in seconds.",
+                        json!({ "type": "integer", "format": "int64", "minimum": 0 })
+                    )
+                ],
+                "responses": {
+                    "200": json_response("Messages from the other peer.", schema_ref("SignalMessages")),
+                    "401": error_response("The signal token is missing or invalid.")
+                }
+            },
+            "post": {
+                "tags": ["signaling"],
+                "summary": "Send a message to the other peer.",
+                "description": "Only available when signaling is enabled.",
+                "requestBody": json_body(json!({})),
+                "responses": {
+                    "204": { "description": "The message was sent." },
+                    "401": error_response("The signal token is missing or invalid."),
+                    "429": error_response("Too many messages are waiting to be received.")
+                }
+            }
+        }
+    })
+}
+
+/// Routes of the admin API, which require the admin token.
+fn admin_paths() -> Value {
+    json!({
+        "/admin/audit": {
+            "get": {
+                "tags": ["admin"],
+                "summary": "Query the audit log.",
+                "security": [{ "adminToken": [] }],
+                "parameters": [
+                    query_parameter("action", "Only return events of this action.", schema_ref("AuditAction")),
+                    query_parameter("transfer_id", "Only return events for this transfer.", json!({ "type": "string" })),
+                    query_parameter(
+                        "since",
+                        "Only return events after this Unix timestamp, in seconds.",
+                        json!({ "type": "integer", "format": "int64" })
+                    ),
+                    query_parameter(
+                        "limit",
+                        "The maximum number of events to return.",
+                        json!({ "type": "integer", "minimum": 1 })
+                    )
+                ],
+                "responses": {
+                    "200": json_response(
+                        "Matching audit events.",
+                        json!({ "type": "array", "items": schema_ref("AuditEvent") })
+                    ),
+                    "404": error_response("The audit log is disabled.")
+                }
+            }
+        },
+        "/admin/stats": {
+            "get": {
+                "tags": ["admin"],
+                "summary": "Get storage, traffic and sweep statistics.",
+                "security": [{ "adminToken": [] }],
+                "responses": {
+                    "200": json_response("The server statistics.", schema_ref("ServerStatistics"))
+                }
+            }
+        },
+        "/admin/integrity": {
+            "get": {
+                "tags": ["admin"],
+                "summary": "Get the result of the most recent integrity verification.",
+                "security": [{ "adminToken": [] }],
+                "responses": {
+                    "200": json_response("The integrity report.", schema_ref("IntegrityReport")),
+                    "404": error_response("No integrity verification has completed yet.")
+                }
+            }
+        },
+        "/admin/pins": {
+            "get": {
+                "tags": ["admin"],
+                "summary": "List pinned transfers.",
+                "security": [{ "adminToken": [] }],
+                "responses": {
+                    "200": json_response(
+                        "The pinned transfers.",
+                        json!({ "type": "array", "items": schema_ref("PinnedTransfer") })
+                    )
+                }
+            }
+        },
+        "/admin/pins/{id}": {
+            "parameters": [transfer_id()],
+            "put": {
+                "tags": ["admin"],
+                "summary": "Pin a transfer so it is kept past its expiry.",
+                "security": [{ "adminToken": [] }],
+                "requestBody": json_body(schema_ref("Pin")),
+                "responses": {
+                    "200": json_response("The transfer was pinned.", schema_ref("PinnedTransfer")),
+                    "400": error_response("The pin expiry is in the past."),
+                    "404": error_response("The transfer does not exist.")
+                }
+            },
+            "delete": {
+                "tags": ["admin"],
+                "summary": "Unpin a transfer.",
+                "security": [{ "adminToken": [] }],
+                "responses": {
+                    "204": { "description": "The transfer was unpinned." },
+                    "404": error_response("The transfer is not pinned.")
+                }
+            }
+        },
+        "/admin/tokens": {
+            "get": {
+                "tags": ["admin"],
+                "summary": "List upload tokens.",
+                "security": [{ "adminToken": [] }],
+                "responses": {
+                    "200": json_response(
+                        "The upload tokens, without their secrets.",
+                        json!({ "type": "array", "items": schema_ref("UploadToken") })
+                    )
+                }
+            },
+            "post": {
+                "tags": ["admin"],
+                "summary": "Create an upload token.",
+                "security": [{ "adminToken": [] }],
+                "requestBody": json_body(schema_ref("UploadToken")),
+                "responses": {
+                    "201": json_response("The token was created.", schema_ref("CreatedUploadToken")),
+                    "409": error_response("A token with the same name already exists.")
+                }
+            }
+        },
+        "/admin/tokens/{name}": {
+            "delete": {
+                "tags": ["admin"],
+                "summary": "Revoke an upload token.",
+                "security": [{ "adminToken": [] }],
+                "parameters": [path_parameter("name", "The name of the upload token.")],
+                "responses": {
+                    "204": { "description": "The token was revoked." },
+                    "404": error_response("The token does not exist.")
+                }
+            }
+        },
+        "/admin/deleted": {
+            "get": {
+                "tags": ["admin"],
+                "summary": "List deleted transfers that have not been purged yet.",
+                "security": [{ "adminToken": [] }],
+                "responses": {
+                    "200": json_response(
+                        "The deleted transfers.",
+                        json!({ "type": "array", "items": schema_ref("DeletedTransfer") })
+                    )
+                }
+            }
+        },
+        "/admin/deleted/{id}": {
+            "delete": {
+                "tags": ["admin"],
+                "summary": "Permanently remove a deleted transfer.",
+                "security": [{ "adminToken": [] }],
+                "parameters": [transfer_id()],
+                "responses": {
+                    "204": { "description": "The transfer was purged." },
+                    "404": error_response("The deleted transfer does not exist.")
+                }
+            }
+        },
+        "/admin/deleted/{id}/restore": {
+            "post": {
+                "tags": ["admin"],
+                "summary": "Restore a deleted transfer, optionally pinning it.",
+                "security": [{ "adminToken": [] }],
+                "parameters": [transfer_id()],
+                "requestBody": {
+                    "required": false,
+                    "content": { "application/json": { "schema": schema_ref("Pin") } }
+                },
+                "responses": {
+                    "204": { "description": "The transfer was restored." },
+                    "404": error_response("The deleted transfer does not exist.")
+                }
+            }
+        },
+        "/admin/maintenance": {
+            "get": {
+                "tags": ["admin"],
+                "summary": "Get whether maintenance mode is enabled.",
+                "security": [{ "adminToken": [] }],
+                "responses": {
+                    "200": json_response("The maintenance mode state.", schema_ref("MaintenanceMode"))
+                }
+            },
+            "put": {
+                "tags": ["admin"],
+                "summary": "Enable or disable maintenance mode, which rejects new uploads.",
+                "security": [{ "adminToken": [] }],
+                "requestBody": json_body(schema_ref("MaintenanceMode")),
+                "responses": {
+                    "200": json_response("The new maintenance mode state.", schema_ref("MaintenanceMode"))
+                }
+            }
+        }
+    })
+}
+
+/// Schemas of the request and response bodies.
+fn schemas() -> Value {
+    json!({
+        "Error": {
+            "type": "object",
+            "required": ["error"],
+            "properties": { "error": { "type": "string" } }
+        },
+        "Configuration": {
+            "type": "object",
+            "properties": {
+                "transfer": {
+                    "type": "object",
+                    "properties": {
+                        "expire_after_ms": { "type": "integer", "format": "int64" },
+                        "min_expire_after_ms": { "type": "integer", "format": "int64" },
+                        "max_expire_after_ms": { "type": "integer", "format": "int64" },
+                        "max_size_bytes": { "type": "integer", "format": "int64" },
+                        "uploads_enabled": { "type": "boolean" },
+                        "upload_token_required": { "type": "boolean" },
+                        "claim_webhooks_enabled": { "type": "boolean" },
+                        "notification_emails_enabled": { "type": "boolean" },
+                        "chunked_uploads_enabled": { "type": "boolean" },
+                        "manifests_enabled": { "type": "boolean" },
+                        "download_limits_enabled": { "type": "boolean" },
+                        "proof_of_work": {
+                            "type": "object",
+                            "nullable": true,
+                            "properties": {
+                                "challenge": { "type": "string" },
+                                "difficulty": { "type": "integer" }
+                            }
+                        }
+                    }
+                },
+                "web": {
+                    "type": "object",
+                    "properties": { "download_page_enabled": { "type": "boolean" } }
+                },
+                "relay": {
+                    "type": "object",
+                    "properties": {
+                        "enabled": { "type": "boolean" },
+                        "max_size_bytes": { "type": "integer", "format": "int64", "nullable": true }
+                    }
+                },
+                "signaling": {
+                    "type": "object",
+                    "properties": { "enabled": { "type": "boolean" } }
+                },
+                "motd": { "type": "string", "nullable": true }
+            }
+        },
+        "TransferSize": {
+            "type": "object",
+            "required": ["size"],
+            "properties": {
+                "size": {
+                    "type": "integer",
+                    "format": "int64",
+                    "description": "The exact size of the transfer, in bytes."
+                }
+            }
+        },
+        "CreateTransferResponse": {
+            "type": "object",
+            "required": ["id", "expires_at", "activity_token", "deletion_token"],
+            "properties": {
+                "id": { "type": "string" },
+                "expires_at": { "type": "string", "format": "date-time" },
+                "activity_token": {
+                    "type": "string",
+                    "description": "Secret to send in the `X-Xfer-Activity-Token` header when viewing the transfer's activity."
+                },
+                "deletion_token": {
+                    "type": "string",
+                    "description": "Secret to send in the `X-Xfer-Deletion-Token` header when deleting the transfer."
+                }
+            }
+        },
+        "ReserveTransferResponse": {
+            "type": "object",
+            "required": ["id", "upload_token", "expires_at"],
+            "properties": {
+                "id": { "type": "string" },
+                "upload_token": { "type": "string" },
+                "expires_at": { "type": "string", "format": "date-time" }
+            }
+        },
+        "TransferChunkResponse": {
+            "type": "object",
+            "required": ["size", "expires_at"],
+            "properties": {
+                "size": { "type": "integer", "format": "int64" },
+                "expires_at": { "type": "string", "format": "date-time" }
+            }
+        },
+        "TransferActivity": {
+            "type": "object",
+            "required": ["download_count", "downloads"],
+            "properties": {
+                "download_count": { "type": "integer", "format": "int64" },
+                "downloads": {
+                    "type": "array",
+                    "description": "The times of the most recent downloads, in RFC 3339 format.",
+                    "items": { "type": "string", "format": "date-time" }
+                }
+            }
+        },
+        "CreateLinkResponse": {
+            "type": "object",
+            "required": ["path", "expires_at"],
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "Path of the download link, relative to the API root."
+                },
+                "expires_at": { "type": "string", "format": "date-time" }
+            }
+        },
+        "SignalSession": {
+            "type": "object",
+            "required": ["id", "token", "expires_at"],
+            "properties": {
+                "id": { "type": "string" },
+                "token": { "type": "string" },
+                "expires_at": { "type": "string", "format": "date-time" }
+            }
+        },
+        "SignalMessages": {
+            "type": "object",
+            "required": ["messages"],
+            "properties": {
+                "messages": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "required": ["seq", "message"],
+                        "properties": {
+                            "seq": { "type": "integer", "format": "int64" },
+                            "message": {}
+                        }
+                    }
+                }
+            }
+        },
+        "AuditAction": {
+            "type": "string",
+            "enum": [
+                "upload", "download", "delete", "expire", "block", "report", "admin_query",
+                "maintenance", "pin", "unpin", "create_upload_token", "revoke_upload_token",
+                "restore", "purge"
+            ]
+        },
+        "AuditEvent": {
+            "type": "object",
+            "required": ["timestamp", "action"],
+            "properties": {
+                "timestamp": {
+                    "type": "object",
+                    "properties": {
+                        "secs_since_epoch": { "type": "integer", "format": "int64" },
+                        "nanos_since_epoch": { "type": "integer" }
+                    }
+                },
+                "action": schema_ref("AuditAction"),
+                "transfer_id": { "type": "string", "nullable": true },
+                "client_ip_hash": { "type": "string", "nullable": true }
+            }
+        },
+        "ServerStatistics": {
+            "type": "object",
+            "properties": {
+                "storage": {
+                    "type": "object",
+                    "properties": {
+                        "transfers": { "type": "integer", "format": "int64" },
+                        "bytes": { "type": "integer", "format": "int64" }
+                    }
+                },
+                "traffic_24h": {
+                    "type": "object",
+                    "properties": {
+                        "uploaded_bytes": { "type": "integer", "format": "int64" },
+                        "downloaded_bytes": { "type": "integer", "format": "int64" }
+                    }
+                },
+                "sweeps": {
+                    "type": "object",
+                    "properties": {
+                        "total": { "type": "integer", "format": "int64" },
+                        "failed": { "type": "integer", "format": "int64" },
+                        "transfers_removed": { "type": "integer", "format": "int64" },
+                        "last_duration_ms": { "type": "integer", "format": "int64" },
+                        "last_run_timestamp_seconds": { "type": "integer", "format": "int64" }
+                    }
+                }
+            }
+        },
+        "IntegrityReport": {
+            "type": "object",
+            "properties": {
+                "verified": { "type": "integer", "format": "int64" },
+                "skipped": { "type": "integer", "format": "int64" },
+                "corrupted": { "type": "array", "items": { "type": "string" } },
+                "failed": { "type": "integer", "format": "int64" },
+                "duration_ms": { "type": "integer", "format": "int64" },
+                "run_timestamp_seconds": { "type": "integer", "format": "int64" }
+            }
+        },
+        "Pin": {
+            "type": "object",
+            "properties": {
+                "expires_at": {
+                    "type": "integer",
+                    "format": "int64",
+                    "nullable": true,
+                    "description": "Unix timestamp, in seconds, the transfer should expire at instead. Kept until unpinned when omitted."
+                }
+            }
+        },
+        "PinnedTransfer": {
+            "type": "object",
+            "required": ["id"],
+            "properties": {
+                "id": { "type": "string" },
+                "expires_at": { "type": "integer", "format": "int64", "nullable": true }
+            }
+        },
+        "UploadToken": {
+            "type": "object",
+            "required": ["name"],
+            "properties": {
+                "name": { "type": "string" },
+                "max_size_bytes": { "type": "integer", "format": "int64" },
+                "max_expire_after_secs": { "type": "integer", "format": "int64" },
+                "rate_limit": { "type": "integer", "format": "int64" },
+                "rate_limit_window_secs": { "type": "integer", "format": "int64" },
+                "created_at": { "type": "integer", "format": "int64", "readOnly": true }
+            }
+        },
+        "CreatedUploadToken": {
+            "allOf": [
+                schema_ref("UploadToken"),
+                {
+                    "type": "object",
+                    "required": ["secret"],
+                    "properties": {
+                        "secret": {
+                            "type": "string",
+                            "description": "The token's secret. It is only returned once."
+                        }
+                    }
+                }
+            ]
+        },
+        "DeletedTransfer": {
+            "type": "object",
+            "required": ["id", "deleted_at", "purge_at"],
+            "properties": {
+                "id": { "type": "string" },
+                "deleted_at": { "type": "integer", "format": "int64" },
+                "purge_at": { "type": "integer", "format": "int64" }
+            }
+        },
+        "MaintenanceMode": {
+            "type": "object",
+            "required": ["enabled"],
+            "properties": { "enabled": { "type": "boolean" } }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OPENAPI_DOCUMENT;
+    use serde_json::Value;
+
+    /// Methods routes can be registered for. `any` registers a route for every method.
+    const METHODS: [&str; 7] = ["get", "head", "post", "put", "delete", "patch", "any"];
+
+    /// Find the end of the parenthesised arguments that start at `start`, just after the opening parenthesis.
+    fn arguments_end(source: &str, start: usize) -> usize {
+        let mut depth = 1;
+        for (offset, c) in source[start..].char_indices() {
+            match c {
+                '(' => depth += 1,
+                ')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return start + offset;
+                    }
+                }
+                _ => {}
+            }
+        }
+        panic!("unbalanced parentheses in the server's routing code");
+    }
+
+    /// Get the string literal that `source` starts with, ignoring leading whitespace.
+    fn leading_string(source: &str) -> Option<&str> {
+        let rest = source.trim_start().strip_prefix('"')?;
+        rest.split_once('"').map(|(string, _)| string)
+    }
+
+    /// Find the calls to `function` in `source`, returning where their arguments start and end.
+    fn calls<'a>(source: &'a str, function: &'a str) -> impl Iterator<Item = (usize, usize)> + 'a {
+        source.match_indices(function).map(move |(index, _)| {
+            let start = index + function.len();
+            (start, arguments_end(source, start))
+        })
+    }
+
+    /// List the path and method of every route registered on the versioned API router, by reading the server's routing code.
+    fn registered_routes() -> Vec<(String, &'static str)> {
+        let source = include_str!("../main.rs");
+        // Routes registered once the router has been nested under the version prefix aren't part of the versioned API.
+        let start = source
+            .find("let mut router = Router::new()")
+            .expect("routing code should start by creating the router");
+        let end = source
+            .find(".nest(&format!(\"/v{API_VERSION}\")")
+            .expect("routing code should nest the router under the version prefix");
+        let source = &source[start..end];
+
+        let nests: Vec<_> = calls(source, ".nest(")
+            .filter_map(|(start, end)| Some((start, end, leading_string(&source[start..end])?)))
+            .collect();
+        let mut routes = Vec::new();
+        for (start, end) in calls(source, ".route(") {
+            let arguments = &source[start..end];
+            let path = leading_string(arguments).expect("routes should have a literal path");
+            let prefix: String = nests
+                .iter()
+                .filter(|(nest_start, nest_end, _)| (*nest_start..*nest_end).contains(&start))
+                .map(|(_, _, prefix)| *prefix)
+                .collect();
+            // Handlers all live in the routes module, which tells method routers apart from other calls.
+            for method in METHODS {
+                let call = format!("{method}(");
+                let registered = arguments.match_indices(&call).any(|(index, _)| {
+                    !arguments[..index].ends_with(|c: char| c.is_alphanumeric() || c == '_')
+                        && arguments[index + call.len()..]
+                            .trim_start()
+                            .starts_with("routes::")
+                });
+                if registered {
+                    routes.push((format!("{prefix}{path}"), method));
+                }
+            }
+        }
+        routes
+    }
+
+    #[test]
+    fn every_route_is_documented() {
+        let paths = OPENAPI_DOCUMENT["paths"]
+            .as_object()
+            .expect("document should have paths");
+        let routes = registered_routes();
+        assert!(
+            routes.len() > 30,
+            "only found {} routes in the server's routing code",
+            routes.len()
+        );
+        for (path, method) in routes {
+            let operations = paths.get(&path).and_then(Value::as_object);
+            let documented = match method {
+                "any" => operations.is_some_and(|operations| !operations.is_empty()),
+                method => operations.is_some_and(|operations| operations.contains_key(method)),
+            };
+            assert!(
+                documented,
+                "{} {path} is not described by the OpenAPI document",
+                method.to_uppercase()
+            );
+        }
+    }
+
+    #[test]
+    fn every_documented_route_is_registered() {
+        let routes = registered_routes();
+        for (path, operations) in OPENAPI_DOCUMENT["paths"]
+            .as_object()
+            .expect("document should have paths")
+        {
+            // Paths can also hold fields shared by their operations, such as parameters.
+            for method in operations
+                .as_object()
+                .expect("paths should list their operations")
+                .keys()
+                .filter(|key| METHODS.contains(&key.as_str()))
+            {
+                assert!(
+                    routes
+                        .iter()
+                        .any(|(route_path, route_method)| route_path == path
+                            && (route_method == method || *route_method == "any")),
+                    "{} {path} is described by the OpenAPI document but isn't registered",
+                    method.to_uppercase()
+                );
+            }
+        }
+    }
+}
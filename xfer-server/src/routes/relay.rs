@@ -0,0 +1,165 @@
+use super::transfer::{ReserveTransferResponse, UPLOAD_TOKEN_HEADER, format_timestamp};
+use crate::{AppState, error::ApiError, relay::RELAY_WAIT_TIMEOUT, relay::RelayMessage};
+use anyhow::Context;
+use axum::{
+    Json,
+    body::Body,
+    extract::{Path, State},
+    http::{HeaderMap, Response, StatusCode, header},
+};
+use futures_util::StreamExt;
+use serde::Deserialize;
+use tracing::debug;
+
+#[derive(Deserialize)]
+pub struct OpenRelayRequest {
+    /// The exact size of the transfer that will be relayed, in bytes.
+    size: u64,
+}
+
+/// Open a relay that pipes a transfer straight from its uploader to its downloader without storing it.
+///
+/// The downloader fetches the transfer from the usual download endpoint using the returned identifier.
+pub async fn open_relay_handler(
+    State(state): State<AppState>,
+    Json(request): Json<OpenRelayRequest>,
+) -> Result<(StatusCode, Json<ReserveTransferResponse>), ApiError> {
+    if state
+        .relay
+        .max_size()
+        .is_some_and(|max_size| request.size > max_size)
+    {
+        return Err(ApiError::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "transfer exceeds the server's maximum relay size",
+        ));
+    }
+    loop {
+        let id = state.transfer_storage.generate_unused_identifier()?;
+        if let Some(reservation) = state.relay.open(id.clone(), request.size) {
+            debug!("Opened relay with ID '{id}' for {} bytes", request.size);
+            return Ok((
+                StatusCode::CREATED,
+                Json(ReserveTransferResponse {
+                    expires_at: format_timestamp(reservation.expires_at)?,
+                    id,
+                    upload_token: reservation.upload_token,
+                }),
+            ));
+        }
+    }
+}
+
+/// Send the data for a relay opened with [`open_relay_handler`].
+///
+/// Responds once the downloader has received all of the data.
+pub async fn upload_relay_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<StatusCode, ApiError> {
+    let Some(upload_token) = headers
+        .get(UPLOAD_TOKEN_HEADER)
+        .and_then(|v| v.to_str().ok())
+    else {
+        return Err(ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            "an upload token is required to upload to a relay",
+        ));
+    };
+    let Some((size, sender)) = state.relay.connect_uploader(&id, upload_token) else {
+        return Err(ApiError::not_found(
+            "no matching relay exists, it may have lapsed or already be in use",
+        ));
+    };
+
+    let mut received = 0u64;
+    let mut stream = body.into_data_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|_| ApiError::bad_request("failed to read request body"))?;
+        received += chunk.len() as u64;
+        if received > size {
+            return Err(ApiError::new(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "relayed transfer is larger than the size it was opened with",
+            ));
+        }
+        match tokio::time::timeout(RELAY_WAIT_TIMEOUT, sender.send(RelayMessage::Data(chunk))).await
+        {
+            Ok(Ok(())) => {}
+            Ok(Err(_)) => {
+                return Err(ApiError::new(
+                    StatusCode::GONE,
+                    "the downloader disconnected before receiving all data",
+                ));
+            }
+            Err(_) => {
+                return Err(ApiError::new(
+                    StatusCode::REQUEST_TIMEOUT,
+                    "timed out waiting for the downloader to receive data",
+                ));
+            }
+        }
+    }
+    if received != size {
+        return Err(ApiError::bad_request(
+            "relayed transfer is smaller than the size it was opened with",
+        ));
+    }
+
+    // The downloader drops its end of the channel once it has read everything.
+    let delivered = tokio::time::timeout(RELAY_WAIT_TIMEOUT, async {
+        sender.send(RelayMessage::End).await.is_ok() && {
+            sender.closed().await;
+            true
+        }
+    })
+    .await;
+    if delivered != Ok(true) {
+        return Err(ApiError::new(
+            StatusCode::GONE,
+            "the downloader disconnected before receiving all data",
+        ));
+    }
+    debug!("Relayed {size} bytes (id: '{id}')");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Build the download response for a relay that is waiting for its downloader, if one matches.
+pub(super) fn relay_download_response(
+    state: &AppState,
+    id: &str,
+) -> Result<Option<Response<Body>>, ApiError> {
+    let Some((size, stream)) = state.relay.connect_downloader(id) else {
+        return Ok(None);
+    };
+    debug!("Downloader connected to relay (id: '{id}')");
+    Ok(Some(
+        Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "application/octet-stream")
+            .header(header::CONTENT_LENGTH, size)
+            .header(header::CACHE_CONTROL, "no-store")
+            .body(Body::from_stream(stream))
+            .context("Failed to build relay download response")?,
+    ))
+}
+
+/// Build the metadata response for a relay that is waiting for its downloader, if one matches.
+pub(super) fn relay_metadata_response(
+    state: &AppState,
+    id: &str,
+) -> Result<Option<Response<Body>>, ApiError> {
+    let Some(size) = state.relay.pending_size(id) else {
+        return Ok(None);
+    };
+    Ok(Some(
+        Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_LENGTH, size)
+            .header(header::CACHE_CONTROL, "no-store")
+            .body(Body::empty())
+            .context("Failed to build relay metadata response")?,
+    ))
+}
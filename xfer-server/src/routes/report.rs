@@ -0,0 +1,67 @@
+use crate::{AppState, audit::AuditAction, client_ip::ClientIp, error::ApiError};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+};
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+/// Maximum number of reports a single client can submit per [`REPORT_RATE_LIMIT_WINDOW`].
+const REPORT_RATE_LIMIT: u64 = 10;
+const REPORT_RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60 * 60);
+/// Maximum length of a report reason in bytes.
+pub const REPORT_REASON_MAX_LENGTH: usize = 2048;
+
+pub async fn report_transfer_handler(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    Path(id): Path<String>,
+    reason: String,
+) -> Result<(StatusCode, &'static str), ApiError> {
+    if !state.transfer_storage.validate_identifier(&id) {
+        return Err(ApiError::transfer_not_found());
+    }
+
+    match state
+        .transfer_storage
+        .state_store()
+        .rate_limit_hit(&format!("report:{client_ip}"), REPORT_RATE_LIMIT_WINDOW)
+    {
+        Ok((hits, _)) if hits > REPORT_RATE_LIMIT => {
+            return Err(ApiError::new(
+                StatusCode::TOO_MANY_REQUESTS,
+                "too many reports submitted recently, try again later",
+            ));
+        }
+        Ok(_) => {}
+        Err(err) => warn!("Failed to check report rate limit: {err:?}"),
+    }
+
+    if !state.transfer_storage.transfer_exists(&id)? {
+        return Err(ApiError::transfer_not_found());
+    }
+
+    let checksum = state
+        .transfer_storage
+        .state_store()
+        .get_metadata(&id)
+        .ok()
+        .flatten()
+        .and_then(|metadata| metadata.checksum);
+    let reason = reason.trim();
+    if let Err(err) = state.report_log.record(&id, checksum.as_deref(), reason) {
+        error!("Failed to record abuse report: {err:?}");
+        return Err(ApiError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "failed to record report server-side",
+        ));
+    }
+    info!("Abuse report received for transfer (id: '{id}')");
+    state
+        .audit_log
+        .record(AuditAction::Report, Some(&id), Some(client_ip));
+    Ok((
+        StatusCode::ACCEPTED,
+        "report received and will be reviewed by the server operator",
+    ))
+}
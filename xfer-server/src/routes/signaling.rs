@@ -0,0 +1,132 @@
+use super::transfer::format_timestamp;
+use crate::{
+    AppState,
+    error::ApiError,
+    signaling::{SendOutcome, SignalMessage},
+};
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Duration;
+
+/// Header clients use to send their signaling session token.
+const SIGNAL_TOKEN_HEADER: &str = "X-Xfer-Signal-Token";
+
+/// Maximum size of a single signaling message, in bytes.
+pub const SIGNAL_MESSAGE_MAX_SIZE: usize = 16 * 1024;
+
+/// Maximum amount of time a request for messages waits for one to arrive.
+const SIGNAL_MAX_WAIT: Duration = Duration::from_secs(25);
+
+#[derive(Serialize)]
+pub struct SignalSessionResponse {
+    pub id: String,
+    /// Secret to send in the [`SIGNAL_TOKEN_HEADER`] header in later requests.
+    pub token: String,
+    /// The time the session expires at, in RFC 3339 format.
+    pub expires_at: String,
+}
+
+#[derive(Serialize)]
+pub struct JoinSignalSessionResponse {
+    /// Secret to send in the [`SIGNAL_TOKEN_HEADER`] header in later requests.
+    pub token: String,
+}
+
+#[derive(Deserialize)]
+pub struct ReceiveSignalQuery {
+    /// Only return messages numbered after this one.
+    #[serde(default)]
+    after: u64,
+    /// How long to wait for a message to arrive, in seconds.
+    #[serde(default)]
+    wait: u64,
+}
+
+#[derive(Serialize)]
+pub struct ReceiveSignalResponse {
+    pub messages: Vec<SignalMessage>,
+}
+
+/// Create a signaling session for negotiating a direct connection with another peer.
+pub async fn create_signal_session_handler(
+    State(state): State<AppState>,
+) -> Result<(StatusCode, Json<SignalSessionResponse>), ApiError> {
+    let session = state.signaling.create();
+    Ok((
+        StatusCode::CREATED,
+        Json(SignalSessionResponse {
+            expires_at: format_timestamp(session.expires_at)?,
+            id: session.id,
+            token: session.token,
+        }),
+    ))
+}
+
+/// Join a signaling session created by another peer.
+pub async fn join_signal_session_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<JoinSignalSessionResponse>, ApiError> {
+    match state.signaling.join(&id) {
+        Some(token) => Ok(Json(JoinSignalSessionResponse { token })),
+        None => Err(ApiError::not_found(
+            "signaling session does not exist or has already been joined",
+        )),
+    }
+}
+
+/// Send a message to the other peer in a signaling session.
+pub async fn send_signal_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Json(message): Json<Value>,
+) -> Result<StatusCode, ApiError> {
+    match state.signaling.send(&id, signal_token(&headers)?, message) {
+        SendOutcome::Sent => Ok(StatusCode::NO_CONTENT),
+        SendOutcome::NotFound => Err(signal_session_not_found()),
+        SendOutcome::TooManyMessages => Err(ApiError::new(
+            StatusCode::TOO_MANY_REQUESTS,
+            "too many messages have been sent in this signaling session",
+        )),
+    }
+}
+
+/// Get the messages sent by the other peer in a signaling session, optionally waiting for one to arrive.
+pub async fn receive_signal_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<ReceiveSignalQuery>,
+    headers: HeaderMap,
+) -> Result<Json<ReceiveSignalResponse>, ApiError> {
+    let wait = Duration::from_secs(query.wait).min(SIGNAL_MAX_WAIT);
+    match state
+        .signaling
+        .receive(&id, signal_token(&headers)?, query.after, wait)
+        .await
+    {
+        Some(messages) => Ok(Json(ReceiveSignalResponse { messages })),
+        None => Err(signal_session_not_found()),
+    }
+}
+
+fn signal_token(headers: &HeaderMap) -> Result<&str, ApiError> {
+    headers
+        .get(SIGNAL_TOKEN_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(|| {
+            ApiError::new(
+                StatusCode::UNAUTHORIZED,
+                "a signaling session token is required",
+            )
+        })
+}
+
+fn signal_session_not_found() -> ApiError {
+    ApiError::not_found("signaling session does not exist, it may have expired")
+}
@@ -1,107 +1,1074 @@
-use crate::{AppState, storage::TransferStorage};
+use super::relay::{relay_download_response, relay_metadata_response};
+use crate::{
+    AppState,
+    audit::AuditAction,
+    client_ip::ClientIp,
+    download_slots::CONCURRENT_DOWNLOADS_RETRY_AFTER,
+    error::ApiError,
+    storage::{CreateTransferOptions, RedeemedReservation},
+    stream_guard::ThroughputGuard,
+    upload_token::UploadScope,
+};
+use anyhow::Context;
 use axum::{
-    Json,
-    body::Body,
+    Extension, Json,
+    body::{Body, Bytes},
     extract::{Path, State},
     http::{
-        Response, StatusCode,
+        HeaderMap, Response, StatusCode,
         header::{self},
     },
-    response::IntoResponse,
 };
-use serde::Serialize;
-use std::time::SystemTime;
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::{
+    net::IpAddr,
+    ops::RangeInclusive,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+use tokio_util::io::ReaderStream;
+use tracing::{Span, debug, error, info, warn};
+
+/// Header containing the time a transfer expires at, in RFC 3339 format.
+const EXPIRES_AT_HEADER: &str = "X-Xfer-ExpiresAt";
+
+/// Header clients must use to supply the upload token of a reserved transfer.
+pub(super) const UPLOAD_TOKEN_HEADER: &str = "X-Xfer-Upload-Token";
+
+/// Header uploaders must use to supply the activity token of a transfer when viewing its activity.
+const ACTIVITY_TOKEN_HEADER: &str = "X-Xfer-Activity-Token";
+
+/// Header uploaders must use to supply the deletion token of a transfer when deleting it.
+const DELETION_TOKEN_HEADER: &str = "X-Xfer-Deletion-Token";
+
+/// Header clients may use to supply a checksum of the transfer data, in the form `<algorithm>=<hex digest>`.
+const CHECKSUM_HEADER: &str = "X-Xfer-Checksum";
+
+/// Header uploaders may use to limit how many times a transfer can be downloaded.
+const MAX_DOWNLOADS_HEADER: &str = "X-Xfer-Max-Downloads";
+
+/// Header containing how many more times a transfer with a download limit can be downloaded.
+const DOWNLOADS_REMAINING_HEADER: &str = "X-Xfer-Downloads-Remaining";
+
+/// Header containing a token that lets a counted download of a transfer with a download limit be
+/// resumed after all of its downloads have been taken.
+const DOWNLOAD_TOKEN_HEADER: &str = "X-Xfer-Download-Token";
+
+/// Prefix of the identifiers sealed into download tokens, so they can't be used as download links.
+const DOWNLOAD_TOKEN_PREFIX: &str = "download:";
+
+/// How long a transfer is kept after its final allowed download starts, so that download can be resumed.
+const FINAL_DOWNLOAD_EXPIRE_WITHIN: Duration = Duration::from_secs(60 * 60);
+
+/// Largest encrypted manifest that can be stored for a transfer, in bytes.
+pub const MANIFEST_MAX_SIZE: usize = 1024 * 1024;
 
 #[derive(Serialize)]
 pub struct CreateTransferResponse {
     pub id: String,
+    /// The time the transfer expires at, in RFC 3339 format.
+    pub expires_at: String,
+    /// Secret to send in the [`ACTIVITY_TOKEN_HEADER`] header when viewing the transfer's activity.
+    pub activity_token: String,
+    /// Secret to send in the [`DELETION_TOKEN_HEADER`] header when deleting the transfer.
+    pub deletion_token: String,
+}
+
+#[derive(Serialize)]
+pub struct TransferActivityResponse {
+    /// The number of times the transfer has been downloaded.
+    pub download_count: u64,
+    /// The most recent times the transfer was downloaded, oldest first, in RFC 3339 format.
+    pub downloads: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ReserveTransferRequest {
+    /// The exact size of the transfer that will be uploaded, in bytes.
+    size: u64,
+}
+
+#[derive(Serialize)]
+pub struct TransferChunkResponse {
+    /// The number of bytes of the chunk that were stored.
+    pub size: u64,
+    /// The time the reservation now lapses if the upload isn't completed, in RFC 3339 format.
+    pub expires_at: String,
+}
+
+#[derive(Serialize)]
+pub struct ReserveTransferResponse {
+    pub id: String,
+    /// Secret to send in the [`UPLOAD_TOKEN_HEADER`] header when uploading the transfer's data.
+    pub upload_token: String,
+    /// The time the reservation lapses if the upload hasn't completed, in RFC 3339 format.
+    pub expires_at: String,
 }
 
 pub async fn create_transfer_handler(
     State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    scope: Option<Extension<UploadScope>>,
+    headers: HeaderMap,
     body: Body,
-) -> Result<(StatusCode, Json<CreateTransferResponse>), (StatusCode, &'static str)> {
-    let id = state
+) -> Result<(StatusCode, Json<CreateTransferResponse>), ApiError> {
+    let (max_size, expire_after) = upload_limits(&state, scope.as_deref(), &headers)?;
+    store_transfer(
+        &state,
+        client_ip,
+        &headers,
+        body,
+        max_size,
+        UploadSource::New,
+        expire_after,
+    )
+    .await
+}
+
+/// Allocate a transfer identifier before any data is sent, so oversized transfers
+/// are rejected early and clients know the identifier while uploading.
+pub async fn reserve_transfer_handler(
+    State(state): State<AppState>,
+    scope: Option<Extension<UploadScope>>,
+    headers: HeaderMap,
+    Json(request): Json<ReserveTransferRequest>,
+) -> Result<(StatusCode, Json<ReserveTransferResponse>), ApiError> {
+    let (max_size, expire_after) = upload_limits(&state, scope.as_deref(), &headers)?;
+    if request.size > max_size {
+        return Err(ApiError::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "transfer exceeds the server's maximum transfer size",
+        ));
+    }
+    ensure_storage_available(&state, request.size).await?;
+    let reservation = state
         .transfer_storage
-        .create_transfer(body.into_data_stream())
+        .reserve_transfer(request.size, expire_after)?;
+    Ok((
+        StatusCode::CREATED,
+        Json(ReserveTransferResponse {
+            expires_at: format_timestamp(reservation.expires_at)?,
+            id: reservation.id,
+            upload_token: reservation.upload_token,
+        }),
+    ))
+}
+
+/// Upload the data for a transfer previously reserved with [`reserve_transfer_handler`].
+pub async fn upload_reserved_transfer_handler(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<(StatusCode, Json<CreateTransferResponse>), ApiError> {
+    let reservation = redeem_reservation(&state, &id, &headers)?;
+    store_transfer(
+        &state,
+        client_ip,
+        &headers,
+        body,
+        reservation.size,
+        UploadSource::Reserved(id),
+        reservation.expire_after,
+    )
+    .await
+}
+
+/// Upload part of the data for a reserved transfer, starting `offset` bytes into it.
+///
+/// Chunks can be sent in any order and in parallel, and a failed chunk can be sent again.
+/// Each chunk pushes back when the reservation lapses.
+pub async fn upload_transfer_chunk_handler(
+    State(state): State<AppState>,
+    Path((id, offset)): Path<(String, u64)>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<Json<TransferChunkResponse>, ApiError> {
+    let reservation = redeem_reservation(&state, &id, &headers)?;
+    let size = state
+        .transfer_storage
+        .write_transfer_chunk(
+            &id,
+            offset,
+            reservation.size,
+            body.into_data_stream(),
+            state.stream_limits,
+        )
+        .await?;
+    let expires_at = state.transfer_storage.extend_reservation(&id)?;
+    Ok(Json(TransferChunkResponse {
+        size,
+        expires_at: format_timestamp(expires_at)?,
+    }))
+}
+
+/// Store the chunks uploaded with [`upload_transfer_chunk_handler`] as the reserved transfer's data.
+///
+/// A checksum of the whole transfer is required, so missing or corrupted chunks are always caught.
+pub async fn complete_chunked_transfer_handler(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<(StatusCode, Json<CreateTransferResponse>), ApiError> {
+    let reservation = redeem_reservation(&state, &id, &headers)?;
+    if !headers.contains_key(CHECKSUM_HEADER) {
+        return Err(ApiError::bad_request(
+            "a checksum is required to complete a chunked upload",
+        ));
+    }
+    let file = match state.transfer_storage.open_chunked_upload(&id).await {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return Err(ApiError::bad_request(
+                "no chunks have been uploaded for this transfer",
+            ));
+        }
+        Err(err) => {
+            return Err(anyhow::Error::from(err)
+                .context("Failed to open chunked upload")
+                .into());
+        }
+    };
+    let uploaded = file
+        .metadata()
         .await
-        .unwrap();
-    Ok((StatusCode::CREATED, Json(CreateTransferResponse { id })))
+        .context("Failed to read chunked upload metadata")?
+        .len();
+    if uploaded != reservation.size {
+        return Err(ApiError::bad_request(format!(
+            "uploaded chunks cover {uploaded} of the {} reserved bytes",
+            reservation.size
+        )));
+    }
+    store_transfer(
+        &state,
+        client_ip,
+        &headers,
+        Body::from_stream(ReaderStream::new(file)),
+        reservation.size,
+        UploadSource::Chunked(id),
+        reservation.expire_after,
+    )
+    .await
+}
+
+/// Check the upload token sent for a reserved transfer, returning the limits it was reserved with.
+fn redeem_reservation(
+    state: &AppState,
+    id: &str,
+    headers: &HeaderMap,
+) -> Result<RedeemedReservation, ApiError> {
+    if !state.transfer_storage.validate_identifier(id) {
+        return Err(ApiError::bad_request(
+            "transfer identifier failed to validate server-side",
+        ));
+    }
+    let Some(upload_token) = headers
+        .get(UPLOAD_TOKEN_HEADER)
+        .and_then(|v| v.to_str().ok())
+    else {
+        return Err(ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            "an upload token is required to upload a reserved transfer",
+        ));
+    };
+    state
+        .transfer_storage
+        .redeem_reservation(id, upload_token)?
+        .ok_or_else(|| {
+            ApiError::not_found(
+                "no matching reservation exists for this transfer, it may have lapsed",
+            )
+        })
+}
+
+/// Get the maximum transfer size and any expire-after override for an upload, taking the
+/// lifetime it requested and the limits of the upload token it was made with into account.
+fn upload_limits(
+    state: &AppState,
+    scope: Option<&UploadScope>,
+    headers: &HeaderMap,
+) -> Result<(u64, Option<Duration>), ApiError> {
+    let default_max_size = state.transfer_max_size.as_u64();
+    let (max_size, expiry, token_expire_after) = match scope {
+        Some(UploadScope(token)) => (
+            token.policy.max_size(default_max_size),
+            token.policy.expiry_bounds(state.transfer_expiry),
+            token.policy.max_expire_after_secs.map(Duration::from_secs),
+        ),
+        None => (default_max_size, state.transfer_expiry, None),
+    };
+    Ok((max_size, expiry.requested(headers)?.or(token_expire_after)))
+}
+
+/// Where the data of an upload comes from.
+enum UploadSource {
+    /// The request body of a new transfer.
+    New,
+    /// The request body of a reserved transfer.
+    Reserved(String),
+    /// Chunks already uploaded for a reserved transfer.
+    Chunked(String),
+}
+
+/// Store an uploaded transfer of at most `max_size` bytes, optionally under a reserved identifier
+/// and with a non-default lifetime.
+async fn store_transfer(
+    state: &AppState,
+    client_ip: IpAddr,
+    headers: &HeaderMap,
+    body: Body,
+    max_size: u64,
+    source: UploadSource,
+    expire_after: Option<Duration>,
+) -> Result<(StatusCode, Json<CreateTransferResponse>), ApiError> {
+    // Reject early when the client declares a size that is already over the limit.
+    // Chunked uploads are assembled from data that was already checked against the reserved size.
+    let declared_size = match source {
+        UploadSource::Chunked(_) => Some(max_size),
+        UploadSource::New | UploadSource::Reserved(_) => headers
+            .get(header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok()),
+    };
+    if declared_size.is_some_and(|len| len > max_size) {
+        return Err(ApiError::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "transfer exceeds the permitted transfer size",
+        ));
+    }
+
+    // Parse the optional client-supplied checksum.
+    let expected_checksum = match headers.get(CHECKSUM_HEADER) {
+        Some(value) => {
+            let Some(("blake3", digest)) = value.to_str().ok().and_then(|v| v.split_once('='))
+            else {
+                return Err(ApiError::bad_request(
+                    "checksum header must be in the format 'blake3=<hex digest>'",
+                ));
+            };
+            Some(blake3::Hash::from_hex(digest.trim()).map_err(|_| {
+                ApiError::bad_request("checksum header contained an invalid blake3 digest")
+            })?)
+        }
+        None => None,
+    };
+
+    state.content_check.check_declared_version(headers)?;
+    let claim_webhook = state.claim_webhooks.uploader_webhook(headers)?;
+    let notify_email = state.email_notifications.uploader_address(headers)?;
+    let max_downloads = match headers.get(MAX_DOWNLOADS_HEADER) {
+        Some(value) => Some(
+            value
+                .to_str()
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .filter(|max_downloads| *max_downloads > 0)
+                .ok_or_else(|| {
+                    ApiError::bad_request("max downloads header must be a positive whole number")
+                })?,
+        ),
+        None => None,
+    };
+
+    // Ensure there is enough space to store the transfer before accepting any data.
+    // When the client doesn't declare a size assume the worst case.
+    ensure_storage_available(state, declared_size.unwrap_or(max_size)).await?;
+
+    let activity_token = blake3::Hash::from_bytes(rand::random())
+        .to_hex()
+        .to_string();
+    let deletion_token = blake3::Hash::from_bytes(rand::random())
+        .to_hex()
+        .to_string();
+    let id = state
+        .transfer_storage
+        .create_transfer(
+            body.into_data_stream(),
+            CreateTransferOptions {
+                max_size,
+                expected_checksum,
+                stream_limits: state.stream_limits,
+                throttle: !matches!(source, UploadSource::Chunked(_)),
+                reserved_id: match source {
+                    UploadSource::New => None,
+                    UploadSource::Reserved(id) | UploadSource::Chunked(id) => Some(id),
+                },
+                expire_after,
+                content_check: state.content_check,
+                activity_token_hash: Some(
+                    blake3::hash(activity_token.as_bytes()).to_hex().to_string(),
+                ),
+                deletion_token_hash: Some(
+                    blake3::hash(deletion_token.as_bytes()).to_hex().to_string(),
+                ),
+                claim_webhook: claim_webhook.map(String::from),
+                notify_email: notify_email.as_ref().map(ToString::to_string),
+                max_downloads,
+            },
+        )
+        .await?;
+    if state
+        .blocklist
+        .is_transfer_blocked(&state.transfer_storage, &id)
+    {
+        warn!("Rejecting upload of blocked content (id: '{id}')");
+        if let Err(err) = state.transfer_storage.delete_transfer(&id) {
+            error!("Failed to remove blocked transfer (id: '{id}'): {err:?}");
+        }
+        state
+            .audit_log
+            .record(AuditAction::Block, Some(&id), Some(client_ip));
+        return Err(ApiError::new(
+            StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS,
+            "this content has been blocked by the server operator",
+        ));
+    }
+
+    let span = Span::current();
+    span.record("transfer_id", &id);
+    if let Ok(size) = state.transfer_storage.get_transfer_size(&id) {
+        span.record("bytes", size);
+        state.metrics.record_upload(size);
+        state.usage_statistics.record_upload(size);
+    }
+    state
+        .audit_log
+        .record(AuditAction::Upload, Some(&id), Some(client_ip));
+
+    // Replicate in the background so the upload doesn't wait on the replica's disk.
+    let storage = Arc::clone(&state.transfer_storage);
+    let replica_id = id.clone();
+    tokio::task::spawn_blocking(move || match storage.replicate_transfer(&replica_id) {
+        Ok(Some(_)) => debug!("Replicated transfer (id: '{replica_id}')"),
+        Ok(None) => {}
+        Err(err) => warn!("Failed to replicate transfer (id: '{replica_id}'): {err:?}"),
+    });
+
+    if let Ok(size) = state.transfer_storage.get_transfer_size(&id)
+        && let Ok(expires_at) = state.transfer_storage.get_transfer_expiry(&id)
+    {
+        state.email_notifications.notify_created(
+            &id,
+            size,
+            expires_at,
+            notify_email.map(|address| address.to_string()).as_deref(),
+        );
+    }
+
+    let expires_at = transfer_expires_at(state, &id)?;
+    Ok((
+        StatusCode::CREATED,
+        Json(CreateTransferResponse {
+            id,
+            expires_at,
+            activity_token,
+            deletion_token,
+        }),
+    ))
 }
 
 pub async fn download_transfer_handler(
     State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
     Path(id): Path<String>,
-) -> impl IntoResponse {
-    if !TransferStorage::validate_identifier(&id) {
-        return (
-            StatusCode::BAD_REQUEST,
-            "transfer identifier failed to validate server-side",
+    headers: HeaderMap,
+) -> Result<Response<Body>, ApiError> {
+    if let Some(response) = relay_download_response(&state, &id)? {
+        return Ok(response);
+    }
+    ensure_transfer_available(&state, &id)?;
+
+    let etag = transfer_etag(&state, &id);
+    if let Some(etag) = &etag
+        && headers
+            .get(header::IF_NONE_MATCH)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| etag_list_matches(v, etag))
+    {
+        return Ok(Response::builder()
+            .status(StatusCode::NOT_MODIFIED)
+            .header(header::ETAG, etag)
+            .header(header::CACHE_CONTROL, cache_control(&state, &id)?)
+            .header(EXPIRES_AT_HEADER, transfer_expires_at(&state, &id)?)
+            .body(Body::empty())
+            .context("Failed to build not modified response")?);
+    }
+
+    // Only honour a range request when any `If-Range` precondition still matches the current ETag.
+    let size = state.transfer_storage.get_transfer_size(&id)?;
+    let if_range_matches = match headers.get(header::IF_RANGE) {
+        Some(value) => etag
+            .as_deref()
+            .is_some_and(|etag| value.to_str().is_ok_and(|v| v.trim() == etag)),
+        None => true,
+    };
+    let range = match headers
+        .get(header::RANGE)
+        .filter(|_| if_range_matches)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| parse_range(v, size))
+    {
+        Some(Some(Ok(range))) => Some(range),
+        Some(Some(Err(()))) => {
+            return Ok(Response::builder()
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(header::CONTENT_RANGE, format!("bytes */{size}"))
+                .body(Body::empty())
+                .context("Failed to build range not satisfiable response")?);
+        }
+        Some(None) | None => None,
+    };
+
+    // Only downloads from the start of the transfer are counted, so interrupted downloads can be
+    // resumed. Once every download has been taken, only the downloads that took them can be.
+    let counted = range.as_ref().is_none_or(|range| *range.start() == 0);
+    if downloads_remaining(&state, &id)? == Some(0)
+        && (counted || !download_token_matches(&state, &id, &headers))
+    {
+        return Err(download_limit_reached());
+    }
+
+    let Some(slot) = state.download_slots.try_acquire(&id) else {
+        return Err(ApiError::new(
+            StatusCode::TOO_MANY_REQUESTS,
+            "too many downloads of this transfer are in progress",
         )
-            .into_response();
+        .with_retry_after(CONCURRENT_DOWNLOADS_RETRY_AFTER));
     };
+    let cache_control = cache_control(&state, &id)?;
+    let expires_at = transfer_expires_at(&state, &id)?;
+    // The slot is held until the response body has been sent or dropped.
+    let stream = state
+        .transfer_storage
+        .get_transfer(&id, range.clone())
+        .await?
+        .map(move |chunk| {
+            let _ = &slot;
+            chunk
+        });
+
+    let mut remaining = None;
+    if counted {
+        match state
+            .transfer_storage
+            .state_store()
+            .increment_download_count(&id)
+        {
+            Ok(count) => {
+                let metadata = state
+                    .transfer_storage
+                    .state_store()
+                    .get_metadata(&id)
+                    .ok()
+                    .flatten();
+                if let Some(max_downloads) = metadata
+                    .as_ref()
+                    .and_then(|metadata| metadata.max_downloads)
+                {
+                    // Another download may have taken the final one since it was checked above.
+                    if count > max_downloads {
+                        return Err(download_limit_reached());
+                    }
+                    remaining = Some(max_downloads - count);
+                    if count == max_downloads
+                        && let Err(err) = state
+                            .transfer_storage
+                            .expire_transfer_within(&id, FINAL_DOWNLOAD_EXPIRE_WITHIN)
+                    {
+                        warn!(
+                            "Failed to expire transfer after its final download (id: '{id}'): {err:?}"
+                        );
+                    }
+                }
+                if count == 1 {
+                    let claimed_at = SystemTime::now();
+                    state.claim_webhooks.notify_claimed(
+                        &id,
+                        claimed_at,
+                        metadata
+                            .as_ref()
+                            .and_then(|metadata| metadata.claim_webhook.as_deref()),
+                    );
+                    state.email_notifications.notify_downloaded(
+                        &id,
+                        claimed_at,
+                        metadata
+                            .as_ref()
+                            .and_then(|metadata| metadata.notify_email.as_deref()),
+                    );
+                }
+            }
+            Err(err) => {
+                warn!("Failed to increment download count for transfer (id: '{id}'): {err:?}")
+            }
+        }
+        if let Err(err) = state
+            .transfer_storage
+            .state_store()
+            .record_download_time(&id, SystemTime::now())
+        {
+            warn!("Failed to record download time for transfer (id: '{id}'): {err:?}");
+        }
+        state.usage_statistics.record_download();
+        state
+            .audit_log
+            .record(AuditAction::Download, Some(&id), Some(client_ip));
+    }
 
-    if !state.transfer_storage.transfer_exists(&id).unwrap() {
-        return StatusCode::NOT_FOUND.into_response();
+    let sent_bytes = range
+        .as_ref()
+        .map_or(size, |range| range.end() - range.start() + 1);
+    let span = Span::current();
+    span.record("transfer_id", &id);
+    span.record("bytes", sent_bytes);
+    state.metrics.record_download(sent_bytes);
+    let mut response = Response::builder()
+        .header(header::CONTENT_TYPE, "application/octet-stream")
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::CACHE_CONTROL, cache_control)
+        .header(EXPIRES_AT_HEADER, expires_at);
+    let remaining = match counted {
+        true => remaining,
+        false => downloads_remaining(&state, &id)?,
+    };
+    if let Some(remaining) = remaining {
+        response = response.header(DOWNLOADS_REMAINING_HEADER, remaining);
+        if counted {
+            let token = state.link_signer.sign(
+                &format!("{DOWNLOAD_TOKEN_PREFIX}{id}"),
+                state.transfer_storage.get_transfer_expiry(&id)?,
+            )?;
+            response = response.header(DOWNLOAD_TOKEN_HEADER, token);
+        }
     }
+    if let Some(etag) = &etag {
+        response = response.header(header::ETAG, etag);
+    }
+    response = match &range {
+        Some(range) => response
+            .status(StatusCode::PARTIAL_CONTENT)
+            .header(
+                header::CONTENT_RANGE,
+                format!("bytes {}-{}/{size}", range.start(), range.end()),
+            )
+            .header(header::CONTENT_LENGTH, sent_bytes),
+        None => response
+            .status(StatusCode::OK)
+            .header(header::CONTENT_LENGTH, size),
+    };
+    Ok(response
+        .body(Body::from_stream(ThroughputGuard::new(
+            stream,
+            state.stream_limits,
+        )))
+        .context("Failed to build transfer download response")?)
+}
 
-    Response::builder()
+/// Show the uploader of a transfer when and how often it has been downloaded.
+///
+/// Requires the activity token issued when the transfer was created.
+pub async fn transfer_activity_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<TransferActivityResponse>, ApiError> {
+    ensure_transfer_available(&state, &id)?;
+    let Some(activity_token) = headers
+        .get(ACTIVITY_TOKEN_HEADER)
+        .and_then(|v| v.to_str().ok())
+    else {
+        return Err(ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            "an activity token is required to view transfer activity",
+        ));
+    };
+    let store = state.transfer_storage.state_store();
+    let token_hash = store
+        .get_metadata(&id)?
+        .and_then(|metadata| metadata.activity_token_hash);
+    if !token_matches(token_hash, activity_token) {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            "the activity token is not valid for this transfer",
+        ));
+    }
+    Ok(Json(TransferActivityResponse {
+        download_count: store.get_download_count(&id)?,
+        downloads: store
+            .get_download_times(&id)?
+            .into_iter()
+            .map(format_timestamp)
+            .collect::<anyhow::Result<_>>()?,
+    }))
+}
+
+/// Delete a transfer before it expires.
+///
+/// Requires the deletion token issued when the transfer was created.
+pub async fn delete_transfer_handler(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<StatusCode, ApiError> {
+    ensure_transfer_available(&state, &id)?;
+    let Some(deletion_token) = headers
+        .get(DELETION_TOKEN_HEADER)
+        .and_then(|v| v.to_str().ok())
+    else {
+        return Err(ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            "a deletion token is required to delete a transfer",
+        ));
+    };
+    let token_hash = state
+        .transfer_storage
+        .state_store()
+        .get_metadata(&id)?
+        .and_then(|metadata| metadata.deletion_token_hash);
+    if !token_matches(token_hash, deletion_token) {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            "the deletion token is not valid for this transfer",
+        ));
+    }
+    state.transfer_storage.delete_transfer(&id)?;
+    state
+        .audit_log
+        .record(AuditAction::Delete, Some(&id), Some(client_ip));
+    info!("Transfer (id: '{id}') was deleted by its uploader");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Get the encrypted manifest of a transfer's contents, if its uploader stored one.
+///
+/// Transfers without a manifest respond with no content rather than not found, so fetching the
+/// manifest of a transfer that exists is never counted as a miss. Fetching the manifest doesn't
+/// count as a download of the transfer.
+pub async fn transfer_manifest_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Response<Body>, ApiError> {
+    ensure_transfer_available(&state, &id)?;
+    let Some(manifest) = state.transfer_storage.get_transfer_manifest(&id)? else {
+        return Ok(Response::builder()
+            .status(StatusCode::NO_CONTENT)
+            .header(header::CACHE_CONTROL, cache_control(&state, &id)?)
+            .body(Body::empty())
+            .context("Failed to build transfer manifest response")?);
+    };
+    Ok(Response::builder()
         .status(StatusCode::OK)
         .header(header::CONTENT_TYPE, "application/octet-stream")
-        .header(
-            header::CACHE_CONTROL,
-            format!(
-                "public, max-age={}, must-revalidate",
-                state
-                    .transfer_storage
-                    .get_transfer_expiry(&id)
-                    .unwrap()
-                    .duration_since(SystemTime::now())
-                    .map(|d| d.as_secs())
-                    .unwrap_or(0)
-            ),
-        )
-        .body(Body::from_stream(
-            state.transfer_storage.get_transfer(&id).await.unwrap(),
-        ))
-        .unwrap()
+        .header(header::CACHE_CONTROL, cache_control(&state, &id)?)
+        .body(Body::from(manifest))
+        .context("Failed to build transfer manifest response")?)
 }
 
-pub async fn transfer_metadata_handler(
+/// Store an encrypted manifest of a transfer's contents, replacing any stored before.
+///
+/// Requires the deletion token issued when the transfer was created.
+pub async fn upload_transfer_manifest_handler(
     State(state): State<AppState>,
     Path(id): Path<String>,
-) -> impl IntoResponse {
-    if !TransferStorage::validate_identifier(&id) {
-        return (
-            StatusCode::BAD_REQUEST,
-            "transfer identifier failed to validate server-side",
-        )
-            .into_response();
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<StatusCode, ApiError> {
+    ensure_transfer_available(&state, &id)?;
+    let Some(deletion_token) = headers
+        .get(DELETION_TOKEN_HEADER)
+        .and_then(|v| v.to_str().ok())
+    else {
+        return Err(ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            "a deletion token is required to store a transfer manifest",
+        ));
     };
+    let token_hash = state
+        .transfer_storage
+        .state_store()
+        .get_metadata(&id)?
+        .and_then(|metadata| metadata.deletion_token_hash);
+    if !token_matches(token_hash, deletion_token) {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            "the deletion token is not valid for this transfer",
+        ));
+    }
+    if body.is_empty() {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "the transfer manifest must not be empty",
+        ));
+    }
+    state.transfer_storage.set_transfer_manifest(&id, &body)?;
+    debug!("Stored manifest for transfer (id: '{id}')");
+    Ok(StatusCode::NO_CONTENT)
+}
 
-    if !state.transfer_storage.transfer_exists(&id).unwrap() {
-        return StatusCode::NOT_FOUND.into_response();
+pub async fn transfer_metadata_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Response<Body>, ApiError> {
+    if let Some(response) = relay_metadata_response(&state, &id)? {
+        return Ok(response);
     }
+    ensure_transfer_available(&state, &id)?;
 
-    Response::builder()
+    let mut response = Response::builder()
         .status(StatusCode::OK)
-        .header(
-            header::CACHE_CONTROL,
-            format!(
-                "public, max-age={}, must-revalidate",
-                state
-                    .transfer_storage
-                    .get_transfer_expiry(&id)
-                    .unwrap()
-                    .duration_since(SystemTime::now())
-                    .map(|d| d.as_secs())
-                    .unwrap_or(0)
-            ),
-        )
+        .header(header::CACHE_CONTROL, cache_control(&state, &id)?)
+        .header(EXPIRES_AT_HEADER, transfer_expires_at(&state, &id)?)
+        .header(header::ACCEPT_RANGES, "bytes")
         .header(
             header::CONTENT_LENGTH,
-            state.transfer_storage.get_transfer_size(&id).unwrap(),
-        )
+            state.transfer_storage.get_transfer_size(&id)?,
+        );
+    if let Some(etag) = transfer_etag(&state, &id) {
+        response = response.header(header::ETAG, etag);
+    }
+    if let Some(remaining) = downloads_remaining(&state, &id)? {
+        response = response.header(DOWNLOADS_REMAINING_HEADER, remaining);
+    }
+    Ok(response
         .body(Body::empty())
-        .unwrap()
+        .context("Failed to build transfer metadata response")?)
+}
+
+/// Get how many more times a transfer can be downloaded, or `None` when its downloads aren't limited.
+fn downloads_remaining(state: &AppState, id: &str) -> anyhow::Result<Option<u64>> {
+    let store = state.transfer_storage.state_store();
+    let Some(max_downloads) = store
+        .get_metadata(id)?
+        .and_then(|metadata| metadata.max_downloads)
+    else {
+        return Ok(None);
+    };
+    Ok(Some(
+        max_downloads.saturating_sub(store.get_download_count(id)?),
+    ))
+}
+
+/// Whether a request carries a download token issued by a counted download of the transfer.
+fn download_token_matches(state: &AppState, id: &str, headers: &HeaderMap) -> bool {
+    headers
+        .get(DOWNLOAD_TOKEN_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|token| state.link_signer.verify(token))
+        .and_then(|sealed| {
+            sealed
+                .strip_prefix(DOWNLOAD_TOKEN_PREFIX)
+                .map(str::to_string)
+        })
+        .is_some_and(|sealed| sealed == id)
+}
+
+/// The error returned for downloads of a transfer that has already been downloaded as many times as allowed.
+fn download_limit_reached() -> ApiError {
+    ApiError::new(
+        StatusCode::GONE,
+        "this transfer has reached its download limit",
+    )
+}
+
+/// Ensure there is at least `required_space` bytes of storage available for a new transfer.
+async fn ensure_storage_available(state: &AppState, required_space: u64) -> Result<(), ApiError> {
+    match state.transfer_storage.available_space() {
+        Ok(available) if available < required_space => {
+            warn!(
+                "Rejecting transfer as storage is full (required: {required_space}, available: {available})"
+            );
+            // Expired transfers are only removed by the next sweep after they expire.
+            // Estimating this can mean scanning every stored transfer, so keep it off the async workers.
+            let storage = Arc::clone(&state.transfer_storage);
+            let space_available_at =
+                tokio::task::spawn_blocking(move || storage.space_available_at(required_space))
+                    .await
+                    .map_err(anyhow::Error::from)
+                    .and_then(|result| result);
+            match space_available_at {
+                Ok(Some(available_at)) => Err(ApiError::new(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "server storage is currently full, try again once space has been freed",
+                )
+                .with_retry_after(
+                    available_at
+                        .duration_since(SystemTime::now())
+                        .unwrap_or_default()
+                        + state.sweep_interval,
+                )),
+                Ok(None) => Err(ApiError::new(
+                    StatusCode::INSUFFICIENT_STORAGE,
+                    "server does not have enough storage space for this transfer, try again with a smaller transfer",
+                )),
+                Err(err) => {
+                    warn!("Unable to estimate when storage space will be freed: {err:?}");
+                    Err(ApiError::new(
+                        StatusCode::INSUFFICIENT_STORAGE,
+                        "server does not currently have enough storage space for this transfer, try again later or with a smaller transfer",
+                    ))
+                }
+            }
+        }
+        Ok(_) => Ok(()),
+        Err(err) => {
+            warn!("Unable to check available storage space: {err:?}");
+            Ok(())
+        }
+    }
+}
+
+/// Ensure the given identifier is valid and refers to a stored transfer that hasn't been blocked.
+pub(super) fn ensure_transfer_available(state: &AppState, id: &str) -> Result<(), ApiError> {
+    if !state.transfer_storage.validate_identifier(id) {
+        return Err(ApiError::transfer_not_found());
+    }
+    // Sweeps remove expired transfers gradually, so one may still be stored for a while after it expires.
+    if !state.transfer_storage.transfer_exists(id)?
+        || state.transfer_storage.is_transfer_expired(id)?
+    {
+        return Err(ApiError::transfer_not_found());
+    }
+    if state
+        .blocklist
+        .is_transfer_blocked(&state.transfer_storage, id)
+    {
+        return Err(ApiError::new(
+            StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS,
+            "this transfer has been blocked by the server operator",
+        ));
+    }
+    Ok(())
+}
+
+/// Build the `Cache-Control` header value for a transfer so caches never outlive its expiry.
+fn cache_control(state: &AppState, id: &str) -> anyhow::Result<String> {
+    Ok(format!(
+        "public, max-age={}, must-revalidate",
+        state
+            .transfer_storage
+            .get_transfer_expiry(id)?
+            .duration_since(SystemTime::now())
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+    ))
+}
+
+/// Get the time a transfer expires at in RFC 3339 format.
+fn transfer_expires_at(state: &AppState, id: &str) -> anyhow::Result<String> {
+    format_timestamp(state.transfer_storage.get_transfer_expiry(id)?)
+}
+
+/// Format a timestamp in RFC 3339 format.
+pub(super) fn format_timestamp(time: SystemTime) -> anyhow::Result<String> {
+    OffsetDateTime::from(time)
+        .format(&Rfc3339)
+        .context("Failed to format timestamp")
+}
+
+/// Get the strong ETag for a transfer from its stored checksum, if it has one.
+pub(super) fn transfer_etag(state: &AppState, id: &str) -> Option<String> {
+    state
+        .transfer_storage
+        .state_store()
+        .get_metadata(id)
+        .ok()
+        .flatten()
+        .and_then(|metadata| metadata.checksum)
+        .map(|checksum| format!("\"{checksum}\""))
+}
+
+/// Whether a token matches the hex-encoded BLAKE3 hash stored for it.
+fn token_matches(hash: Option<String>, token: &str) -> bool {
+    hash.and_then(|hash| blake3::Hash::from_hex(hash).ok())
+        .is_some_and(|hash| hash == blake3::hash(token.as_bytes()))
+}
+
+/// Whether an `If-None-Match` header value matches the given ETag using weak comparison.
+fn etag_list_matches(header_value: &str, etag: &str) -> bool {
+    header_value.split(',').map(str::trim).any(|candidate| {
+        candidate == "*" || candidate.strip_prefix("W/").unwrap_or(candidate) == etag
+    })
+}
+
+/// Parse a `Range` header value against a resource of the given size.
+///
+/// Returns `None` when the header should be ignored (unsupported unit or multiple ranges),
+/// `Some(Err(()))` when the range is unsatisfiable, and the inclusive byte range otherwise.
+fn parse_range(header_value: &str, size: u64) -> Option<Result<RangeInclusive<u64>, ()>> {
+    let spec = header_value.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') {
+        return None;
+    }
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            let suffix = suffix.parse::<u64>().ok()?;
+            if suffix == 0 {
+                return Some(Err(()));
+            }
+            (size.saturating_sub(suffix), size.checked_sub(1)?)
+        }
+        (start, "") => (start.parse().ok()?, size.checked_sub(1)?),
+        (start, end) => (
+            start.parse().ok()?,
+            end.parse::<u64>().ok()?.min(size.saturating_sub(1)),
+        ),
+    };
+    if start >= size || start > end {
+        return Some(Err(()));
+    }
+    Some(Ok(start..=end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_range_reads_byte_ranges() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Some(Ok(0..=99)));
+        assert_eq!(parse_range("bytes=100-", 1000), Some(Ok(100..=999)));
+        assert_eq!(parse_range("bytes=-100", 1000), Some(Ok(900..=999)));
+        assert_eq!(parse_range(" bytes= 5 - 9 ", 1000), Some(Ok(5..=9)));
+    }
+
+    #[test]
+    fn parse_range_clamps_to_size() {
+        assert_eq!(parse_range("bytes=900-5000", 1000), Some(Ok(900..=999)));
+        assert_eq!(parse_range("bytes=-5000", 1000), Some(Ok(0..=999)));
+    }
+
+    #[test]
+    fn parse_range_rejects_unsatisfiable_ranges() {
+        assert_eq!(parse_range("bytes=1000-", 1000), Some(Err(())));
+        assert_eq!(parse_range("bytes=1000-1999", 1000), Some(Err(())));
+        assert_eq!(parse_range("bytes=10-5", 1000), Some(Err(())));
+        assert_eq!(parse_range("bytes=-0", 1000), Some(Err(())));
+    }
+
+    #[test]
+    fn parse_range_ignores_unsupported_ranges() {
+        assert_eq!(parse_range("items=0-99", 1000), None);
+        assert_eq!(parse_range("bytes=0-1,5-6", 1000), None);
+        assert_eq!(parse_range("bytes=abc-", 1000), None);
+        assert_eq!(parse_range("bytes=0", 1000), None);
+        assert_eq!(parse_range("bytes=0-", 0), None);
+    }
+
+    #[test]
+    fn etag_list_matches_any_listed_etag() {
+        let etag = "\"abc\"";
+        assert!(etag_list_matches("\"abc\"", etag));
+        assert!(etag_list_matches("\"def\", \"abc\"", etag));
+        assert!(etag_list_matches("W/\"abc\"", etag));
+        assert!(etag_list_matches("*", etag));
+        assert!(!etag_list_matches("\"def\"", etag));
+        assert!(!etag_list_matches("abc", etag));
+    }
 }
@@ -0,0 +1,5 @@
+use axum::response::Html;
+
+pub async fn web_download_page_handler() -> Html<&'static str> {
+    Html(include_str!("web_download.html"))
+}
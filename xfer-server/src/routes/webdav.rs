@@ -0,0 +1,154 @@
+use super::transfer::{
+    download_transfer_handler, ensure_transfer_available, transfer_etag, transfer_metadata_handler,
+};
+use crate::{AppState, client_ip::ClientIp, error::ApiError};
+use anyhow::Context;
+use axum::{
+    body::Body,
+    extract::{OriginalUri, Path, State},
+    http::{HeaderMap, Method, Response, StatusCode, header},
+};
+use std::{fmt::Write, time::SystemTime};
+use time::OffsetDateTime;
+
+/// Methods allowed on the read-only WebDAV endpoint.
+const WEBDAV_ALLOW: &str = "OPTIONS, GET, HEAD, PROPFIND";
+
+/// Answer WebDAV requests for the root collection.
+///
+/// The collection is always listed as empty so mounting it can't be used to enumerate transfers.
+/// Transfers are still reachable by opening their identifier within it.
+pub async fn webdav_collection_handler(
+    method: Method,
+    OriginalUri(uri): OriginalUri,
+) -> Result<Response<Body>, ApiError> {
+    match method.as_str() {
+        "OPTIONS" => options_response(),
+        "GET" | "HEAD" => Ok(Response::builder()
+            .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+            .body(Body::from(
+                "Open a transfer by its identifier within this collection.\n",
+            ))
+            .context("Failed to build WebDAV collection response")?),
+        "PROPFIND" => {
+            multistatus_response(&response_entry(uri.path(), "", "<D:collection/>", None))
+        }
+        _ => method_not_allowed(),
+    }
+}
+
+/// Answer WebDAV requests for a single transfer, which is exposed as a read-only file.
+pub async fn webdav_transfer_handler(
+    state: State<AppState>,
+    client_ip: ClientIp,
+    method: Method,
+    OriginalUri(uri): OriginalUri,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response<Body>, ApiError> {
+    match method.as_str() {
+        "OPTIONS" => options_response(),
+        "GET" => download_transfer_handler(state, client_ip, Path(id), headers).await,
+        "HEAD" => transfer_metadata_handler(state, Path(id)).await,
+        "PROPFIND" => {
+            ensure_transfer_available(&state, &id)?;
+            let mut props = String::new();
+            let _ = write!(
+                props,
+                "<D:getcontentlength>{}</D:getcontentlength><D:getcontenttype>application/octet-stream</D:getcontenttype>",
+                state.transfer_storage.get_transfer_size(&id)?
+            );
+            if let Some(etag) = transfer_etag(&state, &id) {
+                let _ = write!(props, "<D:getetag>{}</D:getetag>", escape_xml(&etag));
+            }
+            let created_at = state
+                .transfer_storage
+                .state_store()
+                .get_metadata(&id)?
+                .map(|metadata| metadata.created_at);
+            multistatus_response(&response_entry(
+                uri.path(),
+                &id,
+                "",
+                Some((props, created_at)),
+            ))
+        }
+        _ => method_not_allowed(),
+    }
+}
+
+fn options_response() -> Result<Response<Body>, ApiError> {
+    Ok(Response::builder()
+        .header("DAV", "1")
+        .header(header::ALLOW, WEBDAV_ALLOW)
+        .body(Body::empty())
+        .context("Failed to build WebDAV options response")?)
+}
+
+fn method_not_allowed() -> Result<Response<Body>, ApiError> {
+    Ok(Response::builder()
+        .status(StatusCode::METHOD_NOT_ALLOWED)
+        .header(header::ALLOW, WEBDAV_ALLOW)
+        .body(Body::empty())
+        .context("Failed to build WebDAV method not allowed response")?)
+}
+
+/// Build a `<D:response>` element describing a single resource.
+fn response_entry(
+    href: &str,
+    name: &str,
+    resource_type: &str,
+    file: Option<(String, Option<SystemTime>)>,
+) -> String {
+    let mut props = format!(
+        "<D:displayname>{}</D:displayname><D:resourcetype>{resource_type}</D:resourcetype>",
+        escape_xml(name)
+    );
+    if let Some((file_props, created_at)) = file {
+        props.push_str(&file_props);
+        if let Some(created_at) = created_at {
+            let _ = write!(
+                props,
+                "<D:getlastmodified>{}</D:getlastmodified>",
+                http_date(created_at)
+            );
+        }
+    }
+    format!(
+        "<D:response><D:href>{}</D:href><D:propstat><D:prop>{props}</D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>",
+        escape_xml(href)
+    )
+}
+
+fn multistatus_response(entries: &str) -> Result<Response<Body>, ApiError> {
+    Ok(Response::builder()
+        .status(StatusCode::MULTI_STATUS)
+        .header(header::CONTENT_TYPE, "application/xml; charset=utf-8")
+        .body(Body::from(format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:multistatus xmlns:D=\"DAV:\">{entries}</D:multistatus>\n"
+        )))
+        .context("Failed to build WebDAV multistatus response")?)
+}
+
+/// Format a timestamp as an HTTP date, such as `Sun, 06 Nov 1994 08:49:37 GMT`.
+fn http_date(time: SystemTime) -> String {
+    let time = OffsetDateTime::from(time);
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        &time.weekday().to_string()[..3],
+        time.day(),
+        &time.month().to_string()[..3],
+        time.year(),
+        time.hour(),
+        time.minute(),
+        time.second()
+    )
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
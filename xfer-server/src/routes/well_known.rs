@@ -0,0 +1,35 @@
+use super::transfer::format_timestamp;
+use crate::{AppState, error::ApiError};
+use axum::{extract::State, http::header, response::IntoResponse};
+use std::{
+    fmt::Write,
+    time::{Duration, SystemTime},
+};
+
+/// How far ahead of the current time the `Expires` field of `security.txt` is set.
+const SECURITY_TXT_LIFETIME: Duration = Duration::from_secs(180 * 24 * 60 * 60);
+
+/// Ask all crawlers to stay away, as nothing served by xfer is meant to be indexed.
+pub async fn robots_txt_handler() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+        "User-agent: *\nDisallow: /\n",
+    )
+}
+
+/// Serve an RFC 9116 `security.txt` listing the operator's security contacts.
+pub async fn security_txt_handler(
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    let mut body = String::new();
+    for contact in state.security_contacts.iter() {
+        let _ = writeln!(body, "Contact: {contact}");
+    }
+    let _ = writeln!(
+        body,
+        "Expires: {}",
+        format_timestamp(SystemTime::now() + SECURITY_TXT_LIFETIME)?
+    );
+    let _ = writeln!(body, "Preferred-Languages: en");
+    Ok(([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], body))
+}
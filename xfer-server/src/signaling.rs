@@ -0,0 +1,244 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    pin::pin,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+use tokio::sync::Notify;
+use uuid::Uuid;
+
+/// How long a signaling session lasts after it is created.
+pub const SIGNAL_SESSION_EXPIRE_AFTER: Duration = Duration::from_secs(10 * 60);
+
+/// Maximum number of messages each side of a session can send.
+const SIGNAL_MAX_MESSAGES: usize = 64;
+
+/// A side of a signaling session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignalSide {
+    /// The peer that created the session, usually the uploader.
+    Initiator,
+    /// The peer that joined the session, usually the downloader.
+    Responder,
+}
+
+/// A message sent by one side of a signaling session to the other.
+#[derive(Debug, Clone, Serialize)]
+pub struct SignalMessage {
+    /// Position of the message in the order the recipient received them, starting from 1.
+    pub seq: u64,
+    pub message: Value,
+}
+
+/// The result of sending a message with [`Signaling::send`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum SendOutcome {
+    Sent,
+    /// No session matches the identifier and token.
+    NotFound,
+    /// The sender has already sent [`SIGNAL_MAX_MESSAGES`] messages.
+    TooManyMessages,
+}
+
+#[derive(Debug, Default)]
+struct Inbox {
+    messages: Vec<SignalMessage>,
+    notify: Arc<Notify>,
+}
+
+#[derive(Debug)]
+struct SignalSession {
+    initiator_token_hash: blake3::Hash,
+    responder_token_hash: Option<blake3::Hash>,
+    expires_at: SystemTime,
+    initiator_inbox: Inbox,
+    responder_inbox: Inbox,
+}
+
+impl SignalSession {
+    /// Get the side a token belongs to.
+    fn side(&self, token: &str) -> Option<SignalSide> {
+        // Compare hashes so the comparison runs in constant time.
+        let hash = blake3::hash(token.as_bytes());
+        if hash == self.initiator_token_hash {
+            Some(SignalSide::Initiator)
+        } else if self.responder_token_hash == Some(hash) {
+            Some(SignalSide::Responder)
+        } else {
+            None
+        }
+    }
+
+    fn inbox(&mut self, side: SignalSide) -> &mut Inbox {
+        match side {
+            SignalSide::Initiator => &mut self.initiator_inbox,
+            SignalSide::Responder => &mut self.responder_inbox,
+        }
+    }
+}
+
+/// A signaling session created with [`Signaling::create`].
+#[derive(Debug)]
+pub struct SignalSessionGrant {
+    pub id: String,
+    /// Secret identifying the initiator in later requests.
+    pub token: String,
+    pub expires_at: SystemTime,
+}
+
+/// Relays connection negotiation messages (such as WebRTC offers, answers and ICE candidates) between
+/// two peers so they can establish a direct connection, only falling back to the server if that fails.
+///
+/// Messages are opaque to the server and sessions are held in memory, so both peers must reach the
+/// same instance.
+#[derive(Debug)]
+pub struct Signaling {
+    enabled: bool,
+    sessions: Mutex<HashMap<String, SignalSession>>,
+}
+
+impl Signaling {
+    /// Create a new [`Signaling`].
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            sessions: Mutex::default(),
+        }
+    }
+
+    /// Whether signaling sessions can be created.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Create a new session, returning its identifier and the initiator's token.
+    pub fn create(&self) -> SignalSessionGrant {
+        let token = Self::generate_secret();
+        let expires_at = SystemTime::now() + SIGNAL_SESSION_EXPIRE_AFTER;
+        let mut sessions = self.sessions.lock().unwrap();
+        Self::remove_expired(&mut sessions);
+        let id = loop {
+            let id = Uuid::now_v7().simple().to_string();
+            if !sessions.contains_key(&id) {
+                break id;
+            }
+        };
+        sessions.insert(
+            id.clone(),
+            SignalSession {
+                initiator_token_hash: blake3::hash(token.as_bytes()),
+                responder_token_hash: None,
+                expires_at,
+                initiator_inbox: Inbox::default(),
+                responder_inbox: Inbox::default(),
+            },
+        );
+        SignalSessionGrant {
+            id,
+            token,
+            expires_at,
+        }
+    }
+
+    /// Join a session as its responder, returning the responder's token or `None` if the session
+    /// doesn't exist or has already been joined.
+    pub fn join(&self, id: &str) -> Option<String> {
+        let mut sessions = self.sessions.lock().unwrap();
+        Self::remove_expired(&mut sessions);
+        let session = sessions.get_mut(id)?;
+        if session.responder_token_hash.is_some() {
+            return None;
+        }
+        let token = Self::generate_secret();
+        session.responder_token_hash = Some(blake3::hash(token.as_bytes()));
+        Some(token)
+    }
+
+    /// Send a message to the other side of a session.
+    pub fn send(&self, id: &str, token: &str, message: Value) -> SendOutcome {
+        let mut sessions = self.sessions.lock().unwrap();
+        Self::remove_expired(&mut sessions);
+        let Some(session) = sessions.get_mut(id) else {
+            return SendOutcome::NotFound;
+        };
+        let Some(side) = session.side(token) else {
+            return SendOutcome::NotFound;
+        };
+        let inbox = session.inbox(match side {
+            SignalSide::Initiator => SignalSide::Responder,
+            SignalSide::Responder => SignalSide::Initiator,
+        });
+        if inbox.messages.len() >= SIGNAL_MAX_MESSAGES {
+            return SendOutcome::TooManyMessages;
+        }
+        inbox.messages.push(SignalMessage {
+            seq: inbox.messages.len() as u64 + 1,
+            message,
+        });
+        inbox.notify.notify_waiters();
+        SendOutcome::Sent
+    }
+
+    /// Get the messages sent to the holder of `token` after the message numbered `after`,
+    /// waiting up to `wait` for one to arrive if there are none yet.
+    ///
+    /// Returns `None` if no session matches the identifier and token.
+    pub async fn receive(
+        &self,
+        id: &str,
+        token: &str,
+        after: u64,
+        wait: Duration,
+    ) -> Option<Vec<SignalMessage>> {
+        let deadline = tokio::time::Instant::now() + wait;
+        loop {
+            let (_, notify) = self.pending(id, token, after)?;
+            // Start listening before checking for messages so one sent in between isn't missed.
+            let mut notified = pin!(notify.notified());
+            notified.as_mut().enable();
+            let (messages, _) = self.pending(id, token, after)?;
+            if !messages.is_empty() {
+                return Some(messages);
+            }
+            if tokio::time::timeout_at(deadline, notified).await.is_err() {
+                return Some(Vec::new());
+            }
+        }
+    }
+
+    /// Get the messages sent to the holder of `token` after the message numbered `after`,
+    /// along with the notifier for new messages.
+    fn pending(
+        &self,
+        id: &str,
+        token: &str,
+        after: u64,
+    ) -> Option<(Vec<SignalMessage>, Arc<Notify>)> {
+        let mut sessions = self.sessions.lock().unwrap();
+        Self::remove_expired(&mut sessions);
+        let session = sessions.get_mut(id)?;
+        let side = session.side(token)?;
+        let inbox = session.inbox(side);
+        let messages = inbox
+            .messages
+            .iter()
+            .skip(usize::try_from(after).unwrap_or(usize::MAX))
+            .cloned()
+            .collect();
+        Some((messages, Arc::clone(&inbox.notify)))
+    }
+
+    fn generate_secret() -> String {
+        blake3::Hash::from_bytes(rand::random())
+            .to_hex()
+            .to_string()
+    }
+
+    /// Remove sessions that have expired.
+    fn remove_expired(sessions: &mut HashMap<String, SignalSession>) {
+        let now = SystemTime::now();
+        sessions.retain(|_, session| session.expires_at > now);
+    }
+}
@@ -1,87 +1,808 @@
+use crate::{
+    content_check::{CONTENT_SAMPLE_SIZE, ContentCheckMode, TransferNotEncryptedError},
+    encryption::AtRestCipher,
+    expiry_index::ExpiryIndex,
+    identifier::IdentifierGenerator,
+    store::{StateStore, TransferMetadata, TransferPin},
+    stream_guard::StreamLimits,
+    throttle::BandwidthLimits,
+};
 use anyhow::{Context, Result};
-use axum::body::BodyDataStream;
-use futures_util::StreamExt;
-use rand::seq::IndexedRandom;
+use axum::body::{BodyDataStream, Bytes};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use futures_util::{StreamExt, stream::BoxStream};
+use serde::{Deserialize, Serialize};
 use std::{
-    fs::{self, File},
-    io::Write,
-    path::PathBuf,
-    time::{Duration, SystemTime},
+    collections::HashSet,
+    fs::{self, DirEntry, File, TryLockError},
+    io::{self, ErrorKind, Seek, SeekFrom, Write},
+    mem,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
 };
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
 use tokio_util::io::ReaderStream;
-use tracing::{debug, info, trace, warn};
+use tracing::{debug, error, info, trace, warn};
 
-const TRANSFER_IDENTIFIER_WORDS: usize = 4;
-const TRANSFER_IDENTIFIER_WORD_SEPARATOR: &str = "-";
+const SWEEP_LOCK_FILE_NAME: &str = ".sweep.lock";
+const PARTIAL_TRANSFER_EXTENSION: &str = "partial";
+const QUARANTINE_DIRECTORY_NAME: &str = ".quarantine";
+const DELETED_DIRECTORY_NAME: &str = ".deleted";
+const DELETED_RECORD_EXTENSION: &str = "json";
+const RESERVATION_EXTENSION: &str = "reserved";
+const CHUNKED_UPLOAD_EXTENSION: &str = "chunked";
+/// Number of hex characters of an identifier's hash used to name the shard directory its transfer is stored in.
+const SHARD_PREFIX_LENGTH: usize = 2;
+/// Number of shard directories a transfer directory can contain.
+const SHARD_COUNT: usize = 1 << (4 * SHARD_PREFIX_LENGTH);
+/// How long each sweep spends scanning shard directories for transfers, unless configured otherwise.
+const DEFAULT_SWEEP_BUDGET: Duration = Duration::from_secs(2);
+/// Size of each read from disk when streaming a download, unless configured otherwise.
+const DEFAULT_DOWNLOAD_CHUNK_SIZE: usize = 256 * 1024;
+/// How long the forecast of when stored transfers will free their space is reused for before being rebuilt.
+const SPACE_FORECAST_TTL: Duration = Duration::from_secs(30);
+/// When each transfer that can expire will free its space and how many bytes it frees, soonest first.
+type SpaceForecast = Arc<[(SystemTime, u64)]>;
+/// How long a reserved transfer identifier remains valid for if nothing is uploaded to it.
+pub const RESERVATION_EXPIRE_AFTER: Duration = Duration::from_secs(15 * 60);
+
+/// Error returned by [`TransferStorage::create_transfer`] when the incoming
+/// stream exceeds the permitted transfer size.
+#[derive(Debug)]
+pub struct TransferTooLargeError {
+    pub max_size: u64,
+}
+
+impl std::fmt::Display for TransferTooLargeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "transfer exceeded the maximum size of {} bytes",
+            self.max_size
+        )
+    }
+}
+
+impl std::error::Error for TransferTooLargeError {}
+
+/// Error returned by [`TransferStorage::create_transfer`] when the received data
+/// doesn't match the checksum supplied by the client.
+#[derive(Debug)]
+pub struct TransferChecksumMismatchError;
+
+impl std::fmt::Display for TransferChecksumMismatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "transfer data did not match the supplied checksum")
+    }
+}
+
+impl std::error::Error for TransferChecksumMismatchError {}
+
+/// Error returned by [`TransferStorage::create_transfer`] when the incoming stream
+/// stalls or falls below the minimum throughput.
+#[derive(Debug)]
+pub struct TransferStalledError;
+
+impl std::fmt::Display for TransferStalledError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "transfer stream stalled or was too slow")
+    }
+}
+
+impl std::error::Error for TransferStalledError {}
+
+/// Options that control how [`TransferStorage::create_transfer`] accepts a transfer.
+#[derive(Debug, Clone)]
+pub struct CreateTransferOptions {
+    /// The maximum number of bytes the transfer may contain.
+    pub max_size: u64,
+    /// The BLAKE3 hash the transfer data is expected to have, if the client supplied one.
+    pub expected_checksum: Option<blake3::Hash>,
+    /// Limits used to drop stalled or excessively slow uploads.
+    pub stream_limits: StreamLimits,
+    /// Store the transfer under this previously reserved identifier instead of generating one.
+    pub reserved_id: Option<String>,
+    /// How long to keep the transfer for instead of the default expire-after duration.
+    pub expire_after: Option<Duration>,
+    /// How strictly the start of the transfer is checked for looking encrypted.
+    pub content_check: ContentCheckMode,
+    /// Hex-encoded BLAKE3 hash of the token that grants access to the transfer's activity.
+    pub activity_token_hash: Option<String>,
+    /// Hex-encoded BLAKE3 hash of the token that allows the uploader to delete the transfer.
+    pub deletion_token_hash: Option<String>,
+    /// URL the uploader asked to be notified at when the transfer is first downloaded.
+    pub claim_webhook: Option<String>,
+    /// Address the uploader asked to be emailed at about the transfer.
+    pub notify_email: Option<String>,
+    /// How many times the transfer may be downloaded, if limited.
+    pub max_downloads: Option<u64>,
+    /// Whether the upload counts against the upload bandwidth limit.
+    ///
+    /// Transfers assembled from chunks were already throttled as each chunk was received.
+    pub throttle: bool,
+}
+
+/// Totals for the transfers currently held in storage.
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct StorageStatistics {
+    /// Number of stored transfers.
+    pub transfers: u64,
+    /// Number of bytes the stored transfer files occupy on disk.
+    pub bytes: u64,
+}
+
+/// A transfer identifier that has been allocated ahead of its data being uploaded.
+#[derive(Debug, Clone)]
+pub struct TransferReservation {
+    pub id: String,
+    /// Secret that must be presented when uploading the data for the reserved transfer.
+    pub upload_token: String,
+    /// The time the reservation lapses if no upload has completed.
+    pub expires_at: SystemTime,
+}
+
+/// Reservation details persisted while waiting for the upload.
+#[derive(Debug, Serialize, Deserialize)]
+struct ReservationRecord {
+    size: u64,
+    /// Hex-encoded BLAKE3 hash of the upload token.
+    upload_token_hash: String,
+    expires_at: SystemTime,
+    #[serde(default)]
+    transfer_expire_after: Option<Duration>,
+}
+
+/// The limits an upload must be stored with after redeeming its reservation.
+#[derive(Debug, Clone, Copy)]
+pub struct RedeemedReservation {
+    /// The exact number of bytes that were reserved.
+    pub size: u64,
+    /// How long to keep the transfer for instead of the default expire-after duration.
+    pub expire_after: Option<Duration>,
+}
+
+/// Statistics gathered during a single run of [`TransferStorage::collect_garbage`].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct GarbageCollectionStatistics {
+    /// Number of leftover partial upload files that were removed.
+    pub partial_removed: u64,
+    /// Number of empty transfer files that were removed.
+    pub empty_removed: u64,
+    /// Number of unrecognised entries moved into the quarantine directory.
+    pub quarantined: u64,
+    /// Number of entries that could not be inspected or cleaned up.
+    pub failed: u64,
+}
+
+/// Statistics gathered during a single run of [`TransferStorage::migrate_to_cold_storage`].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ColdMigrationStatistics {
+    /// Number of transfers moved into cold storage.
+    pub migrated: u64,
+    /// Number of bytes moved into cold storage.
+    pub bytes: u64,
+    /// Number of transfers that could not be moved.
+    pub failed: u64,
+}
+
+/// Statistics gathered during a single run of [`TransferStorage::verify_integrity`].
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct IntegrityStatistics {
+    /// Number of transfers whose data was re-hashed.
+    pub verified: u64,
+    /// Number of transfers skipped as no hash was recorded for them.
+    pub skipped: u64,
+    /// Identifiers of transfers whose data no longer matched its recorded hash.
+    pub corrupted: Vec<String>,
+    /// Number of transfers that could not be read.
+    pub failed: u64,
+}
+
+/// Statistics gathered during a single run of [`TransferStorage::remove_expired_transfers`].
+#[derive(Debug, Default, Clone)]
+pub struct SweepStatistics {
+    /// Number of transfers that were checked for expiry.
+    pub scanned: u64,
+    /// Number of transfers whose expiry is indexed after the sweep.
+    pub indexed: usize,
+    /// Whether the sweep finished a full pass over every shard directory.
+    pub completed_pass: bool,
+    /// Identifiers of the expired transfers that were removed.
+    pub removed: Vec<String>,
+    /// Number of transfers that could not be checked or removed.
+    pub failed: u64,
+    /// Number of deleted transfers permanently removed after their recovery window passed.
+    pub purged: u64,
+}
+
+/// A transfer that has been deleted but can still be recovered until its recovery window passes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeletedTransfer {
+    pub id: String,
+    pub deleted_at: SystemTime,
+    /// The transfer's metadata at the time it was deleted, restored along with it.
+    pub metadata: Option<TransferMetadata>,
+}
+
+/// Statistics gathered during a single run of [`TransferStorage::replicate_missing_transfers`].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ReplicationStatistics {
+    /// Number of transfers copied to the replica directory.
+    pub replicated: u64,
+    /// Number of bytes copied to the replica directory.
+    pub bytes: u64,
+    /// Number of transfers that could not be copied.
+    pub failed: u64,
+}
+
+/// Directories that transfer files are stored in.
+#[derive(Debug, Clone)]
+pub struct TransferDirectories {
+    /// Directory new transfers are written to.
+    pub primary: PathBuf,
+    /// Directory older transfers are moved to by [`TransferStorage::migrate_to_cold_storage`].
+    pub cold: Option<PathBuf>,
+    /// Directory every transfer is copied to, so it can still be served if the other directories are lost.
+    pub replica: Option<PathBuf>,
+}
 
 #[derive(Debug)]
 pub struct TransferStorage {
     base_dir: PathBuf,
+    cold_dir: Option<PathBuf>,
+    replica_dir: Option<PathBuf>,
+    state_store: Arc<dyn StateStore>,
     expire_after: Duration,
+    cipher: Option<AtRestCipher>,
+    bandwidth: BandwidthLimits,
+    identifiers: IdentifierGenerator,
+    deleted_retention: Option<Duration>,
+    download_chunk_size: usize,
+    expiry_index: ExpiryIndex,
+    sweep_budget: Duration,
+    /// Position of the next shard directory to scan, counting through every directory's shards in turn.
+    sweep_cursor: Mutex<usize>,
+    /// The most recent space forecast and when it was built.
+    space_forecast: Mutex<Option<(Instant, SpaceForecast)>>,
+    #[cfg(all(feature = "io-uring", target_os = "linux"))]
+    uring: Option<crate::uring::UringReader>,
 }
 
 impl TransferStorage {
-    /// Create a new [`TransferStorage`] using the provided base path and expire-after duration.
-    pub fn new(base_dir: PathBuf, expire_after: Duration) -> Result<Self> {
-        fs::create_dir_all(&base_dir)?;
+    /// Create a new [`TransferStorage`] using the provided base path, state store and expire-after duration.
+    ///
+    /// When a cipher is given, newly created transfers are encrypted with it before being written to disk.
+    /// Uploads and downloads are paced according to the given bandwidth limits, and new transfers
+    /// are given identifiers from the given generator.
+    ///
+    /// When a deleted retention is given, deleted and expired transfers are kept aside for that long
+    /// so they can be recovered, rather than being removed immediately.
+    pub fn new(
+        directories: TransferDirectories,
+        state_store: Arc<dyn StateStore>,
+        expire_after: Duration,
+        cipher: Option<AtRestCipher>,
+        bandwidth: BandwidthLimits,
+        identifiers: IdentifierGenerator,
+        deleted_retention: Option<Duration>,
+    ) -> Result<Self> {
+        fs::create_dir_all(&directories.primary)?;
+        if let Some(cold_dir) = &directories.cold {
+            fs::create_dir_all(cold_dir).context("Failed to create cold storage directory")?;
+        }
+        if let Some(replica_dir) = &directories.replica {
+            fs::create_dir_all(replica_dir).context("Failed to create replica directory")?;
+        }
+        for dir in std::iter::once(&directories.primary)
+            .chain(&directories.cold)
+            .chain(&directories.replica)
+        {
+            let migrated = Self::migrate_flat_layout(dir)
+                .with_context(|| format!("Failed to move transfers in {dir:?} into shards"))?;
+            if migrated > 0 {
+                info!("Moved {migrated} transfers in {dir:?} into shard directories");
+            }
+        }
         Ok(Self {
-            base_dir,
+            base_dir: directories.primary,
+            cold_dir: directories.cold,
+            replica_dir: directories.replica,
+            state_store,
             expire_after,
+            cipher,
+            bandwidth,
+            identifiers,
+            deleted_retention,
+            download_chunk_size: DEFAULT_DOWNLOAD_CHUNK_SIZE,
+            expiry_index: ExpiryIndex::default(),
+            sweep_budget: DEFAULT_SWEEP_BUDGET,
+            sweep_cursor: Mutex::new(0),
+            space_forecast: Mutex::new(None),
+            #[cfg(all(feature = "io-uring", target_os = "linux"))]
+            uring: None,
         })
     }
 
+    /// Read transfer files in chunks of the given size when serving downloads.
+    pub fn with_download_chunk_size(self, download_chunk_size: usize) -> Self {
+        Self {
+            download_chunk_size,
+            ..self
+        }
+    }
+
+    /// Spend at most the given time scanning shard directories for expired transfers in each sweep.
+    pub fn with_sweep_budget(self, sweep_budget: Duration) -> Self {
+        Self {
+            sweep_budget,
+            ..self
+        }
+    }
+
+    /// Read unencrypted transfer files through io_uring when serving downloads.
+    #[cfg(all(feature = "io-uring", target_os = "linux"))]
+    pub fn with_uring_reader(self, reader: crate::uring::UringReader) -> Self {
+        Self {
+            uring: Some(reader),
+            ..self
+        }
+    }
+
+    /// How long deleted transfers are kept for before being permanently removed, if they are kept at all.
+    pub fn deleted_retention(&self) -> Option<Duration> {
+        self.deleted_retention
+    }
+
+    /// Get the state store used for transfer metadata.
+    pub fn state_store(&self) -> &dyn StateStore {
+        self.state_store.as_ref()
+    }
+
     /// Check if the provided transfer has expired.
-    fn is_transfer_expired(&self, id: &str) -> Result<bool> {
+    pub fn is_transfer_expired(&self, id: &str) -> Result<bool> {
         Ok(self.get_transfer_expiry(id)? <= SystemTime::now())
     }
 
-    /// Generate a unique transfer identifier.
+    /// Validates that the given value is in the format new transfer identifiers are generated in.
+    /// Used for light validation of transfer identifiers when receiving them from clients.
+    pub fn validate_identifier(&self, id: &str) -> bool {
+        self.identifiers.validate(id)
+    }
+
+    /// Iterate over every directory transfer files are stored in, in the order they are looked up.
+    fn directories(&self) -> impl Iterator<Item = &PathBuf> {
+        std::iter::once(&self.base_dir)
+            .chain(&self.cold_dir)
+            .chain(&self.replica_dir)
+    }
+
+    /// List the identifiers of all stored transfers, including those in cold storage or only in the replica.
+    pub fn list_transfers(&self) -> Result<Vec<String>> {
+        let mut seen = HashSet::new();
+        let mut ids = Vec::new();
+        for dir in self.directories() {
+            for id in Self::list_directory(dir)
+                .with_context(|| format!("Failed to read transfer directory {dir:?}"))?
+            {
+                if seen.insert(id.clone()) {
+                    ids.push(id);
+                }
+            }
+        }
+        Ok(ids)
+    }
+
+    /// List the identifiers of the transfers stored in a directory's shards.
+    fn list_directory(dir: &Path) -> io::Result<Vec<String>> {
+        let mut ids = Vec::new();
+        for shard in Self::shard_directories(dir)? {
+            ids.extend(
+                fs::read_dir(shard)?
+                    .filter_map(|f| f.ok())
+                    .filter_map(|f| f.file_name().into_string().ok())
+                    .filter(|name| !name.starts_with('.')),
+            );
+        }
+        Ok(ids)
+    }
+
+    /// List the shard directories within a directory.
+    fn shard_directories(dir: &Path) -> io::Result<Vec<PathBuf>> {
+        Ok(fs::read_dir(dir)?
+            .filter_map(|f| f.ok())
+            .filter(|f| f.file_type().is_ok_and(|t| t.is_dir()))
+            .filter(|f| f.file_name().to_str().is_some_and(Self::is_shard_name))
+            .map(|f| f.path())
+            .collect())
+    }
+
+    /// Get the name of the shard directory a transfer is stored in, taken from the start of its identifier's hash.
+    ///
+    /// Spreading transfers over shards keeps directories small enough to stay fast with many transfers.
+    fn shard_name(id: &str) -> String {
+        blake3::hash(id.as_bytes()).to_hex()[..SHARD_PREFIX_LENGTH].to_string()
+    }
+
+    /// Whether a directory entry is named like a shard directory.
+    fn is_shard_name(name: &str) -> bool {
+        name.len() == SHARD_PREFIX_LENGTH
+            && name
+                .chars()
+                .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c))
+    }
+
+    /// Get the path of a transfer's file within one of the transfer directories.
+    fn sharded_path(dir: &Path, id: &str) -> PathBuf {
+        dir.join(Self::shard_name(id)).join(id)
+    }
+
+    /// Create the parent directory of a file that is about to be written, such as a shard directory.
+    fn create_parent_directory(path: &Path) -> io::Result<()> {
+        match path.parent() {
+            Some(parent) => fs::create_dir_all(parent),
+            None => Ok(()),
+        }
+    }
+
+    /// Move a completed transfer file into place without replacing a transfer already stored there.
+    ///
+    /// Linking fails when the destination exists, unlike renaming, so this can't race with another
+    /// upload finishing under the same identifier. The source file is left for the caller to remove.
+    /// Filesystems that don't support hard links fall back to renaming.
+    fn move_into_place(from: &Path, to: &Path) -> io::Result<()> {
+        match fs::hard_link(from, to) {
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    ErrorKind::Unsupported | ErrorKind::PermissionDenied
+                ) =>
+            {
+                if fs::exists(to)? {
+                    return Err(ErrorKind::AlreadyExists.into());
+                }
+                fs::rename(from, to)
+            }
+            result => result,
+        }
+    }
+
+    /// Move transfer files stored directly in a directory, as they were before transfers were
+    /// sharded, into their shard directories, returning the number of transfers moved.
+    fn migrate_flat_layout(dir: &Path) -> Result<u64> {
+        let mut migrated = 0;
+        for entry in fs::read_dir(dir)?.filter_map(|f| f.ok()) {
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            if !IdentifierGenerator::is_safe(&name) || !entry.file_type().is_ok_and(|t| t.is_file())
+            {
+                continue;
+            }
+            let path = Self::sharded_path(dir, &name);
+            Self::create_parent_directory(&path)?;
+            fs::rename(entry.path(), path).with_context(|| {
+                format!("Failed to move transfer (id: '{name}') into its shard")
+            })?;
+            migrated += 1;
+        }
+        Ok(migrated)
+    }
+
+    /// Get the path of a transfer's file, which is in cold storage if it has been migrated there,
+    /// or in the replica directory if the other copies have been lost.
     ///
-    /// Transfer identifiers are passphrases that are [`TRANSFER_IDENTIFIER_WORDS`] words long.
-    fn generate_transfer_identifier() -> String {
-        eff_wordlist::large::LIST
-            .choose_multiple(&mut rand::rng(), TRANSFER_IDENTIFIER_WORDS)
-            .map(|word| word.1)
-            .collect::<Vec<_>>()
-            .join(TRANSFER_IDENTIFIER_WORD_SEPARATOR)
+    /// Transfers are looked up in the main directory first so a transfer being migrated
+    /// is read from there until the migration completes.
+    fn transfer_path(&self, id: &str) -> PathBuf {
+        self.directories()
+            .map(|dir| Self::sharded_path(dir, id))
+            .find(|path| fs::exists(path).unwrap_or(false))
+            .unwrap_or_else(|| Self::sharded_path(&self.base_dir, id))
+    }
+
+    /// Copy a transfer into the replica directory if it isn't already there,
+    /// returning the number of bytes copied.
+    ///
+    /// Does nothing when no replica directory is configured.
+    pub fn replicate_transfer(&self, id: &str) -> Result<Option<u64>> {
+        let Some(replica_dir) = &self.replica_dir else {
+            return Ok(None);
+        };
+        let replica_path = Self::sharded_path(replica_dir, id);
+        if fs::exists(&replica_path)? {
+            return Ok(None);
+        }
+        Self::copy_file(&self.transfer_path(id), &replica_path)
     }
 
-    /// Validates that the given value is in the same format as [`Self::generate_transfer_identifier`]
-    /// would generate. Used for light validation of transfer identifiers when receiving them from clients.
-    pub fn validate_identifier(id: &str) -> bool {
-        let parts = id
-            .split(TRANSFER_IDENTIFIER_WORD_SEPARATOR)
-            .collect::<Vec<_>>();
-        parts.len() == TRANSFER_IDENTIFIER_WORDS && parts.iter().all(|word| !word.is_empty())
+    /// Copy every transfer that is missing from the replica directory into it,
+    /// such as those whose replication failed or was interrupted by a restart.
+    pub fn replicate_missing_transfers(&self) -> Result<ReplicationStatistics> {
+        let mut stats = ReplicationStatistics::default();
+        if self.replica_dir.is_none() {
+            return Ok(stats);
+        }
+        for id in self.list_transfers()? {
+            match self.replicate_transfer(&id) {
+                Ok(Some(bytes)) => {
+                    debug!("Replicated transfer (id: '{id}')");
+                    stats.replicated += 1;
+                    stats.bytes += bytes;
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    stats.failed += 1;
+                    warn!("Failed to replicate transfer (id: '{id}'): {err:?}");
+                }
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Move transfers created more than `older_than` ago from the main directory into cold storage.
+    ///
+    /// Transfers are copied before being removed from the main directory, so they remain
+    /// available throughout. Does nothing when no cold directory is configured.
+    pub fn migrate_to_cold_storage(&self, older_than: Duration) -> Result<ColdMigrationStatistics> {
+        let mut stats = ColdMigrationStatistics::default();
+        let Some(cold_dir) = &self.cold_dir else {
+            return Ok(stats);
+        };
+        for id in
+            Self::list_directory(&self.base_dir).context("Failed to read transfer directory")?
+        {
+            let path = Self::sharded_path(&self.base_dir, &id);
+            let created_at = match self.state_store.get_metadata(&id) {
+                Ok(Some(metadata)) => metadata.created_at,
+                _ => match fs::metadata(&path).and_then(|m| m.modified()) {
+                    Ok(modified) => modified,
+                    Err(_) => continue,
+                },
+            };
+            if created_at.elapsed().unwrap_or_default() < older_than {
+                continue;
+            }
+            match Self::move_file(&path, &Self::sharded_path(cold_dir, &id)) {
+                Ok(Some(bytes)) => {
+                    debug!("Moved transfer (id: '{id}') into cold storage");
+                    stats.migrated += 1;
+                    stats.bytes += bytes;
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    stats.failed += 1;
+                    warn!("Failed to move transfer (id: '{id}') into cold storage: {err:?}");
+                }
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Copy a file to another path via a hidden partial file and then remove the original,
+    /// returning the number of bytes moved or `None` if another instance is already moving it.
+    ///
+    /// Unlike a rename this works across filesystems.
+    fn move_file(path: &Path, destination: &Path) -> Result<Option<u64>> {
+        let bytes = Self::copy_file(path, destination)?;
+        if bytes.is_some() {
+            fs::remove_file(path).context("Failed to remove original file")?;
+        }
+        Ok(bytes)
     }
 
-    /// Iterates through all stored transfer files and removes expired ones.
-    pub fn remove_expired_transfers(&self) -> Result<()> {
-        fs::read_dir(&self.base_dir)
-            .unwrap()
+    /// Copy a file to another path via a hidden partial file next to it, returning the number
+    /// of bytes copied or `None` if another instance is already copying it.
+    fn copy_file(path: &Path, destination_path: &Path) -> Result<Option<u64>> {
+        let name = destination_path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let partial_path =
+            destination_path.with_file_name(format!(".{name}.{PARTIAL_TRANSFER_EXTENSION}"));
+        Self::create_parent_directory(&partial_path).context("Failed to create shard directory")?;
+        let mut destination = match File::create_new(&partial_path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => return Ok(None),
+            Err(err) => return Err(err).context("Failed to create partial file"),
+        };
+        let mut partial = PartialTransferGuard::new(partial_path.clone());
+        let bytes = match File::open(path) {
+            Ok(mut source) => {
+                io::copy(&mut source, &mut destination).context("Failed to copy transfer file")?
+            }
+            // The transfer was removed or moved by another instance.
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err).context("Failed to open transfer file"),
+        };
+        destination
+            .sync_all()
+            .context("Failed to flush copied file to disk")?;
+        drop(destination);
+        fs::rename(&partial_path, destination_path).context("Failed to move file into place")?;
+        partial.disarm();
+        Ok(Some(bytes))
+    }
+
+    /// Removes expired transfers, including those in cold storage or the replica.
+    ///
+    /// Transfers whose expiry is indexed are only checked once they are due. The shard directories are
+    /// also scanned a few at a time, within the sweep budget, to index transfers created by other
+    /// instances or before the server started, so each sweep does a bounded amount of work however
+    /// many transfers are stored.
+    ///
+    /// Failures for individual transfers are logged and counted instead of aborting the sweep.
+    ///
+    /// Only one instance sharing this storage will sweep at a time; if another instance
+    /// currently holds the sweep lock this returns immediately without checking any transfers.
+    pub fn remove_expired_transfers(&self) -> Result<SweepStatistics> {
+        let mut stats = SweepStatistics::default();
+        let lock_file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.base_dir.join(SWEEP_LOCK_FILE_NAME))
+            .context("Failed to open sweep lock file")?;
+        match lock_file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                debug!(
+                    "Skipping expired transfer check as another instance is already running one"
+                );
+                return Ok(stats);
+            }
+            Err(TryLockError::Error(err)) => {
+                return Err(err).context("Failed to acquire sweep lock");
+            }
+        }
+        // Lapsed reservations and their chunks are kept alongside the shards and cleaned up here too.
+        for file in fs::read_dir(&self.base_dir)
+            .context("Failed to read transfer directory")?
             .filter_map(|f| f.ok())
-            .for_each(|file| {
-                let Ok(file_name) = file.file_name().into_string() else {
-                    return;
-                };
-                match self.is_transfer_expired(&file_name) {
-                    Ok(expired) => {
-                        if expired {
-                            info!("Removing expired transfer (id: '{file_name}')");
-                            self.delete_transfer(&file_name).unwrap();
-                        }
-                    }
+        {
+            let Ok(file_name) = file.file_name().into_string() else {
+                continue;
+            };
+            if file_name.starts_with('.')
+                && file_name.ends_with(&format!(".{RESERVATION_EXTENSION}"))
+                && let Err(err) = self.remove_lapsed_reservation(&file.path())
+            {
+                warn!("Failed to remove lapsed reservation '{file_name}': {err:?}");
+            }
+            if let Some(id) = file_name
+                .strip_prefix('.')
+                .and_then(|name| name.strip_suffix(&format!(".{CHUNKED_UPLOAD_EXTENSION}")))
+                && !fs::exists(self.reservation_path(id)).unwrap_or(true)
+            {
+                self.remove_chunked_upload(id);
+            }
+        }
+        for id in self.expiry_index.take_due(SystemTime::now()) {
+            self.sweep_transfer(id, &mut stats);
+        }
+        self.scan_shards(&mut stats);
+        if self.deleted_retention.is_some() {
+            self.purge_deleted_transfers(&mut stats)?;
+        }
+        stats.indexed = self.expiry_index.len();
+        Ok(stats)
+    }
+
+    /// Sweep the transfers in the next shard directories until the sweep budget is used up or every
+    /// shard has been scanned once, continuing from where the previous sweep stopped.
+    fn scan_shards(&self, stats: &mut SweepStatistics) {
+        let started = Instant::now();
+        let directories: Vec<&PathBuf> = self.directories().collect();
+        let total = directories.len() * SHARD_COUNT;
+        let mut cursor = self.sweep_cursor.lock().unwrap();
+        for _ in 0..total {
+            if started.elapsed() >= self.sweep_budget {
+                break;
+            }
+            let shard = directories[*cursor / SHARD_COUNT].join(format!(
+                "{:0width$x}",
+                *cursor % SHARD_COUNT,
+                width = SHARD_PREFIX_LENGTH
+            ));
+            *cursor = (*cursor + 1) % total;
+            if *cursor == 0 {
+                stats.completed_pass = true;
+            }
+            let entries = match fs::read_dir(&shard) {
+                Ok(entries) => entries,
+                Err(err) if err.kind() == ErrorKind::NotFound => continue,
+                Err(err) => {
+                    stats.failed += 1;
+                    warn!("Failed to read shard directory {shard:?}: {err:?}");
+                    continue;
+                }
+            };
+            for id in entries
+                .filter_map(|f| f.ok())
+                .filter_map(|f| f.file_name().into_string().ok())
+                .filter(|name| !name.starts_with('.'))
+            {
+                self.sweep_transfer(id, stats);
+            }
+        }
+    }
+
+    /// Permanently remove deleted transfers whose recovery window has passed, recording the outcome in `stats`.
+    fn purge_deleted_transfers(&self, stats: &mut SweepStatistics) -> Result<()> {
+        let Some(retention) = self.deleted_retention else {
+            return Ok(());
+        };
+        for deleted in self.list_deleted_transfers()? {
+            if deleted.deleted_at + retention > SystemTime::now() {
+                continue;
+            }
+            match self.purge_deleted_transfer(&deleted.id) {
+                Ok(()) => {
+                    debug!("Purged deleted transfer (id: '{}')", deleted.id);
+                    stats.purged += 1;
+                }
+                Err(err) => {
+                    stats.failed += 1;
+                    warn!(
+                        "Failed to purge deleted transfer (id: '{}'): {err:?}",
+                        deleted.id
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove a single transfer if it has expired, or index when it expires otherwise, recording the outcome in `stats`.
+    fn sweep_transfer(&self, id: String, stats: &mut SweepStatistics) {
+        stats.scanned += 1;
+        match self.get_transfer_expiry(&id) {
+            Ok(expires_at) if expires_at <= SystemTime::now() => {
+                info!("Removing expired transfer (id: '{id}')");
+                match self.delete_transfer(&id) {
+                    Ok(()) => stats.removed.push(id),
+                    Err(err)
+                        if err
+                            .downcast_ref::<std::io::Error>()
+                            .is_some_and(|err| err.kind() == ErrorKind::NotFound) => {}
                     Err(err) => {
-                        warn!("Failed to check if transfer (id: '{file_name}') expired: {err:?}");
+                        stats.failed += 1;
+                        warn!("Failed to remove expired transfer (id: '{id}'): {err:?}");
                     }
                 }
-            });
-        Ok(())
+            }
+            Ok(expires_at) => self.expiry_index.insert(&id, expires_at),
+            // The transfer was removed since it was listed or indexed.
+            Err(err)
+                if err
+                    .downcast_ref::<std::io::Error>()
+                    .is_some_and(|err| err.kind() == ErrorKind::NotFound) => {}
+            Err(err) => {
+                stats.failed += 1;
+                warn!("Failed to check if transfer (id: '{id}') expired: {err:?}");
+            }
+        }
     }
 
     /// Get the given transfer file's expiry time as a [`SystemTime`].
     pub fn get_transfer_expiry(&self, id: &str) -> Result<SystemTime> {
-        let metadata = fs::metadata(self.base_dir.join(id))?;
+        if let Some(metadata) = self.state_store.get_metadata(id)? {
+            trace!("Transfer (id: '{id}') created at {:?}", metadata.created_at);
+            let expire_after = metadata.expire_after.unwrap_or(self.expire_after);
+            return Ok(match metadata.pin {
+                Some(TransferPin {
+                    expires_at: Some(expires_at),
+                }) => expires_at,
+                // Transfers pinned indefinitely are always reported as having a full lifetime left.
+                Some(TransferPin { expires_at: None }) => SystemTime::now() + expire_after,
+                None => metadata.created_at + expire_after,
+            });
+        }
+
+        // Transfers created before metadata was persisted have no metadata file,
+        // so fall back to the file's timestamps for those.
+        let metadata = fs::metadata(self.transfer_path(id))?;
         // btime isn't available on all targets/environments (e.g some containers)
         // if this happens we just fallback to mtime which is usually available.
         let write_date = match metadata.created() {
@@ -97,57 +818,1036 @@ impl TransferStorage {
         Ok(write_date + self.expire_after)
     }
 
+    /// Pin a transfer so it isn't removed at its normal expiry.
+    ///
+    /// The transfer instead expires at `expires_at`, or is kept until unpinned when no time is given.
+    pub fn pin_transfer(&self, id: &str, expires_at: Option<SystemTime>) -> Result<()> {
+        let mut metadata = match self.state_store.get_metadata(id)? {
+            Some(metadata) => metadata,
+            None => TransferMetadata {
+                created_at: self.get_transfer_expiry(id)? - self.expire_after,
+                checksum: None,
+                encrypted: false,
+                file_checksum: None,
+                pin: None,
+                expire_after: None,
+                activity_token_hash: None,
+                deletion_token_hash: None,
+                claim_webhook: None,
+                notify_email: None,
+                max_downloads: None,
+                expiry_warning_sent: false,
+                manifest: None,
+            },
+        };
+        metadata.pin = Some(TransferPin { expires_at });
+        self.state_store.set_metadata(id, &metadata)?;
+        self.index_expiry(id);
+        Ok(())
+    }
+
+    /// Bring a transfer's expiry forward so it expires no later than `within` from now.
+    ///
+    /// Transfers pinned by an admin keep their pinned expiry.
+    pub fn expire_transfer_within(&self, id: &str, within: Duration) -> Result<()> {
+        let Some(mut metadata) = self.state_store.get_metadata(id)? else {
+            return Ok(());
+        };
+        let expire_after = SystemTime::now()
+            .duration_since(metadata.created_at)
+            .unwrap_or_default()
+            + within;
+        if metadata.expire_after.unwrap_or(self.expire_after) <= expire_after {
+            return Ok(());
+        }
+        metadata.expire_after = Some(expire_after);
+        self.state_store.set_metadata(id, &metadata)?;
+        self.index_expiry(id);
+        Ok(())
+    }
+
+    /// Remove the pin from a transfer so it expires normally, returning whether it was pinned.
+    pub fn unpin_transfer(&self, id: &str) -> Result<bool> {
+        let Some(mut metadata) = self.state_store.get_metadata(id)? else {
+            return Ok(false);
+        };
+        if metadata.pin.take().is_none() {
+            return Ok(false);
+        }
+        self.state_store.set_metadata(id, &metadata)?;
+        self.index_expiry(id);
+        Ok(true)
+    }
+
+    /// Get the encrypted manifest the uploader stored for a transfer, if there is one.
+    pub fn get_transfer_manifest(&self, id: &str) -> Result<Option<Vec<u8>>> {
+        self.state_store
+            .get_metadata(id)?
+            .and_then(|metadata| metadata.manifest)
+            .map(|manifest| {
+                STANDARD
+                    .decode(manifest)
+                    .context("Failed to decode stored transfer manifest")
+            })
+            .transpose()
+    }
+
+    /// Store the encrypted manifest of a transfer, replacing any existing one.
+    pub fn set_transfer_manifest(&self, id: &str, manifest: &[u8]) -> Result<()> {
+        let mut metadata = self
+            .state_store
+            .get_metadata(id)?
+            .context("Transfer has no metadata to store a manifest in")?;
+        metadata.manifest = Some(STANDARD.encode(manifest));
+        self.state_store.set_metadata(id, &metadata)
+    }
+
+    /// List the indexed transfers that expire at or before `time`, soonest first.
+    ///
+    /// Transfers this instance hasn't indexed yet, and ones that have already been swept, aren't included.
+    pub fn transfers_expiring_before(&self, time: SystemTime) -> Vec<String> {
+        self.expiry_index.due_before(time)
+    }
+
+    /// Index when a transfer expires, after it was created or its expiry changed.
+    fn index_expiry(&self, id: &str) {
+        match self.get_transfer_expiry(id) {
+            Ok(expires_at) => self.expiry_index.insert(id, expires_at),
+            Err(err) => warn!("Failed to index expiry of transfer (id: '{id}'): {err:?}"),
+        }
+    }
+
+    /// List all pinned transfers and their pins.
+    pub fn list_pinned_transfers(&self) -> Result<Vec<(String, TransferPin)>> {
+        let mut pinned = Vec::new();
+        for id in self.list_transfers()? {
+            if let Some(pin) = self
+                .state_store
+                .get_metadata(&id)?
+                .and_then(|metadata| metadata.pin)
+            {
+                pinned.push((id, pin));
+            }
+        }
+        Ok(pinned)
+    }
+
     /// Get the raw bytes of a transfer file's data from storage as a stream.
-    pub async fn get_transfer(&self, id: &str) -> Result<ReaderStream<tokio::fs::File>> {
+    ///
+    /// When a byte range is given only the bytes within it (inclusive) are streamed.
+    pub async fn get_transfer(
+        &self,
+        id: &str,
+        range: Option<RangeInclusive<u64>>,
+    ) -> Result<BoxStream<'static, io::Result<Bytes>>> {
         debug!("Retrieving transfer with ID '{id}' from storage");
-        let file_path = self.base_dir.join(id);
+        let file_path = self.transfer_path(id);
         if fs::metadata(&file_path).is_err() {
             return Err(anyhow::anyhow!("Transfer with id '{id}' does not exist"));
         }
-        let stream = ReaderStream::new(
-            tokio::fs::File::open(&file_path)
+        let encrypted = self.is_transfer_encrypted(id)?;
+        #[cfg(all(feature = "io-uring", target_os = "linux"))]
+        if let Some(uring) = &self.uring
+            && !encrypted
+        {
+            let (offset, limit) = range.map_or((0, u64::MAX), |range| {
+                (*range.start(), range.end() - range.start() + 1)
+            });
+            let stream = uring
+                .read(file_path, offset, limit)
                 .await
-                .context(format!("Failed to open transfer file: {id}"))?,
-        );
-        Ok(stream)
+                .context(format!("Failed to open transfer file: {id}"))?;
+            return Ok(self.bandwidth.download().stream(stream.boxed()).boxed());
+        }
+        let mut file = tokio::fs::File::open(&file_path)
+            .await
+            .context(format!("Failed to open transfer file: {id}"))?;
+        if encrypted {
+            let cipher = self.cipher.as_ref().context(format!(
+                "Transfer (id: '{id}') is encrypted at rest but no encryption key is configured"
+            ))?;
+            let stream = cipher.decrypt_stream(file, range).await?;
+            return Ok(self.bandwidth.download().stream(stream).boxed());
+        }
+        let limit = match range {
+            Some(range) => {
+                file.seek(SeekFrom::Start(*range.start()))
+                    .await
+                    .context(format!("Failed to seek transfer file: {id}"))?;
+                range.end() - range.start() + 1
+            }
+            None => u64::MAX,
+        };
+        Ok(self
+            .bandwidth
+            .download()
+            .stream(ReaderStream::with_capacity(
+                file.take(limit),
+                self.download_chunk_size,
+            ))
+            .boxed())
+    }
+
+    /// Whether a transfer file was encrypted at rest when it was created.
+    fn is_transfer_encrypted(&self, id: &str) -> Result<bool> {
+        Ok(self
+            .state_store
+            .get_metadata(id)?
+            .is_some_and(|metadata| metadata.encrypted))
     }
 
-    /// Get the size of a transfer file in bytes.
+    /// Count the stored transfers and the number of bytes they occupy on disk.
+    pub fn storage_statistics(&self) -> Result<StorageStatistics> {
+        let mut stats = StorageStatistics::default();
+        for id in self.list_transfers()? {
+            // Transfers may be removed while they are being counted.
+            if let Ok(metadata) = fs::metadata(self.transfer_path(&id)) {
+                stats.transfers += 1;
+                stats.bytes += metadata.len();
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Get the amount of space available to transfer storage in bytes.
+    pub fn available_space(&self) -> Result<u64> {
+        fs4::available_space(&self.base_dir)
+            .context("Failed to obtain available space for transfer storage")
+    }
+
+    /// Estimate when enough transfers will have expired for `required` bytes to fit in transfer storage.
+    ///
+    /// Returns `None` when expiring every transfer that can expire still wouldn't free enough space.
+    /// Finding out when transfers expire means scanning every stored transfer, so the result of the
+    /// scan is reused for a short while. This blocks, so shouldn't be called from async workers.
+    pub fn space_available_at(&self, required: u64) -> Result<Option<SystemTime>> {
+        let mut available = self.available_space()?;
+        if available >= required {
+            return Ok(Some(SystemTime::now()));
+        }
+        let expiring = {
+            // Hold the lock while scanning so concurrent callers wait for one scan rather than each starting their own.
+            let mut forecast = self.space_forecast.lock().unwrap();
+            match &*forecast {
+                Some((built_at, expiring)) if built_at.elapsed() < SPACE_FORECAST_TTL => {
+                    Arc::clone(expiring)
+                }
+                _ => {
+                    let expiring: SpaceForecast = self.forecast_freed_space()?.into();
+                    *forecast = Some((Instant::now(), Arc::clone(&expiring)));
+                    expiring
+                }
+            }
+        };
+        for &(expires_at, size) in expiring.iter() {
+            available += size;
+            if available >= required {
+                return Ok(Some(expires_at));
+            }
+        }
+        Ok(None)
+    }
+
+    /// List when each transfer that can expire will free its space and how many bytes it frees, soonest first.
+    fn forecast_freed_space(&self) -> Result<Vec<(SystemTime, u64)>> {
+        let mut expiring = Vec::new();
+        for id in
+            Self::list_directory(&self.base_dir).context("Failed to read transfer directory")?
+        {
+            // Transfers pinned indefinitely will never free their space.
+            if self.state_store.get_metadata(&id)?.is_some_and(|metadata| {
+                matches!(metadata.pin, Some(TransferPin { expires_at: None }))
+            }) {
+                continue;
+            }
+            let (Ok(expires_at), Ok(metadata)) = (
+                self.get_transfer_expiry(&id),
+                fs::metadata(Self::sharded_path(&self.base_dir, &id)),
+            ) else {
+                continue;
+            };
+            // Expired transfers kept aside for recovery only free their space once purged.
+            expiring.push((
+                expires_at + self.deleted_retention.unwrap_or_default(),
+                metadata.len(),
+            ));
+        }
+        if let Some(retention) = self.deleted_retention {
+            for deleted in self.list_deleted_transfers()? {
+                if let Ok(metadata) =
+                    fs::metadata(self.base_dir.join(DELETED_DIRECTORY_NAME).join(&deleted.id))
+                {
+                    expiring.push((deleted.deleted_at + retention, metadata.len()));
+                }
+            }
+        }
+        expiring.sort_unstable();
+        Ok(expiring)
+    }
+
+    /// Get the size of a transfer's data in bytes.
+    ///
+    /// For transfers encrypted at rest this is the size of the decrypted data.
     pub fn get_transfer_size(&self, id: &str) -> Result<u64> {
-        let metadata = fs::metadata(self.base_dir.join(id))?;
+        let metadata = fs::metadata(self.transfer_path(id))?;
+        if self.is_transfer_encrypted(id)? {
+            return Ok(AtRestCipher::plaintext_size(metadata.len()));
+        }
         Ok(metadata.len())
     }
 
+    /// Get the path an in-progress upload is written to before being moved into place.
+    ///
+    /// Partial files are hidden so they are never listed, swept or served as transfers.
+    fn partial_transfer_path(&self, id: &str) -> PathBuf {
+        self.base_dir
+            .join(format!(".{id}.{PARTIAL_TRANSFER_EXTENSION}"))
+    }
+
+    /// Get the path a transfer reservation is recorded at until its data is uploaded.
+    fn reservation_path(&self, id: &str) -> PathBuf {
+        self.base_dir.join(format!(".{id}.{RESERVATION_EXTENSION}"))
+    }
+
+    /// Get the path the chunks of a reserved transfer are written to until the upload is completed.
+    fn chunked_upload_path(&self, id: &str) -> PathBuf {
+        self.base_dir
+            .join(format!(".{id}.{CHUNKED_UPLOAD_EXTENSION}"))
+    }
+
+    /// Allocate a transfer identifier for an upload of `size` bytes that will be sent later.
+    ///
+    /// The returned upload token must be given to [`Self::redeem_reservation`] before uploading.
+    /// When `transfer_expire_after` is given the transfer should be kept for that long instead of the default.
+    pub fn reserve_transfer(
+        &self,
+        size: u64,
+        transfer_expire_after: Option<Duration>,
+    ) -> Result<TransferReservation> {
+        let upload_token = blake3::Hash::from_bytes(rand::random())
+            .to_hex()
+            .to_string();
+        let expires_at = SystemTime::now() + RESERVATION_EXPIRE_AFTER;
+        let record = serde_json::to_vec(&ReservationRecord {
+            size,
+            upload_token_hash: blake3::hash(upload_token.as_bytes()).to_hex().to_string(),
+            expires_at,
+            transfer_expire_after,
+        })?;
+        loop {
+            let id = self.identifiers.generate();
+            if self.transfer_exists(&id)? || fs::exists(self.partial_transfer_path(&id))? {
+                continue;
+            }
+            match File::create_new(self.reservation_path(&id)) {
+                Ok(mut file) => {
+                    file.write_all(&record)
+                        .context("Failed to write transfer reservation")?;
+                    debug!("Reserved transfer with ID '{id}' for {size} bytes");
+                    return Ok(TransferReservation {
+                        id,
+                        upload_token,
+                        expires_at,
+                    });
+                }
+                Err(err) if err.kind() == ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(err).context("Failed to create transfer reservation"),
+            }
+        }
+    }
+
+    /// Generate an identifier that isn't used by any stored, in-progress or reserved transfer.
+    pub fn generate_unused_identifier(&self) -> Result<String> {
+        loop {
+            let id = self.identifiers.generate();
+            if !self.transfer_exists(&id)?
+                && !fs::exists(self.partial_transfer_path(&id))?
+                && !fs::exists(self.reservation_path(&id))?
+            {
+                return Ok(id);
+            }
+        }
+    }
+
+    /// Check that a reservation exists for `id`, hasn't lapsed and was made with `upload_token`.
+    ///
+    /// Returns the limits the upload was reserved with, or `None` if there is no matching reservation.
+    pub fn redeem_reservation(
+        &self,
+        id: &str,
+        upload_token: &str,
+    ) -> Result<Option<RedeemedReservation>> {
+        let record = match fs::read(self.reservation_path(id)) {
+            Ok(bytes) => serde_json::from_slice::<ReservationRecord>(&bytes)
+                .context("Failed to parse transfer reservation")?,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err).context("Failed to read transfer reservation"),
+        };
+        // Compare hashes so the comparison runs in constant time.
+        let token_matches = blake3::Hash::from_hex(&record.upload_token_hash)
+            .is_ok_and(|hash| hash == blake3::hash(upload_token.as_bytes()));
+        Ok(
+            (token_matches && record.expires_at > SystemTime::now()).then_some(
+                RedeemedReservation {
+                    size: record.size,
+                    expire_after: record.transfer_expire_after,
+                },
+            ),
+        )
+    }
+
+    /// Push back when a reservation lapses, so uploads sent in chunks stay reserved while chunks keep arriving.
+    pub fn extend_reservation(&self, id: &str) -> Result<SystemTime> {
+        let path = self.reservation_path(id);
+        let mut record = serde_json::from_slice::<ReservationRecord>(
+            &fs::read(&path).context("Failed to read transfer reservation")?,
+        )
+        .context("Failed to parse transfer reservation")?;
+        record.expires_at = SystemTime::now() + RESERVATION_EXPIRE_AFTER;
+        fs::write(&path, serde_json::to_vec(&record)?)
+            .context("Failed to write transfer reservation")?;
+        Ok(record.expires_at)
+    }
+
+    /// Write a chunk of a reserved transfer's data at `offset`, returning the number of bytes written.
+    ///
+    /// Chunks may be written in any order and concurrently, and rewriting a chunk replaces its data.
+    /// The stream is aborted with a [`TransferTooLargeError`] if the chunk would extend past the reserved `size`.
+    pub async fn write_transfer_chunk(
+        &self,
+        id: &str,
+        offset: u64,
+        size: u64,
+        mut bytes: BodyDataStream,
+        stream_limits: StreamLimits,
+    ) -> Result<u64> {
+        if offset > size {
+            return Err(TransferTooLargeError { max_size: size }.into());
+        }
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.chunked_upload_path(id))
+            .context("Failed to open chunked upload file")?;
+        file.seek(SeekFrom::Start(offset))
+            .context("Failed to seek chunked upload file")?;
+        let mut written: u64 = 0;
+        let started = Instant::now();
+        let throttle = self.bandwidth.upload();
+        loop {
+            let chunk = match tokio::time::timeout(stream_limits.idle_timeout, bytes.next()).await {
+                Ok(Some(chunk)) => chunk.context("Failed to read chunk from stream")?,
+                Ok(None) => break,
+                Err(_) => {
+                    warn!("Chunk of transfer (id: '{id}') stalled during upload");
+                    return Err(TransferStalledError.into());
+                }
+            };
+            written += chunk.len() as u64;
+            throttle.consume(chunk.len()).await;
+            if stream_limits.is_too_slow(written, started.elapsed()) {
+                warn!("Chunk of transfer (id: '{id}') fell below minimum upload throughput");
+                return Err(TransferStalledError.into());
+            }
+            if offset + written > size {
+                warn!("Chunk of transfer (id: '{id}') extended past the reserved size");
+                return Err(TransferTooLargeError { max_size: size }.into());
+            }
+            file.write_all(&chunk)
+                .context("Failed to write chunk to file")?;
+        }
+        file.sync_data().context("Failed to flush chunk to disk")?;
+        Ok(written)
+    }
+
+    /// Open the chunks written for a reserved transfer so they can be stored as the transfer's data.
+    ///
+    /// Fails with [`ErrorKind::NotFound`] if no chunks have been written.
+    pub async fn open_chunked_upload(&self, id: &str) -> io::Result<tokio::fs::File> {
+        tokio::fs::File::open(self.chunked_upload_path(id)).await
+    }
+
+    /// Remove the chunks written for a reserved transfer, if there are any.
+    fn remove_chunked_upload(&self, id: &str) {
+        match fs::remove_file(self.chunked_upload_path(id)) {
+            Ok(()) => debug!("Removed chunked upload of transfer (id: '{id}')"),
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => warn!("Failed to remove chunked upload of transfer (id: '{id}'): {err:?}"),
+        }
+    }
+
+    /// Remove a reservation file, along with any chunks written for it, if it has lapsed.
+    fn remove_lapsed_reservation(&self, path: &Path) -> Result<()> {
+        let record = serde_json::from_slice::<ReservationRecord>(&fs::read(path)?);
+        if record.is_ok_and(|record| record.expires_at > SystemTime::now()) {
+            return Ok(());
+        }
+        debug!("Removing lapsed transfer reservation {path:?}");
+        if let Some(id) = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix('.'))
+            .and_then(|name| name.strip_suffix(&format!(".{RESERVATION_EXTENSION}")))
+        {
+            self.remove_chunked_upload(id);
+        }
+        Ok(fs::remove_file(path)?)
+    }
+
+    /// Clean up files left behind by crashes or manual tampering with the transfer directories.
+    ///
+    /// Partial files that haven't been written to for at least `partial_older_than` and empty
+    /// transfer files are removed, while entries that aren't named like a transfer are moved into
+    /// a quarantine directory for the operator to inspect. Partial files written more recently are
+    /// left alone as they may belong to uploads in progress on other instances sharing this storage.
+    pub fn collect_garbage(
+        &self,
+        partial_older_than: Duration,
+    ) -> Result<GarbageCollectionStatistics> {
+        let mut stats = GarbageCollectionStatistics::default();
+        for dir in self.directories() {
+            for file in fs::read_dir(dir)
+                .context("Failed to read transfer directory")?
+                .filter_map(|f| f.ok())
+            {
+                let shard = file
+                    .file_name()
+                    .into_string()
+                    .ok()
+                    .filter(|name| Self::is_shard_name(name))
+                    .filter(|_| file.file_type().is_ok_and(|t| t.is_dir()));
+                let Some(shard) = shard else {
+                    self.collect_entry_garbage(&file, None, partial_older_than, &mut stats);
+                    continue;
+                };
+                for file in fs::read_dir(file.path())
+                    .context("Failed to read shard directory")?
+                    .filter_map(|f| f.ok())
+                {
+                    self.collect_entry_garbage(&file, Some(&shard), partial_older_than, &mut stats);
+                }
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Clean up a single entry of a transfer directory, or of one of its shards when `shard` is given.
+    fn collect_entry_garbage(
+        &self,
+        file: &DirEntry,
+        shard: Option<&str>,
+        partial_older_than: Duration,
+        stats: &mut GarbageCollectionStatistics,
+    ) {
+        let Ok(file_name) = file.file_name().into_string() else {
+            if self.quarantine(&file.path(), "unrecognised entry") {
+                stats.quarantined += 1;
+            } else {
+                stats.failed += 1;
+            }
+            return;
+        };
+        let Ok(metadata) = file.metadata() else {
+            stats.failed += 1;
+            return;
+        };
+
+        if file_name.starts_with('.') {
+            if !file_name.ends_with(&format!(".{PARTIAL_TRANSFER_EXTENSION}"))
+                || !metadata.is_file()
+            {
+                return;
+            }
+            let is_stale = metadata.modified().is_ok_and(|modified| {
+                modified
+                    .elapsed()
+                    .is_ok_and(|age| age >= partial_older_than)
+            });
+            if !is_stale {
+                return;
+            }
+            match fs::remove_file(file.path()) {
+                Ok(()) => {
+                    debug!("Removed leftover partial transfer file '{file_name}'");
+                    stats.partial_removed += 1;
+                }
+                Err(err) => {
+                    stats.failed += 1;
+                    warn!("Failed to remove leftover partial transfer file '{file_name}': {err:?}");
+                }
+            }
+            return;
+        }
+
+        // Transfers only ever live in the shard their identifier hashes to.
+        if !metadata.is_file()
+            || !IdentifierGenerator::is_safe(&file_name)
+            || shard != Some(Self::shard_name(&file_name).as_str())
+        {
+            if self.quarantine(&file.path(), "unrecognised entry") {
+                stats.quarantined += 1;
+            } else {
+                stats.failed += 1;
+            }
+            return;
+        }
+
+        if metadata.len() == 0 {
+            match self.remove_transfer(&file_name) {
+                Ok(()) => {
+                    debug!("Removed empty transfer file (id: '{file_name}')");
+                    stats.empty_removed += 1;
+                }
+                Err(err) => {
+                    stats.failed += 1;
+                    warn!("Failed to remove empty transfer file (id: '{file_name}'): {err:?}");
+                }
+            }
+        }
+    }
+
+    /// Re-hash every stored transfer file and compare it against the hash recorded when it was written.
+    ///
+    /// Transfers that no longer match are moved into the quarantine directory and their state removed,
+    /// as they can no longer be decrypted by recipients.
+    pub fn verify_integrity(&self) -> Result<IntegrityStatistics> {
+        let mut stats = IntegrityStatistics::default();
+        for id in self.list_transfers()? {
+            let expected = match self.state_store.get_metadata(&id) {
+                Ok(Some(metadata)) if metadata.encrypted => metadata.file_checksum,
+                Ok(Some(metadata)) => metadata.checksum,
+                Ok(None) => None,
+                Err(err) => {
+                    stats.failed += 1;
+                    warn!("Failed to read metadata of transfer (id: '{id}'): {err:?}");
+                    continue;
+                }
+            };
+            let Some(expected) = expected.and_then(|hex| blake3::Hash::from_hex(hex).ok()) else {
+                stats.skipped += 1;
+                continue;
+            };
+            let path = self.transfer_path(&id);
+            let actual = match File::open(&path).and_then(|file| {
+                blake3::Hasher::new()
+                    .update_reader(file)
+                    .map(|h| h.finalize())
+            }) {
+                Ok(actual) => actual,
+                // The transfer was removed while being verified.
+                Err(err) if err.kind() == ErrorKind::NotFound => continue,
+                Err(err) => {
+                    stats.failed += 1;
+                    warn!("Failed to read transfer (id: '{id}') for verification: {err:?}");
+                    continue;
+                }
+            };
+            stats.verified += 1;
+            if actual == expected {
+                continue;
+            }
+            error!("Transfer (id: '{id}') failed integrity verification");
+            if self.quarantine(&path, "corrupted transfer")
+                && let Err(err) = self.state_store.remove_transfer(&id)
+            {
+                warn!("Failed to remove state of corrupted transfer (id: '{id}'): {err:?}");
+            }
+            stats.corrupted.push(id);
+        }
+        Ok(stats)
+    }
+
+    /// Move an entry out of the transfer directory into the quarantine directory, returning whether it was moved.
+    fn quarantine(&self, path: &Path, description: &str) -> bool {
+        let name = path.file_name().unwrap_or_default();
+        let quarantine_dir = self.base_dir.join(QUARANTINE_DIRECTORY_NAME);
+        match fs::create_dir_all(&quarantine_dir)
+            .and_then(|()| fs::rename(path, quarantine_dir.join(name)))
+        {
+            Ok(()) => {
+                warn!("Quarantined {description} {name:?} in transfer directory");
+                true
+            }
+            Err(err) => {
+                warn!("Failed to quarantine {description} {name:?} in transfer directory: {err:?}");
+                false
+            }
+        }
+    }
+
     /// Save the given Axum BodyDataStream to storage as a transfer file.
     ///
+    /// Data is written to a hidden partial file that is only moved into place once the whole
+    /// stream has been received and validated, so an interrupted upload is never served.
+    ///
+    /// The partial file is removed whenever the upload doesn't complete, including when the
+    /// stream errors or the returned future is dropped because the client disconnected.
+    ///
+    /// The stream is aborted with a [`TransferTooLargeError`] as soon as more than `max_size`
+    /// bytes have been received. Likewise, if an
+    /// expected checksum is given and the received data doesn't match it the file is removed
+    /// and a [`TransferChecksumMismatchError`] is returned. Transfers whose first bytes don't look
+    /// encrypted under the given content check are aborted with a [`TransferNotEncryptedError`].
+    ///
     /// Returns the identifier that the transfer was stored with upon success.
-    pub async fn create_transfer(&self, mut bytes: BodyDataStream) -> Result<String> {
-        let id = loop {
-            let id = Self::generate_transfer_identifier();
-            if !self.transfer_exists(&id).unwrap() {
-                break id;
+    pub async fn create_transfer(
+        &self,
+        mut bytes: BodyDataStream,
+        options: CreateTransferOptions,
+    ) -> Result<String> {
+        // Claim the identifier by exclusively creating its partial file so that
+        // other instances sharing this storage can never pick the same one.
+        let (partial, id, mut file) = loop {
+            let id = match &options.reserved_id {
+                Some(id) => id.clone(),
+                None => self.identifiers.generate(),
+            };
+            if options.reserved_id.is_none()
+                && (self.transfer_exists(&id)? || fs::exists(self.reservation_path(&id))?)
+            {
+                continue;
+            }
+            match File::create_new(self.partial_transfer_path(&id)) {
+                Ok(file) => {
+                    break (
+                        PartialTransferGuard::new(self.partial_transfer_path(&id)),
+                        id,
+                        file,
+                    );
+                }
+                Err(err)
+                    if err.kind() == ErrorKind::AlreadyExists && options.reserved_id.is_none() =>
+                {
+                    continue;
+                }
+                Err(err) => return Err(err).context("Failed to create transfer file"),
             }
         };
         debug!("Creating transfer with ID '{id}' in storage");
-        let mut file = File::create(self.base_dir.join(&id))?;
-        while let Some(chunk) = bytes.next().await {
-            let chunk = chunk.context("Failed to read chunk from stream")?;
-            file.write_all(&chunk)
-                .context("Failed to write chunk to file")?;
+        let mut written: u64 = 0;
+        let mut hasher = blake3::Hasher::new();
+        let mut file_hasher = blake3::Hasher::new();
+        let started = Instant::now();
+        let throttle = self.bandwidth.upload();
+        let mut encryptor = self.cipher.as_ref().map(AtRestCipher::encryptor);
+        let mut sample = (options.content_check != ContentCheckMode::Off)
+            .then(|| Vec::with_capacity(CONTENT_SAMPLE_SIZE));
+        if let Some(encryptor) = &encryptor {
+            let header = encryptor.header();
+            file_hasher.update(&header);
+            file.write_all(&header)
+                .context("Failed to write encryption header to file")?;
+        }
+        loop {
+            let chunk = match tokio::time::timeout(options.stream_limits.idle_timeout, bytes.next())
+                .await
+            {
+                Ok(Some(chunk)) => chunk.map_err(|err| {
+                    warn!("Transfer (id: '{id}') upload stream failed - removing: {err}");
+                    anyhow::Error::from(err).context("Failed to read chunk from stream")
+                })?,
+                Ok(None) => break,
+                Err(_) => {
+                    drop(file);
+                    warn!("Transfer (id: '{id}') stalled during upload - removing");
+                    return Err(TransferStalledError.into());
+                }
+            };
+            written += chunk.len() as u64;
+            if options.throttle {
+                throttle.consume(chunk.len()).await;
+            }
+            if options
+                .stream_limits
+                .is_too_slow(written, started.elapsed())
+            {
+                drop(file);
+                warn!("Transfer (id: '{id}') fell below minimum upload throughput - removing");
+                return Err(TransferStalledError.into());
+            }
+            if written > options.max_size {
+                drop(file);
+                warn!("Transfer (id: '{id}') exceeded maximum size during upload - removing");
+                return Err(TransferTooLargeError {
+                    max_size: options.max_size,
+                }
+                .into());
+            }
+            if let Some(buffer) = &mut sample {
+                let wanted = CONTENT_SAMPLE_SIZE - buffer.len();
+                buffer.extend_from_slice(&chunk[..chunk.len().min(wanted)]);
+                if buffer.len() == CONTENT_SAMPLE_SIZE {
+                    if !options.content_check.looks_encrypted(buffer) {
+                        drop(file);
+                        warn!("Transfer (id: '{id}') does not appear to be encrypted - removing");
+                        return Err(TransferNotEncryptedError.into());
+                    }
+                    sample = None;
+                }
+            }
+            hasher.update(&chunk);
+            match &mut encryptor {
+                Some(encryptor) => {
+                    let encrypted = encryptor.update(&chunk)?;
+                    file_hasher.update(&encrypted);
+                    file.write_all(&encrypted)
+                }
+                None => file.write_all(&chunk),
+            }
+            .context("Failed to write chunk to file")?;
+        }
+        if let Some(buffer) = sample
+            && !options.content_check.looks_encrypted(&buffer)
+        {
+            drop(file);
+            warn!("Transfer (id: '{id}') does not appear to be encrypted - removing");
+            return Err(TransferNotEncryptedError.into());
+        }
+        let encrypted = encryptor.is_some();
+        if let Some(encryptor) = encryptor {
+            let encrypted = encryptor.finish()?;
+            file_hasher.update(&encrypted);
+            file.write_all(&encrypted)
+                .context("Failed to write final chunk to file")?;
+        }
+        let checksum = hasher.finalize();
+        if let Some(expected) = options.expected_checksum
+            && checksum != expected
+        {
+            drop(file);
+            warn!("Transfer (id: '{id}') did not match the supplied checksum - removing");
+            return Err(TransferChecksumMismatchError.into());
+        }
+        file.sync_all()
+            .context("Failed to flush transfer file to disk")?;
+        drop(file);
+        let partial_path = self.partial_transfer_path(&id);
+        let mut id = id;
+        loop {
+            let path = Self::sharded_path(&self.base_dir, &id);
+            Self::create_parent_directory(&path).context("Failed to create shard directory")?;
+            match Self::move_into_place(&partial_path, &path) {
+                Ok(()) => break,
+                // Another upload was stored under the generated identifier after it was checked, so
+                // store this one under a different identifier instead of replacing it.
+                Err(err)
+                    if err.kind() == ErrorKind::AlreadyExists && options.reserved_id.is_none() =>
+                {
+                    let taken = mem::replace(&mut id, self.generate_unused_identifier()?);
+                    debug!("Transfer ID '{taken}' was taken while uploading, storing as '{id}'");
+                }
+                Err(err) => {
+                    return Err(err).context("Failed to move completed transfer into place");
+                }
+            }
         }
+        // The partial file is linked into place, so the guard removing it leaves the transfer behind.
+        drop(partial);
+        if options.reserved_id.is_some()
+            && let Err(err) = fs::remove_file(self.reservation_path(&id))
+        {
+            warn!("Failed to remove reservation of uploaded transfer (id: '{id}'): {err:?}");
+        }
+        if options.reserved_id.is_some() {
+            self.remove_chunked_upload(&id);
+        }
+        self.state_store.set_metadata(
+            &id,
+            &TransferMetadata {
+                created_at: SystemTime::now(),
+                checksum: Some(checksum.to_hex().to_string()),
+                encrypted,
+                file_checksum: encrypted.then(|| file_hasher.finalize().to_hex().to_string()),
+                pin: None,
+                expire_after: options.expire_after,
+                activity_token_hash: options.activity_token_hash,
+                deletion_token_hash: options.deletion_token_hash,
+                claim_webhook: options.claim_webhook,
+                notify_email: options.notify_email,
+                max_downloads: options.max_downloads,
+                expiry_warning_sent: false,
+                manifest: None,
+            },
+        )?;
+        self.index_expiry(&id);
         Ok(id)
     }
 
-    /// Delete the given transfer file from storage.
+    /// Delete the given transfer from storage.
+    ///
+    /// When a deleted retention is configured the transfer is moved aside so it can be recovered
+    /// with [`Self::restore_deleted_transfer`] until the retention passes.
     pub fn delete_transfer(&self, id: &str) -> Result<()> {
-        debug!("Deleting transfer with ID '{id}' from storage");
-        fs::remove_file(self.base_dir.join(id))?;
+        if self.deleted_retention.is_none() {
+            return self.remove_transfer(id);
+        }
+        debug!("Moving transfer with ID '{id}' to deleted transfers");
+        let deleted_dir = self.base_dir.join(DELETED_DIRECTORY_NAME);
+        fs::create_dir_all(&deleted_dir).context("Failed to create deleted transfer directory")?;
+        let path = self.transfer_path(id);
+        let deleted_path = deleted_dir.join(id);
+        // A transfer deleted earlier may have had the same identifier.
+        match fs::remove_file(&deleted_path) {
+            Ok(()) => {}
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => return Err(err).context("Failed to replace previously deleted transfer"),
+        }
+        // Transfers in cold storage or the replica may be on another filesystem and need copying.
+        if let Err(err) = fs::rename(&path, &deleted_path) {
+            if err.kind() == ErrorKind::NotFound {
+                return Err(err.into());
+            }
+            if Self::move_file(&path, &deleted_path)?.is_none() {
+                return Err(io::Error::from(ErrorKind::NotFound).into());
+            }
+        }
+        let record = DeletedTransfer {
+            id: id.to_string(),
+            deleted_at: SystemTime::now(),
+            metadata: self.state_store.get_metadata(id)?,
+        };
+        fs::write(
+            self.deleted_record_path(id),
+            serde_json::to_vec(&record).context("Failed to serialize deleted transfer record")?,
+        )
+        .context("Failed to write deleted transfer record")?;
+        match self.remove_transfer(id) {
+            // The only copy was the one moved aside.
+            Err(err)
+                if err
+                    .downcast_ref::<io::Error>()
+                    .is_some_and(|err| err.kind() == ErrorKind::NotFound) =>
+            {
+                self.expiry_index.remove(id);
+                self.state_store.remove_transfer(id)
+            }
+            result => result,
+        }
+    }
+
+    /// Get the path the record of a deleted transfer is kept at.
+    fn deleted_record_path(&self, id: &str) -> PathBuf {
+        self.base_dir
+            .join(DELETED_DIRECTORY_NAME)
+            .join(format!("{id}.{DELETED_RECORD_EXTENSION}"))
+    }
+
+    /// List the deleted transfers that can still be recovered.
+    pub fn list_deleted_transfers(&self) -> Result<Vec<DeletedTransfer>> {
+        let deleted_dir = self.base_dir.join(DELETED_DIRECTORY_NAME);
+        let entries = match fs::read_dir(&deleted_dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err).context("Failed to read deleted transfer directory"),
+        };
+        let mut deleted = Vec::new();
+        for entry in entries.filter_map(|e| e.ok()) {
+            let path = entry.path();
+            if path
+                .extension()
+                .is_none_or(|ext| ext != DELETED_RECORD_EXTENSION)
+            {
+                continue;
+            }
+            match fs::read(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|bytes| Ok(serde_json::from_slice::<DeletedTransfer>(&bytes)?))
+            {
+                Ok(record) => deleted.push(record),
+                Err(err) => warn!("Failed to read deleted transfer record {path:?}: {err:?}"),
+            }
+        }
+        deleted.sort_unstable_by_key(|record| record.deleted_at);
+        Ok(deleted)
+    }
+
+    /// Move a deleted transfer back into storage along with its metadata.
+    pub fn restore_deleted_transfer(&self, id: &str) -> Result<DeletedTransfer> {
+        if self.transfer_exists(id)? {
+            return Err(io::Error::from(ErrorKind::AlreadyExists).into());
+        }
+        let record_path = self.deleted_record_path(id);
+        let record: DeletedTransfer = serde_json::from_slice(&fs::read(&record_path)?)
+            .context("Failed to parse deleted transfer record")?;
+        let path = Self::sharded_path(&self.base_dir, id);
+        Self::create_parent_directory(&path)?;
+        fs::rename(self.base_dir.join(DELETED_DIRECTORY_NAME).join(id), path)?;
+        if let Some(metadata) = &record.metadata {
+            self.state_store.set_metadata(id, metadata)?;
+        }
+        fs::remove_file(record_path).context("Failed to remove deleted transfer record")?;
+        self.index_expiry(id);
+        Ok(record)
+    }
+
+    /// Permanently remove a deleted transfer before its recovery window passes.
+    pub fn purge_deleted_transfer(&self, id: &str) -> Result<()> {
+        let deleted_dir = self.base_dir.join(DELETED_DIRECTORY_NAME);
+        match fs::remove_file(deleted_dir.join(id)) {
+            Ok(()) => {}
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => return Err(err).context("Failed to remove deleted transfer"),
+        }
+        fs::remove_file(self.deleted_record_path(id))?;
         Ok(())
     }
 
+    /// Remove the given transfer's files and state from storage immediately.
+    fn remove_transfer(&self, id: &str) -> Result<()> {
+        debug!("Deleting transfer with ID '{id}' from storage");
+        // Transfers may exist in several directories, such as while being migrated to cold storage or once replicated.
+        let mut removed = false;
+        for dir in self.directories() {
+            match fs::remove_file(Self::sharded_path(dir, id)) {
+                Ok(()) => removed = true,
+                Err(err) if err.kind() == ErrorKind::NotFound => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        if !removed {
+            return Err(io::Error::from(ErrorKind::NotFound).into());
+        }
+        self.expiry_index.remove(id);
+        self.state_store.remove_transfer(id)
+    }
+
     /// Whether a transfer file exists in storage.
     pub fn transfer_exists(&self, id: &str) -> Result<bool> {
         debug!("Checking for transfer with ID '{id}' in storage");
-        Ok(fs::exists(self.base_dir.join(self.base_dir.join(id)))?)
+        Ok(fs::exists(self.transfer_path(id))?)
+    }
+}
+
+/// Removes an upload's partial file when dropped, unless it has been disarmed.
+struct PartialTransferGuard {
+    path: PathBuf,
+    armed: bool,
+}
+
+impl PartialTransferGuard {
+    fn new(path: PathBuf) -> Self {
+        Self { path, armed: true }
+    }
+
+    /// Keep the partial file, e.g. because it has been moved into place.
+    fn disarm(&mut self) {
+        self.armed = false;
+    }
+}
+
+impl Drop for PartialTransferGuard {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+        match fs::remove_file(&self.path) {
+            Ok(()) => debug!("Removed partial transfer file {:?}", self.path),
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => warn!(
+                "Failed to remove partial transfer file {:?}: {err:?}",
+                self.path
+            ),
+        }
     }
 }
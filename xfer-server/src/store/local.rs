@@ -0,0 +1,162 @@
+use super::{MAX_RECORDED_DOWNLOAD_TIMES, StateStore, TransferMetadata};
+use anyhow::{Context, Result};
+use std::{
+    collections::HashMap,
+    fs,
+    io::ErrorKind,
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Upper bound of tracked rate-limit keys before expired windows are pruned.
+const RATE_LIMIT_PRUNE_THRESHOLD: usize = 4096;
+
+/// [`StateStore`] that keeps metadata and counters on the local filesystem
+/// and rate-limit windows in memory.
+///
+/// Suitable for single-instance deployments.
+#[derive(Debug)]
+pub struct LocalStateStore {
+    metadata_dir: PathBuf,
+    download_counts_dir: PathBuf,
+    download_times_dir: PathBuf,
+    counter_lock: Mutex<()>,
+    /// When each rate-limit key's window ends and the hits within it.
+    ///
+    /// Keys belong to limiters with different windows, so each records when its own window ends.
+    rate_limits: Mutex<HashMap<String, (Instant, u64)>>,
+}
+
+impl LocalStateStore {
+    /// Create a new [`LocalStateStore`] rooted at the given directory.
+    pub fn new(base_dir: PathBuf) -> Result<Self> {
+        let metadata_dir = base_dir.join("metadata");
+        let download_counts_dir = base_dir.join("downloads");
+        let download_times_dir = base_dir.join("download-times");
+        fs::create_dir_all(&metadata_dir)?;
+        fs::create_dir_all(&download_counts_dir)?;
+        fs::create_dir_all(&download_times_dir)?;
+        Ok(Self {
+            metadata_dir,
+            download_counts_dir,
+            download_times_dir,
+            counter_lock: Mutex::default(),
+            rate_limits: Mutex::default(),
+        })
+    }
+
+    fn metadata_path(&self, id: &str) -> PathBuf {
+        self.metadata_dir.join(format!("{id}.json"))
+    }
+
+    fn download_count_path(&self, id: &str) -> PathBuf {
+        self.download_counts_dir.join(id)
+    }
+
+    fn download_times_path(&self, id: &str) -> PathBuf {
+        self.download_times_dir.join(format!("{id}.json"))
+    }
+
+    fn remove_if_exists(path: PathBuf) -> Result<()> {
+        match fs::remove_file(path) {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl StateStore for LocalStateStore {
+    fn get_metadata(&self, id: &str) -> Result<Option<TransferMetadata>> {
+        let data = match fs::read(self.metadata_path(id)) {
+            Ok(data) => data,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => {
+                return Err(err).context(format!("Failed to read metadata for transfer: {id}"));
+            }
+        };
+        Ok(Some(serde_json::from_slice(&data).context(format!(
+            "Failed to parse metadata for transfer: {id}"
+        ))?))
+    }
+
+    fn set_metadata(&self, id: &str, metadata: &TransferMetadata) -> Result<()> {
+        fs::write(self.metadata_path(id), serde_json::to_vec(metadata)?)
+            .context(format!("Failed to write metadata for transfer: {id}"))
+    }
+
+    fn remove_transfer(&self, id: &str) -> Result<()> {
+        Self::remove_if_exists(self.metadata_path(id))?;
+        Self::remove_if_exists(self.download_count_path(id))?;
+        Self::remove_if_exists(self.download_times_path(id))?;
+        Ok(())
+    }
+
+    fn increment_download_count(&self, id: &str) -> Result<u64> {
+        let _guard = self.counter_lock.lock().unwrap();
+        let count = self.get_download_count(id)? + 1;
+        fs::write(self.download_count_path(id), count.to_string())
+            .context(format!("Failed to write download count for transfer: {id}"))?;
+        Ok(count)
+    }
+
+    fn get_download_count(&self, id: &str) -> Result<u64> {
+        match fs::read_to_string(self.download_count_path(id)) {
+            Ok(count) => count
+                .trim()
+                .parse()
+                .context(format!("Failed to parse download count for transfer: {id}")),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(0),
+            Err(err) => {
+                Err(err).context(format!("Failed to read download count for transfer: {id}"))
+            }
+        }
+    }
+
+    fn record_download_time(&self, id: &str, time: SystemTime) -> Result<()> {
+        let _guard = self.counter_lock.lock().unwrap();
+        let mut times = self.get_download_times(id)?;
+        times.push(time);
+        let excess = times.len().saturating_sub(MAX_RECORDED_DOWNLOAD_TIMES);
+        times.drain(..excess);
+        fs::write(self.download_times_path(id), serde_json::to_vec(&times)?)
+            .context(format!("Failed to write download times for transfer: {id}"))
+    }
+
+    fn get_download_times(&self, id: &str) -> Result<Vec<SystemTime>> {
+        match fs::read(self.download_times_path(id)) {
+            Ok(data) => serde_json::from_slice(&data)
+                .context(format!("Failed to parse download times for transfer: {id}")),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+            Err(err) => {
+                Err(err).context(format!("Failed to read download times for transfer: {id}"))
+            }
+        }
+    }
+
+    fn rate_limit_hit(&self, key: &str, window: Duration) -> Result<(u64, Duration)> {
+        let mut rate_limits = self.rate_limits.lock().unwrap();
+        let now = Instant::now();
+        if rate_limits.len() > RATE_LIMIT_PRUNE_THRESHOLD {
+            rate_limits.retain(|_, (ends_at, _)| *ends_at > now);
+        }
+        let entry = rate_limits
+            .entry(key.to_string())
+            .or_insert((now + window, 0));
+        if entry.0 <= now {
+            *entry = (now + window, 0);
+        }
+        entry.1 += 1;
+        Ok((entry.1, entry.0.saturating_duration_since(now)))
+    }
+
+    fn rate_limit_count(&self, key: &str, window: Duration) -> Result<(u64, Duration)> {
+        let now = Instant::now();
+        Ok(match self.rate_limits.lock().unwrap().get(key) {
+            Some((ends_at, hits)) if *ends_at > now => {
+                (*hits, ends_at.saturating_duration_since(now))
+            }
+            _ => (0, window),
+        })
+    }
+}
@@ -0,0 +1,109 @@
+mod local;
+#[cfg(feature = "redis")]
+mod redis;
+
+pub use local::LocalStateStore;
+#[cfg(feature = "redis")]
+pub use redis::RedisStateStore;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Debug,
+    time::{Duration, SystemTime},
+};
+
+/// Number of most recent download times kept for each transfer.
+pub const MAX_RECORDED_DOWNLOAD_TIMES: usize = 100;
+
+/// Metadata persisted alongside each transfer file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferMetadata {
+    /// The time at which the transfer finished uploading.
+    pub created_at: SystemTime,
+    /// Hex-encoded BLAKE3 hash of the transfer data.
+    ///
+    /// Not available for transfers created before hashes were recorded.
+    #[serde(default)]
+    pub checksum: Option<String>,
+    /// Whether the transfer file has been encrypted at rest by the server.
+    #[serde(default)]
+    pub encrypted: bool,
+    /// Hex-encoded BLAKE3 hash of the transfer file as stored on disk, for transfers encrypted at rest.
+    ///
+    /// For other transfers the stored file is identical to the transfer data, so [`Self::checksum`] is used instead.
+    #[serde(default)]
+    pub file_checksum: Option<String>,
+    /// Set by an admin to keep the transfer past its normal expiry.
+    #[serde(default)]
+    pub pin: Option<TransferPin>,
+    /// How long the transfer is kept after being uploaded, when it differs from the server default.
+    #[serde(default)]
+    pub expire_after: Option<Duration>,
+    /// Hex-encoded BLAKE3 hash of the token the uploader can use to view the transfer's activity.
+    ///
+    /// Not available for transfers created before activity tokens were issued.
+    #[serde(default)]
+    pub activity_token_hash: Option<String>,
+    /// Hex-encoded BLAKE3 hash of the token the uploader can use to delete the transfer.
+    ///
+    /// Not available for transfers created before deletion tokens were issued.
+    #[serde(default)]
+    pub deletion_token_hash: Option<String>,
+    /// URL the uploader asked to be notified at when the transfer is first downloaded.
+    #[serde(default)]
+    pub claim_webhook: Option<String>,
+    /// Address the uploader asked to be emailed at about the transfer.
+    #[serde(default)]
+    pub notify_email: Option<String>,
+    /// How many times the uploader allowed the transfer to be downloaded, if limited.
+    #[serde(default)]
+    pub max_downloads: Option<u64>,
+    /// Whether an email warning that the transfer expires soon without being downloaded has been sent.
+    #[serde(default)]
+    pub expiry_warning_sent: bool,
+    /// Base64-encoded manifest of the transfer's contents, encrypted by the client that uploaded it.
+    #[serde(default)]
+    pub manifest: Option<String>,
+}
+
+/// An admin override of when a transfer expires.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct TransferPin {
+    /// The time the transfer should expire at instead, or `None` to keep it until it is unpinned.
+    pub expires_at: Option<SystemTime>,
+}
+
+/// Backend for state that must be shared between all server instances,
+/// such as transfer metadata, download counters and rate-limit windows.
+pub trait StateStore: Debug + Send + Sync {
+    /// Get the metadata for a transfer, or `None` if no metadata has been stored for it.
+    fn get_metadata(&self, id: &str) -> Result<Option<TransferMetadata>>;
+
+    /// Store metadata for a transfer, replacing any existing metadata.
+    fn set_metadata(&self, id: &str, metadata: &TransferMetadata) -> Result<()>;
+
+    /// Remove all state associated with a transfer.
+    fn remove_transfer(&self, id: &str) -> Result<()>;
+
+    /// Increment the download counter for a transfer, returning the new count.
+    fn increment_download_count(&self, id: &str) -> Result<u64>;
+
+    /// Get the number of times a transfer has been downloaded.
+    fn get_download_count(&self, id: &str) -> Result<u64>;
+
+    /// Record the time a transfer was downloaded, keeping at most [`MAX_RECORDED_DOWNLOAD_TIMES`] times.
+    fn record_download_time(&self, id: &str, time: SystemTime) -> Result<()>;
+
+    /// Get the most recent times a transfer was downloaded, oldest first.
+    fn get_download_times(&self, id: &str) -> Result<Vec<SystemTime>>;
+
+    /// Record a hit against the given rate-limit key.
+    ///
+    /// Returns the number of hits within the current window and the time until the window resets.
+    fn rate_limit_hit(&self, key: &str, window: Duration) -> Result<(u64, Duration)>;
+
+    /// Get the number of hits recorded against the given rate-limit key within the current window
+    /// and the time until the window resets, without recording a new hit.
+    fn rate_limit_count(&self, key: &str, window: Duration) -> Result<(u64, Duration)>;
+}
@@ -0,0 +1,161 @@
+use super::{MAX_RECORDED_DOWNLOAD_TIMES, StateStore, TransferMetadata};
+use anyhow::{Context, Result};
+use redis::{Commands, Connection, RedisResult};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::runtime::{Handle, RuntimeFlavor};
+
+const KEY_PREFIX: &str = "xfer";
+
+/// Most connections to Redis kept open at once, so concurrent requests don't wait on each other.
+const MAX_CONNECTIONS: u32 = 16;
+
+/// How long to wait for a free or new connection to Redis before failing.
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// [`StateStore`] backed by a Redis server.
+///
+/// Allows multiple server instances to share consistent metadata, download counters and rate limits.
+pub struct RedisStateStore {
+    pool: r2d2::Pool<redis::Client>,
+}
+
+impl std::fmt::Debug for RedisStateStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedisStateStore")
+            .field("state", &self.pool.state())
+            .finish_non_exhaustive()
+    }
+}
+
+impl RedisStateStore {
+    /// Create a new [`RedisStateStore`] connecting to the given Redis URL.
+    pub fn new(url: &str) -> Result<Self> {
+        let client = redis::Client::open(url).context("Invalid Redis URL")?;
+        let pool = r2d2::Pool::builder()
+            .max_size(MAX_CONNECTIONS)
+            .min_idle(Some(1))
+            .connection_timeout(CONNECTION_TIMEOUT)
+            .build(client)
+            .context("Failed to connect to Redis")?;
+        Ok(Self { pool })
+    }
+
+    /// Run the given function against a pooled connection.
+    ///
+    /// Store methods are called from async handlers, so on a multi-threaded runtime the worker's other
+    /// tasks are moved to another thread while waiting on Redis instead of stalling behind it.
+    fn with_connection<T>(&self, f: impl FnOnce(&mut Connection) -> RedisResult<T>) -> Result<T> {
+        let run = || {
+            let mut connection = self
+                .pool
+                .get()
+                .context("Failed to get a connection to Redis")?;
+            f(&mut connection).context("Redis command failed")
+        };
+        match Handle::try_current().map(|handle| handle.runtime_flavor()) {
+            Ok(RuntimeFlavor::MultiThread) => tokio::task::block_in_place(run),
+            _ => run(),
+        }
+    }
+
+    fn metadata_key(id: &str) -> String {
+        format!("{KEY_PREFIX}:transfer:{id}:metadata")
+    }
+
+    fn download_count_key(id: &str) -> String {
+        format!("{KEY_PREFIX}:transfer:{id}:downloads")
+    }
+
+    fn download_times_key(id: &str) -> String {
+        format!("{KEY_PREFIX}:transfer:{id}:download-times")
+    }
+
+    fn rate_limit_key(key: &str) -> String {
+        format!("{KEY_PREFIX}:ratelimit:{key}")
+    }
+}
+
+impl StateStore for RedisStateStore {
+    fn get_metadata(&self, id: &str) -> Result<Option<TransferMetadata>> {
+        let data: Option<String> = self.with_connection(|con| con.get(Self::metadata_key(id)))?;
+        data.map(|data| {
+            serde_json::from_str(&data)
+                .context(format!("Failed to parse metadata for transfer: {id}"))
+        })
+        .transpose()
+    }
+
+    fn set_metadata(&self, id: &str, metadata: &TransferMetadata) -> Result<()> {
+        let data = serde_json::to_string(metadata)?;
+        self.with_connection(|con| con.set(Self::metadata_key(id), data))
+    }
+
+    fn remove_transfer(&self, id: &str) -> Result<()> {
+        self.with_connection(|con| {
+            con.del(&[
+                Self::metadata_key(id),
+                Self::download_count_key(id),
+                Self::download_times_key(id),
+            ])
+        })
+    }
+
+    fn increment_download_count(&self, id: &str) -> Result<u64> {
+        self.with_connection(|con| con.incr(Self::download_count_key(id), 1))
+    }
+
+    fn get_download_count(&self, id: &str) -> Result<u64> {
+        let count: Option<u64> =
+            self.with_connection(|con| con.get(Self::download_count_key(id)))?;
+        Ok(count.unwrap_or(0))
+    }
+
+    fn record_download_time(&self, id: &str, time: SystemTime) -> Result<()> {
+        let key = Self::download_times_key(id);
+        let millis = time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        self.with_connection(|con| {
+            let _: () = con.lpush(&key, millis)?;
+            con.ltrim(&key, 0, MAX_RECORDED_DOWNLOAD_TIMES as isize - 1)
+        })
+    }
+
+    fn get_download_times(&self, id: &str) -> Result<Vec<SystemTime>> {
+        let millis: Vec<u64> =
+            self.with_connection(|con| con.lrange(Self::download_times_key(id), 0, -1))?;
+        // Times are pushed to the front of the list, so it holds the newest first.
+        Ok(millis
+            .into_iter()
+            .rev()
+            .map(|millis| UNIX_EPOCH + Duration::from_millis(millis))
+            .collect())
+    }
+
+    fn rate_limit_hit(&self, key: &str, window: Duration) -> Result<(u64, Duration)> {
+        let key = Self::rate_limit_key(key);
+        self.with_connection(|con| {
+            let count: u64 = con.incr(&key, 1)?;
+            let ttl: i64 = con.pttl(&key)?;
+            // A key without a TTL has just been created (or lost its expiry), so start the window now.
+            if ttl < 0 {
+                let _: () = con.pexpire(&key, window.as_millis() as i64)?;
+                return Ok((count, window));
+            }
+            Ok((count, Duration::from_millis(ttl as u64)))
+        })
+    }
+
+    fn rate_limit_count(&self, key: &str, window: Duration) -> Result<(u64, Duration)> {
+        let key = Self::rate_limit_key(key);
+        self.with_connection(|con| {
+            let count: Option<u64> = con.get(&key)?;
+            let ttl: i64 = con.pttl(&key)?;
+            Ok(match (count, ttl) {
+                (Some(count), ttl) if ttl >= 0 => (count, Duration::from_millis(ttl as u64)),
+                _ => (0, window),
+            })
+        })
+    }
+}
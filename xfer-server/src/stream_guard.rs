@@ -0,0 +1,66 @@
+use axum::body::Bytes;
+use futures_util::Stream;
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+/// Limits used to detect stalled or excessively slow transfer streams.
+#[derive(Debug, Clone, Copy)]
+pub struct StreamLimits {
+    /// How long a stream may go without making any progress.
+    pub idle_timeout: Duration,
+    /// The minimum average throughput in bytes per second, or 0 to disable the check.
+    pub min_throughput: u64,
+}
+
+impl StreamLimits {
+    /// Whether a stream that has moved `bytes` within `elapsed` is below the minimum throughput.
+    ///
+    /// Streams are given a grace period equal to the idle timeout before throughput is enforced.
+    pub fn is_too_slow(&self, bytes: u64, elapsed: Duration) -> bool {
+        self.min_throughput > 0
+            && elapsed > self.idle_timeout
+            && (bytes as f64 / elapsed.as_secs_f64()) < self.min_throughput as f64
+    }
+}
+
+/// Stream wrapper that ends the stream with an error once it falls below the minimum throughput.
+pub struct ThroughputGuard<S> {
+    inner: S,
+    limits: StreamLimits,
+    started: Instant,
+    bytes: u64,
+}
+
+impl<S> ThroughputGuard<S> {
+    pub fn new(inner: S, limits: StreamLimits) -> Self {
+        Self {
+            inner,
+            limits,
+            started: Instant::now(),
+            bytes: 0,
+        }
+    }
+}
+
+impl<S: Stream<Item = io::Result<Bytes>> + Unpin> Stream for ThroughputGuard<S> {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.limits.is_too_slow(this.bytes, this.started.elapsed()) {
+            return Poll::Ready(Some(Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "stream fell below the minimum throughput",
+            ))));
+        }
+        let poll = Pin::new(&mut this.inner).poll_next(cx);
+        if let Poll::Ready(Some(Ok(chunk))) = &poll {
+            this.bytes += chunk.len() as u64;
+        }
+        poll
+    }
+}
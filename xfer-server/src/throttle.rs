@@ -0,0 +1,158 @@
+use futures_util::Stream;
+use std::{
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, ready},
+    time::{Duration, Instant},
+};
+use tokio::time::Sleep;
+
+/// Token bucket that paces byte throughput to a fixed rate, allowing up to one second of burst.
+#[derive(Debug)]
+pub struct TokenBucket {
+    bytes_per_second: f64,
+    state: Mutex<(f64, Instant)>,
+}
+
+impl TokenBucket {
+    /// Create a new [`TokenBucket`] that starts full.
+    pub fn new(bytes_per_second: u64) -> Self {
+        Self {
+            bytes_per_second: bytes_per_second as f64,
+            state: Mutex::new((bytes_per_second as f64, Instant::now())),
+        }
+    }
+
+    /// Take `bytes` from the bucket, returning how long the caller should wait before sending more.
+    ///
+    /// The bucket is allowed to go into debt so that chunks larger than the rate are never rejected.
+    fn reserve(&self, bytes: usize) -> Duration {
+        let mut state = self.state.lock().unwrap();
+        let (available, last_refill) = &mut *state;
+        let now = Instant::now();
+        *available = (*available
+            + now.duration_since(*last_refill).as_secs_f64() * self.bytes_per_second)
+            .min(self.bytes_per_second);
+        *last_refill = now;
+        *available -= bytes as f64;
+        if *available >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-*available / self.bytes_per_second)
+        }
+    }
+}
+
+/// Upload and download bandwidth limits, applied both across the server and to each connection.
+#[derive(Debug, Default, Clone)]
+pub struct BandwidthLimits {
+    global_upload: Option<Arc<TokenBucket>>,
+    global_download: Option<Arc<TokenBucket>>,
+    connection_upload: u64,
+    connection_download: u64,
+}
+
+impl BandwidthLimits {
+    /// Create a new [`BandwidthLimits`] from rates in bytes per second, where 0 means unlimited.
+    pub fn new(
+        global_upload: u64,
+        global_download: u64,
+        connection_upload: u64,
+        connection_download: u64,
+    ) -> Self {
+        let bucket = |rate| (rate > 0).then(|| Arc::new(TokenBucket::new(rate)));
+        Self {
+            global_upload: bucket(global_upload),
+            global_download: bucket(global_download),
+            connection_upload,
+            connection_download,
+        }
+    }
+
+    /// Create a throttle for a single upload.
+    pub fn upload(&self) -> Throttle {
+        Throttle::new(self.global_upload.clone(), self.connection_upload)
+    }
+
+    /// Create a throttle for a single download.
+    pub fn download(&self) -> Throttle {
+        Throttle::new(self.global_download.clone(), self.connection_download)
+    }
+}
+
+/// Paces a single stream against the shared server-wide bucket and its own per-connection bucket.
+#[derive(Debug)]
+pub struct Throttle {
+    global: Option<Arc<TokenBucket>>,
+    connection: Option<TokenBucket>,
+}
+
+impl Throttle {
+    fn new(global: Option<Arc<TokenBucket>>, connection_rate: u64) -> Self {
+        Self {
+            global,
+            connection: (connection_rate > 0).then(|| TokenBucket::new(connection_rate)),
+        }
+    }
+
+    /// Record that `bytes` were transferred, returning how long to pause before transferring more.
+    fn reserve(&self, bytes: usize) -> Duration {
+        let global = self
+            .global
+            .as_ref()
+            .map_or(Duration::ZERO, |bucket| bucket.reserve(bytes));
+        let connection = self
+            .connection
+            .as_ref()
+            .map_or(Duration::ZERO, |bucket| bucket.reserve(bytes));
+        global.max(connection)
+    }
+
+    /// Record that `bytes` were transferred and wait until more may be transferred.
+    pub async fn consume(&self, bytes: usize) {
+        let wait = self.reserve(bytes);
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Wrap a stream so that items are yielded no faster than this throttle allows.
+    pub fn stream<S>(self, inner: S) -> ThrottledStream<S> {
+        ThrottledStream {
+            inner,
+            throttle: self,
+            sleep: None,
+        }
+    }
+}
+
+/// Stream wrapper created by [`Throttle::stream`].
+pub struct ThrottledStream<S> {
+    inner: S,
+    throttle: Throttle,
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl<S, B, E> Stream for ThrottledStream<S>
+where
+    S: Stream<Item = Result<B, E>> + Unpin,
+    B: AsRef<[u8]>,
+{
+    type Item = Result<B, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if let Some(sleep) = &mut this.sleep {
+            ready!(sleep.as_mut().poll(cx));
+            this.sleep = None;
+        }
+        let item = ready!(Pin::new(&mut this.inner).poll_next(cx));
+        if let Some(Ok(chunk)) = &item {
+            let wait = this.throttle.reserve(chunk.as_ref().len());
+            if !wait.is_zero() {
+                this.sleep = Some(Box::pin(tokio::time::sleep(wait)));
+            }
+        }
+        Poll::Ready(item)
+    }
+}
@@ -0,0 +1,264 @@
+use crate::{AppState, error::ApiError, expiry::ExpiryBounds};
+use anyhow::{Context, Result, bail};
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::ErrorKind,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+use tracing::{debug, info};
+
+/// Limits applied to transfers uploaded with an upload token.
+///
+/// Limits that aren't set fall back to the server-wide setting.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UploadTokenPolicy {
+    /// The maximum transfer size, in bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size_bytes: Option<u64>,
+    /// How long transfers are kept after being uploaded, in seconds.
+    ///
+    /// Also the longest lifetime uploads made with the token can request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_expire_after_secs: Option<u64>,
+    /// The maximum number of transfers that can be created with the token within the rate-limit window.
+    ///
+    /// Set to 0 to disable rate limiting for the token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<u64>,
+    /// The window over which the rate limit applies, in seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit_window_secs: Option<u64>,
+}
+
+impl UploadTokenPolicy {
+    /// The maximum transfer size, in bytes, given the server-wide default.
+    pub fn max_size(&self, default: u64) -> u64 {
+        self.max_size_bytes.unwrap_or(default)
+    }
+
+    /// The lifetimes uploads may request and the default lifetime, given the server-wide bounds.
+    pub fn expiry_bounds(&self, server: ExpiryBounds) -> ExpiryBounds {
+        match self.max_expire_after_secs.map(Duration::from_secs) {
+            Some(expire_after) => ExpiryBounds {
+                min: server.min.min(expire_after),
+                default: expire_after,
+                max: expire_after,
+            },
+            None => server,
+        }
+    }
+}
+
+/// A named upload token and the limits that apply to it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadToken {
+    pub name: String,
+    /// Hex-encoded BLAKE3 hash of the token's secret.
+    pub secret_hash: String,
+    #[serde(flatten)]
+    pub policy: UploadTokenPolicy,
+    /// The time the token was created, if it was created via the admin API.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<SystemTime>,
+}
+
+#[derive(Debug, Default)]
+struct UploadTokenEntries {
+    tokens: Vec<UploadToken>,
+    modified: Option<SystemTime>,
+}
+
+/// Named upload tokens, each with its own limits, so a single server can apply different
+/// policies to different groups of uploaders.
+///
+/// Tokens are stored as a JSON array in the token file, which is written to when tokens are
+/// created or revoked via the admin API and reloaded automatically when it changes.
+#[derive(Debug)]
+pub struct UploadTokens {
+    path: PathBuf,
+    required: bool,
+    entries: RwLock<UploadTokenEntries>,
+}
+
+impl UploadTokens {
+    /// Create a new [`UploadTokens`] loaded from the given file, which doesn't need to exist yet.
+    ///
+    /// When `required` is set, uploads without a valid token are rejected.
+    pub fn new(path: PathBuf, required: bool) -> Result<Self> {
+        let tokens = Self {
+            path,
+            required,
+            entries: RwLock::default(),
+        };
+        tokens.reload()?;
+        Ok(tokens)
+    }
+
+    /// Whether uploads must be made with a valid token.
+    pub fn is_required(&self) -> bool {
+        self.required
+    }
+
+    /// Reload the tokens from disk if the file has changed since it was last read.
+    pub fn reload(&self) -> Result<()> {
+        let modified = match fs::metadata(&self.path).and_then(|m| m.modified()) {
+            Ok(modified) => Some(modified),
+            Err(err) if err.kind() == ErrorKind::NotFound => None,
+            Err(err) => return Err(err).context("Failed to read upload token file metadata"),
+        };
+        if self.entries.read().unwrap().modified == modified {
+            return Ok(());
+        }
+        let tokens = match modified {
+            Some(_) => serde_json::from_str::<Vec<UploadToken>>(
+                &fs::read_to_string(&self.path).context("Failed to read upload token file")?,
+            )
+            .context("Failed to parse upload token file")?,
+            None => Vec::new(),
+        };
+        for token in &tokens {
+            if blake3::Hash::from_hex(&token.secret_hash).is_err() {
+                bail!(
+                    "Upload token '{}' does not have a valid secret hash",
+                    token.name
+                );
+            }
+            if token.policy.rate_limit_window_secs == Some(0) {
+                bail!(
+                    "Upload token '{}' has a rate limit window of 0 seconds",
+                    token.name
+                );
+            }
+        }
+        info!("Loaded {} upload tokens", tokens.len());
+        *self.entries.write().unwrap() = UploadTokenEntries { tokens, modified };
+        Ok(())
+    }
+
+    /// Get the token with the given secret, or `None` if no token matches.
+    pub fn authenticate(&self, secret: &str) -> Option<UploadToken> {
+        let hash = blake3::hash(secret.as_bytes());
+        // Comparing hashes keeps the comparison constant-time.
+        self.entries
+            .read()
+            .unwrap()
+            .tokens
+            .iter()
+            .find(|token| blake3::Hash::from_hex(&token.secret_hash).is_ok_and(|h| h == hash))
+            .cloned()
+    }
+
+    /// Get the token sent as a bearer token in the given request headers.
+    ///
+    /// Returns `Ok(None)` when no token was sent and an error when the token isn't valid.
+    pub fn authenticate_headers(&self, headers: &HeaderMap) -> Result<Option<UploadToken>> {
+        match headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+        {
+            Some(secret) => match self.authenticate(secret.trim()) {
+                Some(token) => Ok(Some(token)),
+                None => bail!("Invalid upload token"),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// List all tokens.
+    pub fn list(&self) -> Vec<UploadToken> {
+        self.entries.read().unwrap().tokens.clone()
+    }
+
+    /// Create a new token with the given name and policy, returning its secret
+    /// or `None` if a token with the name already exists.
+    pub fn create(&self, name: &str, policy: UploadTokenPolicy) -> Result<Option<String>> {
+        let secret = blake3::Hash::from_bytes(rand::random())
+            .to_hex()
+            .to_string();
+        let mut entries = self.entries.write().unwrap();
+        if entries.tokens.iter().any(|token| token.name == name) {
+            return Ok(None);
+        }
+        let mut tokens = entries.tokens.clone();
+        tokens.push(UploadToken {
+            name: name.to_string(),
+            secret_hash: blake3::hash(secret.as_bytes()).to_hex().to_string(),
+            policy,
+            created_at: Some(SystemTime::now()),
+        });
+        self.save(&mut entries, tokens)?;
+        Ok(Some(secret))
+    }
+
+    /// Revoke the token with the given name, returning whether it existed.
+    pub fn revoke(&self, name: &str) -> Result<bool> {
+        let mut entries = self.entries.write().unwrap();
+        if !entries.tokens.iter().any(|token| token.name == name) {
+            return Ok(false);
+        }
+        let tokens = entries
+            .tokens
+            .iter()
+            .filter(|token| token.name != name)
+            .cloned()
+            .collect();
+        self.save(&mut entries, tokens)?;
+        Ok(true)
+    }
+
+    /// Atomically replace the contents of the token file, updating the loaded tokens once written.
+    fn save(&self, entries: &mut UploadTokenEntries, tokens: Vec<UploadToken>) -> Result<()> {
+        let temp_path = self.path.with_extension("tmp");
+        fs::write(&temp_path, serde_json::to_vec_pretty(&tokens)?)
+            .context("Failed to write upload token file")?;
+        fs::rename(&temp_path, &self.path).context("Failed to replace upload token file")?;
+        *entries = UploadTokenEntries {
+            tokens,
+            modified: fs::metadata(&self.path).and_then(|m| m.modified()).ok(),
+        };
+        Ok(())
+    }
+}
+
+/// The upload token an upload request was authenticated with, added to the request's extensions.
+#[derive(Debug, Clone)]
+pub struct UploadScope(pub Arc<UploadToken>);
+
+/// Middleware that authenticates the upload token sent with an upload, if any.
+///
+/// Uploads without a token are only allowed through when tokens aren't required.
+pub async fn upload_token_middleware(
+    State(state): State<AppState>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    match state.upload_tokens.authenticate_headers(req.headers()) {
+        Ok(Some(token)) => {
+            debug!("Upload authenticated with upload token '{}'", token.name);
+            req.extensions_mut().insert(UploadScope(Arc::new(token)));
+            next.run(req).await
+        }
+        Ok(None) if !state.upload_tokens.is_required() => next.run(req).await,
+        Ok(None) | Err(_) => {
+            debug!("Rejecting upload with missing or invalid upload token");
+            (
+                [(header::WWW_AUTHENTICATE, "Bearer")],
+                ApiError::new(
+                    StatusCode::UNAUTHORIZED,
+                    "a valid upload token is required to upload to this server",
+                ),
+            )
+                .into_response()
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use anyhow::{Context, Result, anyhow};
+use reqwest::Url;
+use serde::Serialize;
+use std::{
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+/// How long to wait for the usage statistics endpoint to respond.
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Upper bounds of the upload size histogram buckets in bytes, with a final bucket for anything larger.
+const SIZE_BUCKETS: [u64; 5] = [
+    1024 * 1024,
+    10 * 1024 * 1024,
+    100 * 1024 * 1024,
+    1024 * 1024 * 1024,
+    10 * 1024 * 1024 * 1024,
+];
+
+/// A bucket of the upload size histogram.
+#[derive(Debug, Serialize)]
+struct SizeBucket {
+    /// The largest upload counted in the bucket in bytes, or `None` for the final bucket.
+    max_bytes: Option<u64>,
+    count: u64,
+}
+
+/// Usage statistics sent to the export endpoint, covering the period since the previous export.
+#[derive(Debug, Serialize)]
+struct UsageReport {
+    server_version: &'static str,
+    period_seconds: u64,
+    transfers_created: u64,
+    transfers_downloaded: u64,
+    upload_sizes: Vec<SizeBucket>,
+}
+
+/// Coarse, anonymous usage counters that self-hosters can opt in to sharing for capacity planning.
+///
+/// Only totals and a histogram of upload sizes are kept. Nothing identifying a transfer, client or
+/// the server itself is recorded, and nothing is recorded at all unless an export endpoint is set.
+#[derive(Debug)]
+pub struct UsageStatistics {
+    endpoint: Option<Url>,
+    client: reqwest::Client,
+    period_started: Mutex<Instant>,
+    transfers_created: AtomicU64,
+    transfers_downloaded: AtomicU64,
+    upload_sizes: [AtomicU64; SIZE_BUCKETS.len() + 1],
+}
+
+impl UsageStatistics {
+    /// Create a new [`UsageStatistics`] that exports to `endpoint`, or records nothing if it is `None`.
+    pub fn new(endpoint: Option<Url>) -> Result<Self> {
+        Ok(Self {
+            endpoint,
+            client: reqwest::Client::builder()
+                .user_agent(concat!("xfer-server/", env!("CARGO_PKG_VERSION")))
+                .timeout(EXPORT_TIMEOUT)
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .context("Failed to create usage statistics HTTP client")?,
+            period_started: Mutex::new(Instant::now()),
+            transfers_created: AtomicU64::new(0),
+            transfers_downloaded: AtomicU64::new(0),
+            upload_sizes: Default::default(),
+        })
+    }
+
+    /// Whether usage statistics are recorded and exported.
+    pub fn enabled(&self) -> bool {
+        self.endpoint.is_some()
+    }
+
+    /// Record a new transfer of `size` bytes.
+    pub fn record_upload(&self, size: u64) {
+        if !self.enabled() {
+            return;
+        }
+        self.transfers_created.fetch_add(1, Ordering::Relaxed);
+        let bucket = SIZE_BUCKETS
+            .iter()
+            .position(|max| size <= *max)
+            .unwrap_or(SIZE_BUCKETS.len());
+        self.upload_sizes[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a transfer being downloaded in full.
+    pub fn record_download(&self) {
+        if !self.enabled() {
+            return;
+        }
+        self.transfers_downloaded.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Send the counters recorded since the previous export to the endpoint and reset them.
+    ///
+    /// The counters are kept for the next export if sending fails.
+    pub async fn export(&self) -> Result<()> {
+        let Some(endpoint) = &self.endpoint else {
+            return Ok(());
+        };
+        let report = self.take_report();
+        let result = self
+            .client
+            .post(endpoint.clone())
+            .json(&report)
+            .send()
+            .await
+            .map_err(|err| anyhow::Error::new(err.without_url()));
+        let result = match result {
+            Ok(res) if res.status().is_success() => return Ok(()),
+            Ok(res) => Err(anyhow!(
+                "Endpoint rejected usage statistics with status {}",
+                res.status()
+            )),
+            Err(err) => Err(err.context("Failed to send usage statistics")),
+        };
+        self.restore_report(&report);
+        result
+    }
+
+    fn take_report(&self) -> UsageReport {
+        let period_started =
+            std::mem::replace(&mut *self.period_started.lock().unwrap(), Instant::now());
+        UsageReport {
+            server_version: env!("CARGO_PKG_VERSION"),
+            period_seconds: period_started.elapsed().as_secs(),
+            transfers_created: self.transfers_created.swap(0, Ordering::Relaxed),
+            transfers_downloaded: self.transfers_downloaded.swap(0, Ordering::Relaxed),
+            upload_sizes: SIZE_BUCKETS
+                .iter()
+                .map(|max| Some(*max))
+                .chain([None])
+                .zip(&self.upload_sizes)
+                .map(|(max_bytes, count)| SizeBucket {
+                    max_bytes,
+                    count: count.swap(0, Ordering::Relaxed),
+                })
+                .collect(),
+        }
+    }
+
+    /// Add the counters of a report that couldn't be sent back, so they are included in the next one.
+    fn restore_report(&self, report: &UsageReport) {
+        let mut period_started = self.period_started.lock().unwrap();
+        *period_started = period_started
+            .checked_sub(Duration::from_secs(report.period_seconds))
+            .unwrap_or(*period_started);
+        self.transfers_created
+            .fetch_add(report.transfers_created, Ordering::Relaxed);
+        self.transfers_downloaded
+            .fetch_add(report.transfers_downloaded, Ordering::Relaxed);
+        for (bucket, counter) in report.upload_sizes.iter().zip(&self.upload_sizes) {
+            counter.fetch_add(bucket.count, Ordering::Relaxed);
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use crate::error::ApiError;
+use anyhow::{Context, Result};
+use axum::http::HeaderMap;
+use reqwest::{Url, header};
+use serde::Serialize;
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+use tracing::{debug, warn};
+
+/// Header uploaders can use to register a URL that is notified when their transfer is first downloaded.
+pub const CLAIM_WEBHOOK_HEADER: &str = "X-Xfer-Claim-Webhook";
+
+/// How long to wait for a webhook endpoint to respond.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How many times delivery of an event is attempted before giving up.
+const WEBHOOK_ATTEMPTS: u32 = 3;
+
+/// How long to wait before retrying a failed delivery, doubled after each attempt.
+const WEBHOOK_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Event sent to webhooks when a transfer is downloaded for the first time.
+#[derive(Debug, Serialize)]
+struct ClaimedEvent<'a> {
+    event: &'static str,
+    id: &'a str,
+    /// The time the transfer was first downloaded, in RFC 3339 format.
+    claimed_at: String,
+}
+
+/// Webhooks that are notified when a transfer is claimed by its first download,
+/// so automated pipelines can proceed once the recipient has the data.
+#[derive(Debug)]
+pub struct ClaimWebhooks {
+    url: Option<Url>,
+    uploader_webhooks_allowed: bool,
+    client: reqwest::Client,
+}
+
+impl ClaimWebhooks {
+    /// Create a new [`ClaimWebhooks`] that notifies `url` of every claimed transfer, and uploaders'
+    /// own webhooks when `uploader_webhooks_allowed` is set.
+    pub fn new(url: Option<Url>, uploader_webhooks_allowed: bool) -> Result<Self> {
+        Ok(Self {
+            url,
+            uploader_webhooks_allowed,
+            client: reqwest::Client::builder()
+                .user_agent(concat!("xfer-server/", env!("CARGO_PKG_VERSION")))
+                .timeout(WEBHOOK_TIMEOUT)
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .context("Failed to create webhook HTTP client")?,
+        })
+    }
+
+    /// Whether uploaders may register their own webhook for a transfer.
+    pub fn uploader_webhooks_allowed(&self) -> bool {
+        self.uploader_webhooks_allowed
+    }
+
+    /// Get the webhook an uploader registered in the headers of an upload, if any.
+    pub fn uploader_webhook(&self, headers: &HeaderMap) -> Result<Option<Url>, ApiError> {
+        let Some(value) = headers.get(CLAIM_WEBHOOK_HEADER) else {
+            return Ok(None);
+        };
+        if !self.uploader_webhooks_allowed {
+            return Err(ApiError::bad_request(
+                "this server does not allow uploaders to register claim webhooks",
+            ));
+        }
+        let url = value
+            .to_str()
+            .ok()
+            .and_then(|v| Url::parse(v.trim()).ok())
+            .filter(|url| matches!(url.scheme(), "http" | "https"))
+            .ok_or_else(|| {
+                ApiError::bad_request("claim webhook must be a valid http or https URL")
+            })?;
+        Ok(Some(url))
+    }
+
+    /// Notify the configured webhooks, and the uploader's webhook if one was registered,
+    /// that a transfer has been claimed.
+    ///
+    /// Delivery happens in the background and is retried a few times on failure.
+    pub fn notify_claimed(
+        self: &Arc<Self>,
+        id: &str,
+        claimed_at: SystemTime,
+        uploader_webhook: Option<&str>,
+    ) {
+        let uploader_webhook = uploader_webhook
+            .filter(|_| self.uploader_webhooks_allowed)
+            .and_then(|url| Url::parse(url).ok());
+        let targets: Vec<Url> = self.url.iter().cloned().chain(uploader_webhook).collect();
+        if targets.is_empty() {
+            return;
+        }
+        let body = match OffsetDateTime::from(claimed_at)
+            .format(&Rfc3339)
+            .context("Failed to format timestamp")
+            .and_then(|claimed_at| {
+                serde_json::to_vec(&ClaimedEvent {
+                    event: "transfer.claimed",
+                    id,
+                    claimed_at,
+                })
+                .context("Failed to serialize claimed event")
+            }) {
+            Ok(body) => body,
+            Err(err) => {
+                warn!("Failed to build claimed event for transfer (id: '{id}'): {err:?}");
+                return;
+            }
+        };
+        for url in targets {
+            let webhooks = Arc::clone(self);
+            let body = body.clone();
+            let id = id.to_string();
+            tokio::spawn(async move { webhooks.deliver(url, body, &id).await });
+        }
+    }
+
+    /// Send an event to a webhook, retrying with a growing delay until it is accepted.
+    ///
+    /// Only the webhook's host is logged, as its URL may contain a secret.
+    async fn deliver(&self, url: Url, body: Vec<u8>, id: &str) {
+        let host = url.host_str().unwrap_or_default().to_string();
+        let mut delay = WEBHOOK_RETRY_DELAY;
+        for attempt in 1..=WEBHOOK_ATTEMPTS {
+            let result = self
+                .client
+                .post(url.clone())
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(body.clone())
+                .send()
+                .await;
+            match result {
+                Ok(res) if res.status().is_success() => {
+                    debug!("Delivered claimed event for transfer (id: '{id}') to {host}");
+                    return;
+                }
+                Ok(res) => warn!(
+                    "Webhook at {host} rejected claimed event for transfer (id: '{id}') with status {} (attempt {attempt}/{WEBHOOK_ATTEMPTS})",
+                    res.status()
+                ),
+                Err(err) => warn!(
+                    "Failed to deliver claimed event for transfer (id: '{id}') to {host} (attempt {attempt}/{WEBHOOK_ATTEMPTS}): {}",
+                    err.without_url()
+                ),
+            }
+            if attempt < WEBHOOK_ATTEMPTS {
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+        }
+    }
+}
@@ -0,0 +1,10 @@
+use wasm_bindgen::prelude::*;
+
+/// Decrypt a transfer downloaded from a relay server with the decryption part of its transfer key,
+/// or the passphrase it was protected with.
+///
+/// Returns the decrypted transfer archive.
+#[wasm_bindgen]
+pub fn decrypt(bytes: &[u8], key: &str) -> Result<Vec<u8>, JsError> {
+    xfer_crypto::Cryptography::decrypt(bytes, key).map_err(|err| JsError::new(&format!("{err:#}")))
+}